@@ -0,0 +1,58 @@
+//! Header parsing, RECT decoding and decompression throughput, on the
+//! inputs from `swf_headers::bench`.
+//!
+//! Run with `cargo bench --features bench`.
+
+#[macro_use]
+extern crate criterion;
+extern crate swf_headers;
+
+use std::io::{self, Cursor};
+
+use criterion::{Criterion, Throughput};
+use swf_headers::{bench, codec, Parser, SwfHeaders};
+
+fn header_parse(c: &mut Criterion) {
+    let inputs = bench::inputs();
+    let mut group = c.benchmark_group("header_parse");
+    for input in inputs.iter().filter(|input| input.name.starts_with("small_")) {
+        group.bench_function(format!("read_from/{}", input.name), |b| {
+            b.iter(|| SwfHeaders::read_from(Cursor::new(input.data.clone())).unwrap().0)
+        });
+        let mut parser = Parser::new();
+        group.bench_function(format!("parser/{}", input.name), |b| {
+            b.iter(|| parser.parse(&input.data[..]).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn rect_decode(c: &mut Criterion) {
+    let rects = bench::rects();
+    let mut group = c.benchmark_group("rect_decode");
+    for &nbits in &[0, 15, 31] {
+        let rect = &rects[nbits];
+        group.bench_function(format!("nbits_{}", nbits), |b| b.iter(|| codec::rect_from_bytes(rect).unwrap()));
+    }
+    group.finish();
+}
+
+fn decompression(c: &mut Criterion) {
+    let inputs = bench::inputs();
+    let mut group = c.benchmark_group("decompression");
+    group.sample_size(10);
+    for input in inputs.iter().filter(|input| input.name.starts_with("large_")) {
+        let (headers, _) = SwfHeaders::read_from(Cursor::new(input.data.clone())).unwrap();
+        group.throughput(Throughput::Bytes(headers.file_length() as u64));
+        group.bench_function(input.name, |b| {
+            b.iter(|| {
+                let (headers, mut decoded) = SwfHeaders::read_from(Cursor::new(input.data.clone())).unwrap();
+                decoded.copy_body_to(io::sink(), &headers).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, header_parse, rect_decode, decompression);
+criterion_main!(benches);
@@ -0,0 +1,429 @@
+//! Reading the identifiers and strings out of the ActionScript 3 bytecode in
+//! DoABC tags.
+//!
+//! An ABC file starts with a constant pool, then tables describing every
+//! method, class and script, with the method bodies at the end. Everything
+//! up to the bodies is read, which is enough to name every class and
+//! method; the bytecode itself isn't decoded.
+
+use std::io::Read;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use error::Error;
+use merge::parse_symbol_list;
+use primitives::{read_encoded_u32, read_string};
+use tag::{codes, Tag, Tags};
+
+/// The FileAttributes flag for movies using ActionScript 3.
+const FILE_ATTRIBUTES_AS3: u8 = 0x08;
+
+/// The namespace kind of package and top-level names.
+const PACKAGE_NAMESPACE: u8 = 0x16;
+
+/// What an ABC file defines, as far as names and strings go.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AbcFile {
+    /// The name a DoABC tag gives the file, which is empty for DoABC tags
+    /// made by some tools and for DoABCDefine, which doesn't have one.
+    pub name: String,
+    /// The version of the ABC format, as (major, minor).
+    pub version: (u16, u16),
+    /// Every string in the constant pool, in order.
+    pub strings: Vec<String>,
+    /// The fully qualified name of every class defined, such as
+    /// `com.example.Main`.
+    pub classes: Vec<String>,
+    /// The name of every method, getter and setter a class defines, as
+    /// `com.example.Main/run`, `com.example.Main/get width` and so on, then
+    /// the package-level functions.
+    pub methods: Vec<String>
+}
+
+impl AbcFile {
+    /// Parses the ABC file in a DoABC or DoABCDefine tag, returning `None`
+    /// for other tags.
+    pub fn parse(tag: &Tag) -> Result<Option<Self>, Error> {
+        let mut reader = tag.data();
+        let name = match tag.code() {
+            codes::DO_ABC => {
+                // The lazy initialization flag comes first.
+                try!(reader.read_u32::<LittleEndian>());
+                try!(read_string(&mut reader))
+            },
+            codes::DO_ABC_DEFINE => String::new(),
+            _ => return Ok(None)
+        };
+        let mut abc = try!(parse_abc(reader));
+        abc.name = name;
+        Ok(Some(abc))
+    }
+}
+
+/// Parses every DoABC and DoABCDefine in a tag list.
+pub fn abc_files(tags: &[Tag]) -> Result<Vec<AbcFile>, Error> {
+    let mut files = Vec::new();
+    for tag in tags {
+        if let Some(abc) = try!(AbcFile::parse(tag)) {
+            files.push(abc);
+        }
+    }
+    Ok(files)
+}
+
+/// Finds the document class of an ActionScript 3 movie: the class the main
+/// timeline is an instance of, and where its code starts running. Returns
+/// `None` for movies that don't use ActionScript 3, and for the rare ones
+/// that leave the main timeline as a plain MovieClip.
+///
+/// The document class is the one SymbolClass gives character ID 0, which
+/// stands for the main timeline.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::SwfHeaders;
+/// use swf_headers::abc;
+/// if let Ok((_, decoded)) = SwfHeaders::open("example.swf") {
+///     if let Ok(Some(class)) = abc::document_class(decoded) {
+///         println!("Start reading at {}", class);
+///     }
+/// }
+/// ```
+pub fn document_class<R: Read>(body: R) -> Result<Option<String>, Error> {
+    let mut is_as3 = false;
+    for tag in Tags::new(body) {
+        let tag = try!(tag);
+        match tag.code() {
+            // Only a FileAttributes tag can say a movie is ActionScript 3,
+            // and it has to be the first tag.
+            codes::FILE_ATTRIBUTES => is_as3 = tag.data().first().map_or(false, |&flags| {
+                flags & FILE_ATTRIBUTES_AS3 != 0
+            }),
+            codes::SYMBOL_CLASS if is_as3 => {
+                let symbols = try!(parse_symbol_list(tag.data()));
+                if let Some((_, name)) = symbols.into_iter().find(|&(id, _)| id == 0) {
+                    return Ok(Some(name));
+                }
+            },
+            _ => {}
+        }
+    }
+    Ok(None)
+}
+
+/// The parts of the constant pool names are made of. Index 0 of each is
+/// the implicit entry the pool leaves out.
+struct Pool {
+    strings: Vec<String>,
+    namespaces: Vec<(u8, u32)>,
+    // The namespace and name of each multiname, where it has them.
+    multinames: Vec<(Option<u32>, Option<u32>)>
+}
+
+impl Pool {
+    fn string(&self, index: u32) -> &str {
+        self.strings.get(index as usize).map_or("", |string| &string[..])
+    }
+
+    /// The unqualified name of a multiname.
+    fn name(&self, index: u32) -> &str {
+        match self.multinames.get(index as usize) {
+            Some(&(_, Some(name))) => self.string(name),
+            _ => ""
+        }
+    }
+
+    /// The name of a multiname, with the package in front if it's in one.
+    fn qualified_name(&self, index: u32) -> String {
+        let name = self.name(index);
+        let package = match self.multinames.get(index as usize) {
+            Some(&(Some(ns), _)) => match self.namespaces.get(ns as usize) {
+                Some(&(PACKAGE_NAMESPACE, package)) => self.string(package),
+                _ => ""
+            },
+            _ => ""
+        };
+        if package.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", package, name)
+        }
+    }
+}
+
+fn parse_abc(mut reader: &[u8]) -> Result<AbcFile, Error> {
+    let minor = try!(reader.read_u16::<LittleEndian>());
+    let major = try!(reader.read_u16::<LittleEndian>());
+    let pool = try!(read_pool(&mut reader));
+
+    let method_count = try!(read_u30(&mut reader));
+    for _ in 0..method_count {
+        try!(skip_method_info(&mut reader));
+    }
+    let metadata_count = try!(read_u30(&mut reader));
+    for _ in 0..metadata_count {
+        try!(read_u30(&mut reader));
+        let items = try!(read_u30(&mut reader));
+        try!(skip_u30s(&mut reader, items.saturating_mul(2)));
+    }
+
+    // The instances come first, then the statics of the same classes, in
+    // the same order.
+    let class_count = try!(read_u30(&mut reader));
+    let mut classes = Vec::new();
+    let mut methods = Vec::new();
+    for _ in 0..class_count {
+        let class = pool.qualified_name(try!(read_u30(&mut reader)));
+        // The superclass.
+        try!(read_u30(&mut reader));
+        let flags = try!(reader.read_u8());
+        if flags & 0x08 != 0 {
+            // The protected namespace.
+            try!(read_u30(&mut reader));
+        }
+        let interfaces = try!(read_u30(&mut reader));
+        try!(skip_u30s(&mut reader, interfaces));
+        // The constructor.
+        try!(read_u30(&mut reader));
+        try!(read_traits(&mut reader, &pool, &class, &mut methods));
+        classes.push(class);
+    }
+    for class in &classes {
+        // The static initializer.
+        try!(read_u30(&mut reader));
+        try!(read_traits(&mut reader, &pool, class, &mut methods));
+    }
+    let script_count = try!(read_u30(&mut reader));
+    for _ in 0..script_count {
+        try!(read_u30(&mut reader));
+        try!(read_traits(&mut reader, &pool, "", &mut methods));
+    }
+
+    Ok(AbcFile {
+        name: String::new(),
+        version: (major, minor),
+        strings: pool.strings.into_iter().skip(1).collect(),
+        classes: classes,
+        methods: methods
+    })
+}
+
+fn read_pool(reader: &mut &[u8]) -> Result<Pool, Error> {
+    // Ints and uints are variable length, and doubles 8 bytes each.
+    let ints = try!(read_u30(reader));
+    try!(skip_u30s(reader, ints.saturating_sub(1)));
+    let uints = try!(read_u30(reader));
+    try!(skip_u30s(reader, uints.saturating_sub(1)));
+    let doubles = try!(read_u30(reader)).saturating_sub(1) as usize;
+    if reader.len() < doubles * 8 {
+        return Err(Error::NotSwf);
+    }
+    *reader = &reader[doubles * 8..];
+
+    let mut strings = vec![String::new()];
+    for _ in 1..try!(read_u30(reader)) {
+        let len = try!(read_u30(reader)) as usize;
+        if reader.len() < len {
+            return Err(Error::NotSwf);
+        }
+        let (string, rest) = reader.split_at(len);
+        strings.push(String::from_utf8_lossy(string).into_owned());
+        *reader = rest;
+    }
+
+    let mut namespaces = vec![(0, 0)];
+    for _ in 1..try!(read_u30(reader)) {
+        let kind = try!(reader.read_u8());
+        namespaces.push((kind, try!(read_u30(reader))));
+    }
+    for _ in 1..try!(read_u30(reader)) {
+        let count = try!(read_u30(reader));
+        try!(skip_u30s(reader, count));
+    }
+
+    let mut multinames = vec![(None, None)];
+    for _ in 1..try!(read_u30(reader)) {
+        multinames.push(match try!(reader.read_u8()) {
+            // QName and QNameA.
+            0x07 | 0x0d => {
+                let ns = try!(read_u30(reader));
+                (Some(ns), Some(try!(read_u30(reader))))
+            },
+            // RTQName and RTQNameA.
+            0x0f | 0x10 => (None, Some(try!(read_u30(reader)))),
+            // RTQNameL and RTQNameLA.
+            0x11 | 0x12 => (None, None),
+            // Multiname and MultinameA, with a namespace set.
+            0x09 | 0x0e => {
+                let name = try!(read_u30(reader));
+                try!(read_u30(reader));
+                (None, Some(name))
+            },
+            // MultinameL and MultinameLA.
+            0x1b | 0x1c => {
+                try!(read_u30(reader));
+                (None, None)
+            },
+            // TypeName, such as Vector.<int>.
+            0x1d => {
+                try!(read_u30(reader));
+                let params = try!(read_u30(reader));
+                try!(skip_u30s(reader, params));
+                (None, None)
+            },
+            _ => return Err(Error::NotSwf)
+        });
+    }
+
+    Ok(Pool {
+        strings: strings,
+        namespaces: namespaces,
+        multinames: multinames
+    })
+}
+
+fn skip_method_info(reader: &mut &[u8]) -> Result<(), Error> {
+    let params = try!(read_u30(reader));
+    // The return type, the parameter types and the name.
+    try!(skip_u30s(reader, params.saturating_add(2)));
+    let flags = try!(reader.read_u8());
+    if flags & 0x08 != 0 {
+        for _ in 0..try!(read_u30(reader)) {
+            try!(read_u30(reader));
+            try!(reader.read_u8());
+        }
+    }
+    if flags & 0x80 != 0 {
+        try!(skip_u30s(reader, params));
+    }
+    Ok(())
+}
+
+/// Reads a list of traits, adding the methods, getters and setters among
+/// them to `methods`, named after `owner`.
+fn read_traits(reader: &mut &[u8], pool: &Pool, owner: &str, methods: &mut Vec<String>) -> Result<(), Error> {
+    for _ in 0..try!(read_u30(reader)) {
+        let name = try!(read_u30(reader));
+        let kind = try!(reader.read_u8());
+        match kind & 0x0f {
+            // Slots and constants, with an optional default value.
+            0 | 6 => {
+                try!(skip_u30s(reader, 2));
+                if try!(read_u30(reader)) != 0 {
+                    try!(reader.read_u8());
+                }
+            },
+            1..=3 => {
+                try!(skip_u30s(reader, 2));
+                let prefix = match kind & 0x0f {
+                    2 => "get ",
+                    3 => "set ",
+                    _ => ""
+                };
+                methods.push(if owner.is_empty() {
+                    format!("{}{}", prefix, pool.qualified_name(name))
+                } else {
+                    format!("{}/{}{}", owner, prefix, pool.name(name))
+                });
+            },
+            // Classes, which are named where they're defined.
+            4 => try!(skip_u30s(reader, 2)),
+            5 => {
+                try!(skip_u30s(reader, 2));
+                methods.push(pool.qualified_name(name));
+            },
+            _ => return Err(Error::NotSwf)
+        }
+        if kind & 0x40 != 0 {
+            let metadata = try!(read_u30(reader));
+            try!(skip_u30s(reader, metadata));
+        }
+    }
+    Ok(())
+}
+
+/// Reads a u30, which is stored the same way as an EncodedU32.
+fn read_u30<R: Read>(reader: &mut R) -> Result<u32, Error> {
+    read_encoded_u32(reader)
+}
+
+fn skip_u30s(reader: &mut &[u8], count: u32) -> Result<(), Error> {
+    for _ in 0..count {
+        try!(read_u30(reader));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tag::{codes, Tag};
+
+    #[test]
+    fn test_abc_file() {
+        let mut data = vec![1, 0, 0, 0, b'f', b'r', b'a', b'm', b'e', b'1', 0];
+        data.extend_from_slice(&[
+            // Version 46.16.
+            16, 0, 46, 0,
+            // No ints, uints or doubles.
+            0, 0, 0,
+            // "com.example", "Main", "width" and a URL.
+            5,
+            11, b'c', b'o', b'm', b'.', b'e', b'x', b'a', b'm', b'p', b'l', b'e',
+            4, b'M', b'a', b'i', b'n',
+            5, b'w', b'i', b'd', b't', b'h',
+            4, b'h', b't', b't', b'p',
+            // The com.example package, and no namespace sets.
+            2, 0x16, 1,
+            0,
+            // com.example.Main, com.example.width and a runtime name.
+            4, 0x07, 1, 2, 0x07, 1, 3, 0x11,
+            // Three methods without parameters, one with an optional one.
+            3,
+            0, 0, 0, 0,
+            0, 0, 0, 0,
+            1, 0, 0, 0, 0x08, 1, 4, 0x01,
+            // No metadata.
+            0,
+            // Main, with no superclass or interfaces, constructed by method
+            // 0, with a getter and a slot with a default value.
+            1,
+            1, 0, 0, 0, 0,
+            2, 2, 0x02, 0, 2, 3, 0x00, 1, 0, 4, 0x01,
+            // Main's static initializer is method 1, with no traits.
+            1, 0,
+            // A script defining Main and a package-level function.
+            1,
+            1, 2, 1, 0x04, 0, 0, 2, 0x05, 0, 2
+        ]);
+        let abc = AbcFile::parse(&Tag::new(codes::DO_ABC, data)).unwrap().unwrap();
+        assert_eq!(abc.name, "frame1");
+        assert_eq!(abc.version, (46, 16));
+        assert_eq!(abc.strings, vec!["com.example", "Main", "width", "http"]);
+        assert_eq!(abc.classes, vec!["com.example.Main"]);
+        assert_eq!(abc.methods, vec!["com.example.Main/get width", "com.example.width"]);
+
+        assert_eq!(AbcFile::parse(&Tag::new(codes::DO_ACTION, vec![0])).unwrap(), None);
+        assert!(AbcFile::parse(&Tag::new(codes::DO_ABC_DEFINE, vec![16, 0, 46, 0, 0, 0, 0, 2])).is_err());
+    }
+
+    #[test]
+    fn test_document_class() {
+        let body = |attributes: u8| {
+            let mut body = Vec::new();
+            let tags = vec![
+                Tag::new(codes::FILE_ATTRIBUTES, vec![attributes, 0, 0, 0]),
+                Tag::new(codes::SYMBOL_CLASS, vec![2, 0, 3, 0, b'B', 0, 0, 0, b'a', b'.', b'M', 0]),
+                Tag::new(codes::SHOW_FRAME, vec![]),
+                Tag::new(codes::END, vec![])
+            ];
+            for tag in tags {
+                tag.write_to(&mut body).unwrap();
+            }
+            body
+        };
+        assert_eq!(document_class(&body(0x08)[..]).unwrap(), Some("a.M".to_string()));
+        assert_eq!(document_class(&body(0x00)[..]).unwrap(), None);
+    }
+}
@@ -0,0 +1,806 @@
+//! Pulling embedded assets, such as bitmaps, sounds and binary data, out of
+//! a tag list.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::{Compression, FlateReadExt};
+use flate2::write::ZlibEncoder;
+#[cfg(feature = "image")]
+use image::{self, DynamicImage};
+
+use error::Error;
+use merge::parse_symbol_list;
+use primitives::read_u16_at;
+use shape::shape_version;
+use symbols::dependencies;
+use tag::{codes, Tag};
+
+/// The different ways a bitmap can be stored.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BitmapKind {
+    /// DefineBits: JPEG data missing its encoding tables, which live in the
+    /// shared JPEGTables tag.
+    Jpeg,
+    /// DefineBitsJPEG2: a complete JPEG (or, from SWF 8, a PNG or GIF).
+    JpegWithTables,
+    /// DefineBitsJPEG3: a complete image plus a zlib-compressed alpha
+    /// channel.
+    JpegWithAlpha,
+    /// DefineBitsJPEG4: the same as `JpegWithAlpha`, plus a deblocking
+    /// filter strength.
+    JpegWithDeblocking,
+    /// DefineBitsLossless: zlib-compressed colormapped or RGB pixels.
+    Lossless,
+    /// DefineBitsLossless2: zlib-compressed colormapped or ARGB pixels.
+    LosslessWithAlpha
+}
+
+/// A bitmap character, with its payload still in whatever form the tag
+/// stores it in.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Bitmap {
+    /// The character ID.
+    pub id: u16,
+    /// How the payload is stored.
+    pub kind: BitmapKind,
+    /// The tag payload, minus the character ID.
+    pub data: Vec<u8>
+}
+
+impl Bitmap {
+    /// Pulls a bitmap out of a tag, returning `None` if it isn't a bitmap.
+    pub fn from_tag(tag: &Tag) -> Result<Option<Self>, Error> {
+        let kind = match tag.code() {
+            codes::DEFINE_BITS => BitmapKind::Jpeg,
+            codes::DEFINE_BITS_JPEG_2 => BitmapKind::JpegWithTables,
+            codes::DEFINE_BITS_JPEG_3 => BitmapKind::JpegWithAlpha,
+            codes::DEFINE_BITS_JPEG_4 => BitmapKind::JpegWithDeblocking,
+            codes::DEFINE_BITS_LOSSLESS => BitmapKind::Lossless,
+            codes::DEFINE_BITS_LOSSLESS_2 => BitmapKind::LosslessWithAlpha,
+            _ => return Ok(None)
+        };
+        let id = try!(read_u16_at(tag.data(), 0));
+        Ok(Some(Bitmap {
+            id: id,
+            kind: kind,
+            data: tag.data()[2..].to_vec()
+        }))
+    }
+
+    /// Returns the image as a standalone file that an ordinary decoder can
+    /// open: a JPEG, or for SWF 8 and later possibly a PNG or GIF. Returns
+    /// `None` for lossless bitmaps, which have no file format of their own.
+    ///
+    /// `jpeg_tables` is the JPEGTables payload, which `BitmapKind::Jpeg`
+    /// bitmaps need merged back in. JPEG data is also cleaned of the bogus
+    /// EOI/SOI markers that older Flash versions wrote, which most decoders
+    /// refuse.
+    pub fn image_data(&self, jpeg_tables: Option<&[u8]>) -> Result<Option<Vec<u8>>, Error> {
+        let image = match self.kind {
+            BitmapKind::Jpeg => {
+                let image = fix_jpeg(&self.data);
+                return Ok(Some(match jpeg_tables {
+                    Some(tables) if !tables.is_empty() => merge_jpeg_tables(&fix_jpeg(tables), &image),
+                    _ => image
+                }));
+            },
+            BitmapKind::JpegWithTables => &self.data[..],
+            BitmapKind::JpegWithAlpha | BitmapKind::JpegWithDeblocking => {
+                let mut reader = &self.data[..];
+                let alpha_offset = try!(reader.read_u32::<LittleEndian>()) as usize;
+                if self.kind == BitmapKind::JpegWithDeblocking {
+                    try!(reader.read_u16::<LittleEndian>());
+                }
+                match reader.get(..alpha_offset) {
+                    Some(image) => image,
+                    None => return Err(Error::NotSwf)
+                }
+            },
+            BitmapKind::Lossless | BitmapKind::LosslessWithAlpha => return Ok(None)
+        };
+        Ok(Some(if image.starts_with(&[0xff, 0xd8]) || image.starts_with(&[0xff, 0xd9]) {
+            fix_jpeg(image)
+        } else {
+            image.to_vec()
+        }))
+    }
+}
+
+/// A decoded image, with straight (not premultiplied) alpha.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RgbaImage {
+    /// The width in pixels.
+    pub width: u16,
+    /// The height in pixels.
+    pub height: u16,
+    /// Four bytes per pixel, in RGBA order, row by row from the top with no
+    /// padding.
+    pub pixels: Vec<u8>
+}
+
+impl Bitmap {
+    /// Decodes a DefineBitsLossless or DefineBitsLossless2 bitmap, returning
+    /// `None` for any other kind.
+    ///
+    /// Colormapped, 15-bit and 24-bit pixel data are all handled, with their
+    /// row padding removed. DefineBitsLossless2 stores colors premultiplied
+    /// by alpha, which is undone here; DefineBitsLossless bitmaps come out
+    /// fully opaque.
+    pub fn decode_lossless(&self) -> Result<Option<RgbaImage>, Error> {
+        let has_alpha = match self.kind {
+            BitmapKind::Lossless => false,
+            BitmapKind::LosslessWithAlpha => true,
+            _ => return Ok(None)
+        };
+        let mut reader = &self.data[..];
+        let format = try!(reader.read_u8());
+        let width = try!(reader.read_u16::<LittleEndian>());
+        let height = try!(reader.read_u16::<LittleEndian>());
+        let color_count = if format == 3 {
+            try!(reader.read_u8()) as usize + 1
+        } else {
+            0
+        };
+
+        let mut data = Vec::new();
+        try!(reader.zlib_decode().read_to_end(&mut data));
+        let mut reader = &data[..];
+
+        let pixel_count = width as usize * height as usize;
+        let mut pixels = Vec::with_capacity(pixel_count * 4);
+        match format {
+            3 => {
+                let mut colors = Vec::with_capacity(color_count);
+                for _ in 0..color_count {
+                    let (r, g, b) = (try!(reader.read_u8()), try!(reader.read_u8()), try!(reader.read_u8()));
+                    let a = if has_alpha { try!(reader.read_u8()) } else { 255 };
+                    colors.push([r, g, b, a]);
+                }
+                // Rows of one-byte indices, padded to 32 bits.
+                let stride = (width as usize + 3) / 4 * 4;
+                for y in 0..height as usize {
+                    let row = match reader.get(y * stride..y * stride + width as usize) {
+                        Some(row) => row,
+                        None => return Err(Error::NotSwf)
+                    };
+                    for &index in row {
+                        // Out of range indices are drawn transparent.
+                        let color = colors.get(index as usize).cloned().unwrap_or([0, 0, 0, 0]);
+                        pixels.extend_from_slice(&color);
+                    }
+                }
+            },
+            4 if !has_alpha => {
+                // Rows of PIX15, padded to 32 bits.
+                let padding = (width as usize % 2) * 2;
+                for _ in 0..height {
+                    for _ in 0..width {
+                        let pixel = try!(reader.read_u16::<BigEndian>());
+                        let expand = |value: u16| ((value & 0x1f) * 255 / 31) as u8;
+                        pixels.extend_from_slice(&[expand(pixel >> 10), expand(pixel >> 5), expand(pixel), 255]);
+                    }
+                    reader = match reader.get(padding..) {
+                        Some(rest) => rest,
+                        None => return Err(Error::NotSwf)
+                    };
+                }
+            },
+            5 => {
+                // PIX24 or ARGB, always 32 bits so never padded.
+                for _ in 0..pixel_count {
+                    let a = try!(reader.read_u8());
+                    let (r, g, b) = (try!(reader.read_u8()), try!(reader.read_u8()), try!(reader.read_u8()));
+                    pixels.extend_from_slice(&[r, g, b, if has_alpha { a } else { 255 }]);
+                }
+            },
+            _ => return Err(Error::NotSwf)
+        }
+
+        if has_alpha {
+            for pixel in pixels.chunks_mut(4) {
+                let a = pixel[3] as u32;
+                if a != 0 && a != 255 {
+                    for channel in &mut pixel[..3] {
+                        *channel = (*channel as u32 * 255 / a).min(255) as u8;
+                    }
+                }
+            }
+        }
+
+        Ok(Some(RgbaImage {
+            width: width,
+            height: height,
+            pixels: pixels
+        }))
+    }
+}
+
+impl RgbaImage {
+    /// Encodes the image as a PNG file.
+    pub fn to_png(&self) -> Result<Vec<u8>, Error> {
+        // Each row starts with a filter type byte, and 0 means unfiltered.
+        let row_len = self.width as usize * 4;
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::Default);
+        for row in self.pixels.chunks(row_len.max(1)).take(self.height as usize) {
+            try!(encoder.write_all(&[0]));
+            try!(encoder.write_all(row));
+        }
+
+        let mut header = Vec::with_capacity(13);
+        try!(header.write_u32::<BigEndian>(self.width as u32));
+        try!(header.write_u32::<BigEndian>(self.height as u32));
+        // 8 bits per channel, RGBA, then the only compression, filtering
+        // and (no) interlacing methods there are.
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+        try!(write_png_chunk(&mut png, b"IHDR", &header));
+        try!(write_png_chunk(&mut png, b"IDAT", &try!(encoder.finish())));
+        try!(write_png_chunk(&mut png, b"IEND", &[]));
+        Ok(png)
+    }
+}
+
+#[cfg(feature = "image")]
+impl Bitmap {
+    /// Decodes any kind of bitmap into an `image::DynamicImage`, with
+    /// straight alpha. Needs the `image` feature.
+    ///
+    /// `jpeg_tables` is as for `image_data()`. Lossless bitmaps come out as
+    /// from `decode_lossless()`, and JPEGs with an alpha channel as RGBA
+    /// with the channel applied. Anything else comes out in whatever color
+    /// type its decoder gives.
+    pub fn to_image(&self, jpeg_tables: Option<&[u8]>) -> Result<DynamicImage, Error> {
+        if let Some(decoded) = try!(self.decode_lossless()) {
+            return decoded.to_image();
+        }
+        let data = match try!(self.image_data(jpeg_tables)) {
+            Some(data) => data,
+            None => return Err(Error::NotSwf)
+        };
+        let decoded = try!(image::load_from_memory(&data).map_err(|_| Error::NotSwf));
+        // The alpha channel only applies to JPEGs. PNGs and GIFs bring
+        // their own.
+        if !data.starts_with(&[0xff, 0xd8]) {
+            return Ok(decoded);
+        }
+        let alpha = match try!(self.jpeg_alpha()) {
+            Some(alpha) => alpha,
+            None => return Ok(decoded)
+        };
+        let mut rgba = decoded.to_rgba8();
+        // A short channel leaves the rest of the pixels opaque.
+        for (pixel, &a) in rgba.pixels_mut().zip(&alpha) {
+            pixel[3] = a;
+        }
+        Ok(DynamicImage::ImageRgba8(rgba))
+    }
+
+    /// Returns the decompressed alpha channel of a DefineBitsJPEG3 or
+    /// DefineBitsJPEG4 bitmap, one byte per pixel, or `None` for any other
+    /// kind or if it's empty.
+    fn jpeg_alpha(&self) -> Result<Option<Vec<u8>>, Error> {
+        let header_len = match self.kind {
+            BitmapKind::JpegWithAlpha => 4,
+            BitmapKind::JpegWithDeblocking => 6,
+            _ => return Ok(None)
+        };
+        let alpha_offset = try!((&self.data[..]).read_u32::<LittleEndian>()) as usize;
+        let compressed = match self.data.get(header_len + alpha_offset..) {
+            Some(compressed) if !compressed.is_empty() => compressed,
+            Some(_) => return Ok(None),
+            None => return Err(Error::NotSwf)
+        };
+        let mut alpha = Vec::new();
+        try!(compressed.zlib_decode().read_to_end(&mut alpha));
+        Ok(Some(alpha))
+    }
+}
+
+#[cfg(feature = "image")]
+impl RgbaImage {
+    /// Converts the image into an `image::DynamicImage`. Needs the `image`
+    /// feature.
+    pub fn to_image(&self) -> Result<DynamicImage, Error> {
+        match image::RgbaImage::from_raw(self.width as u32, self.height as u32, self.pixels.clone()) {
+            Some(buffer) => Ok(DynamicImage::ImageRgba8(buffer)),
+            None => Err(Error::NotSwf)
+        }
+    }
+}
+
+fn write_png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) -> Result<(), Error> {
+    try!(png.write_u32::<BigEndian>(data.len() as u32));
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[png.len() - data.len() - 4..]);
+    try!(png.write_u32::<BigEndian>(crc));
+    Ok(())
+}
+
+/// The CRC-32 used by PNG (and zip, and gzip).
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Strips the erroneous EOI/SOI marker pair that some files start their
+/// JPEG data with, and removes the EOI/SOI pair between the tables and the
+/// image when both are stored in one tag.
+fn fix_jpeg(data: &[u8]) -> Vec<u8> {
+    let data = if data.starts_with(&[0xff, 0xd9, 0xff, 0xd8]) { &data[4..] } else { data };
+    let pair = data.windows(4).position(|window| window == [0xff, 0xd9, 0xff, 0xd8]);
+    match pair {
+        Some(pos) => {
+            let mut fixed = data[..pos].to_vec();
+            fixed.extend_from_slice(&data[pos + 4..]);
+            fixed
+        },
+        None => data.to_vec()
+    }
+}
+
+/// Joins the tables and image into one stream, dropping the tables' EOI and
+/// the image's SOI.
+fn merge_jpeg_tables(tables: &[u8], image: &[u8]) -> Vec<u8> {
+    let tables = if tables.ends_with(&[0xff, 0xd9]) { &tables[..tables.len() - 2] } else { tables };
+    let image = if image.starts_with(&[0xff, 0xd8]) { &image[2..] } else { image };
+    let mut merged = tables.to_vec();
+    merged.extend_from_slice(image);
+    merged
+}
+
+/// Collects every bitmap defined in a tag list.
+pub fn bitmaps(tags: &[Tag]) -> Result<Vec<Bitmap>, Error> {
+    let mut bitmaps = Vec::new();
+    for tag in tags {
+        if let Some(bitmap) = try!(Bitmap::from_tag(tag)) {
+            bitmaps.push(bitmap);
+        }
+    }
+    Ok(bitmaps)
+}
+
+/// Where a bitmap is used, as found by `bitmap_usage()`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BitmapUsage {
+    /// The bitmap's character ID.
+    pub id: u16,
+    /// The shapes with a fill using the bitmap.
+    pub shapes: Vec<u16>,
+    /// The sprites placing any of those shapes.
+    pub sprites: Vec<u16>,
+    /// The names the bitmap, then its shapes, then its sprites are exported
+    /// or linked to classes under.
+    pub names: Vec<String>
+}
+
+impl BitmapUsage {
+    /// Returns the most specific name there is for the bitmap, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.names.first().map(|name| &name[..])
+    }
+}
+
+/// Works out which shapes and sprites use each bitmap defined in a tag list,
+/// so extracted images can be named after what they're part of. Bitmaps
+/// nothing uses are still listed, in the order they're defined.
+pub fn bitmap_usage(tags: &[Tag]) -> Result<Vec<BitmapUsage>, Error> {
+    let mut names: HashMap<u16, Vec<String>> = HashMap::new();
+    for tag in tags {
+        if tag.code() == codes::EXPORT_ASSETS || tag.code() == codes::SYMBOL_CLASS {
+            for (id, name) in try!(parse_symbol_list(tag.data())) {
+                names.entry(id).or_insert_with(Vec::new).push(name);
+            }
+        }
+    }
+
+    let mut usages = Vec::new();
+    let mut bitmap_ids = HashSet::new();
+    for bitmap in try!(bitmaps(tags)) {
+        bitmap_ids.insert(bitmap.id);
+        usages.push(BitmapUsage {
+            id: bitmap.id,
+            shapes: Vec::new(),
+            sprites: Vec::new(),
+            names: Vec::new()
+        });
+    }
+
+    let mut shape_bitmaps: HashMap<u16, Vec<u16>> = HashMap::new();
+    for tag in tags.iter().filter(|tag| shape_version(tag.code()).is_some()) {
+        let shape_id = try!(read_u16_at(tag.data(), 0));
+        let mut used = try!(dependencies(tag));
+        used.retain(|id| bitmap_ids.contains(id));
+        used.sort();
+        used.dedup();
+        for usage in usages.iter_mut().filter(|usage| used.contains(&usage.id)) {
+            usage.shapes.push(shape_id);
+        }
+        shape_bitmaps.insert(shape_id, used);
+    }
+
+    for tag in tags.iter().filter(|tag| tag.code() == codes::DEFINE_SPRITE) {
+        let sprite_id = try!(read_u16_at(tag.data(), 0));
+        for id in try!(dependencies(tag)) {
+            for bitmap_id in shape_bitmaps.get(&id).into_iter().flat_map(|ids| ids) {
+                let usage = usages.iter_mut().find(|usage| usage.id == *bitmap_id).unwrap();
+                if !usage.sprites.contains(&sprite_id) {
+                    usage.sprites.push(sprite_id);
+                }
+            }
+        }
+    }
+
+    for usage in &mut usages {
+        let ids = Some(usage.id).into_iter().chain(usage.shapes.iter().cloned()).chain(usage.sprites.iter().cloned());
+        usage.names = ids.flat_map(|id| names.get(&id).cloned().unwrap_or_default()).collect();
+    }
+    Ok(usages)
+}
+
+/// How an event sound is encoded.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SoundFormat {
+    /// Uncompressed samples in the byte order of whatever machine made the
+    /// file, which in practice was always little endian.
+    Uncompressed,
+    /// Flash's own ADPCM.
+    Adpcm,
+    /// MP3.
+    Mp3,
+    /// Uncompressed little endian samples.
+    UncompressedLittleEndian,
+    /// Nellymoser Asao at 16kHz.
+    Nellymoser16k,
+    /// Nellymoser Asao at 8kHz.
+    Nellymoser8k,
+    /// Nellymoser Asao.
+    Nellymoser,
+    /// Speex.
+    Speex,
+    /// A format code the spec doesn't define.
+    Unknown(u8)
+}
+
+impl SoundFormat {
+    /// Turns the four-bit format code used by DefineSound and
+    /// SoundStreamHead into a format.
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            0 => SoundFormat::Uncompressed,
+            1 => SoundFormat::Adpcm,
+            2 => SoundFormat::Mp3,
+            3 => SoundFormat::UncompressedLittleEndian,
+            4 => SoundFormat::Nellymoser16k,
+            5 => SoundFormat::Nellymoser8k,
+            6 => SoundFormat::Nellymoser,
+            11 => SoundFormat::Speex,
+            other => SoundFormat::Unknown(other)
+        }
+    }
+}
+
+/// An event sound, from a DefineSound tag.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Sound {
+    /// The character ID.
+    pub id: u16,
+    /// How the sound data is encoded.
+    pub format: SoundFormat,
+    /// The sample rate in Hz.
+    pub sample_rate: u32,
+    /// Whether samples are 16 bits rather than 8. Only meaningful for
+    /// uncompressed sounds.
+    pub is_16_bit: bool,
+    /// Whether the sound is stereo.
+    pub stereo: bool,
+    /// The number of samples per channel.
+    pub sample_count: u32,
+    /// The sound data, as stored.
+    pub data: Vec<u8>
+}
+
+impl Sound {
+    /// Pulls a sound out of a tag, returning `None` if it isn't a
+    /// DefineSound.
+    pub fn from_tag(tag: &Tag) -> Result<Option<Self>, Error> {
+        if tag.code() != codes::DEFINE_SOUND {
+            return Ok(None);
+        }
+        // DEFINESOUND overview:
+        // SoundId: u16, SoundFormat: UB4, SoundRate: UB2, SoundSize: UB1,
+        // SoundType: UB1, SoundSampleCount: u32, then the data.
+        let mut reader = tag.data();
+        let id = try!(reader.read_u16::<LittleEndian>());
+        let flags = try!(reader.read_u8());
+        let sample_count = try!(reader.read_u32::<LittleEndian>());
+        let format = SoundFormat::from_code(flags >> 4);
+        // The Nellymoser variants named for a rate ignore SoundRate.
+        let sample_rate = match format {
+            SoundFormat::Nellymoser16k => 16000,
+            SoundFormat::Nellymoser8k => 8000,
+            _ => [5512, 11025, 22050, 44100][((flags >> 2) & 0x03) as usize]
+        };
+        Ok(Some(Sound {
+            id: id,
+            format: format,
+            sample_rate: sample_rate,
+            is_16_bit: flags & 0x02 != 0,
+            stereo: flags & 0x01 != 0,
+            sample_count: sample_count,
+            data: reader.to_vec()
+        }))
+    }
+
+    /// Returns the sound as a standalone file that an ordinary player can
+    /// open, along with its usual extension: the MP3 frames as an `.mp3`,
+    /// or uncompressed samples wrapped up as a `.wav`. Returns `None` for
+    /// the other formats, which have no common file format of their own.
+    pub fn file_data(&self) -> Result<Option<(Vec<u8>, &'static str)>, Error> {
+        match self.format {
+            SoundFormat::Mp3 => {
+                // The frames come after SeekSamples, an i16.
+                match self.data.get(2..) {
+                    Some(frames) => Ok(Some((frames.to_vec(), "mp3"))),
+                    None => Err(Error::NotSwf)
+                }
+            },
+            SoundFormat::Uncompressed | SoundFormat::UncompressedLittleEndian => {
+                let mut wav = Vec::with_capacity(44 + self.data.len());
+                let channels = if self.stereo { 2 } else { 1 };
+                let bits = if self.is_16_bit { 16 } else { 8 };
+                try!(write_wav(&mut wav, self.sample_rate, channels, bits, &self.data));
+                Ok(Some((wav, "wav")))
+            },
+            _ => Ok(None)
+        }
+    }
+}
+
+/// Writes PCM samples out as a WAV file. 8-bit samples are unsigned, and
+/// 16-bit ones little endian, as both SWF and WAV have them.
+pub fn write_wav<W: Write>(mut writer: W, sample_rate: u32, channels: u16, bits: u16, samples: &[u8])
+    -> Result<(), Error>
+{
+    let block_align = channels * bits / 8;
+    try!(writer.write_all(b"RIFF"));
+    try!(writer.write_u32::<LittleEndian>(36 + samples.len() as u32));
+    try!(writer.write_all(b"WAVEfmt "));
+    try!(writer.write_u32::<LittleEndian>(16));
+    // PCM.
+    try!(writer.write_u16::<LittleEndian>(1));
+    try!(writer.write_u16::<LittleEndian>(channels));
+    try!(writer.write_u32::<LittleEndian>(sample_rate));
+    try!(writer.write_u32::<LittleEndian>(sample_rate * block_align as u32));
+    try!(writer.write_u16::<LittleEndian>(block_align));
+    try!(writer.write_u16::<LittleEndian>(bits));
+    try!(writer.write_all(b"data"));
+    try!(writer.write_u32::<LittleEndian>(samples.len() as u32));
+    try!(writer.write_all(samples));
+    Ok(())
+}
+
+/// Collects every event sound defined in a tag list.
+pub fn sounds(tags: &[Tag]) -> Result<Vec<Sound>, Error> {
+    let mut sounds = Vec::new();
+    for tag in tags {
+        if let Some(sound) = try!(Sound::from_tag(tag)) {
+            sounds.push(sound);
+        }
+    }
+    Ok(sounds)
+}
+
+/// An opaque blob, from a DefineBinaryData tag. These usually hold
+/// embedded files, or whole other SWFs.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BinaryData {
+    /// The character ID.
+    pub id: u16,
+    /// The data.
+    pub data: Vec<u8>
+}
+
+impl BinaryData {
+    /// Pulls the data out of a tag, returning `None` if it isn't a
+    /// DefineBinaryData.
+    pub fn from_tag(tag: &Tag) -> Result<Option<Self>, Error> {
+        if tag.code() != codes::DEFINE_BINARY_DATA {
+            return Ok(None);
+        }
+        // The character ID, then a reserved u32.
+        let id = try!(read_u16_at(tag.data(), 0));
+        match tag.data().get(6..) {
+            Some(data) => Ok(Some(BinaryData {
+                id: id,
+                data: data.to_vec()
+            })),
+            None => Err(Error::NotSwf)
+        }
+    }
+}
+
+/// Collects every DefineBinaryData blob in a tag list.
+pub fn binary_data(tags: &[Tag]) -> Result<Vec<BinaryData>, Error> {
+    let mut blobs = Vec::new();
+    for tag in tags {
+        if let Some(blob) = try!(BinaryData::from_tag(tag)) {
+            blobs.push(blob);
+        }
+    }
+    Ok(blobs)
+}
+
+/// Every bitmap, event sound and binary blob in a tag list.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Assets {
+    /// The bitmaps, as `bitmaps()` finds them.
+    pub bitmaps: Vec<Bitmap>,
+    /// The event sounds, as `sounds()` finds them.
+    pub sounds: Vec<Sound>,
+    /// The binary blobs, as `binary_data()` finds them.
+    pub binary_data: Vec<BinaryData>
+}
+
+/// Collects every asset in a tag list at once.
+pub fn collect(tags: &[Tag]) -> Result<Assets, Error> {
+    Ok(Assets {
+        bitmaps: try!(bitmaps(tags)),
+        sounds: try!(sounds(tags)),
+        binary_data: try!(binary_data(tags))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tag::{codes, Tag};
+
+    #[test]
+    fn test_jpeg_with_tables() {
+        // An erroneous header, then an image with no tables of its own.
+        let tag = Tag::new(codes::DEFINE_BITS, vec![1, 0, 0xff, 0xd9, 0xff, 0xd8, 0xff, 0xd8, 0xaa, 0xff, 0xd9]);
+        let bitmap = Bitmap::from_tag(&tag).unwrap().unwrap();
+        let tables = [0xff, 0xd8, 0xbb, 0xff, 0xd9];
+        assert_eq!(bitmap.image_data(Some(&tables)).unwrap().unwrap(),
+                   vec![0xff, 0xd8, 0xbb, 0xaa, 0xff, 0xd9]);
+    }
+
+    #[test]
+    fn test_bitmap_usage() {
+        // Bitmap 1 fills shape 2, which sprite 3 places. Bitmap 4 is unused.
+        let shape = Tag::new(codes::DEFINE_SHAPE, vec![
+            2, 0, 0x00, 1, 0x41, 1, 0, 0x00, 0, 0x00, 0x00
+        ]);
+        let mut sprite = vec![3, 0, 1, 0];
+        Tag::new(codes::PLACE_OBJECT_2, vec![0x02, 1, 0, 2, 0]).write_to(&mut sprite).unwrap();
+        Tag::new(codes::END, vec![]).write_to(&mut sprite).unwrap();
+        let tags = vec![
+            Tag::new(codes::DEFINE_BITS_JPEG_2, vec![1, 0, 0xff, 0xd8, 0xff, 0xd9]),
+            Tag::new(codes::DEFINE_BITS_JPEG_2, vec![4, 0, 0xff, 0xd8, 0xff, 0xd9]),
+            shape,
+            Tag::new(codes::DEFINE_SPRITE, sprite),
+            Tag::new(codes::EXPORT_ASSETS, vec![1, 0, 3, 0, b'H', b'e', b'r', b'o', 0])
+        ];
+
+        let usage = bitmap_usage(&tags).unwrap();
+        assert_eq!(usage[0], BitmapUsage {
+            id: 1,
+            shapes: vec![2],
+            sprites: vec![3],
+            names: vec!["Hero".to_owned()]
+        });
+        assert_eq!(usage[0].name(), Some("Hero"));
+        assert_eq!((usage[1].id, usage[1].name()), (4, None));
+        assert!(usage[1].shapes.is_empty());
+    }
+
+    #[test]
+    fn test_jpeg_with_alpha() {
+        // Tables and image as two streams, followed by the alpha channel.
+        let tag = Tag::new(codes::DEFINE_BITS_JPEG_3, vec![
+            1, 0, 10, 0, 0, 0,
+            0xff, 0xd8, 0xbb, 0xff, 0xd9, 0xff, 0xd8, 0xaa, 0xff, 0xd9,
+            0x78
+        ]);
+        let bitmap = Bitmap::from_tag(&tag).unwrap().unwrap();
+        assert_eq!(bitmap.kind, BitmapKind::JpegWithAlpha);
+        assert_eq!(bitmap.image_data(None).unwrap().unwrap(), vec![0xff, 0xd8, 0xbb, 0xaa, 0xff, 0xd9]);
+    }
+
+    #[test]
+    fn test_lossless_colormapped() {
+        use flate2::Compression;
+        use flate2::write::ZlibEncoder;
+        use std::io::Write;
+
+        // A 3x2 image with a two-color RGBA palette: opaque red and a
+        // half-transparent premultiplied white. Rows are padded to 4 bytes.
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::Default);
+        encoder.write_all(&[255, 0, 0, 255, 128, 128, 128, 128,
+                            0, 1, 0, 0xee,
+                            1, 1, 1, 0xee]).unwrap();
+        let mut data = vec![1, 0, 3, 3, 0, 2, 0, 1];
+        data.extend(encoder.finish().unwrap());
+
+        let tag = Tag::new(codes::DEFINE_BITS_LOSSLESS_2, data);
+        let image = Bitmap::from_tag(&tag).unwrap().unwrap().decode_lossless().unwrap().unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(&image.pixels[..8], &[255, 0, 0, 255, 255, 255, 255, 128]);
+        assert_eq!(image.pixels.len(), 24);
+        assert_eq!(&image.pixels[20..], &[255, 255, 255, 128]);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_to_image() {
+        use flate2::Compression;
+        use flate2::write::ZlibEncoder;
+        use image::{DynamicImage, ImageOutputFormat};
+        use std::io::{Cursor, Write};
+
+        let rgba = RgbaImage { width: 2, height: 1, pixels: vec![255, 0, 0, 255, 0, 0, 255, 255] };
+        let converted = rgba.to_image().unwrap().to_rgba8();
+        assert_eq!(converted.into_raw(), rgba.pixels);
+
+        // A DefineBitsJPEG3 whose alpha channel makes the first pixel
+        // transparent.
+        let mut jpeg = Cursor::new(Vec::new());
+        rgba.to_image().unwrap().write_to(&mut jpeg, ImageOutputFormat::Jpeg(90)).unwrap();
+        let jpeg = jpeg.into_inner();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::Default);
+        encoder.write_all(&[0, 200]).unwrap();
+        let mut data = vec![1, 0];
+        data.write_u32::<LittleEndian>(jpeg.len() as u32).unwrap();
+        data.extend_from_slice(&jpeg);
+        data.extend(encoder.finish().unwrap());
+
+        let bitmap = Bitmap::from_tag(&Tag::new(codes::DEFINE_BITS_JPEG_3, data)).unwrap().unwrap();
+        let decoded = bitmap.to_image(None).unwrap();
+        match decoded {
+            DynamicImage::ImageRgba8(ref pixels) => {
+                assert_eq!((pixels.width(), pixels.height()), (2, 1));
+                assert_eq!((pixels.get_pixel(0, 0)[3], pixels.get_pixel(1, 0)[3]), (0, 200));
+                assert!(pixels.get_pixel(1, 0)[2] > 200);
+            },
+            ref other => panic!("{:?}", other.color())
+        }
+    }
+
+    #[test]
+    fn test_png() {
+        let image = RgbaImage {
+            width: 1,
+            height: 1,
+            pixels: vec![255, 0, 0, 255]
+        };
+        let png = image.to_png().unwrap();
+        assert_eq!(&png[..8], &[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a]);
+        assert_eq!(&png[12..16], b"IHDR");
+        // IEND's CRC never changes.
+        assert_eq!(&png[png.len() - 8..], &[b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+    }
+
+    #[test]
+    fn test_sound_wav() {
+        // Uncompressed little endian, 22kHz, 16-bit mono, two samples.
+        let tag = Tag::new(codes::DEFINE_SOUND, vec![3, 0, 0x3a, 2, 0, 0, 0, 1, 0, 2, 0]);
+        let sound = Sound::from_tag(&tag).unwrap().unwrap();
+        assert_eq!(sound.format, SoundFormat::UncompressedLittleEndian);
+        assert_eq!((sound.sample_rate, sound.is_16_bit, sound.stereo), (22050, true, false));
+        let (wav, extension) = sound.file_data().unwrap().unwrap();
+        assert_eq!(extension, "wav");
+        assert_eq!(wav.len(), 44 + 4);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[44..], &[1, 0, 2, 0]);
+    }
+}
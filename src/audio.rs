@@ -0,0 +1,142 @@
+//! Sounds as plain audio assets, ready to hand to a decoder or player.
+//!
+//! Event sounds and reassembled MP3 streams both become an `AudioAsset`,
+//! which can be written out in the container its format belongs in: WAV
+//! for uncompressed samples, and MP3 for MP3. Those are what Rust decoders
+//! such as symphonia and rodio take, from a file or an `io::Cursor`. Flash's
+//! other codecs have no standard container, and are left as stored.
+//!
+//! Needs the `audio` feature.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use swf_headers::assets::{Sound, SoundFormat};
+//! use swf_headers::audio::AudioAsset;
+//! let sound = Sound {
+//!     id: 1,
+//!     format: SoundFormat::UncompressedLittleEndian,
+//!     sample_rate: 22050,
+//!     is_16_bit: true,
+//!     stereo: false,
+//!     sample_count: 2,
+//!     data: vec![1, 0, 2, 0]
+//! };
+//! let asset = AudioAsset::from(sound);
+//! let mut wav = Vec::new();
+//! assert_eq!(asset.write_to(&mut wav).unwrap(), Some("wav"));
+//! ```
+
+use std::io::Write;
+
+use assets::{write_wav, Sound, SoundFormat};
+use error::Error;
+use sound::Mp3Stream;
+
+/// A sound with its parameters, and its data ready for a container.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AudioAsset {
+    /// How `data` is encoded.
+    pub format: SoundFormat,
+    /// The sample rate in Hz.
+    pub sample_rate: u32,
+    /// 1 for mono, 2 for stereo.
+    pub channels: u16,
+    /// The bits per sample for uncompressed sounds, 8 or 16. Compressed
+    /// sounds decode to 16.
+    pub bits_per_sample: u16,
+    /// The samples or frames, with anything SWF-specific in front of them
+    /// removed.
+    pub data: Vec<u8>
+}
+
+impl AudioAsset {
+    /// Returns the extension of the container `write_to()` writes, or
+    /// `None` if the format doesn't have one.
+    pub fn extension(&self) -> Option<&'static str> {
+        match self.format {
+            SoundFormat::Uncompressed | SoundFormat::UncompressedLittleEndian => Some("wav"),
+            SoundFormat::Mp3 => Some("mp3"),
+            _ => None
+        }
+    }
+
+    /// Writes the sound out in its container, returning the container's
+    /// extension. Writes nothing and returns `None` if the format doesn't
+    /// have one.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<Option<&'static str>, Error> {
+        match self.format {
+            SoundFormat::Uncompressed | SoundFormat::UncompressedLittleEndian => {
+                try!(write_wav(writer, self.sample_rate, self.channels, self.bits_per_sample, &self.data));
+            },
+            // MP3 frames are a file as they are.
+            SoundFormat::Mp3 => try!(writer.write_all(&self.data)),
+            _ => {}
+        }
+        Ok(self.extension())
+    }
+}
+
+impl From<Sound> for AudioAsset {
+    fn from(sound: Sound) -> Self {
+        let mut data = sound.data;
+        if sound.format == SoundFormat::Mp3 {
+            // The frames come after SeekSamples, an i16.
+            data.drain(..2.min(data.len()));
+        }
+        AudioAsset {
+            format: sound.format,
+            sample_rate: sound.sample_rate,
+            channels: if sound.stereo { 2 } else { 1 },
+            bits_per_sample: if sound.is_16_bit || sound.format == SoundFormat::Mp3 { 16 } else { 8 },
+            data: data
+        }
+    }
+}
+
+impl From<Mp3Stream> for AudioAsset {
+    fn from(stream: Mp3Stream) -> Self {
+        AudioAsset {
+            format: SoundFormat::Mp3,
+            sample_rate: stream.sample_rate,
+            channels: if stream.stereo { 2 } else { 1 },
+            bits_per_sample: 16,
+            data: stream.data
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assets::{Sound, SoundFormat};
+
+    #[test]
+    fn test_containers() {
+        let sound = Sound {
+            id: 1,
+            format: SoundFormat::Uncompressed,
+            sample_rate: 11025,
+            is_16_bit: false,
+            stereo: true,
+            sample_count: 1,
+            data: vec![0x80, 0x7f]
+        };
+        let (expected, _) = sound.file_data().unwrap().unwrap();
+        let asset = AudioAsset::from(sound.clone());
+        assert_eq!((asset.channels, asset.bits_per_sample), (2, 8));
+        let mut wav = Vec::new();
+        assert_eq!(asset.write_to(&mut wav).unwrap(), Some("wav"));
+        assert_eq!(wav, expected);
+
+        let mp3 = AudioAsset::from(Sound { format: SoundFormat::Mp3, data: vec![0, 0, 0xff, 0xfb], ..sound.clone() });
+        let mut file = Vec::new();
+        assert_eq!(mp3.write_to(&mut file).unwrap(), Some("mp3"));
+        assert_eq!(file, vec![0xff, 0xfb]);
+
+        let adpcm = AudioAsset::from(Sound { format: SoundFormat::Adpcm, ..sound });
+        let mut nothing = Vec::new();
+        assert_eq!(adpcm.write_to(&mut nothing).unwrap(), None);
+        assert!(nothing.is_empty());
+    }
+}
@@ -0,0 +1,698 @@
+//! Disassembling the AVM1 bytecode in DoAction and DoInitAction tags.
+//!
+//! This only decodes action records one after another, in the order they're
+//! stored. Nothing is executed, and branches aren't followed, so function
+//! bodies show up inline right after the action that defines them.
+//!
+//! `as2_classes()` lists the classes a movie defines, the ActionScript 2
+//! counterpart of `abc::document_class()` for finding where to start
+//! reading.
+
+use std::collections::HashSet;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use error::Error;
+use merge::parse_symbol_list;
+use primitives::{read_string, read_u16_at};
+use tag::{self, codes, Tag};
+
+/// The prefix the Flash IDE exports ActionScript 2 classes under.
+const PACKAGES_PREFIX: &'static str = "__Packages.";
+
+/// A value pushed onto the stack by a Push action.
+#[derive(Clone, PartialEq, Debug)]
+pub enum PushValue {
+    /// A literal string.
+    Str(String),
+    /// A 32-bit float.
+    Float(f32),
+    /// `null`.
+    Null,
+    /// `undefined`.
+    Undefined,
+    /// The contents of a register.
+    Register(u8),
+    /// A boolean.
+    Bool(bool),
+    /// A 64-bit float.
+    Double(f64),
+    /// A 32-bit integer.
+    Int(i32),
+    /// An index into the current constant pool.
+    Constant(u16)
+}
+
+/// A single decoded action record.
+///
+/// Actions without a payload of their own, which covers most of the stack
+/// and arithmetic operations, come out as `Simple`. Use `Action::name()` to
+/// get a readable mnemonic for any of them.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Action {
+    /// Any action with no payload.
+    Simple(u8),
+    /// ActionGotoFrame, with a zero-based frame number.
+    GotoFrame(u16),
+    /// ActionGetURL.
+    GetUrl {
+        /// The URL to load.
+        url: String,
+        /// The window or level to load it into.
+        target: String
+    },
+    /// ActionStoreRegister.
+    StoreRegister(u8),
+    /// ActionConstantPool, replacing the current constant pool.
+    ConstantPool(Vec<String>),
+    /// ActionWaitForFrame.
+    WaitForFrame {
+        /// The frame to wait for.
+        frame: u16,
+        /// How many actions to skip if it isn't loaded.
+        skip_count: u8
+    },
+    /// ActionSetTarget.
+    SetTarget(String),
+    /// ActionGoToLabel.
+    GotoLabel(String),
+    /// ActionWaitForFrame2, with the frame taken from the stack.
+    WaitForFrame2 {
+        /// How many actions to skip if the frame isn't loaded.
+        skip_count: u8
+    },
+    /// ActionDefineFunction2.
+    DefineFunction2 {
+        /// The function name, empty for anonymous functions.
+        name: String,
+        /// How many registers the function uses.
+        register_count: u8,
+        /// The preload and suppress flags.
+        flags: u16,
+        /// Each parameter's name, and the register it's stored in, with
+        /// register 0 meaning it isn't stored in one.
+        params: Vec<(u8, String)>,
+        /// The size in bytes of the function body that follows.
+        code_size: u16
+    },
+    /// ActionWith.
+    With {
+        /// The size in bytes of the block that follows.
+        code_size: u16
+    },
+    /// ActionPush.
+    Push(Vec<PushValue>),
+    /// ActionJump, by a byte offset from the end of this action.
+    Jump(i16),
+    /// ActionGetURL2.
+    GetUrl2 {
+        /// 0 for no variables, 1 to send them with GET, 2 with POST.
+        send_vars_method: u8,
+        /// Whether the target is a sprite rather than a window.
+        load_target: bool,
+        /// Whether variables are loaded rather than a movie.
+        load_variables: bool
+    },
+    /// ActionDefineFunction.
+    DefineFunction {
+        /// The function name, empty for anonymous functions.
+        name: String,
+        /// The parameter names.
+        params: Vec<String>,
+        /// The size in bytes of the function body that follows.
+        code_size: u16
+    },
+    /// ActionIf, jumping by a byte offset from the end of this action if
+    /// the top of the stack is true.
+    If(i16),
+    /// ActionGotoFrame2.
+    GotoFrame2 {
+        /// Whether to play after going to the frame.
+        play: bool,
+        /// A number of frames to add to the frame taken from the stack.
+        scene_bias: Option<u16>
+    },
+    /// Any action with a payload that isn't decoded, such as ActionTry.
+    Unknown {
+        /// The action code.
+        code: u8,
+        /// The payload.
+        data: Vec<u8>
+    }
+}
+
+impl Action {
+    /// Returns the action code.
+    pub fn code(&self) -> u8 {
+        match *self {
+            Action::Simple(code) | Action::Unknown { code, .. } => code,
+            Action::GotoFrame(_) => 0x81,
+            Action::GetUrl { .. } => 0x83,
+            Action::StoreRegister(_) => 0x87,
+            Action::ConstantPool(_) => 0x88,
+            Action::WaitForFrame { .. } => 0x8a,
+            Action::SetTarget(_) => 0x8b,
+            Action::GotoLabel(_) => 0x8c,
+            Action::WaitForFrame2 { .. } => 0x8d,
+            Action::DefineFunction2 { .. } => 0x8e,
+            Action::With { .. } => 0x94,
+            Action::Push(_) => 0x96,
+            Action::Jump(_) => 0x99,
+            Action::GetUrl2 { .. } => 0x9a,
+            Action::DefineFunction { .. } => 0x9b,
+            Action::If(_) => 0x9d,
+            Action::GotoFrame2 { .. } => 0x9f
+        }
+    }
+
+    /// Returns the action's name as used in the SWF specification, minus the
+    /// "Action" prefix, or `None` for codes the specification doesn't define.
+    pub fn name(&self) -> Option<&'static str> {
+        action_name(self.code())
+    }
+}
+
+/// Returns the specification's name for an action code, minus the "Action"
+/// prefix.
+pub fn action_name(code: u8) -> Option<&'static str> {
+    Some(match code {
+        0x04 => "NextFrame",
+        0x05 => "PreviousFrame",
+        0x06 => "Play",
+        0x07 => "Stop",
+        0x08 => "ToggleQuality",
+        0x09 => "StopSounds",
+        0x0a => "Add",
+        0x0b => "Subtract",
+        0x0c => "Multiply",
+        0x0d => "Divide",
+        0x0e => "Equals",
+        0x0f => "Less",
+        0x10 => "And",
+        0x11 => "Or",
+        0x12 => "Not",
+        0x13 => "StringEquals",
+        0x14 => "StringLength",
+        0x15 => "StringExtract",
+        0x17 => "Pop",
+        0x18 => "ToInteger",
+        0x1c => "GetVariable",
+        0x1d => "SetVariable",
+        0x20 => "SetTarget2",
+        0x21 => "StringAdd",
+        0x22 => "GetProperty",
+        0x23 => "SetProperty",
+        0x24 => "CloneSprite",
+        0x25 => "RemoveSprite",
+        0x26 => "Trace",
+        0x27 => "StartDrag",
+        0x28 => "EndDrag",
+        0x29 => "StringLess",
+        0x2a => "Throw",
+        0x2b => "CastOp",
+        0x2c => "ImplementsOp",
+        0x30 => "RandomNumber",
+        0x31 => "MBStringLength",
+        0x32 => "CharToAscii",
+        0x33 => "AsciiToChar",
+        0x34 => "GetTime",
+        0x35 => "MBStringExtract",
+        0x36 => "MBCharToAscii",
+        0x37 => "MBAsciiToChar",
+        0x3a => "Delete",
+        0x3b => "Delete2",
+        0x3c => "DefineLocal",
+        0x3d => "CallFunction",
+        0x3e => "Return",
+        0x3f => "Modulo",
+        0x40 => "NewObject",
+        0x41 => "DefineLocal2",
+        0x42 => "InitArray",
+        0x43 => "InitObject",
+        0x44 => "TypeOf",
+        0x45 => "TargetPath",
+        0x46 => "Enumerate",
+        0x47 => "Add2",
+        0x48 => "Less2",
+        0x49 => "Equals2",
+        0x4a => "ToNumber",
+        0x4b => "ToString",
+        0x4c => "PushDuplicate",
+        0x4d => "StackSwap",
+        0x4e => "GetMember",
+        0x4f => "SetMember",
+        0x50 => "Increment",
+        0x51 => "Decrement",
+        0x52 => "CallMethod",
+        0x53 => "NewMethod",
+        0x54 => "InstanceOf",
+        0x55 => "Enumerate2",
+        0x60 => "BitAnd",
+        0x61 => "BitOr",
+        0x62 => "BitXor",
+        0x63 => "BitLShift",
+        0x64 => "BitRShift",
+        0x65 => "BitURShift",
+        0x66 => "StrictEquals",
+        0x67 => "Greater",
+        0x68 => "StringGreater",
+        0x69 => "Extends",
+        0x81 => "GotoFrame",
+        0x83 => "GetURL",
+        0x87 => "StoreRegister",
+        0x88 => "ConstantPool",
+        0x8a => "WaitForFrame",
+        0x8b => "SetTarget",
+        0x8c => "GoToLabel",
+        0x8d => "WaitForFrame2",
+        0x8e => "DefineFunction2",
+        0x8f => "Try",
+        0x94 => "With",
+        0x96 => "Push",
+        0x99 => "Jump",
+        0x9a => "GetURL2",
+        0x9b => "DefineFunction",
+        0x9d => "If",
+        0x9e => "Call",
+        0x9f => "GotoFrame2",
+        _ => return None
+    })
+}
+
+/// An iterator over the action records in a block of AVM1 bytecode.
+///
+/// It stops at the ActionEndFlag, at the end of the data, or after the
+/// first error.
+pub struct Actions<'a> {
+    data: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Actions<'a> {
+    /// Creates an iterator over raw bytecode, such as a DoAction payload.
+    pub fn new(data: &'a [u8]) -> Self {
+        Actions {
+            data: data,
+            pos: 0
+        }
+    }
+
+    /// Creates an iterator over the bytecode in a DoAction or DoInitAction
+    /// tag, returning `None` for any other kind of tag.
+    pub fn from_tag(tag: &'a Tag) -> Option<Self> {
+        match tag.code() {
+            codes::DO_ACTION => Some(Actions::new(tag.data())),
+            // DoInitAction starts with the ID of the sprite it initializes.
+            codes::DO_INIT_ACTION => Some(Actions::new(tag.data().get(2..).unwrap_or(&[]))),
+            _ => None
+        }
+    }
+
+    /// Returns the byte offset of the next action. Jump and If offsets are
+    /// relative to this, as it stands after reading them.
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+
+    fn read_action(&mut self) -> Result<Option<Action>, Error> {
+        let mut reader = &self.data[self.pos..];
+        let code = try!(reader.read_u8());
+        if code == 0 {
+            return Ok(None);
+        }
+        // Only actions with the high bit set have a length and payload.
+        if code < 0x80 {
+            self.pos += 1;
+            return Ok(Some(Action::Simple(code)));
+        }
+        let length = try!(reader.read_u16::<LittleEndian>()) as usize;
+        if reader.len() < length {
+            return Err(Error::NotSwf);
+        }
+        let payload = &reader[..length];
+        let action = try!(parse_payload(code, payload));
+        self.pos += 3 + length;
+        Ok(Some(action))
+    }
+}
+
+impl<'a> Iterator for Actions<'a> {
+    type Item = Result<Action, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        match self.read_action() {
+            Ok(Some(action)) => Some(Ok(action)),
+            Ok(None) => {
+                self.pos = self.data.len();
+                None
+            },
+            Err(err) => {
+                self.pos = self.data.len();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+fn parse_payload(code: u8, payload: &[u8]) -> Result<Action, Error> {
+    let mut reader = payload;
+    Ok(match code {
+        0x81 => Action::GotoFrame(try!(reader.read_u16::<LittleEndian>())),
+        0x83 => Action::GetUrl {
+            url: try!(read_string(&mut reader)),
+            target: try!(read_string(&mut reader))
+        },
+        0x87 => Action::StoreRegister(try!(reader.read_u8())),
+        0x88 => {
+            let count = try!(reader.read_u16::<LittleEndian>());
+            let mut constants = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                constants.push(try!(read_string(&mut reader)));
+            }
+            Action::ConstantPool(constants)
+        },
+        0x8a => Action::WaitForFrame {
+            frame: try!(reader.read_u16::<LittleEndian>()),
+            skip_count: try!(reader.read_u8())
+        },
+        0x8b => Action::SetTarget(try!(read_string(&mut reader))),
+        0x8c => Action::GotoLabel(try!(read_string(&mut reader))),
+        0x8d => Action::WaitForFrame2 {
+            skip_count: try!(reader.read_u8())
+        },
+        0x8e => {
+            let name = try!(read_string(&mut reader));
+            let param_count = try!(reader.read_u16::<LittleEndian>());
+            let register_count = try!(reader.read_u8());
+            let flags = try!(reader.read_u16::<LittleEndian>());
+            let mut params = Vec::with_capacity(param_count as usize);
+            for _ in 0..param_count {
+                let register = try!(reader.read_u8());
+                params.push((register, try!(read_string(&mut reader))));
+            }
+            Action::DefineFunction2 {
+                name: name,
+                register_count: register_count,
+                flags: flags,
+                params: params,
+                code_size: try!(reader.read_u16::<LittleEndian>())
+            }
+        },
+        0x94 => Action::With {
+            code_size: try!(reader.read_u16::<LittleEndian>())
+        },
+        0x96 => {
+            let mut values = Vec::new();
+            while !reader.is_empty() {
+                values.push(try!(read_push_value(&mut reader)));
+            }
+            Action::Push(values)
+        },
+        0x99 => Action::Jump(try!(reader.read_i16::<LittleEndian>())),
+        0x9a => {
+            let flags = try!(reader.read_u8());
+            Action::GetUrl2 {
+                send_vars_method: flags >> 6,
+                load_target: flags & 0x02 != 0,
+                load_variables: flags & 0x01 != 0
+            }
+        },
+        0x9b => {
+            let name = try!(read_string(&mut reader));
+            let param_count = try!(reader.read_u16::<LittleEndian>());
+            let mut params = Vec::with_capacity(param_count as usize);
+            for _ in 0..param_count {
+                params.push(try!(read_string(&mut reader)));
+            }
+            Action::DefineFunction {
+                name: name,
+                params: params,
+                code_size: try!(reader.read_u16::<LittleEndian>())
+            }
+        },
+        0x9d => Action::If(try!(reader.read_i16::<LittleEndian>())),
+        0x9f => {
+            let flags = try!(reader.read_u8());
+            let scene_bias = if flags & 0x02 != 0 {
+                Some(try!(reader.read_u16::<LittleEndian>()))
+            } else {
+                None
+            };
+            Action::GotoFrame2 {
+                play: flags & 0x01 != 0,
+                scene_bias: scene_bias
+            }
+        },
+        _ => Action::Unknown {
+            code: code,
+            data: payload.to_vec()
+        }
+    })
+}
+
+fn read_push_value(reader: &mut &[u8]) -> Result<PushValue, Error> {
+    Ok(match try!(reader.read_u8()) {
+        0 => PushValue::Str(try!(read_string(reader))),
+        1 => PushValue::Float(try!(reader.read_f32::<LittleEndian>())),
+        2 => PushValue::Null,
+        3 => PushValue::Undefined,
+        4 => PushValue::Register(try!(reader.read_u8())),
+        5 => PushValue::Bool(try!(reader.read_u8()) != 0),
+        6 => {
+            // Doubles are stored as two little-endian halves, high half first.
+            let high = try!(reader.read_u32::<LittleEndian>()) as u64;
+            let low = try!(reader.read_u32::<LittleEndian>()) as u64;
+            PushValue::Double(f64::from_bits(high << 32 | low))
+        },
+        7 => PushValue::Int(try!(reader.read_i32::<LittleEndian>())),
+        8 => PushValue::Constant(try!(reader.read_u8()) as u16),
+        9 => PushValue::Constant(try!(reader.read_u16::<LittleEndian>())),
+        _ => return Err(Error::NotSwf)
+    })
+}
+
+/// Collects every string literal in the scripts of a tag list, including
+/// those inside sprites: constant pool entries, pushed strings, and getURL
+/// arguments. Each string appears once, in the order it was first seen.
+pub fn extract_strings(tags: &[Tag]) -> Result<Vec<String>, Error> {
+    let mut harvest = Harvest::new();
+    try!(harvest.walk(tags));
+    Ok(harvest.strings)
+}
+
+/// Collects every URL a tag list's scripts could load, including those
+/// inside sprites. Each URL appears once, in the order it was first seen.
+///
+/// getURL URLs are always found. getURL2, which is what loadMovie,
+/// loadVariables and friends compile to, takes its URL from the stack, so
+/// those are only found when the URL was pushed as a literal right before
+/// the call, which covers what the Flash IDE generates for constant URLs.
+pub fn extract_urls(tags: &[Tag]) -> Result<Vec<String>, Error> {
+    let mut harvest = Harvest::new();
+    try!(harvest.walk(tags));
+    Ok(harvest.urls)
+}
+
+/// Lists the ActionScript 2 classes defined in a tag list by their class
+/// paths, such as `com.example.Main`, in the order they're exported.
+///
+/// The Flash IDE compiles each class into the DoInitAction of an empty
+/// sprite, exported as `__Packages.` and the class path, which is how
+/// they're found. Exports without a DoInitAction define nothing, so they're
+/// left out.
+pub fn as2_classes(tags: &[Tag]) -> Result<Vec<String>, Error> {
+    let mut initialized = HashSet::new();
+    for tag in tags.iter().filter(|tag| tag.code() == codes::DO_INIT_ACTION) {
+        initialized.insert(try!(read_u16_at(tag.data(), 0)));
+    }
+
+    let mut classes = Vec::new();
+    let mut seen = HashSet::new();
+    for tag in tags.iter().filter(|tag| tag.code() == codes::EXPORT_ASSETS) {
+        for (id, name) in try!(parse_symbol_list(tag.data())) {
+            if !name.starts_with(PACKAGES_PREFIX) || !initialized.contains(&id) {
+                continue;
+            }
+            let path = name[PACKAGES_PREFIX.len()..].to_string();
+            if seen.insert(path.clone()) {
+                classes.push(path);
+            }
+        }
+    }
+    Ok(classes)
+}
+
+struct Harvest {
+    strings: Vec<String>,
+    seen_strings: HashSet<String>,
+    urls: Vec<String>,
+    seen_urls: HashSet<String>
+}
+
+impl Harvest {
+    fn new() -> Self {
+        Harvest {
+            strings: Vec::new(),
+            seen_strings: HashSet::new(),
+            urls: Vec::new(),
+            seen_urls: HashSet::new()
+        }
+    }
+
+    fn walk(&mut self, tags: &[Tag]) -> Result<(), Error> {
+        for tag in tags {
+            if tag.code() == codes::DEFINE_SPRITE {
+                if tag.data().len() < 4 {
+                    return Err(Error::NotSwf);
+                }
+                try!(self.walk(&try!(tag::read_all(&tag.data()[4..]))));
+            } else if let Some(actions) = Actions::from_tag(tag) {
+                try!(self.script(actions));
+            }
+        }
+        Ok(())
+    }
+
+    fn script(&mut self, actions: Actions) -> Result<(), Error> {
+        let mut pool: Vec<String> = Vec::new();
+        // Strings pushed since the last action that wasn't a push.
+        let mut pushed: Vec<String> = Vec::new();
+
+        for action in actions {
+            match try!(action) {
+                Action::ConstantPool(constants) => {
+                    for constant in &constants {
+                        self.string(constant);
+                    }
+                    pool = constants;
+                    pushed.clear();
+                },
+                Action::Push(values) => {
+                    for value in values {
+                        match value {
+                            PushValue::Str(string) => {
+                                self.string(&string);
+                                pushed.push(string);
+                            },
+                            PushValue::Constant(index) => {
+                                match pool.get(index as usize) {
+                                    Some(string) => pushed.push(string.clone()),
+                                    None => pushed.clear()
+                                }
+                            },
+                            _ => pushed.clear()
+                        }
+                    }
+                },
+                Action::GetUrl { url, target } => {
+                    self.string(&url);
+                    self.string(&target);
+                    self.url(&url);
+                    pushed.clear();
+                },
+                Action::GetUrl2 { .. } => {
+                    // The URL is pushed first, then the target.
+                    if pushed.len() >= 2 {
+                        let url = pushed[pushed.len() - 2].clone();
+                        self.url(&url);
+                    }
+                    pushed.clear();
+                },
+                _ => pushed.clear()
+            }
+        }
+        Ok(())
+    }
+
+    fn string(&mut self, string: &str) {
+        if self.seen_strings.insert(string.to_owned()) {
+            self.strings.push(string.to_owned());
+        }
+    }
+
+    fn url(&mut self, url: &str) {
+        if !url.is_empty() && self.seen_urls.insert(url.to_owned()) {
+            self.urls.push(url.to_owned());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tag::{codes, Tag};
+
+    #[test]
+    fn test_disassemble() {
+        let tag = Tag::new(codes::DO_ACTION, vec![
+            // Push "a", 1.5 as a double, register 2
+            0x96, 14, 0, 0, b'a', 0, 6, 0, 0, 0xf8, 0x3f, 0, 0, 0, 0, 4, 2,
+            // GetURL "u" "_self"
+            0x83, 8, 0, b'u', 0, b'_', b's', b'e', b'l', b'f', 0,
+            // Stop, then the end flag
+            0x07, 0x00,
+            // Never reached
+            0x06
+        ]);
+        let actions: Vec<_> = Actions::from_tag(&tag).unwrap().map(Result::unwrap).collect();
+        assert_eq!(actions, vec![
+            Action::Push(vec![
+                PushValue::Str("a".to_owned()),
+                PushValue::Double(1.5),
+                PushValue::Register(2)
+            ]),
+            Action::GetUrl { url: "u".to_owned(), target: "_self".to_owned() },
+            Action::Simple(0x07)
+        ]);
+        assert_eq!(actions[2].name(), Some("Stop"));
+    }
+
+    #[test]
+    fn test_harvest() {
+        let tags = vec![
+            Tag::new(codes::DO_ACTION, vec![
+                // ConstantPool ["http://a/b.swf", "_level1"]
+                0x88, 25, 0, 2, 0,
+                b'h', b't', b't', b'p', b':', b'/', b'/', b'a', b'/', b'b', b'.', b's', b'w', b'f', 0,
+                b'_', b'l', b'e', b'v', b'e', b'l', b'1', 0,
+                // Push constant 0, constant 1, then loadMovie's GetURL2
+                0x96, 4, 0, 8, 0, 8, 1,
+                0x9a, 1, 0, 0x40,
+                // GetURL "http://c" ""
+                0x83, 10, 0, b'h', b't', b't', b'p', b':', b'/', b'/', b'c', 0, 0,
+                0x00
+            ])
+        ];
+        assert_eq!(extract_urls(&tags).unwrap(), vec!["http://a/b.swf", "http://c"]);
+        assert_eq!(extract_strings(&tags).unwrap(), vec!["http://a/b.swf", "_level1", "http://c", ""]);
+    }
+
+    #[test]
+    fn test_as2_classes() {
+        let tags = vec![
+            Tag::new(codes::DEFINE_SPRITE, vec![1, 0, 1, 0, 0x40, 0, 0, 0]),
+            Tag::new(codes::DEFINE_SPRITE, vec![2, 0, 1, 0, 0x40, 0, 0, 0]),
+            Tag::new(codes::DEFINE_SPRITE, vec![3, 0, 1, 0, 0x40, 0, 0, 0]),
+            Tag::new(codes::EXPORT_ASSETS, vec![
+                3, 0,
+                1, 0, b'_', b'_', b'P', b'a', b'c', b'k', b'a', b'g', b'e', b's', b'.', b'a', b'.', b'B', 0,
+                2, 0, b'_', b'_', b'P', b'a', b'c', b'k', b'a', b'g', b'e', b's', b'.', b'C', 0,
+                3, 0, b'l', b'o', b'g', b'o', 0
+            ]),
+            // Only a.B and the logo have init actions.
+            Tag::new(codes::DO_INIT_ACTION, vec![1, 0, 0]),
+            Tag::new(codes::DO_INIT_ACTION, vec![3, 0, 0])
+        ];
+        assert_eq!(as2_classes(&tags).unwrap(), vec!["a.B".to_string()]);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut actions = Actions::new(&[0x81, 2, 0, 1]);
+        assert!(actions.next().unwrap().is_err());
+        assert!(actions.next().is_none());
+    }
+}
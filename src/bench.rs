@@ -0,0 +1,109 @@
+//! Generated inputs for benchmarking, the same ones the crate's own
+//! benchmarks in `benches/` run on, so results from different machines and
+//! backends can be compared.
+//!
+//! Every input is built from a fixed seed, so it's identical from run to
+//! run. The bodies compress about as well as a typical movie's: better than
+//! random bytes, worse than a run of zeros.
+//!
+//! Needs the `bench` feature.
+
+use super::{Signature, SwfHeaders};
+use codec;
+use primitives::Rect;
+use tag::{codes, Tag};
+use writer;
+
+/// A single generated file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Input {
+    /// What the file is, usable as a benchmark name.
+    pub name: &'static str,
+    /// The complete file.
+    pub data: Vec<u8>
+}
+
+/// Returns a small and a large file with each signature, in a fixed order.
+///
+/// The small files are a few KiB, where the header parse dominates, and the
+/// large ones around 4MiB, where decompression does.
+pub fn inputs() -> Vec<Input> {
+    let mut inputs = Vec::new();
+    for &(name, signature, body_len) in &[
+        ("small_fws", Signature::Uncompressed, 4 * 1024),
+        ("small_cws", Signature::ZlibCompressed, 4 * 1024),
+        ("small_zws", Signature::LzmaCompressed, 4 * 1024),
+        ("large_fws", Signature::Uncompressed, 4 * 1024 * 1024),
+        ("large_cws", Signature::ZlibCompressed, 4 * 1024 * 1024),
+        ("large_zws", Signature::LzmaCompressed, 4 * 1024 * 1024)
+    ] {
+        inputs.push(Input { name: name, data: movie(signature, body_len) });
+    }
+    inputs
+}
+
+/// Builds a movie of roughly `body_len` bytes once decompressed: a frame
+/// at a time, each with 2KiB of binary data and a ShowFrame.
+pub fn movie(signature: Signature, body_len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491u32;
+    let mut tags = Vec::new();
+    let mut len = 0;
+    let mut id = 1u16;
+    while len < body_len {
+        let mut data = vec![id as u8, (id >> 8) as u8, 0, 0, 0, 0];
+        for _ in 0..2048 {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            // Mostly a handful of common bytes, with the odd random one.
+            let byte = (state >> 16) as u8;
+            data.push(if byte < 0xe0 { byte & 0x0f } else { byte });
+        }
+        len += data.len() + 6 + 2;
+        tags.push(Tag::new(codes::DEFINE_BINARY_DATA, data));
+        tags.push(Tag::new(codes::SHOW_FRAME, vec![]));
+        id = id.wrapping_add(1);
+    }
+    let headers = SwfHeaders {
+        signature: signature,
+        version: 13,
+        file_length: 0,
+        width: 11000,
+        height: 8000,
+        frame_rate: 24,
+        frame_rate_fraction: 0,
+        frame_count: id - 1
+    };
+    writer::to_bytes(&headers, &tags).unwrap()
+}
+
+/// Returns encoded RECTs of every field width from 0 to 31 bits, in order.
+pub fn rects() -> Vec<Vec<u8>> {
+    (0..32).map(|nbits: u32| {
+        // The largest value that needs all the bits, or -1 for the one bit
+        // that can only be a sign.
+        let max = match nbits {
+            0 => 0,
+            1 => -1,
+            _ => (1i32 << (nbits - 2)) | 1
+        };
+        codec::rect_to_bytes(&Rect { x_min: 0, x_max: max, y_min: 0, y_max: max }).unwrap()
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use {Signature, SwfHeaders};
+
+    #[test]
+    fn test_inputs() {
+        for (nbits, rect) in rects().iter().enumerate() {
+            assert_eq!(rect[0] >> 3, nbits as u8);
+        }
+        let small = movie(Signature::ZlibCompressed, 4096);
+        let (headers, _) = SwfHeaders::read_from(Cursor::new(small)).unwrap();
+        assert_eq!((headers.dimensions(), headers.frame_count()), ((550, 400), 2));
+        assert!(headers.file_length() >= 4096 + 8);
+    }
+}
@@ -0,0 +1,239 @@
+//! The `extract` command, dumping a movie's bitmaps, sounds, video and
+//! binary data into a directory as ordinary files.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use swf_headers::{Error, SwfHeaders};
+use swf_headers::assets::{self, BinaryData, Bitmap, Sound};
+use swf_headers::sound;
+use swf_headers::tag::{self, codes, Tag};
+use swf_headers::video::{self, ExtractedVideo};
+
+use usage;
+
+/// Runs `swfinfo extract [--images] [--sounds] [--video] [--binary] OUT_DIR
+/// FILE`, returning the exit code. With none of the kinds asked for,
+/// everything is extracted.
+pub fn run(args: Vec<String>) -> i32 {
+    let (mut images, mut sounds, mut videos, mut binary) = (false, false, false, false);
+    let mut paths = Vec::new();
+    for arg in args {
+        match &arg[..] {
+            "--images" => images = true,
+            "--sounds" => sounds = true,
+            "--video" => videos = true,
+            "--binary" => binary = true,
+            _ if arg.starts_with("--") => usage(),
+            _ => paths.push(arg)
+        }
+    }
+    if paths.len() != 2 {
+        usage();
+    }
+    if !images && !sounds && !videos && !binary {
+        images = true;
+        sounds = true;
+        videos = true;
+        binary = true;
+    }
+    let (out_dir, input) = (Path::new(&paths[0]), &paths[1]);
+
+    let (headers, tags) = match SwfHeaders::open(input).and_then(|(headers, body)| {
+        tag::read_all(body).map(|tags| (headers, tags))
+    }) {
+        Ok(read) => read,
+        Err(err) => {
+            eprintln!("{}: {:?}", input, err);
+            return 1;
+        }
+    };
+    if let Err(err) = fs::create_dir_all(out_dir) {
+        eprintln!("{}: {:?}", out_dir.display(), err);
+        return 1;
+    }
+
+    let mut extractor = Extractor {
+        out_dir: out_dir,
+        written: 0,
+        failed: 0
+    };
+    if images {
+        extractor.images(&tags);
+    }
+    if sounds {
+        extractor.sounds(&tags);
+    }
+    if videos {
+        extractor.videos(&tags, headers.nominal_frame_rate());
+    }
+    if binary {
+        extractor.binary(&tags);
+    }
+
+    println!("{}: wrote {} files to {}", input, extractor.written, out_dir.display());
+    if extractor.failed > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Writes assets out, counting what worked and what didn't. Failures are
+/// reported as they happen, and don't stop the rest.
+struct Extractor<'a> {
+    out_dir: &'a Path,
+    written: usize,
+    failed: usize
+}
+
+impl<'a> Extractor<'a> {
+    fn images(&mut self, tags: &[Tag]) {
+        let jpeg_tables = tags.iter().find(|tag| tag.code() == codes::JPEG_TABLES).map(|tag| tag.data());
+        // Naming images after the symbols using them is only a nicety, so a
+        // movie too broken to work that out still has its images written.
+        let usage = assets::bitmap_usage(tags).unwrap_or_default();
+        for tag in tags {
+            let bitmap = match Bitmap::from_tag(tag) {
+                Ok(Some(bitmap)) => bitmap,
+                Ok(None) => continue,
+                Err(err) => {
+                    self.fail("a bitmap", err);
+                    continue;
+                }
+            };
+            let name = match usage.iter().find(|usage| usage.id == bitmap.id).and_then(|usage| usage.name()) {
+                Some(symbol) => format!("image_{}_{}", bitmap.id, file_name_safe(symbol)),
+                None => format!("image_{}", bitmap.id)
+            };
+            // Lossless bitmaps have no file format of their own, so they're
+            // decoded and turned into PNGs.
+            let file = match bitmap.image_data(jpeg_tables) {
+                Ok(Some(data)) => {
+                    let extension = image_extension(&data);
+                    Ok(Some((data, extension)))
+                },
+                Ok(None) => bitmap.decode_lossless().and_then(|image| match image {
+                    Some(image) => image.to_png().map(|png| Some((png, "png"))),
+                    None => Ok(None)
+                }),
+                Err(err) => Err(err)
+            };
+            match file {
+                Ok(Some((data, extension))) => self.write(&name, extension, &data),
+                Ok(None) => {},
+                Err(err) => self.fail(&name, err)
+            }
+        }
+    }
+
+    fn sounds(&mut self, tags: &[Tag]) {
+        for tag in tags {
+            let sound = match Sound::from_tag(tag) {
+                Ok(Some(sound)) => sound,
+                Ok(None) => continue,
+                Err(err) => {
+                    self.fail("a sound", err);
+                    continue;
+                }
+            };
+            let name = format!("sound_{}", sound.id);
+            match sound.file_data() {
+                Ok(Some((data, extension))) => self.write(&name, extension, &data),
+                // Kept as they are, for tools that know the format.
+                Ok(None) => self.write(&name, &format!("{:?}", sound.format).to_lowercase(), &sound.data),
+                Err(err) => self.fail(&name, err)
+            }
+        }
+        match sound::mp3_stream(tags) {
+            Ok(Some(stream)) => self.write("stream", "mp3", &stream.data),
+            Ok(None) => {},
+            Err(err) => self.fail("the streaming sound", err)
+        }
+    }
+
+    /// Writes each stream as an FLV, or a PNG per frame where it could be
+    /// decoded, and says what's left to do with it.
+    fn videos(&mut self, tags: &[Tag], frame_rate: u16) {
+        let streams = match video::video_streams(tags) {
+            Ok(streams) => streams,
+            Err(err) => return self.fail("video", err)
+        };
+        for stream in streams {
+            let name = format!("video_{}", stream.id);
+            match stream.extract(frame_rate) {
+                Ok(ExtractedVideo::Flv(flv)) => self.write(&name, "flv", &flv),
+                Ok(ExtractedVideo::Frames(images)) => {
+                    for (index, image) in images.iter().enumerate() {
+                        let frame_name = format!("{}_{:05}", name, index);
+                        match image.to_png() {
+                            Ok(png) => self.write(&frame_name, "png", &png),
+                            Err(err) => self.fail(&frame_name, err)
+                        }
+                    }
+                },
+                Err(err) => {
+                    self.fail(&name, err);
+                    continue;
+                }
+            }
+            println!("{}: {}", name, stream.codec.next_step());
+        }
+    }
+
+    fn binary(&mut self, tags: &[Tag]) {
+        for tag in tags {
+            match BinaryData::from_tag(tag) {
+                Ok(Some(blob)) => {
+                    let name = format!("binary_{}", blob.id);
+                    let extension = if blob.data.starts_with(b"FWS") || blob.data.starts_with(b"CWS") ||
+                                       blob.data.starts_with(b"ZWS") {
+                        "swf"
+                    } else {
+                        "bin"
+                    };
+                    self.write(&name, extension, &blob.data);
+                },
+                Ok(None) => {},
+                Err(err) => self.fail("binary data", err)
+            }
+        }
+    }
+
+    fn write(&mut self, name: &str, extension: &str, data: &[u8]) {
+        let path = self.out_dir.join(format!("{}.{}", name, extension));
+        match File::create(&path).and_then(|mut file| file.write_all(data)) {
+            Ok(()) => self.written += 1,
+            Err(err) => {
+                eprintln!("{}: {:?}", path.display(), err);
+                self.failed += 1;
+            }
+        }
+    }
+
+    fn fail(&mut self, what: &str, err: Error) {
+        eprintln!("couldn't extract {}: {:?}", what, err);
+        self.failed += 1;
+    }
+}
+
+/// Guesses the extension for an image from its magic number. DefineBits
+/// tags from SWF 8 on can hold PNGs and GIFs as well as JPEGs.
+fn image_extension(data: &[u8]) -> &'static str {
+    if data.starts_with(&[0x89, b'P', b'N', b'G']) {
+        "png"
+    } else if data.starts_with(b"GIF8") {
+        "gif"
+    } else {
+        "jpg"
+    }
+}
+
+/// Replaces anything in a symbol name that could be awkward in a file name.
+fn file_name_safe(name: &str) -> String {
+    name.chars().map(|c| match c {
+        'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' | '.' => c,
+        _ => '_'
+    }).collect()
+}
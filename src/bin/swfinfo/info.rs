@@ -0,0 +1,259 @@
+//! The default command, printing a row of header fields per file.
+
+use std::io;
+
+use swf_headers::{Signature, SwfHeaders};
+use swf_headers::report::{self, Record};
+
+use paths;
+use usage;
+
+/// The widest any column of the table is allowed to get.
+const MAX_COLUMN_WIDTH: usize = 40;
+
+const COLUMNS: [&'static str; 8] = [
+    "file", "version", "compression", "length", "width", "height", "fps", "frames"
+];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Format {
+    Table,
+    Json,
+    Ndjson,
+    Csv,
+    Markdown
+}
+
+/// A record laid out for the table, CSV and Markdown. Either `values` holds
+/// one entry per column after the file name, or `error` says why it doesn't.
+struct Row {
+    path: String,
+    values: Vec<String>,
+    error: Option<String>
+}
+
+/// Prints the headers of every file named in `args`, returning the exit
+/// code. Directories and glob patterns are expanded into the files in them.
+pub fn run(args: Vec<String>) -> i32 {
+    let mut format = Format::Table;
+    let mut paths = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--format" || arg == "-f" {
+            format = match args.next().as_ref().map(|value| &value[..]) {
+                Some("table") => Format::Table,
+                Some("json") => Format::Json,
+                Some("ndjson") => Format::Ndjson,
+                Some("csv") => Format::Csv,
+                Some("markdown") | Some("md") => Format::Markdown,
+                _ => usage()
+            };
+        } else if arg.starts_with("--") {
+            usage();
+        } else {
+            paths.push(arg);
+        }
+    }
+    if paths.is_empty() {
+        usage();
+    }
+    let paths = paths::expand(paths);
+
+    let mut failed = false;
+    let records = paths.iter().map(|path| read_record(path)).inspect(|record| failed |= record.is_unreadable());
+    match format {
+        // Streamed a line at a time as each file is read, so it can be piped
+        // on while a big archive is still being worked through. The other
+        // formats need every record before they can print any.
+        Format::Ndjson => if let Err(err) = report::write_ndjson(records, io::stdout()) {
+            eprintln!("{:?}", err);
+            return 1;
+        },
+        Format::Json => print_json(&records.collect::<Vec<_>>()),
+        Format::Table => print_table(&records.map(|record| row(&record)).collect::<Vec<_>>()),
+        Format::Csv => print_csv(&records.map(|record| row(&record)).collect::<Vec<_>>()),
+        Format::Markdown => print_markdown(&records.map(|record| row(&record)).collect::<Vec<_>>())
+    }
+
+    if failed {
+        1
+    } else {
+        0
+    }
+}
+
+fn read_record(path: &str) -> Record {
+    if path != "-" {
+        return Record::scan(path);
+    }
+    match SwfHeaders::read_from(io::stdin()) {
+        Ok((headers, _)) => Record::Headers {
+            path: path.to_owned(),
+            headers: headers
+        },
+        Err(err) => Record::Unreadable {
+            path: path.to_owned(),
+            error: err
+        }
+    }
+}
+
+fn row(record: &Record) -> Row {
+    match *record {
+        Record::Headers { ref path, ref headers } => {
+            let compression = match headers.signature() {
+                Signature::Uncompressed => "none",
+                Signature::ZlibCompressed => "zlib",
+                Signature::LzmaCompressed => "lzma"
+            };
+            let (width, height) = headers.dimensions();
+            Row {
+                path: path.clone(),
+                values: vec![
+                    headers.version().to_string(),
+                    compression.to_owned(),
+                    headers.file_length().to_string(),
+                    width.to_string(),
+                    height.to_string(),
+                    headers.nominal_frame_rate().to_string(),
+                    headers.frame_count().to_string()
+                ],
+                error: None
+            }
+        },
+        Record::Unreadable { ref path, ref error } => Row {
+            path: path.clone(),
+            values: Vec::new(),
+            error: Some(format!("{:?}", error))
+        },
+        Record::Finding { ref path, ref finding } => Row {
+            path: path.clone(),
+            values: Vec::new(),
+            error: Some(finding.to_string())
+        }
+    }
+}
+
+/// Returns the cells of a row, with any error taking up the first column
+/// after the file name.
+fn cells(row: &Row) -> Vec<String> {
+    let mut cells = vec![row.path.clone()];
+    match row.error {
+        Some(ref error) => cells.push(format!("error: {}", error)),
+        None => cells.extend(row.values.iter().cloned())
+    }
+    cells
+}
+
+/// Cuts a value down to `width` characters, marking where it was cut. File
+/// names keep their end, since that's the part that tells them apart.
+fn truncate(value: &str, width: usize, keep_end: bool) -> String {
+    let count = value.chars().count();
+    if count <= width {
+        return value.to_owned();
+    }
+    if keep_end {
+        let tail: String = value.chars().skip(count - (width - 3)).collect();
+        format!("...{}", tail)
+    } else {
+        let head: String = value.chars().take(width - 3).collect();
+        format!("{}...", head)
+    }
+}
+
+fn print_table(rows: &[Row]) {
+    let rows: Vec<Vec<String>> = rows.iter().map(|row| {
+        cells(row).iter().enumerate().map(|(i, cell)| {
+            // Errors run across the rest of the line, so they get more room.
+            let width = if i == 1 && row.error.is_some() { MAX_COLUMN_WIDTH * 2 } else { MAX_COLUMN_WIDTH };
+            truncate(cell, width, i == 0)
+        }).collect()
+    }).collect();
+
+    let mut widths: Vec<usize> = COLUMNS.iter().map(|name| name.len()).collect();
+    for row in &rows {
+        // An error's width doesn't count towards the column it starts in.
+        let counted = if row.len() == COLUMNS.len() { row.len() } else { 1 };
+        for (i, cell) in row.iter().take(counted).enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+
+    let header: Vec<String> = COLUMNS.iter().map(|name| name.to_string()).collect();
+    print_table_row(&header, &widths);
+    let rule: Vec<String> = widths.iter().map(|&width| "-".repeat(width)).collect();
+    print_table_row(&rule, &widths);
+    for row in &rows {
+        print_table_row(row, &widths);
+    }
+}
+
+fn print_table_row(cells: &[String], widths: &[usize]) {
+    let is_error = cells.len() < widths.len();
+    let mut line = String::new();
+    for (i, cell) in cells.iter().enumerate() {
+        if i > 0 {
+            line.push_str("  ");
+        }
+        if is_error && i > 0 {
+            line.push_str(cell);
+        } else if is_text_column(i) {
+            line.push_str(&format!("{:<width$}", cell, width = widths[i]));
+        } else {
+            line.push_str(&format!("{:>width$}", cell, width = widths[i]));
+        }
+    }
+    println!("{}", line.trim_end());
+}
+
+/// Returns true for the columns holding text rather than numbers, which
+/// are left aligned.
+fn is_text_column(index: usize) -> bool {
+    COLUMNS[index] == "file" || COLUMNS[index] == "compression"
+}
+
+fn print_markdown(rows: &[Row]) {
+    let escape = |cell: &str| cell.replace('|', "\\|");
+    println!("| {} |", COLUMNS.join(" | "));
+    println!("|{}", (0..COLUMNS.len()).map(|i| if is_text_column(i) { "---|" } else { "---:|" })
+        .collect::<String>());
+    for row in rows {
+        let mut cells: Vec<String> = cells(row).iter().map(|cell| escape(cell)).collect();
+        cells.resize(COLUMNS.len(), String::new());
+        println!("| {} |", cells.join(" | "));
+    }
+}
+
+fn print_csv(rows: &[Row]) {
+    let quote = |cell: &str| {
+        if cell.contains(',') || cell.contains('"') || cell.contains('\n') {
+            format!("\"{}\"", cell.replace('"', "\"\""))
+        } else {
+            cell.to_owned()
+        }
+    };
+    let mut header: Vec<String> = COLUMNS.iter().map(|name| name.to_string()).collect();
+    header.push("error".to_owned());
+    header.push("schema".to_owned());
+    println!("{}", header.join(","));
+    for row in rows {
+        let mut cells = vec![quote(&row.path)];
+        if row.error.is_some() {
+            cells.resize(COLUMNS.len(), String::new());
+        } else {
+            cells.extend(row.values.iter().cloned());
+        }
+        cells.push(row.error.as_ref().map_or(String::new(), |error| quote(error)));
+        cells.push(report::SCHEMA_VERSION.to_string());
+        println!("{}", cells.join(","));
+    }
+}
+
+fn print_json(records: &[Record]) {
+    println!("[");
+    for (i, record) in records.iter().enumerate() {
+        let comma = if i + 1 < records.len() { "," } else { "" };
+        println!("  {}{}", record.to_json(), comma);
+    }
+    println!("]");
+}
@@ -0,0 +1,68 @@
+//! Prints the headers of SWF files, and converts them between compression
+//! methods.
+//!
+//! A path of `-` reads from stdin, so it can sit at the end of a pipeline:
+//!
+//! ```text
+//! curl -s https://example.com/movie.swf | swfinfo -
+//! ```
+//!
+//! `--format table|json|ndjson|csv|markdown` picks how the results are
+//! laid out, one row per file. The table is the default, with long values
+//! cut short so it stays readable in a terminal. NDJSON is printed as each
+//! file is read, for streaming through other tools.
+//!
+//! Directories are searched for `.swf` files all the way down, and quoted
+//! glob patterns such as `'archive/**/*.swf'` are expanded, so big archives
+//! don't need `find | xargs`.
+//!
+//! `swfinfo decompress in.swf out.swf` writes an uncompressed copy, and
+//! `swfinfo compress [--method zlib|lzma] in.swf out.swf` a compressed one.
+//!
+//! `swfinfo extract [--images] [--sounds] [--video] [--binary] out_dir/ file.swf`
+//! dumps embedded assets as ordinary files, everything if no kind is given.
+//! Images are named after the exported symbol using them, where there is one.
+//! Video comes out as FLV, or as PNG frames for Screen Video, with a note of
+//! what a transcoder has to do with it next.
+//!
+//! `swfinfo validate [--format text|json|ndjson] [--deny-warnings] file.swf...`
+//! runs strict checks and exits non-zero if any file fails them, for use in
+//! CI: 0 means every file passed, 1 that one failed, and 2 that one couldn't
+//! be read.
+
+extern crate swf_headers;
+
+mod convert;
+mod extract;
+mod info;
+mod paths;
+mod validate;
+
+use std::env;
+use std::process;
+
+const USAGE: &'static str = "\
+usage: swfinfo [--format table|json|ndjson|csv|markdown] FILE...  (use - for stdin)
+       swfinfo decompress IN OUT
+       swfinfo compress [--method zlib|lzma] IN OUT
+       swfinfo extract [--images] [--sounds] [--video] [--binary] OUT_DIR FILE
+       swfinfo validate [--format text|json|ndjson] [--deny-warnings] FILE...
+FILE can also be a directory or a glob pattern.";
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let code = match args.first().map(|arg| &arg[..]) {
+        Some("decompress") => convert::decompress(args.split_off(1)),
+        Some("compress") => convert::compress(args.split_off(1)),
+        Some("extract") => extract::run(args.split_off(1)),
+        Some("validate") => validate::run(args.split_off(1)),
+        _ => info::run(args)
+    };
+    process::exit(code);
+}
+
+/// Prints the usage and exits, for arguments that don't make sense.
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
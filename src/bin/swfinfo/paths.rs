@@ -0,0 +1,224 @@
+//! Turning the paths on the command line into the files they mean.
+//!
+//! Directories are searched all the way down for `.swf` files, and glob
+//! patterns are expanded here as well as by the shell, so quoted patterns
+//! like `'archive/**/*.swf'` work where the shell would run out of room for
+//! the arguments, or doesn't do globbing at all.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Expands every argument into the files it names, in order. Directories
+/// and patterns list their files sorted, so runs are repeatable.
+///
+/// Anything that doesn't name a directory or match a pattern is passed on
+/// as it is, for whatever opens it to report on. That includes `-`.
+pub fn expand(args: Vec<String>) -> Vec<String> {
+    let mut paths = Vec::new();
+    for arg in args {
+        let mut found = Vec::new();
+        if arg != "-" && Path::new(&arg).is_dir() {
+            walk(Path::new(&arg), &mut found);
+        } else if is_pattern(&arg) {
+            for path in glob(&arg) {
+                if path.is_dir() {
+                    walk(&path, &mut found);
+                } else {
+                    found.push(path);
+                }
+            }
+            if found.is_empty() {
+                paths.push(arg);
+            }
+        } else {
+            paths.push(arg);
+        }
+        found.sort();
+        found.dedup();
+        paths.extend(found.iter().map(|path| path.to_string_lossy().into_owned()));
+    }
+    paths
+}
+
+/// Collects every `.swf` file under `dir`. Symlinked directories aren't
+/// followed, so a link back up the tree can't loop forever.
+fn walk(dir: &Path, found: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("{}: {:?}", dir.display(), err);
+            return;
+        }
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => walk(&path, found),
+            _ => if path.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("swf")) {
+                found.push(path);
+            }
+        }
+    }
+}
+
+fn is_pattern(value: &str) -> bool {
+    value.contains(|c| c == '*' || c == '?' || c == '[')
+}
+
+/// Returns everything matching a glob pattern, sorted. `*`, `?` and
+/// `[...]` match within a single path component, and a component of `**`
+/// matches any number of directories, none included.
+fn glob(pattern: &str) -> Vec<PathBuf> {
+    let components: Vec<&str> = pattern.split('/').collect();
+    let first_pattern = components.iter().position(|component| is_pattern(component)).unwrap_or(0);
+    let base = match components[..first_pattern].join("/") {
+        ref base if base.is_empty() && pattern.starts_with('/') => PathBuf::from("/"),
+        base => PathBuf::from(base)
+    };
+    let mut matches = Vec::new();
+    glob_from(&base, &components[first_pattern..], &mut matches);
+    matches.sort();
+    matches.dedup();
+    matches
+}
+
+fn glob_from(dir: &Path, components: &[&str], matches: &mut Vec<PathBuf>) {
+    let (component, rest) = match components.split_first() {
+        Some((component, rest)) => (*component, rest),
+        None => return matches.push(dir.to_path_buf())
+    };
+    if component.is_empty() {
+        // A doubled or trailing slash.
+        return glob_from(dir, rest, matches);
+    }
+    if !is_pattern(component) {
+        let path = dir.join(component);
+        if path.exists() {
+            glob_from(&path, rest, matches);
+        }
+        return;
+    }
+
+    if component == "**" {
+        glob_from(dir, rest, matches);
+    }
+    // A relative pattern starts from the current directory, without
+    // putting "./" in front of everything it finds.
+    let listed = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let entries = match fs::read_dir(listed) {
+        Ok(entries) => entries,
+        Err(_) => return
+    };
+    let pattern: Vec<char> = component.chars().collect();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // Like the shell, only a pattern starting with a dot matches
+        // hidden files.
+        if name.starts_with('.') && !component.starts_with('.') {
+            continue;
+        }
+        let path = dir.join(&name);
+        if component == "**" {
+            if entry.file_type().map(|file_type| file_type.is_dir()).unwrap_or(false) {
+                glob_from(&path, components, matches);
+            }
+        } else if matches_component(&pattern, &name.chars().collect::<Vec<char>>()) {
+            glob_from(&path, rest, matches);
+        }
+    }
+}
+
+/// Matches a single path component against a pattern.
+fn matches_component(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some(&'*') => (0..name.len() + 1).any(|skip| matches_component(&pattern[1..], &name[skip..])),
+        Some(&'?') => !name.is_empty() && matches_component(&pattern[1..], &name[1..]),
+        Some(&'[') => match (name.first(), class_end(pattern)) {
+            (Some(&c), Some(end)) => in_class(&pattern[1..end], c) && matches_component(&pattern[end + 1..], &name[1..]),
+            // An unclosed bracket is just a bracket.
+            (Some(&c), None) => c == '[' && matches_component(&pattern[1..], &name[1..]),
+            (None, _) => false
+        },
+        Some(&c) => name.first() == Some(&c) && matches_component(&pattern[1..], &name[1..])
+    }
+}
+
+/// Finds the `]` closing the class that starts the pattern. A `]` straight
+/// after the opening bracket (or its negation) is part of the class.
+fn class_end(pattern: &[char]) -> Option<usize> {
+    let mut start = 1;
+    if pattern.get(start) == Some(&'!') || pattern.get(start) == Some(&'^') {
+        start += 1;
+    }
+    pattern.iter().skip(start + 1).position(|&c| c == ']').map(|pos| pos + start + 1)
+}
+
+fn in_class(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some(&'!') | Some(&'^') => (true, &class[1..]),
+        _ => (false, class)
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, File};
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+        matches_component(&pattern, &name)
+    }
+
+    #[test]
+    fn test_matches_component() {
+        assert!(matches("*.swf", "movie.swf"));
+        assert!(!matches("*.swf", "movie.swf.bak"));
+        assert!(matches("level?.swf", "level1.swf"));
+        assert!(!matches("level?.swf", "level10.swf"));
+        assert!(matches("level[0-9].swf", "level7.swf"));
+        assert!(!matches("level[!0-9].swf", "level7.swf"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("a[b", "a[b"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let root = env::temp_dir().join("swfinfo_test_paths");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("games/old")).unwrap();
+        for name in &["intro.swf", "games/a.swf", "games/old/b.SWF", "games/readme.txt"] {
+            File::create(root.join(name)).unwrap();
+        }
+        let root_str = root.to_string_lossy().into_owned();
+
+        let names = |paths: Vec<String>| -> Vec<String> {
+            paths.iter().map(|path| path[root_str.len() + 1..].to_string()).collect()
+        };
+        assert_eq!(names(expand(vec![root_str.clone()])),
+                   vec!["games/a.swf", "games/old/b.SWF", "intro.swf"]);
+        assert_eq!(names(expand(vec![format!("{}/**/*.swf", root_str)])),
+                   vec!["games/a.swf", "intro.swf"]);
+        assert_eq!(names(expand(vec![format!("{}/g*", root_str)])),
+                   vec!["games/a.swf", "games/old/b.SWF"]);
+        let missing = format!("{}/*.fla", root_str);
+        assert_eq!(expand(vec![missing.clone(), "-".to_string()]), vec![missing, "-".to_string()]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+//! The `validate` command, running the strict checks over files for use as
+//! a build or CI gate.
+
+use std::io;
+
+use swf_headers::report::{self, Record};
+use swf_headers::validate::{self, Severity};
+
+use paths;
+use usage;
+
+/// Passed: no errors, and no warnings either if they're denied.
+const PASSED: i32 = 0;
+/// At least one file failed.
+const FAILED: i32 = 1;
+/// At least one file couldn't be read at all. Bad arguments exit with this
+/// too, through `usage()`.
+const UNREADABLE: i32 = 2;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Format {
+    Text,
+    Json,
+    Ndjson
+}
+
+/// Runs `swfinfo validate [--format text|json|ndjson] [--deny-warnings]
+/// FILE...`, returning the exit code. Directories and glob patterns are
+/// expanded into the files in them.
+///
+/// The text format prints a `file: finding` line per finding, and NDJSON a
+/// JSON object per finding, both as each file is checked. The JSON format
+/// waits for every file, then prints a single array, so an empty array
+/// means everything passed. Both JSON formats include files that couldn't
+/// be read, with an `error` field, where the text format leaves them to
+/// stderr.
+pub fn run(args: Vec<String>) -> i32 {
+    let mut format = Format::Text;
+    let mut deny_warnings = false;
+    let mut paths = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--format" || arg == "-f" {
+            format = match args.next().as_ref().map(|value| &value[..]) {
+                Some("text") => Format::Text,
+                Some("json") => Format::Json,
+                Some("ndjson") => Format::Ndjson,
+                _ => usage()
+            };
+        } else if arg == "--deny-warnings" {
+            deny_warnings = true;
+        } else if arg.starts_with("--") {
+            usage();
+        } else {
+            paths.push(arg);
+        }
+    }
+    if paths.is_empty() {
+        usage();
+    }
+    let paths = paths::expand(paths);
+
+    let mut code = PASSED;
+    let records = paths.iter().flat_map(|path| check(path)).inspect(|record| match *record {
+        Record::Unreadable { .. } => code = UNREADABLE,
+        Record::Finding { ref finding, .. } => if code == PASSED &&
+                                                  (finding.severity == Severity::Error || deny_warnings) {
+            code = FAILED;
+        },
+        Record::Headers { .. } => {}
+    });
+    let written = match format {
+        Format::Text => {
+            for record in records {
+                match record {
+                    Record::Finding { path, finding } => println!("{}: {}", path, finding),
+                    Record::Unreadable { path, error } => eprintln!("{}: {:?}", path, error),
+                    Record::Headers { .. } => {}
+                }
+            }
+            Ok(())
+        },
+        Format::Ndjson => report::write_ndjson(records, io::stdout()).map(|_| ()),
+        Format::Json => {
+            print_json(&records.collect::<Vec<_>>());
+            Ok(())
+        }
+    };
+    if let Err(err) = written {
+        eprintln!("{:?}", err);
+        return UNREADABLE;
+    }
+    code
+}
+
+/// Validates a file, turning what comes of it into records.
+fn check(path: &str) -> Vec<Record> {
+    let result = if path == "-" {
+        validate::validate_from(io::stdin())
+    } else {
+        validate::validate(path)
+    };
+    match result {
+        Ok(findings) => findings.into_iter().map(|finding| Record::Finding {
+            path: path.to_owned(),
+            finding: finding
+        }).collect(),
+        Err(err) => vec![Record::Unreadable {
+            path: path.to_owned(),
+            error: err
+        }]
+    }
+}
+
+fn print_json(records: &[Record]) {
+    if records.is_empty() {
+        println!("[]");
+    } else {
+        let lines: Vec<String> = records.iter().map(|record| format!("  {}", record.to_json())).collect();
+        println!("[\n{}\n]", lines.join(",\n"));
+    }
+}
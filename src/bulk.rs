@@ -0,0 +1,685 @@
+//! Decompressing large numbers of SWF files at once, spread across threads,
+//! and summing up how a batch went.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use super::SwfHeaders;
+use decoded_swf;
+use error::Error;
+use options::ParseOptions;
+use parser::Parser;
+
+/// Settings for `convert()`.
+#[derive(Clone, Debug)]
+pub struct BulkOptions {
+    output_dir: PathBuf,
+    threads: usize,
+    parse_options: ParseOptions
+}
+
+impl BulkOptions {
+    /// Creates options writing into `output_dir`, using one thread per CPU.
+    pub fn new<P: AsRef<Path>>(output_dir: P) -> Self {
+        BulkOptions {
+            output_dir: output_dir.as_ref().to_path_buf(),
+            threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+            parse_options: ParseOptions::default()
+        }
+    }
+
+    /// Sets the number of worker threads. Zero is treated as one.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Sets the options each file is opened and decompressed with, such as
+    /// the largest file to take on.
+    pub fn parse_options(mut self, options: ParseOptions) -> Self {
+        self.parse_options = options;
+        self
+    }
+}
+
+/// The outcome of converting a single file.
+#[derive(Debug)]
+pub struct Conversion {
+    /// The file that was read.
+    pub input: PathBuf,
+    /// The file that was, or would have been, written.
+    pub output: PathBuf,
+    /// The length of the written file, or what went wrong.
+    pub result: Result<u64, Error>
+}
+
+/// Decompresses every file in `paths` to an uncompressed (FWS) SWF in the
+/// output directory, returning one result per file, in the same order.
+///
+/// Each output file has the same name as its input, so inputs should have
+/// distinct names. Files that are already uncompressed are copied as they
+/// are. The file length in the header is corrected if the original lied
+/// about it.
+///
+/// Each worker thread streams its files from disk, through the
+/// decompressor, and back out to disk, so memory use stays flat however big
+/// the files are, and one thread's IO overlaps with another's
+/// decompression. A failed file doesn't stop the others.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::bulk::{self, BulkOptions};
+/// let results = bulk::convert(&["a.swf", "b.swf"], &BulkOptions::new("out/").threads(8));
+/// for conversion in &results {
+///     if let Err(ref err) = conversion.result {
+///         println!("{}: {:?}", conversion.input.display(), err);
+///     }
+/// }
+/// ```
+pub fn convert<P: AsRef<Path>>(paths: &[P], options: &BulkOptions) -> Vec<Conversion> {
+    let jobs: Arc<Vec<(PathBuf, PathBuf)>> = Arc::new(paths.iter().map(|path| {
+        let input = path.as_ref().to_path_buf();
+        let output = options.output_dir.join(input.file_name().unwrap_or_default());
+        (input, output)
+    }).collect());
+    let parse_options = options.parse_options.clone();
+    let results = run(jobs.len(), options.threads, || (), {
+        let jobs = jobs.clone();
+        move |_: &mut (), index| {
+            let (ref input, ref output) = jobs[index];
+            decompress_file(input, output, &parse_options)
+        }
+    });
+
+    jobs.iter().zip(results).map(|(&(ref input, ref output), result)| Conversion {
+        input: input.clone(),
+        output: output.clone(),
+        // Only missing if a worker panicked partway through the file.
+        result: result.unwrap_or(Err(Error::NotSwf))
+    }).collect()
+}
+
+/// How many example paths `ErrorSummary` keeps for each kind of error.
+const MAX_EXAMPLES: usize = 5;
+
+/// A tally of how a batch of files went, for a one-look health report of a
+/// crawl or conversion run.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::bulk::{self, BulkOptions, ErrorSummary};
+/// let results = bulk::convert(&["a.swf", "b.swf"], &BulkOptions::new("out/"));
+/// let summary = ErrorSummary::from_conversions(&results);
+/// println!("{}", summary);
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ErrorSummary {
+    /// How many files went through without an error.
+    pub succeeded: usize,
+    /// How many files failed.
+    pub failed: usize,
+    /// The failures, by `Error::kind()`.
+    pub kinds: BTreeMap<&'static str, ErrorKindSummary>
+}
+
+/// The failures of one kind in an `ErrorSummary`.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ErrorKindSummary {
+    /// How many files failed this way.
+    pub count: usize,
+    /// The first few files that did, to start digging from.
+    pub examples: Vec<PathBuf>
+}
+
+impl ErrorSummary {
+    /// Creates an empty summary.
+    pub fn new() -> Self {
+        ErrorSummary::default()
+    }
+
+    /// Tallies up the results of `convert()`.
+    pub fn from_conversions(conversions: &[Conversion]) -> Self {
+        let mut summary = ErrorSummary::new();
+        for conversion in conversions {
+            summary.add(&conversion.input, conversion.result.as_ref().err());
+        }
+        summary
+    }
+
+    /// Adds a file's outcome, with `None` meaning it succeeded.
+    pub fn add<P: AsRef<Path>>(&mut self, path: P, error: Option<&Error>) {
+        let error = match error {
+            Some(error) => error,
+            None => {
+                self.succeeded += 1;
+                return;
+            }
+        };
+        self.failed += 1;
+        let kind = self.kinds.entry(error.kind()).or_insert_with(ErrorKindSummary::default);
+        kind.count += 1;
+        if kind.examples.len() < MAX_EXAMPLES {
+            kind.examples.push(path.as_ref().to_path_buf());
+        }
+    }
+
+    /// Returns the number of files added.
+    pub fn total(&self) -> usize {
+        self.succeeded + self.failed
+    }
+}
+
+impl fmt::Display for ErrorSummary {
+    /// Writes a line with the totals, then a line per kind of error, most
+    /// common first.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{} files: {} succeeded, {} failed", self.total(), self.succeeded, self.failed));
+        let mut kinds: Vec<_> = self.kinds.iter().collect();
+        kinds.sort_by(|a, b| b.1.count.cmp(&a.1.count));
+        for (name, kind) in kinds {
+            let examples: Vec<String> = kind.examples.iter().map(|path| path.display().to_string()).collect();
+            try!(write!(f, "\n  {}: {} (e.g. {})", name, kind.count, examples.join(", ")));
+        }
+        Ok(())
+    }
+}
+
+/// Reads the headers of every file in `paths`, returning one result per
+/// file, in the same order, using `threads` threads. Zero is treated as one.
+///
+/// Only the start of each file is read. Each thread has its own `Parser`,
+/// and a buffer the start of each file is read into in one go, so a crawl
+/// of millions of files costs little more than opening them. On Unix the
+/// read is positioned, at offset 0, saving a seek; compressed files whose
+/// headers don't decompress from that much fall back to reading on.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::bulk;
+/// for (path, result) in ["a.swf", "b.swf"].iter().zip(bulk::read_headers(&["a.swf", "b.swf"], 8)) {
+///     if let Ok(headers) = result {
+///         println!("{}: SWF {}", path, headers.version());
+///     }
+/// }
+/// ```
+pub fn read_headers<P: AsRef<Path>>(paths: &[P], threads: usize) -> Vec<Result<SwfHeaders, Error>> {
+    read_headers_with_options(paths, threads, &ParseOptions::default())
+}
+
+/// Like `read_headers()`, but opening and parsing each file with `options`.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::ParseOptions;
+/// use swf_headers::bulk;
+/// // Nothing under an upload directory gets to point the scan elsewhere.
+/// let options = ParseOptions::new()
+///     .max_file_size(100 * 1024 * 1024)
+///     .follow_symlinks(false)
+///     .allow_special_files(false);
+/// let results = bulk::read_headers_with_options(&["uploads/a.swf"], 8, &options);
+/// ```
+pub fn read_headers_with_options<P: AsRef<Path>>(paths: &[P], threads: usize, options: &ParseOptions)
+    -> Vec<Result<SwfHeaders, Error>>
+{
+    let paths: Arc<Vec<PathBuf>> = Arc::new(paths.iter().map(|path| path.as_ref().to_path_buf()).collect());
+    let init = {
+        let options = options.clone();
+        move || (Parser::with_options(&options), Vec::new())
+    };
+    let options = options.clone();
+    let results = run(paths.len(), threads, init, {
+        let paths = paths.clone();
+        move |state: &mut (Parser, Vec<u8>), index| {
+            let (ref mut parser, ref mut buf) = *state;
+            read_file_headers(parser, buf, &paths[index], &options)
+        }
+    });
+    // Only missing if a worker panicked partway through the file.
+    results.into_iter().map(|result| result.unwrap_or(Err(Error::NotSwf))).collect()
+}
+
+/// The headers of one file read by `scan_channel()`.
+#[derive(Debug)]
+pub struct ScanResult {
+    /// The file that was read.
+    pub path: PathBuf,
+    /// Its headers, or what went wrong.
+    pub result: Result<SwfHeaders, Error>
+}
+
+/// How many finished results each `scan_channel()` worker can get ahead of
+/// the receiver by.
+const RESULTS_PER_WORKER: usize = 16;
+
+/// Reads the headers of every file `paths` gives, on `workers` threads,
+/// sending each result down the returned channel as it's ready. Zero
+/// workers is treated as one.
+///
+/// Both ends are bounded. Paths are taken from the iterator only as workers
+/// free up, so it can walk a directory tree lazily, and results can only
+/// get a little ahead of the receiver before the workers wait for it. A
+/// slow consumer slows the scan down instead of filling memory, and a
+/// million-file crawl holds a few dozen results at a time.
+///
+/// Results arrive in the order they finish, not the order of `paths`. The
+/// channel closes once every path has been read. Dropping the receiver
+/// stops the workers after the file each one is on.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::bulk;
+/// let paths = vec!["a.swf", "b.swf", "c.swf"];
+/// for scanned in bulk::scan_channel(paths, 8) {
+///     match scanned.result {
+///         Ok(headers) => println!("{}: SWF {}", scanned.path.display(), headers.version()),
+///         Err(err) => println!("{}: {}", scanned.path.display(), err.kind())
+///     }
+/// }
+/// ```
+pub fn scan_channel<I, P>(paths: I, workers: usize) -> Receiver<ScanResult>
+    where I: IntoIterator<Item = P>, I::IntoIter: Send + 'static, P: AsRef<Path>
+{
+    scan_channel_with_options(paths, workers, &ParseOptions::default())
+}
+
+/// Like `scan_channel()`, but opening and parsing each file with `options`.
+pub fn scan_channel_with_options<I, P>(paths: I, workers: usize, options: &ParseOptions) -> Receiver<ScanResult>
+    where I: IntoIterator<Item = P>, I::IntoIter: Send + 'static, P: AsRef<Path>
+{
+    let workers = workers.max(1);
+    let paths = Arc::new(Mutex::new(paths.into_iter()));
+    let (sender, receiver) = mpsc::sync_channel(workers * RESULTS_PER_WORKER);
+    for _ in 0..workers {
+        let paths = paths.clone();
+        let sender = sender.clone();
+        let options = options.clone();
+        thread::spawn(move || {
+            let mut parser = Parser::with_options(&options);
+            let mut buf = Vec::new();
+            loop {
+                // The lock is only held while taking a path, not reading it.
+                let path = match paths.lock() {
+                    Ok(mut paths) => match paths.next() {
+                        Some(path) => path.as_ref().to_path_buf(),
+                        None => break
+                    },
+                    Err(_) => break
+                };
+                let result = read_file_headers(&mut parser, &mut buf, &path, &options);
+                if sender.send(ScanResult { path: path, result: result }).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    receiver
+}
+
+/// Runs `work` on the indices `0..count`, spread across `threads` threads,
+/// each with its own `S` from `init`. A result is `None` only if its thread
+/// panicked.
+fn run<S, T, I, F>(count: usize, threads: usize, init: I, work: F) -> Vec<Option<T>>
+    where T: Send + 'static,
+          I: Fn() -> S + Send + Sync + 'static,
+          F: Fn(&mut S, usize) -> T + Send + Sync + 'static
+{
+    let init = Arc::new(init);
+    let work = Arc::new(work);
+    let next = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+
+    let workers: Vec<_> = (0..threads.max(1).min(count)).map(|_| {
+        let init = init.clone();
+        let work = work.clone();
+        let next = next.clone();
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let mut state = init();
+            loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                if index >= count {
+                    break;
+                }
+                if sender.send((index, work(&mut state, index))).is_err() {
+                    break;
+                }
+            }
+        })
+    }).collect();
+    drop(sender);
+
+    let mut results: Vec<Option<T>> = (0..count).map(|_| None).collect();
+    for (index, result) in receiver {
+        results[index] = Some(result);
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+    results
+}
+
+/// How much of each file `read_headers()` reads up front. It covers the
+/// longest uncompressed header, and compressed ones from any real encoder.
+const PREFIX_LEN: usize = 4096;
+
+fn read_file_headers(parser: &mut Parser, buf: &mut Vec<u8>, path: &Path, options: &ParseOptions)
+    -> Result<SwfHeaders, Error>
+{
+    let file = try!(decoded_swf::open_file(path, options));
+    buf.resize(PREFIX_LEN, 0);
+    let len = try!(read_prefix(&file, buf));
+    match parser.parse(&buf[..len]) {
+        Err(Error::NotSwf) if len == PREFIX_LEN => parser.parse(BufReader::new(file)),
+        result => result
+    }
+}
+
+/// Fills as much of `buf` as the file has from its start, leaving the file
+/// position alone.
+#[cfg(unix)]
+fn read_prefix(file: &File, buf: &mut [u8]) -> io::Result<usize> {
+    use std::os::unix::fs::FileExt;
+
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read_at(&mut buf[filled..], filled as u64) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {},
+            Err(err) => return Err(err)
+        }
+    }
+    Ok(filled)
+}
+
+/// Fills as much of `buf` as the file has from its start, then goes back
+/// to it.
+#[cfg(not(unix))]
+fn read_prefix(mut file: &File, buf: &mut [u8]) -> io::Result<usize> {
+    use std::io::Read;
+
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {},
+            Err(err) => return Err(err)
+        }
+    }
+    try!(file.seek(SeekFrom::Start(0)));
+    Ok(filled)
+}
+
+fn decompress_file(input: &Path, output: &Path, options: &ParseOptions) -> Result<u64, Error> {
+    let (header, mut decoded) = try!(decoded_swf::open_body_with_options(input, options));
+    let result = write_uncompressed(&header, &mut decoded, output);
+    if result.is_err() {
+        let _ = fs::remove_file(output);
+    }
+    result
+}
+
+fn write_uncompressed<R: io::Read>(header: &[u8; 8], body: &mut R, output: &Path) -> Result<u64, Error> {
+    let mut writer = BufWriter::new(try!(File::create(output)));
+    try!(writer.write_all(b"FWS"));
+    try!(writer.write_all(&header[3..]));
+    let length = 8 + try!(io::copy(body, &mut writer));
+
+    let declared = try!((&header[4..]).read_u32::<LittleEndian>());
+    if length != declared as u64 {
+        try!(writer.seek(SeekFrom::Start(4)));
+        try!(writer.write_u32::<LittleEndian>(length as u32));
+    }
+    try!(writer.flush());
+    Ok(length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{Read, Write};
+
+    use {Signature, SwfHeaders};
+    use tag::{codes, Tag};
+    use writer;
+
+    #[test]
+    fn test_convert() {
+        let dir = env::temp_dir().join("swf_headers_test_bulk");
+        let out = dir.join("out");
+        fs::create_dir_all(&out).unwrap();
+
+        let headers = |signature| SwfHeaders {
+            signature: signature,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = vec![Tag::new(codes::SHOW_FRAME, vec![])];
+        let expected = writer::to_bytes(&headers(Signature::Uncompressed), &tags).unwrap();
+        let compressed = writer::to_bytes(&headers(Signature::ZlibCompressed), &tags).unwrap();
+
+        let mut inputs = Vec::new();
+        for name in &["a.swf", "b.swf", "c.swf"] {
+            let path = dir.join(name);
+            File::create(&path).unwrap().write_all(&compressed).unwrap();
+            inputs.push(path);
+        }
+        inputs.push(dir.join("missing.swf"));
+
+        let results = convert(&inputs, &BulkOptions::new(&out).threads(2));
+        assert_eq!(results.len(), 4);
+        for conversion in &results[..3] {
+            assert_eq!(*conversion.result.as_ref().unwrap(), expected.len() as u64);
+            let mut written = Vec::new();
+            File::open(&conversion.output).unwrap().read_to_end(&mut written).unwrap();
+            assert_eq!(written, expected);
+        }
+        assert!(results[3].result.is_err());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let summary = ErrorSummary::from_conversions(&results);
+        assert_eq!((summary.succeeded, summary.failed), (3, 1));
+        assert_eq!(summary.kinds["io"].examples, vec![dir.join("missing.swf")]);
+    }
+
+    #[test]
+    fn test_convert_limits() {
+        let dir = env::temp_dir().join("swf_headers_test_bulk_limits");
+        let out = dir.join("out");
+        fs::create_dir_all(&out).unwrap();
+
+        // An LZMA header asking for a 64MiB dictionary.
+        let mut zws = vec![b'Z', b'W', b'S', 13, 100, 0, 0, 0, 20, 0, 0, 0, 0x5d, 0, 0, 0, 4];
+        zws.extend_from_slice(&[0; 20]);
+        let lzma = dir.join("dict.swf");
+        File::create(&lzma).unwrap().write_all(&zws).unwrap();
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let zlib = dir.join("long.swf");
+        let tags = vec![Tag::new(codes::DEFINE_BINARY_DATA, vec![0; 1000])];
+        File::create(&zlib).unwrap().write_all(&writer::to_bytes(&headers, &tags).unwrap()).unwrap();
+
+        let limits = ParseOptions::new().max_lzma_dict_size(1024 * 1024).max_decompressed(100);
+        let results = convert(&[&lzma, &zlib], &BulkOptions::new(&out).parse_options(limits));
+        for conversion in &results {
+            assert_eq!(conversion.result.as_ref().unwrap_err().kind(), "limit-exceeded");
+            assert!(!conversion.output.exists());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_headers() {
+        let dir = env::temp_dir().join("swf_headers_test_bulk_headers");
+        fs::create_dir_all(&dir).unwrap();
+
+        let headers = |signature, width| SwfHeaders {
+            signature: signature,
+            version: 10,
+            file_length: 0,
+            width: width,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = vec![Tag::new(codes::SHOW_FRAME, vec![])];
+        let files = [
+            writer::to_bytes(&headers(Signature::Uncompressed, 100), &tags).unwrap(),
+            writer::to_bytes(&headers(Signature::ZlibCompressed, 300), &tags).unwrap(),
+            vec![b'X'; PREFIX_LEN * 2]
+        ];
+        let mut paths = Vec::new();
+        for (i, data) in files.iter().enumerate() {
+            let path = dir.join(format!("{}.swf", i));
+            File::create(&path).unwrap().write_all(data).unwrap();
+            paths.push(path);
+        }
+        paths.push(dir.join("missing.swf"));
+
+        let results = read_headers(&paths, 3);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap().dimensions_twips(), (100, 200));
+        assert_eq!(results[1].as_ref().unwrap().dimensions_twips(), (300, 200));
+        assert_eq!(results[1].as_ref().unwrap().file_length() as usize,
+                   writer::to_bytes(&headers(Signature::Uncompressed, 300), &tags).unwrap().len());
+        assert_eq!(results[2].as_ref().unwrap_err().kind(), "not-swf");
+        assert_eq!(results[3].as_ref().unwrap_err().kind(), "io");
+    }
+
+    #[test]
+    fn test_scan_channel() {
+        let dir = env::temp_dir().join("swf_headers_test_bulk_scan");
+        fs::create_dir_all(&dir).unwrap();
+
+        let tags = vec![Tag::new(codes::SHOW_FRAME, vec![])];
+        let mut paths = Vec::new();
+        for i in 0..100 {
+            let path = dir.join(format!("{}.swf", i));
+            let data = if i % 10 == 0 {
+                b"not a swf".to_vec()
+            } else {
+                writer::to_bytes(&SwfHeaders {
+                    signature: Signature::ZlibCompressed,
+                    version: 10,
+                    file_length: 0,
+                    width: i * 20,
+                    height: 200,
+                    frame_rate: 24,
+                    frame_rate_fraction: 0,
+                    frame_count: 1
+                }, &tags).unwrap()
+            };
+            File::create(&path).unwrap().write_all(&data).unwrap();
+            paths.push(path);
+        }
+
+        // More results than the channel holds, so the workers have to wait
+        // for the receiver.
+        let mut scanned: Vec<ScanResult> = scan_channel(paths.clone(), 3).into_iter().collect();
+        fs::remove_dir_all(&dir).unwrap();
+        scanned.sort_by_key(|scanned| paths.iter().position(|path| *path == scanned.path).unwrap());
+        assert_eq!(scanned.len(), 100);
+        for (i, scanned) in scanned.iter().enumerate() {
+            match scanned.result {
+                Ok(ref headers) => assert_eq!(headers.dimensions(), (i as u32, 10)),
+                Err(ref err) => assert!(i % 10 == 0 && err.kind() == "not-swf")
+            }
+        }
+    }
+
+    #[test]
+    fn test_open_guards() {
+        let dir = env::temp_dir().join("swf_headers_test_bulk_guards");
+        fs::create_dir_all(&dir).unwrap();
+
+        let data = writer::to_bytes(&SwfHeaders {
+            signature: Signature::Uncompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        }, &[Tag::new(codes::SHOW_FRAME, vec![])]).unwrap();
+        let file = dir.join("a.swf");
+        File::create(&file).unwrap().write_all(&data).unwrap();
+        let mut paths = vec![file.clone()];
+        #[cfg(unix)]
+        {
+            let link = dir.join("link.swf");
+            let _ = fs::remove_file(&link);
+            ::std::os::unix::fs::symlink(&file, &link).unwrap();
+            paths.push(link);
+            paths.push(PathBuf::from("/dev/null"));
+        }
+
+        let open = read_headers(&paths, 2);
+        let guarded = read_headers_with_options(&paths, 2, &ParseOptions::new()
+            .follow_symlinks(false)
+            .allow_special_files(false));
+        let small = read_headers_with_options(&paths[..1], 1, &ParseOptions::new().max_file_size(data.len() as u64 - 1));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(open[0].is_ok() && guarded[0].is_ok());
+        if paths.len() > 1 {
+            assert!(open[1].is_ok());
+            assert_eq!(guarded[1].as_ref().unwrap_err().kind(), "io");
+            // A device opens fine, it just isn't a SWF.
+            assert_eq!(open[2].as_ref().unwrap_err().kind(), "not-swf");
+            assert_eq!(guarded[2].as_ref().unwrap_err().kind(), "io");
+        }
+        assert_eq!(small[0].as_ref().unwrap_err().kind(), "limit-exceeded");
+    }
+
+    #[test]
+    fn test_error_summary() {
+        let mut summary = ErrorSummary::new();
+        for i in 0..7 {
+            summary.add(format!("{}.swf", i), Some(&Error::NotSwf));
+        }
+        summary.add("big.swf", Some(&Error::LimitExceeded));
+        summary.add("fine.swf", None);
+
+        assert_eq!(summary.total(), 9);
+        assert_eq!(summary.kinds["not-swf"].count, 7);
+        assert_eq!(summary.kinds["not-swf"].examples.len(), MAX_EXAMPLES);
+        assert_eq!(summary.to_string(), "\
+9 files: 1 succeeded, 8 failed
+  not-swf: 7 (e.g. 0.swf, 1.swf, 2.swf, 3.swf, 4.swf)
+  limit-exceeded: 1 (e.g. big.swf)");
+    }
+}
@@ -0,0 +1,335 @@
+//! Parsing DefineButton and DefineButton2: the characters shown in each of
+//! a button's states, and the actions it runs.
+
+use error::Error;
+use primitives::{read_u16_at, BitReader, ColorTransform, Matrix};
+use sound::SoundInfo;
+use tag::{codes, Tag};
+
+/// The state transitions a button action can run on, as the bits of
+/// `ButtonAction::conditions`.
+pub mod conditions {
+    /// The mouse is dragged onto the button while pressed.
+    pub const IDLE_TO_OVER_DOWN: u16 = 0x8000;
+    /// The mouse is released away from the button after being dragged off
+    /// it, `releaseOutside` in ActionScript.
+    pub const OUT_DOWN_TO_IDLE: u16 = 0x4000;
+    /// The mouse is dragged back onto the button while pressed.
+    pub const OUT_DOWN_TO_OVER_DOWN: u16 = 0x2000;
+    /// The mouse is dragged off the button while pressed, `dragOut`.
+    pub const OVER_DOWN_TO_OUT_DOWN: u16 = 0x1000;
+    /// The button is clicked and released, `release`.
+    pub const OVER_DOWN_TO_OVER_UP: u16 = 0x0800;
+    /// The button is pressed, `press`.
+    pub const OVER_UP_TO_OVER_DOWN: u16 = 0x0400;
+    /// The mouse leaves the button, `rollOut`.
+    pub const OVER_UP_TO_IDLE: u16 = 0x0200;
+    /// The mouse moves onto the button, `rollOver`.
+    pub const IDLE_TO_OVER_UP: u16 = 0x0100;
+    /// The mouse is dragged off the button while pressed, for buttons
+    /// tracked as menu items.
+    pub const OVER_DOWN_TO_IDLE: u16 = 0x0001;
+}
+
+/// A parsed DefineButton or DefineButton2 tag.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Button {
+    /// The character ID.
+    pub id: u16,
+    /// Which DefineButton this came from, 1 or 2.
+    pub version: u8,
+    /// Whether the button tracks the mouse like a menu item, so it can be
+    /// released over even if it wasn't pressed over.
+    pub track_as_menu: bool,
+    /// The characters making up the button's states.
+    pub records: Vec<ButtonRecord>,
+    /// The actions the button runs, and when.
+    pub actions: Vec<ButtonAction>
+}
+
+/// A character shown in one or more of a button's states.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ButtonRecord {
+    /// The character shown.
+    pub character_id: u16,
+    /// The depth it's placed at within the button.
+    pub depth: u16,
+    /// How it's transformed.
+    pub matrix: Matrix,
+    /// Its color transform. DefineButton leaves these to a separate
+    /// DefineButtonCxform tag.
+    pub color_transform: Option<ColorTransform>,
+    /// Whether it's shown when the mouse isn't over the button.
+    pub up: bool,
+    /// Whether it's shown when the mouse is over the button.
+    pub over: bool,
+    /// Whether it's shown while the button is pressed.
+    pub down: bool,
+    /// Whether it's part of the area that reacts to the mouse. It's never
+    /// drawn.
+    pub hit_test: bool,
+    /// How many filters are applied to it. The filters themselves aren't
+    /// parsed.
+    pub filter_count: usize,
+    /// Its blend mode, if it sets one.
+    pub blend_mode: Option<u8>
+}
+
+/// Actions a button runs on some state transitions.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ButtonAction {
+    /// The transitions, as a combination of the `conditions` constants.
+    pub conditions: u16,
+    /// The key that runs the actions when pressed, for `on (keyPress ...)`.
+    pub key_code: Option<u8>,
+    /// The AVM1 bytecode, which `avm1::Actions::new()` can disassemble.
+    pub actions: Vec<u8>
+}
+
+impl Button {
+    /// Parses a button, returning `None` if the tag isn't a DefineButton or
+    /// DefineButton2.
+    pub fn parse(tag: &Tag) -> Result<Option<Self>, Error> {
+        Ok(try!(parse_button(tag)).map(|(button, _)| button))
+    }
+
+    /// Returns the IDs of the characters used in the button's states.
+    pub fn characters(&self) -> Vec<u16> {
+        self.records.iter().map(|record| record.character_id).collect()
+    }
+}
+
+/// Returns where the character ID of each record is in the data of a
+/// DefineButton or DefineButton2, or of each sound in a DefineButtonSound,
+/// so that the IDs can be rewritten in place. Other tags have none.
+pub fn character_id_offsets(tag: &Tag) -> Result<Vec<usize>, Error> {
+    if tag.code() == codes::DEFINE_BUTTON_SOUND {
+        // The button's ID, then a sound for each of four transitions, each
+        // followed by a SOUNDINFO unless it's 0.
+        let data = tag.data();
+        let mut offsets = Vec::new();
+        let mut pos = 2;
+        for _ in 0..4 {
+            if try!(read_u16_at(data, pos)) == 0 {
+                pos += 2;
+                continue;
+            }
+            offsets.push(pos);
+            let mut rest = &data[pos + 2..];
+            try!(SoundInfo::read(&mut rest));
+            pos = data.len() - rest.len();
+        }
+        return Ok(offsets);
+    }
+    Ok(try!(parse_button(tag)).map(|(_, offsets)| offsets).unwrap_or_default())
+}
+
+/// Parses a DefineButton or DefineButton2, also returning where the
+/// character ID of each record is.
+fn parse_button(tag: &Tag) -> Result<Option<(Button, Vec<usize>)>, Error> {
+    let is_v2 = match tag.code() {
+        codes::DEFINE_BUTTON => false,
+        codes::DEFINE_BUTTON_2 => true,
+        _ => return Ok(None)
+    };
+    let data = tag.data();
+    let mut reader = BitReader::new(data);
+    let id = try!(reader.read_u16());
+    let (track_as_menu, action_offset) = if is_v2 {
+        (try!(reader.read_u8()) & 0x01 != 0, try!(reader.read_u16()) as usize)
+    } else {
+        (false, 0)
+    };
+
+    let mut records = Vec::new();
+    let mut offsets = Vec::new();
+    loop {
+        let flags = try!(reader.read_u8());
+        if flags == 0 {
+            break;
+        }
+        offsets.push(reader.byte_pos());
+        let character_id = try!(reader.read_u16());
+        let depth = try!(reader.read_u16());
+        let matrix = try!(Matrix::read(&mut reader));
+        let mut record = ButtonRecord {
+            character_id: character_id,
+            depth: depth,
+            matrix: matrix,
+            color_transform: None,
+            up: flags & 0x01 != 0,
+            over: flags & 0x02 != 0,
+            down: flags & 0x04 != 0,
+            hit_test: flags & 0x08 != 0,
+            filter_count: 0,
+            blend_mode: None
+        };
+        if is_v2 {
+            reader.align();
+            record.color_transform = Some(try!(ColorTransform::read(&mut reader, true)));
+            if flags & 0x10 != 0 {
+                record.filter_count = try!(skip_filters(&mut reader));
+            }
+            if flags & 0x20 != 0 {
+                record.blend_mode = Some(try!(reader.read_u8()));
+            }
+        }
+        records.push(record);
+    }
+
+    let actions = if !is_v2 {
+        // The rest of a DefineButton is a single list of actions, run
+        // when the button is clicked.
+        vec![ButtonAction {
+            conditions: conditions::OVER_DOWN_TO_OVER_UP,
+            key_code: None,
+            actions: data[reader.byte_pos()..].to_vec()
+        }]
+    } else if action_offset == 0 {
+        Vec::new()
+    } else {
+        // The offset counts from the ActionOffset field itself.
+        try!(read_cond_actions(data, 3 + action_offset))
+    };
+
+    Ok(Some((Button {
+        id: id,
+        version: if is_v2 { 2 } else { 1 },
+        track_as_menu: track_as_menu,
+        records: records,
+        actions: actions
+    }, offsets)))
+}
+
+/// Collects every button defined in a tag list.
+pub fn buttons(tags: &[Tag]) -> Result<Vec<Button>, Error> {
+    let mut buttons = Vec::new();
+    for tag in tags {
+        if let Some(button) = try!(Button::parse(tag)) {
+            buttons.push(button);
+        }
+    }
+    Ok(buttons)
+}
+
+/// Reads the BUTTONCONDACTIONs starting at `pos`. Each starts with its own
+/// size, so the next can be found without parsing the actions, and the
+/// last has a size of 0.
+fn read_cond_actions(data: &[u8], mut pos: usize) -> Result<Vec<ButtonAction>, Error> {
+    let mut actions = Vec::new();
+    loop {
+        let size = try!(read_u16_at(data, pos)) as usize;
+        let conditions = try!(read_u16_at(data, pos + 2)).swap_bytes();
+        let end = if size == 0 { data.len() } else { pos + size };
+        let code = match data.get(pos + 4..end) {
+            Some(code) => code,
+            None => return Err(Error::NotSwf)
+        };
+        // The key code shares the low byte with OverDownToIdle.
+        let key_code = ((conditions & 0xfe) >> 1) as u8;
+        actions.push(ButtonAction {
+            conditions: conditions & !0xfe,
+            key_code: if key_code == 0 { None } else { Some(key_code) },
+            actions: code.to_vec()
+        });
+        if size == 0 {
+            return Ok(actions);
+        }
+        pos = end;
+    }
+}
+
+/// Skips over a FILTERLIST, returning how many filters it had.
+fn skip_filters(reader: &mut BitReader) -> Result<usize, Error> {
+    let count = try!(reader.read_u8()) as usize;
+    for _ in 0..count {
+        let len = match try!(reader.read_u8()) {
+            // Drop shadow.
+            0 => 23,
+            // Blur.
+            1 => 9,
+            // Glow.
+            2 => 15,
+            // Bevel.
+            3 => 27,
+            // Gradient glow and gradient bevel: a color and a ratio for
+            // each gradient stop, then the same fields as a bevel.
+            4 | 7 => try!(reader.read_u8()) as usize * 5 + 19,
+            // Convolution: the matrix size, divisor and bias, the matrix,
+            // then a default color and flags.
+            5 => {
+                let width = try!(reader.read_u8()) as usize;
+                let height = try!(reader.read_u8()) as usize;
+                8 + width * height * 4 + 5
+            },
+            // Color matrix, of 20 floats.
+            6 => 80,
+            _ => return Err(Error::NotSwf)
+        };
+        for _ in 0..len {
+            try!(reader.read_u8());
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primitives::{ColorTransform, Matrix};
+    use tag::{codes, Tag};
+
+    #[test]
+    fn test_define_button_2() {
+        let mut data = vec![
+            1, 0, 0x01, 0, 0,
+            // Character 2 at depth 1 in the up and over states.
+            0x03, 2, 0, 1, 0, 0x00, 0x00,
+            // Character 3 at depth 2 as the hit area, blurred, with blend
+            // mode 3.
+            0x38, 3, 0, 2, 0, 0x00, 0x00, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3,
+            0
+        ];
+        let offset = data.len() as u8 - 3;
+        data[3] = offset;
+        data.extend_from_slice(&[
+            // Stop on press, then play on the Enter key.
+            6, 0, 0x04, 0x00, 0x07, 0x00,
+            0, 0, 0x00, 0x1a, 0x06, 0x00
+        ]);
+
+        let button = Button::parse(&Tag::new(codes::DEFINE_BUTTON_2, data)).unwrap().unwrap();
+        assert_eq!(button.id, 1);
+        assert!(button.track_as_menu);
+        assert_eq!(button.characters(), vec![2, 3]);
+        assert_eq!(button.records[0], ButtonRecord {
+            character_id: 2,
+            depth: 1,
+            matrix: Matrix::identity(),
+            color_transform: Some(ColorTransform::identity()),
+            up: true,
+            over: true,
+            down: false,
+            hit_test: false,
+            filter_count: 0,
+            blend_mode: None
+        });
+        assert!(button.records[1].hit_test && !button.records[1].up);
+        assert_eq!((button.records[1].filter_count, button.records[1].blend_mode), (1, Some(3)));
+        assert_eq!(button.actions, vec![
+            ButtonAction { conditions: conditions::OVER_UP_TO_OVER_DOWN, key_code: None, actions: vec![0x07, 0x00] },
+            ButtonAction { conditions: 0, key_code: Some(13), actions: vec![0x06, 0x00] }
+        ]);
+    }
+
+    #[test]
+    fn test_define_button() {
+        let tag = Tag::new(codes::DEFINE_BUTTON, vec![4, 0, 0x01, 5, 0, 1, 0, 0x00, 0, 0x07, 0x00]);
+        let button = Button::parse(&tag).unwrap().unwrap();
+        assert_eq!(button.version, 1);
+        assert_eq!(button.records[0].color_transform, None);
+        assert_eq!(button.actions[0].conditions, conditions::OVER_DOWN_TO_OVER_UP);
+        assert_eq!(button.actions[0].actions, vec![0x07, 0x00]);
+        assert_eq!(Button::parse(&Tag::new(codes::SHOW_FRAME, vec![])).unwrap(), None);
+    }
+}
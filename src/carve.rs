@@ -0,0 +1,82 @@
+//! Finding SWF signatures in arbitrary data, such as disk images and memory
+//! dumps, as the first step of carving movies out of them.
+//!
+//! Scanning is throughput-bound, so rather than testing every byte, the
+//! data is read a word at a time and only words holding a `W` are looked at
+//! closely. Each offset found is only a candidate: most will be chance
+//! matches, and `SwfHeaders::read_from()` or `concat::movie_ranges()` on the
+//! data from there tells the real ones apart.
+
+/// Every byte of a word set to `W`, the middle of each signature.
+const W_WORD: u64 = 0x5757_5757_5757_5757;
+const LOW_BITS: u64 = 0x0101_0101_0101_0101;
+const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+/// Returns the offset of every `FWS`, `CWS` and `ZWS` in `data`, in order.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::carve;
+/// assert_eq!(carve::signature_offsets(b"..FWS\x0a....CWS\x0a"), vec![2, 10]);
+/// ```
+pub fn signature_offsets(data: &[u8]) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut pos = 0;
+    while pos + 8 <= data.len() {
+        let word = data[pos..pos + 8].iter().rev().fold(0u64, |word, &byte| word << 8 | byte as u64);
+        // Each byte that was a W is now zero, and has its high bit set
+        // here. A borrow can also flag the byte after a real zero, so
+        // every flagged byte is checked again anyway.
+        let xored = word ^ W_WORD;
+        let mut flagged = xored.wrapping_sub(LOW_BITS) & !xored & HIGH_BITS;
+        while flagged != 0 {
+            let w = pos + (flagged.trailing_zeros() / 8) as usize;
+            check(data, w, &mut offsets);
+            flagged &= flagged - 1;
+        }
+        pos += 8;
+    }
+    for w in pos..data.len() {
+        check(data, w, &mut offsets);
+    }
+    offsets
+}
+
+/// Records the signature with its `W` at `w`, if there is one.
+fn check(data: &[u8], w: usize, offsets: &mut Vec<usize>) {
+    if w == 0 || data[w] != b'W' || data.get(w + 1) != Some(&b'S') {
+        return;
+    }
+    match data[w - 1] {
+        b'F' | b'C' | b'Z' => offsets.push(w - 1),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_byte_loop() {
+        // Signatures at every alignment, split across words, overlapping,
+        // and at both ends, with plenty of near misses in between.
+        let mut data = b"CWS".to_vec();
+        let mut state = 1u32;
+        for _ in 0..20000 {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            data.push(b"FCZWSX\x00\xd7"[(state >> 16) as usize % 8]);
+        }
+        data.extend_from_slice(b"WWSFWZWS");
+
+        let expected: Vec<usize> = data.windows(3)
+            .enumerate()
+            .filter(|&(_, window)| window == b"FWS" || window == b"CWS" || window == b"ZWS")
+            .map(|(offset, _)| offset)
+            .collect();
+        assert!(expected.len() > 50);
+        assert_eq!(signature_offsets(&data), expected);
+        assert_eq!(signature_offsets(b"FW"), Vec::<usize>::new());
+    }
+}
@@ -0,0 +1,170 @@
+//! Each of the format's small building blocks as a pair of pure functions,
+//! one decoding from bytes and one encoding back, for round-trip property
+//! tests.
+//!
+//! Two invariants hold for every pair:
+//!
+//! * Decoding what was encoded gives back the same value, for every value
+//!   the encoder accepts.
+//! * Encoding what was decoded gives back the same bytes when they were
+//!   already in the shortest form. RECTs with more bits than needed, long
+//!   tag headers, and EncodedU32s with extra continuation bytes all decode
+//!   fine, but come back shorter.
+//!
+//! Fixed-point values are the exception, since they're plain bit patterns:
+//! every pattern survives decoding and encoding, but floats between two
+//! patterns are rounded to the nearer one.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use swf_headers::codec;
+//! use swf_headers::primitives::Rect;
+//! let rect = Rect { x_min: -20, x_max: 11000, y_min: 0, y_max: 8000 };
+//! let bytes = codec::rect_to_bytes(&rect).unwrap();
+//! assert_eq!(codec::rect_from_bytes(&bytes).unwrap(), (rect, bytes.len()));
+//! ```
+
+use error::Error;
+use primitives::{read_encoded_u32, signed_bits_needed, write_encoded_u32, BitReader, BitWriter, Rect};
+use tag;
+
+/// Decodes a RECT from the start of `data`, returning it with the number of
+/// bytes it took up.
+pub fn rect_from_bytes(data: &[u8]) -> Result<(Rect, usize), Error> {
+    let mut reader = BitReader::new(data);
+    let rect = try!(Rect::read(&mut reader));
+    Ok((rect, reader.byte_pos()))
+}
+
+/// Encodes a RECT with as few bits as its fields need. A field needing more
+/// than the 31 bits a RECT can give it is an error.
+pub fn rect_to_bytes(rect: &Rect) -> Result<Vec<u8>, Error> {
+    if signed_bits_needed(&[rect.x_min, rect.x_max, rect.y_min, rect.y_max]) > 31 {
+        return Err(Error::NotSwf);
+    }
+    let mut writer = BitWriter::new();
+    rect.write(&mut writer);
+    Ok(writer.into_bytes())
+}
+
+/// Decodes a signed 8.8 fixed-point value, as used for gradient focal
+/// points. The header's frame rate has the same layout, unsigned.
+pub fn fixed8_from_bits(bits: u16) -> f32 {
+    bits as i16 as f32 / 256.0
+}
+
+/// Encodes a signed 8.8 fixed-point value, rounding to the nearest 1/256th
+/// and saturating at the ends of the range.
+pub fn fixed8_to_bits(value: f32) -> u16 {
+    let scaled = (value * 256.0).round();
+    scaled.max(::std::i16::MIN as f32).min(::std::i16::MAX as f32) as i16 as u16
+}
+
+/// Decodes a signed 16.16 fixed-point value, as used for matrix scales.
+pub fn fixed16_from_bits(bits: u32) -> f64 {
+    bits as i32 as f64 / 65536.0
+}
+
+/// Encodes a signed 16.16 fixed-point value, rounding to the nearest
+/// 1/65536th and saturating at the ends of the range.
+pub fn fixed16_to_bits(value: f64) -> u32 {
+    let scaled = (value * 65536.0).round();
+    scaled.max(::std::i32::MIN as f64).min(::std::i32::MAX as f64) as i32 as u32
+}
+
+/// Decodes a tag header from the start of `data`, returning the code, the
+/// payload length, whether the header was a long one, and the number of
+/// bytes it took up.
+pub fn tag_header_from_bytes(data: &[u8]) -> Result<(u16, u32, bool, usize), Error> {
+    let mut reader = data;
+    match try!(tag::read_header(&mut reader)) {
+        Some((code, length)) => {
+            let header_len = data.len() - reader.len();
+            Ok((code, length, header_len == 6, header_len))
+        },
+        None => Err(Error::NotSwf)
+    }
+}
+
+/// Encodes a tag header, using the long form if asked for or if the length
+/// needs it. A code that doesn't fit in 10 bits is an error.
+pub fn tag_header_to_bytes(code: u16, length: u32, long_header: bool) -> Result<Vec<u8>, Error> {
+    if code > 0x3ff {
+        return Err(Error::NotSwf);
+    }
+    let mut bytes = Vec::with_capacity(6);
+    if long_header || length >= 0x3f {
+        let code_and_length = (code << 6) | 0x3f;
+        bytes.extend_from_slice(&[code_and_length as u8, (code_and_length >> 8) as u8]);
+        bytes.extend_from_slice(&[length as u8, (length >> 8) as u8, (length >> 16) as u8, (length >> 24) as u8]);
+    } else {
+        let code_and_length = (code << 6) | length as u16;
+        bytes.extend_from_slice(&[code_and_length as u8, (code_and_length >> 8) as u8]);
+    }
+    Ok(bytes)
+}
+
+/// Decodes an EncodedU32 from the start of `data`, returning it with the
+/// number of bytes it took up.
+pub fn encoded_u32_from_bytes(data: &[u8]) -> Result<(u32, usize), Error> {
+    let mut reader = data;
+    let value = try!(read_encoded_u32(&mut reader));
+    Ok((value, data.len() - reader.len()))
+}
+
+/// Encodes an EncodedU32 in as few bytes as it fits in.
+pub fn encoded_u32_to_bytes(value: u32) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(5);
+    write_encoded_u32(&mut bytes, value).unwrap();
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primitives::Rect;
+
+    #[test]
+    fn test_round_trips() {
+        for &(x_min, x_max) in &[(0, 0), (-1, 1), (-0x4000_0000, 0x3fff_ffff), (11000, -20)] {
+            let rect = Rect { x_min: x_min, x_max: x_max, y_min: x_max, y_max: x_min };
+            let bytes = rect_to_bytes(&rect).unwrap();
+            assert_eq!(rect_from_bytes(&bytes).unwrap(), (rect, bytes.len()));
+        }
+        assert!(rect_to_bytes(&Rect { x_min: 0, x_max: 0x4000_0000, y_min: 0, y_max: 0 }).is_err());
+
+        for bits in 0..0x10000u32 {
+            assert_eq!(fixed8_to_bits(fixed8_from_bits(bits as u16)), bits as u16);
+        }
+        for &bits in &[0, 1, 0x10000, 0x7fff_ffff, 0x8000_0000, 0xffff_ffff] {
+            assert_eq!(fixed16_to_bits(fixed16_from_bits(bits)), bits);
+        }
+        assert_eq!(fixed8_to_bits(1000.0), 0x7fff);
+
+        for &(code, length, long_header) in &[(1, 0, false), (1, 0, true), (0x3ff, 62, false), (6, 63, true),
+                                              (82, 0xffff_ffff, true)] {
+            let bytes = tag_header_to_bytes(code, length, long_header).unwrap();
+            assert_eq!(tag_header_from_bytes(&bytes).unwrap(), (code, length, long_header, bytes.len()));
+        }
+        assert!(tag_header_to_bytes(0x400, 0, false).is_err());
+
+        for &value in &[0, 0x7f, 0x80, 624485, ::std::u32::MAX] {
+            let bytes = encoded_u32_to_bytes(value);
+            assert_eq!(encoded_u32_from_bytes(&bytes).unwrap(), (value, bytes.len()));
+        }
+    }
+
+    #[test]
+    fn test_longer_forms_shrink() {
+        // A RECT with 31-bit fields holding zeros.
+        let padded = [0xf8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let (rect, len) = rect_from_bytes(&padded).unwrap();
+        assert_eq!(len, padded.len());
+        assert_eq!(rect_to_bytes(&rect).unwrap(), vec![0x00]);
+
+        // ShowFrame with a long header.
+        let (code, length, _, _) = tag_header_from_bytes(&[0x7f, 0x00, 0, 0, 0, 0]).unwrap();
+        assert_eq!(tag_header_to_bytes(code, length, false).unwrap(), vec![0x40, 0x00]);
+    }
+}
@@ -0,0 +1,84 @@
+//! Comparing SWF files by what they contain rather than how they're stored.
+
+use std::path::Path;
+
+use decoded_swf;
+use error::Error;
+use super::ReadStatus;
+
+/// Returns true if two SWF files have identical decompressed bodies, however
+/// each one is compressed.
+///
+/// The body is everything after the first 8 bytes, so the stage size, frame
+/// rate, frame count and every tag are compared, while the signature,
+/// version byte and file length aren't. Both files are streamed side by
+/// side, and reading stops at the first difference.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::compare;
+/// if let Ok(true) = compare::are_equivalent("mirror_a/game.swf", "mirror_b/game.swf") {
+///     println!("Same game, different wrapping");
+/// }
+/// ```
+pub fn are_equivalent<P: AsRef<Path>, Q: AsRef<Path>>(path_a: P, path_b: Q) -> Result<bool, Error> {
+    let (_, mut a) = try!(decoded_swf::open_body(path_a));
+    let (_, mut b) = try!(decoded_swf::open_body(path_b));
+    let mut buf_a = [0; 8192];
+    let mut buf_b = [0; 8192];
+    loop {
+        let status_a = try!(a.read_exact_or_eof(&mut buf_a));
+        let status_b = try!(b.read_exact_or_eof(&mut buf_b));
+        let len = match (status_a, status_b) {
+            (ReadStatus::Eof, ReadStatus::Eof) => return Ok(true),
+            (ReadStatus::Complete, ReadStatus::Complete) => buf_a.len(),
+            (ReadStatus::Truncated(len_a), ReadStatus::Truncated(len_b)) if len_a == len_b => len_a,
+            _ => return Ok(false)
+        };
+        if buf_a[..len] != buf_b[..len] {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    use {Signature, SwfHeaders};
+    use tag::{codes, Tag};
+    use writer;
+
+    #[test]
+    fn test_recompressed() {
+        let write = |name: &str, signature: Signature, frame_count: u16| {
+            let headers = SwfHeaders {
+                signature: signature,
+                version: 10,
+                file_length: 0,
+                width: 200,
+                height: 200,
+                frame_rate: 24,
+                frame_rate_fraction: 0,
+                frame_count: frame_count
+            };
+            let path = env::temp_dir().join(name);
+            let tags = vec![Tag::new(codes::SHOW_FRAME, vec![])];
+            File::create(&path).unwrap().write_all(&writer::to_bytes(&headers, &tags).unwrap()).unwrap();
+            path
+        };
+        let plain = write("swf_headers_test_compare_fws.swf", Signature::Uncompressed, 1);
+        let zlib = write("swf_headers_test_compare_cws.swf", Signature::ZlibCompressed, 1);
+        let other = write("swf_headers_test_compare_other.swf", Signature::ZlibCompressed, 2);
+
+        assert!(are_equivalent(&plain, &zlib).unwrap());
+        assert!(!are_equivalent(&plain, &other).unwrap());
+        for path in &[plain, zlib, other] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Finding every movie in a file that holds more than one.
+//!
+//! Some download managers resumed broken transfers by appending the whole
+//! file again, leaving two or more complete SWFs back to back. Players stop
+//! at the end of the first. Here each one's physical end is worked out from
+//! its own header, and a second valid header starting right there is taken
+//! as another movie. Anything else after a movie is left as trailing bytes.
+
+use std::io::{self, Cursor};
+use std::ops::Range;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use flate2::bufread::ZlibDecoder;
+
+use super::{Signature, SwfHeaders};
+use decoded_swf::DecodedSwf;
+use error::Error;
+
+/// Returns where each movie in `data` starts and ends.
+///
+/// The first movie always starts at 0, and the list is empty if `data`
+/// doesn't start with a SWF header at all. A movie whose length can't be
+/// worked out, such as one with a broken zlib stream, runs to the end of
+/// `data`.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::fs::File;
+/// use std::io::Read;
+/// use swf_headers::concat;
+/// if let Ok(mut file) = File::open("example.swf") {
+///     let mut data = Vec::new();
+///     file.read_to_end(&mut data).unwrap();
+///     if concat::movie_ranges(&data).len() > 1 {
+///         println!("more than one movie in here");
+///     }
+/// }
+/// ```
+pub fn movie_ranges(data: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while start < data.len() && starts_movie(&data[start..]) {
+        let end = match physical_len(&data[start..]) {
+            Some(len) => start + len,
+            None => data.len()
+        };
+        ranges.push(start..end);
+        start = end;
+    }
+    ranges
+}
+
+/// Parses each movie `movie_ranges()` finds, separately.
+///
+/// One broken movie doesn't stop the others being read.
+pub fn read_movies(data: &[u8]) -> Vec<Result<(SwfHeaders, DecodedSwf), Error>> {
+    movie_ranges(data).into_iter()
+        .map(|range| SwfHeaders::read_from(Cursor::new(data[range].to_vec())))
+        .collect()
+}
+
+/// Returns true if `data` starts with a header that parses.
+fn starts_movie(data: &[u8]) -> bool {
+    match data.get(..3) {
+        Some(b"FWS") | Some(b"CWS") | Some(b"ZWS") => {},
+        _ => return false
+    }
+    SwfHeaders::read_from(Cursor::new(data.to_vec())).is_ok()
+}
+
+/// Works out how many bytes of `data` the movie at its start takes up.
+///
+/// Uncompressed movies give it in their file length. zlib streams end on
+/// their own, so the compressed body is however much the decoder took, and
+/// LZMA bodies have their compressed length in the header.
+fn physical_len(data: &[u8]) -> Option<usize> {
+    let sig = match data[0] {
+        b'F' => Signature::Uncompressed,
+        b'C' => Signature::ZlibCompressed,
+        _ => Signature::LzmaCompressed
+    };
+    let len = match sig {
+        Signature::Uncompressed => (&data[4..8]).read_u32::<LittleEndian>().unwrap() as usize,
+        Signature::ZlibCompressed => {
+            let mut decoder = ZlibDecoder::new(&data[8..]);
+            if io::copy(&mut decoder, &mut io::sink()).is_err() {
+                return None;
+            }
+            8 + decoder.total_in() as usize
+        },
+        // The header, the compressed length, then the LZMA properties.
+        Signature::LzmaCompressed => {
+            if data.len() < 12 {
+                return None;
+            }
+            17 + (&data[8..12]).read_u32::<LittleEndian>().unwrap() as usize
+        }
+    };
+    // A length shorter than the header would never get anywhere.
+    if len <= 8 || len > data.len() {
+        None
+    } else {
+        Some(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    use {Signature, SwfHeaders};
+    use tag::{codes, Tag};
+    use writer;
+
+    #[test]
+    fn test_concatenated() {
+        let headers = |signature, frame_count| SwfHeaders {
+            signature: signature,
+            version: 10,
+            file_length: 0,
+            width: 320,
+            height: 240,
+            frame_rate: 12,
+            frame_rate_fraction: 0,
+            frame_count: frame_count
+        };
+        let tags = vec![Tag::new(codes::SHOW_FRAME, vec![])];
+        let first = writer::to_bytes(&headers(Signature::ZlibCompressed, 1), &tags).unwrap();
+        let second = writer::to_bytes(&headers(Signature::Uncompressed, 2), &tags).unwrap();
+
+        let mut data = [&first[..], &second[..], &first[..]].concat();
+        let ranges = movie_ranges(&data);
+        assert_eq!(ranges, vec![0..first.len(), first.len()..first.len() + second.len(),
+                                first.len() + second.len()..data.len()]);
+
+        let movies = read_movies(&data);
+        assert_eq!(movies.len(), 3);
+        let frame_counts: Vec<_> = movies.iter().map(|movie| movie.as_ref().unwrap().0.frame_count()).collect();
+        assert_eq!(frame_counts, vec![1, 2, 1]);
+        let mut body = Vec::new();
+        let (_, mut decoded) = read_movies(&data).remove(1).unwrap();
+        decoded.read_to_end(&mut body).unwrap();
+        // What's left after the header: ShowFrame, then End.
+        assert_eq!(body, vec![0x40, 0x00, 0x00, 0x00]);
+
+        // Junk after a movie is just junk.
+        data.truncate(first.len());
+        data.extend_from_slice(b"FWS\x0a garbage");
+        assert_eq!(movie_ranges(&data), vec![0..first.len()]);
+        assert!(movie_ranges(b"not a swf").is_empty());
+    }
+}
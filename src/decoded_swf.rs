@@ -1,49 +1,841 @@
+use std::cmp;
 use std::io;
-use std::io::Read;
-use std::fs::File;
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use flate2::FlateReadExt;
 use flate2::read::ZlibDecoder;
 use lzma;
 
-use super::Signature;
-use error::Error;
+use super::{Signature, SwfHeaders};
+#[cfg(feature = "digests")]
+use digests::{DigestState, Digests};
+use error::{BodyOverrun, BodyTooLong, Error};
+use options::ParseOptions;
 
 enum Inner<R: Read> {
-    Raw(File),
+    Raw(R),
     Zlib(ZlibDecoder<R>),
-    Lzma(lzma::Reader<R>)
+    Lzma(Box<lzma::Reader<R>>),
+    Wrapped(Box<dyn Read + Send>)
+}
+
+/// Whatever the SWF is being read from, shared with the digests so they
+/// can hash anything the decompressor leaves unread.
+pub type Source = Arc<Mutex<Box<dyn Read + Send>>>;
+
+/// The stream underneath the decompressor, hashed as it's read if digests
+/// were asked for.
+struct RawFile {
+    source: Source,
+    #[cfg(feature = "digests")]
+    digests: Option<DigestState>
+}
+
+impl Read for RawFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = try!(self.source.lock().unwrap().read(buf));
+        #[cfg(feature = "digests")]
+        {
+            if let Some(ref digests) = self.digests {
+                digests.raw(&buf[..read]);
+            }
+        }
+        Ok(read)
+    }
+}
+
+/// How far `DecodedSwf::read_exact_or_eof()` got.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ReadStatus {
+    /// The whole buffer was filled.
+    Complete,
+    /// The stream was already at EOF, and nothing was read.
+    Eof,
+    /// The stream ended partway through, after this many bytes.
+    Truncated(usize)
+}
+
+/// How a body was compressed, as read from the start of the compressed
+/// stream, for `DecodedSwf::compression()`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CompressionInfo {
+    /// An FWS file's body, which isn't compressed.
+    Uncompressed,
+    /// A CWS file's zlib stream, going by its 2-byte header.
+    Zlib {
+        /// The size of the compressor's window, as a power of two from 8
+        /// to 15.
+        window_bits: u8,
+        /// The level the compressor says it used, from 0 (fastest) to 3
+        /// (smallest). Decompressing doesn't depend on it.
+        level: u8,
+        /// Whether the stream needs a preset dictionary. Nothing reading
+        /// SWF files has one, so such a stream can't be decompressed.
+        has_dictionary: bool
+    },
+    /// A ZWS file's LZMA stream, going by the fields before it.
+    Lzma {
+        /// The compressed length the file declares, not counting the
+        /// properties.
+        compressed_len: u32,
+        /// The number of literal context bits.
+        lc: u8,
+        /// The number of literal position bits.
+        lp: u8,
+        /// The number of position bits.
+        pb: u8,
+        /// The dictionary size, in bytes.
+        dict_size: u32
+    }
 }
 
 /// Handles decompressing swf innards and reading the results.
 ///
 /// This is a helper struct abstracting over the various kinds of compression
 /// SWF files can use, namely zlib and LZMA.
+///
+/// It's `Send`, so it can be read on another thread from the one that opened
+/// it. For reading one body from several threads at once, see
+/// `into_shared()`.
 pub struct DecodedSwf {
-    _inner: Inner<File>
+    _inner: Inner<RawFile>,
+    // How much has been read, to check against the file length.
+    read: u64,
+    // The most a body can decompress to, and whether going past it is
+    // Error::LimitExceeded rather than Error::BodyOverrun.
+    limit: Option<u64>,
+    limit_is_max: bool,
+    compression: Option<CompressionInfo>,
+    // The file and options it was opened with, for try_clone().
+    origin: Option<(PathBuf, ParseOptions)>,
+    // The first 8 bytes of the file, for checkpoint(), unless the body goes
+    // through a wrapper.
+    header: Option<[u8; 8]>,
+    #[cfg(feature = "digests")]
+    digests: Option<DigestState>
 }
 
 impl DecodedSwf {
-    /// Takes a file and a SWF signature, and handles decompressing the file
-    /// accordingly, returning a reader.
-    pub fn decompress(file: File, sig: Signature) -> Result<Self, super::Error> {
+    /// Takes a file, or any other stream, and a SWF signature, and handles
+    /// decompressing it accordingly, returning a reader.
+    ///
+    /// Only `Read` is needed, so pipes and sockets work as well as files.
+    pub fn decompress<R: Read + Send + 'static>(reader: R, sig: Signature) -> Result<Self, super::Error> {
+        let (compression, source) = try!(peek_compression(Box::new(reader), sig));
+        let mut decoded = try!(DecodedSwf::from_raw(RawFile {
+            source: Arc::new(Mutex::new(source)),
+            #[cfg(feature = "digests")]
+            digests: None
+        }, sig));
+        decoded.compression = compression;
+        Ok(decoded)
+    }
+
+    fn from_raw(raw: RawFile, sig: Signature) -> Result<Self, Error> {
+        #[cfg(feature = "digests")]
+        let digests = raw.digests.clone();
         let inner = match sig {
-            Signature::Uncompressed => Inner::Raw(file),
-            Signature::ZlibCompressed => Inner::Zlib(file.zlib_decode()),
-            Signature::LzmaCompressed => Inner::Lzma(try!(lzma::Reader::from(file)))
+            Signature::Uncompressed => Inner::Raw(raw),
+            Signature::ZlibCompressed => Inner::Zlib(raw.zlib_decode()),
+            Signature::LzmaCompressed => Inner::Lzma(Box::new(try!(lzma::Reader::from(raw))))
         };
         Ok(DecodedSwf {
-            _inner: inner
+            _inner: inner,
+            read: 0,
+            limit: None,
+            limit_is_max: false,
+            compression: None,
+            origin: None,
+            header: None,
+            #[cfg(feature = "digests")]
+            digests: digests
+        })
+    }
+
+    /// Returns how the body was compressed, or `None` if the stream ended
+    /// before saying.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use swf_headers::{CompressionInfo, SwfHeaders};
+    /// if let Ok((headers, decoded)) = SwfHeaders::open("example.swf") {
+    ///     if let Some(CompressionInfo::Lzma { dict_size, .. }) = decoded.compression() {
+    ///         println!("LZMA with a {} byte dictionary", dict_size);
+    ///     }
+    /// }
+    /// ```
+    pub fn compression(&self) -> Option<CompressionInfo> {
+        self.compression
+    }
+
+    /// Fills the buffer like `Read::read_exact()`, but tells a clean EOF
+    /// before the first byte apart from one partway through.
+    ///
+    /// When reading record by record, the first means the stream ended at a
+    /// record boundary, which plenty of SWF files do, and the second means
+    /// the file was truncated.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use swf_headers::{ReadStatus, SwfHeaders};
+    /// if let Ok((headers, mut decoded)) = SwfHeaders::open("example.swf") {
+    ///     let mut header = [0; 2];
+    ///     match decoded.read_exact_or_eof(&mut header).unwrap() {
+    ///         ReadStatus::Complete => println!("got a tag header"),
+    ///         ReadStatus::Eof => println!("no more tags"),
+    ///         ReadStatus::Truncated(_) => println!("the file was cut off")
+    ///     }
+    /// }
+    /// ```
+    pub fn read_exact_or_eof(&mut self, buf: &mut [u8]) -> Result<ReadStatus, Error> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {},
+                Err(err) => return Err(err.into())
+            }
+        }
+        Ok(if filled == buf.len() {
+            ReadStatus::Complete
+        } else if filled == 0 {
+            ReadStatus::Eof
+        } else {
+            ReadStatus::Truncated(filled)
+        })
+    }
+
+    /// Skips forward `n` bytes in the decompressed stream, returning
+    /// `Error::NotSwf` if it ends first.
+    ///
+    /// Nothing skipped is kept around, so this is the cheap way past large
+    /// tags that aren't of interest.
+    pub fn skip(&mut self, n: u64) -> Result<(), Error> {
+        let skipped = try!(io::copy(&mut self.by_ref().take(n), &mut io::sink()));
+        if skipped < n {
+            return Err(Error::NotSwf);
+        }
+        Ok(())
+    }
+
+    /// Opens another, independent stream of the same body, at the same
+    /// position, so that several analyzers can each read the file their own
+    /// way.
+    ///
+    /// Only streams from `SwfHeaders::open()` and
+    /// `SwfHeaders::open_with_options()` can be cloned. The file is opened
+    /// again, with the same options, and decompressed up to where this one
+    /// is, so it has to be unchanged since, and cloning far into a big
+    /// compressed file takes a while.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::thread;
+    /// use swf_headers::SwfHeaders;
+    /// use swf_headers::tag;
+    /// if let Ok((_, decoded)) = SwfHeaders::open("example.swf") {
+    ///     let clone = decoded.try_clone().unwrap();
+    ///     let worker = thread::spawn(move || tag::read_all(clone).unwrap().len());
+    ///     assert_eq!(tag::read_all(decoded).unwrap().len(), worker.join().unwrap());
+    /// }
+    /// ```
+    pub fn try_clone(&self) -> Result<Self, Error> {
+        let (path, options) = match self.origin {
+            Some((ref path, ref options)) => (path, options),
+            None => return Err(Error::IoError(io::Error::new(io::ErrorKind::Other,
+                                                             "only a DecodedSwf opened from a path can be cloned")))
+        };
+        let (_, mut clone) = try!(SwfHeaders::open_with_options(path, options));
+        if clone.read > self.read {
+            return Err(Error::NotSwf);
+        }
+        let behind = self.read - clone.read;
+        try!(clone.skip(behind));
+        Ok(clone)
+    }
+
+    /// Records how far through the body this stream is, for `resume()` to
+    /// pick up from after a restart. Returns `None` for streams that went
+    /// through a body wrapper, or that never saw the start of the file.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::fs::File;
+    /// use std::io::Read;
+    /// use swf_headers::{Checkpoint, DecodedSwf, SwfHeaders};
+    /// if let Ok((_, mut decoded)) = SwfHeaders::open("example.swf") {
+    ///     let mut start = [0; 1024];
+    ///     decoded.read_exact(&mut start).unwrap();
+    ///     let saved = decoded.checkpoint().unwrap().to_bytes();
+    ///     // ... after a restart ...
+    ///     let checkpoint = Checkpoint::from_bytes(&saved).unwrap();
+    ///     let resumed = DecodedSwf::resume(File::open("example.swf").unwrap(), &checkpoint).unwrap();
+    /// }
+    /// ```
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        self.header.map(|header| Checkpoint {
+            header: header,
+            position: self.read
+        })
+    }
+
+    /// Opens `file` again at a checkpoint, failing if it doesn't start the
+    /// way the checkpointed file did.
+    ///
+    /// Uncompressed bodies carry on straight from the checkpoint. The zlib
+    /// and LZMA decoders keep state that can't be saved, so compressed
+    /// bodies are decompressed again up to it, and only the work after the
+    /// checkpoint is saved. For long jobs over compressed files, converting
+    /// them to uncompressed first, with `bulk::convert()`, makes every
+    /// resume instant.
+    ///
+    /// The body is read with the default `ParseOptions`.
+    pub fn resume<R: Read + Seek + Send + 'static>(mut file: R, checkpoint: &Checkpoint) -> Result<Self, Error> {
+        let mut header = [0; 8];
+        try!(file.seek(SeekFrom::Start(0)));
+        try!(file.read_exact(&mut header));
+        if header != checkpoint.header {
+            return Err(Error::NotSwf);
+        }
+        let sig = match header[0] {
+            b'F' => Signature::Uncompressed,
+            b'C' => Signature::ZlibCompressed,
+            _ => Signature::LzmaCompressed
+        };
+        if sig == Signature::Uncompressed {
+            try!(file.seek(SeekFrom::Start(8 + checkpoint.position)));
+        }
+        let mut decoded = try!(decompress_with_options(file, sig, &header, &ParseOptions::default()));
+        if sig == Signature::Uncompressed {
+            decoded.read = checkpoint.position;
+        } else {
+            try!(decoded.skip(checkpoint.position));
+        }
+        Ok(decoded)
+    }
+
+    /// Returns an iterator over the rest of the decompressed body, in chunks
+    /// of `size` bytes, for feeding hashing, uploading or scanning stages.
+    /// Every chunk is full apart from the last, which can be shorter, and an
+    /// error ends the iteration after it's returned.
+    ///
+    /// Panics if `size` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use swf_headers::SwfHeaders;
+    /// if let Ok((headers, mut decoded)) = SwfHeaders::open("example.swf") {
+    ///     let total: usize = decoded.chunks(64 * 1024).map(|chunk| chunk.unwrap().len()).sum();
+    ///     // ...
+    /// }
+    /// ```
+    pub fn chunks<'a>(&'a mut self, size: usize) -> Chunks<'a> {
+        assert!(size != 0, "chunk size must be non-zero");
+        Chunks {
+            decoded: self,
+            size: size,
+            done: false
+        }
+    }
+
+    /// Copies the rest of the decompressed body into `writer`, returning how
+    /// many bytes were copied. Only a small buffer is held at a time, so
+    /// bodies of any size can be copied.
+    ///
+    /// The body should end exactly where the file length in `headers` says
+    /// it does. If it doesn't, the result is `Error::NotSwf`, with
+    /// everything read already written.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::fs::File;
+    /// use swf_headers::SwfHeaders;
+    /// if let Ok((headers, mut decoded)) = SwfHeaders::open("example.swf") {
+    ///     let mut body = File::create("example.body").unwrap();
+    ///     let copied = decoded.copy_body_to(&mut body, &headers);
+    /// }
+    /// ```
+    pub fn copy_body_to<W: Write>(&mut self, writer: W, headers: &SwfHeaders) -> Result<u64, Error> {
+        self.copy_body_to_with_progress(writer, headers, |_| {})
+    }
+
+    /// Like `copy_body_to()`, calling `progress` with the number of bytes
+    /// copied so far after every write.
+    pub fn copy_body_to_with_progress<W, F>(&mut self, mut writer: W, headers: &SwfHeaders, mut progress: F)
+        -> Result<u64, Error>
+        where W: Write, F: FnMut(u64)
+    {
+        let mut buf = [0; 8192];
+        let mut copied = 0;
+        loop {
+            let read = match self.read(&mut buf) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into())
+            };
+            try!(writer.write_all(&buf[..read]));
+            copied += read as u64;
+            progress(copied);
+        }
+        if self.read != headers.expected_body_len() as u64 {
+            return Err(Error::NotSwf);
+        }
+        Ok(copied)
+    }
+
+    /// Decompresses the rest of the body into memory, returning a view of it
+    /// that can be cloned cheaply and sent between threads, so several
+    /// threads can each read the body at their own pace without
+    /// decompressing it again.
+    ///
+    /// Every clone shares the same bytes, and has its own position.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::thread;
+    /// use swf_headers::SwfHeaders;
+    /// use swf_headers::tag;
+    /// if let Ok((_, decoded)) = SwfHeaders::open("example.swf") {
+    ///     let body = decoded.into_shared().unwrap();
+    ///     let workers: Vec<_> = (0..4).map(|_| {
+    ///         let body = body.clone();
+    ///         thread::spawn(move || tag::read_all(body).unwrap().len())
+    ///     }).collect();
+    ///     // ...
+    /// }
+    /// ```
+    pub fn into_shared(mut self) -> Result<SharedBody, Error> {
+        let mut body = Vec::new();
+        try!(self.read_to_end(&mut body));
+        Ok(SharedBody {
+            data: Arc::from(body.into_boxed_slice()),
+            pos: 0
+        })
+    }
+
+    /// Returns the digests of the file and its decompressed body, once the
+    /// body has been read to EOF. Only available with the `digests` feature,
+    /// and only for files opened with `ParseOptions::compute_digests()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::io::{self, Read};
+    /// use swf_headers::{ParseOptions, SwfHeaders};
+    /// let options = ParseOptions::new().compute_digests(true);
+    /// if let Ok((headers, mut decoded)) = SwfHeaders::open_with_options("example.swf", &options) {
+    ///     io::copy(&mut decoded, &mut io::sink()).unwrap();
+    ///     println!("{:?}", decoded.digests().unwrap().raw_sha256);
+    /// }
+    /// ```
+    #[cfg(feature = "digests")]
+    pub fn digests(&self) -> Option<Digests> {
+        self.digests.as_ref().and_then(DigestState::digests)
+    }
+}
+
+/// How far through a body a `DecodedSwf` was, made by
+/// `DecodedSwf::checkpoint()`.
+///
+/// Apart from the position, it holds the first 8 bytes of the file, so that
+/// resuming from it on a different file fails rather than reading nonsense.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Checkpoint {
+    header: [u8; 8],
+    position: u64
+}
+
+impl Checkpoint {
+    /// Returns how many bytes of the decompressed body, counting from the
+    /// 9th byte of the file, had been read.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Encodes the checkpoint in 16 bytes, for saving.
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&self.header);
+        (&mut bytes[8..]).write_u64::<LittleEndian>(self.position).unwrap();
+        bytes
+    }
+
+    /// Decodes a checkpoint saved with `to_bytes()`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != 16 {
+            return Err(Error::NotSwf);
+        }
+        let mut header = [0; 8];
+        header.copy_from_slice(&bytes[..8]);
+        Ok(Checkpoint {
+            header: header,
+            position: try!((&bytes[8..]).read_u64::<LittleEndian>())
+        })
+    }
+}
+
+/// An iterator over a decompressed body in fixed-size chunks, made by
+/// `DecodedSwf::chunks()`.
+pub struct Chunks<'a> {
+    decoded: &'a mut DecodedSwf,
+    size: usize,
+    done: bool
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<u8>>> {
+        if self.done {
+            return None;
+        }
+        let mut chunk = vec![0; self.size];
+        let mut filled = 0;
+        while filled < chunk.len() {
+            match self.decoded.read(&mut chunk[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {},
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        if filled < chunk.len() {
+            self.done = true;
+            if filled == 0 {
+                return None;
+            }
+            chunk.truncate(filled);
+        }
+        Some(Ok(chunk))
+    }
+}
+
+/// A decompressed body held in memory and shared between clones, made by
+/// `DecodedSwf::into_shared()`.
+///
+/// Reading and seeking only move this view's own position. Seeking past the
+/// end is allowed, and reads there give nothing.
+#[derive(Clone, Debug)]
+pub struct SharedBody {
+    data: Arc<[u8]>,
+    pos: u64
+}
+
+impl SharedBody {
+    /// Returns the whole body, whatever the position.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns what's left to read.
+    pub fn remaining(&self) -> &[u8] {
+        let start = cmp::min(self.pos, self.data.len() as u64) as usize;
+        &self.data[start..]
+    }
+
+    /// Returns how far into the body this view is.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+}
+
+impl Read for SharedBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = try!(self.remaining().read(buf));
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl BufRead for SharedBody {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self.remaining())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+    }
+}
+
+impl Seek for SharedBody {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => {
+                self.pos = offset;
+                return Ok(offset);
+            },
+            SeekFrom::End(offset) => (self.data.len() as u64, offset),
+            SeekFrom::Current(offset) => (self.pos, offset)
+        };
+        let target = base as i64 + offset;
+        if target < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek before the start of the body"));
+        }
+        self.pos = target as u64;
+        Ok(self.pos)
+    }
+}
+
+/// Opens a file for reading, refusing it if it's bigger than `options`
+/// allow, or a symlink or special file they don't.
+///
+/// The checks run on the path before opening, so that opening a FIFO can't
+/// block, and again on the open file, so that swapping the path for
+/// something else in between doesn't get past them.
+pub fn open_file(path: &Path, options: &ParseOptions) -> Result<File, Error> {
+    if !options.follows_symlinks() && try!(fs::symlink_metadata(path)).file_type().is_symlink() {
+        return Err(refused("symbolic links aren't followed"));
+    }
+    if !options.allows_special_files() && !try!(fs::metadata(path)).is_file() {
+        return Err(refused("not a regular file"));
+    }
+    let file = try!(File::open(path));
+    let metadata = try!(file.metadata());
+    if !options.allows_special_files() && !metadata.is_file() {
+        return Err(refused("not a regular file"));
+    }
+    if metadata.len() > options.file_size_limit() {
+        return Err(Error::LimitExceeded);
+    }
+    Ok(file)
+}
+
+fn refused(reason: &str) -> Error {
+    Error::IoError(io::Error::new(io::ErrorKind::InvalidInput, reason))
+}
+
+/// Opens a SWF file and returns its first 8 bytes along with its
+/// decompressed body, without parsing any more of the header than it takes
+/// to check the magic number.
+pub fn open_body<P: AsRef<Path>>(path: P) -> Result<([u8; 8], DecodedSwf), Error> {
+    open_body_with_options(path.as_ref(), &ParseOptions::default())
+}
+
+/// Like `open_body()`, but opening the file and limiting the body the way
+/// `options` say, as `SwfHeaders::open_with_options()` does.
+pub fn open_body_with_options(path: &Path, options: &ParseOptions) -> Result<([u8; 8], DecodedSwf), Error> {
+    let mut file = try!(open_file(path, options));
+    let mut header = [0; 8];
+    try!(file.read_exact(&mut header));
+    let sig = match header[0] {
+        b'F' => Signature::Uncompressed,
+        b'C' => Signature::ZlibCompressed,
+        b'Z' => Signature::LzmaCompressed,
+        _ => return Err(Error::NotSwf)
+    };
+    if &header[1..3] != b"WS" {
+        return Err(Error::NotSwf);
+    }
+    let decoded = try!(decompress_with_options(file, sig, &header, options));
+    Ok((header, decoded))
+}
+
+/// How far past the file length in the header a compressed body can go
+/// when the header isn't trusted and `max_decompressed()` isn't set.
+pub const UNTRUSTED_SLACK: u64 = 16 * 1024 * 1024;
+
+/// Works out the most a body can decompress to, from the first 8 bytes of
+/// the file, and whether going past it is a limit being hit rather than an
+/// overrun.
+fn body_limit(sig: Signature, header: &[u8], options: &ParseOptions) -> (Option<u64>, bool) {
+    let max = options.decompressed_limit();
+    let file_length = (&header[4..8]).read_u32::<LittleEndian>().unwrap_or(0);
+    let body_length = file_length.saturating_sub(8) as u64;
+    let declared = if sig == Signature::Uncompressed {
+        None
+    } else if options.tolerates_bad_headers() {
+        // A file length that isn't trusted can't be overrun, but something
+        // still has to stop a zlib bomb.
+        if max == u64::max_value() {
+            return (Some(body_length + UNTRUSTED_SLACK), true);
+        }
+        None
+    } else {
+        Some(body_length + options.body_slack_limit() as u64)
+    };
+    match declared {
+        Some(declared) if declared <= max => (Some(declared), false),
+        _ if max == u64::max_value() => (None, false),
+        _ => (Some(max), true)
+    }
+}
+
+/// Reads the start of a compressed stream for its `CompressionInfo`,
+/// returning it with a stream that gives back everything read.
+fn peek_compression(mut reader: Box<dyn Read + Send>, sig: Signature)
+    -> Result<(Option<CompressionInfo>, Box<dyn Read + Send>), Error>
+{
+    // zlib's header is 2 bytes. LZMA has the compressed length, then a byte
+    // of lc/lp/pb and the dictionary size.
+    let wanted = match sig {
+        Signature::Uncompressed => return Ok((Some(CompressionInfo::Uncompressed), reader)),
+        Signature::ZlibCompressed => 2,
+        Signature::LzmaCompressed => 9
+    };
+    let mut start = [0; 9];
+    let mut filled = 0;
+    while filled < wanted {
+        match reader.read(&mut start[filled..wanted]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {},
+            Err(err) => return Err(err.into())
+        }
+    }
+
+    let compression = if filled < wanted {
+        None
+    } else if sig == Signature::ZlibCompressed {
+        Some(CompressionInfo::Zlib {
+            window_bits: (start[0] >> 4) + 8,
+            level: start[1] >> 6,
+            has_dictionary: start[1] & 0x20 != 0
+        })
+    } else {
+        let props = start[4];
+        Some(CompressionInfo::Lzma {
+            compressed_len: (&start[..4]).read_u32::<LittleEndian>().unwrap(),
+            lc: props % 9,
+            lp: props / 9 % 5,
+            pb: props / 45,
+            dict_size: (&start[5..]).read_u32::<LittleEndian>().unwrap()
         })
+    };
+    Ok((compression, Box::new(io::Cursor::new(start[..filled].to_vec()).chain(reader))))
+}
+
+/// Decompresses a stream whose first 8 bytes, `header`, have already been
+/// read, setting up whatever `options` asks for along the way.
+#[cfg_attr(not(feature = "digests"), allow(unused_variables))]
+pub fn decompress_with_options<R: Read + Send + 'static>(reader: R, sig: Signature, header: &[u8], options: &ParseOptions)
+    -> Result<DecodedSwf, Error>
+{
+    // The LZMA dictionary size is checked before the decoder sees it.
+    let (compression, reader) = try!(peek_compression(Box::new(reader), sig));
+    if let Some(CompressionInfo::Lzma { dict_size, .. }) = compression {
+        if dict_size > options.lzma_dict_size_limit() {
+            return Err(Error::LimitExceeded);
+        }
+    }
+    let source: Source = Arc::new(Mutex::new(reader));
+    #[cfg(feature = "digests")]
+    let digests = if options.digests_enabled() {
+        Some(DigestState::new(source.clone(), header))
+    } else {
+        None
+    };
+    let mut decoded = try!(DecodedSwf::from_raw(RawFile {
+        source: source,
+        #[cfg(feature = "digests")]
+        digests: digests
+    }, sig));
+    let (limit, limit_is_max) = body_limit(sig, header, options);
+    decoded.limit = limit;
+    decoded.limit_is_max = limit_is_max;
+    decoded.compression = compression;
+    let mut first = [0; 8];
+    first.copy_from_slice(&header[..8]);
+    decoded.header = Some(first);
+    if !options.has_wrapper() {
+        return Ok(decoded);
     }
+
+    // The digests stay underneath the wrapper, so they're of the real data
+    // whatever the wrapper does with it.
+    #[cfg(feature = "digests")]
+    let digests = decoded.digests.clone();
+    Ok(DecodedSwf {
+        _inner: Inner::Wrapped(options.wrap(Box::new(decoded))),
+        read: 0,
+        limit: None,
+        limit_is_max: false,
+        compression: compression,
+        origin: None,
+        header: None,
+        #[cfg(feature = "digests")]
+        digests: digests
+    })
+}
+
+/// Records the file and options `decoded` was opened with, so that
+/// `DecodedSwf::try_clone()` can open it again.
+pub fn set_origin(decoded: &mut DecodedSwf, path: &Path, options: &ParseOptions) {
+    decoded.origin = Some((path.to_path_buf(), options.clone()));
+}
+
+/// Has `decoded` give back `rest`, the remainder of its body that's been
+/// read into memory, returning how long the body is in all.
+pub fn refill(decoded: &mut DecodedSwf, rest: Vec<u8>) -> u64 {
+    let body_len = decoded.read;
+    decoded.read -= rest.len() as u64;
+    decoded._inner = Inner::Wrapped(Box::new(io::Cursor::new(rest)));
+    body_len
 }
 
 impl Read for DecodedSwf {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        match self._inner {
+        let limit_is_max = self.limit_is_max;
+        let overrun = || if limit_is_max {
+            io::Error::new(io::ErrorKind::Other, BodyTooLong)
+        } else {
+            io::Error::new(io::ErrorKind::Other, BodyOverrun)
+        };
+        // Reading at most a byte past the limit is enough to tell a body
+        // that ends there from one that overruns it.
+        let buf = match self.limit {
+            Some(limit) if self.read > limit => return Err(overrun()),
+            Some(limit) => {
+                let allowed = (limit - self.read).saturating_add(1);
+                let len = buf.len().min(allowed.min(usize::max_value() as u64) as usize);
+                &mut buf[..len]
+            },
+            None => buf
+        };
+        let mut read = try!(match self._inner {
             Inner::Raw(ref mut f) => f.read(buf),
             Inner::Zlib(ref mut f) => f.read(buf),
-            Inner::Lzma(ref mut f) => f.read(buf)
+            Inner::Lzma(ref mut f) => f.read(buf),
+            Inner::Wrapped(ref mut f) => {
+                let read = try!(f.read(buf));
+                self.read += read as u64;
+                return Ok(read);
+            }
+        });
+        self.read += read as u64;
+        if self.limit.map_or(false, |limit| self.read > limit) {
+            // Everything up to the limit is still returned, and the next
+            // read fails.
+            read -= 1;
+            if read == 0 {
+                return Err(overrun());
+            }
+        }
+        #[cfg(feature = "digests")]
+        {
+            if let Some(ref digests) = self.digests {
+                if read == 0 && !buf.is_empty() {
+                    digests.finish();
+                } else {
+                    digests.body(&buf[..read]);
+                }
+            }
         }
+        Ok(read)
     }
 }
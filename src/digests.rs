@@ -0,0 +1,141 @@
+//! Hashing the file and its decompressed body while they're read. Only
+//! built with the `digests` feature.
+
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use decoded_swf::Source;
+
+/// SHA-256 and MD5 digests of a SWF file, both as stored and decompressed.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Digests {
+    /// The SHA-256 of the whole file as stored on disk.
+    pub raw_sha256: [u8; 32],
+    /// The MD5 of the whole file as stored on disk.
+    pub raw_md5: [u8; 16],
+    /// The SHA-256 of the decompressed body, meaning everything after the
+    /// first 8 bytes of the file.
+    pub body_sha256: [u8; 32],
+    /// The MD5 of the decompressed body.
+    pub body_md5: [u8; 16]
+}
+
+struct Hashers {
+    sha256: Sha256,
+    md5: Md5
+}
+
+impl Hashers {
+    fn new() -> Self {
+        Hashers {
+            sha256: Sha256::new(),
+            md5: Md5::new()
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.sha256.update(data);
+        self.md5.update(data);
+    }
+
+    fn finish(self) -> ([u8; 32], [u8; 16]) {
+        let mut sha256 = [0; 32];
+        let mut md5 = [0; 16];
+        sha256.copy_from_slice(&self.sha256.finalize());
+        md5.copy_from_slice(&self.md5.finalize());
+        (sha256, md5)
+    }
+}
+
+enum Progress {
+    Reading {
+        raw: Hashers,
+        body: Hashers,
+        // The stream the decompressor reads from, for hashing whatever's
+        // left over after the compressed data ends.
+        tail: Source
+    },
+    Done(Digests),
+    Failed
+}
+
+/// The hashing state shared between a DecodedSwf, the file reader inside its
+/// decompressor, and any wrapped DecodedSwf around it.
+#[derive(Clone)]
+pub struct DigestState {
+    progress: Arc<Mutex<Progress>>
+}
+
+impl DigestState {
+    /// Starts hashing a stream whose first 8 bytes, `header`, have already
+    /// been read.
+    pub fn new(source: Source, header: &[u8]) -> Self {
+        let mut raw = Hashers::new();
+        raw.update(header);
+        DigestState {
+            progress: Arc::new(Mutex::new(Progress::Reading {
+                raw: raw,
+                body: Hashers::new(),
+                tail: source
+            }))
+        }
+    }
+
+    /// Feeds bytes read from the file itself.
+    pub fn raw(&self, data: &[u8]) {
+        if let Progress::Reading { ref mut raw, .. } = *self.progress.lock().unwrap() {
+            raw.update(data);
+        }
+    }
+
+    /// Feeds bytes read from the decompressed body.
+    pub fn body(&self, data: &[u8]) {
+        if let Progress::Reading { ref mut body, .. } = *self.progress.lock().unwrap() {
+            body.update(data);
+        }
+    }
+
+    /// Wraps up once the body hits EOF, hashing any trailing bytes in the
+    /// file that the decompressor never asked for.
+    pub fn finish(&self) {
+        let mut progress = self.progress.lock().unwrap();
+        let (mut raw, body, tail) = match ::std::mem::replace(&mut *progress, Progress::Failed) {
+            Progress::Reading { raw, body, tail } => (raw, body, tail),
+            other => {
+                *progress = other;
+                return;
+            }
+        };
+        let mut tail = tail.lock().unwrap();
+        let mut buf = [0; 8192];
+        loop {
+            match tail.read(&mut buf) {
+                Ok(0) => break,
+                Ok(read) => raw.update(&buf[..read]),
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {},
+                // The raw digest can't be trusted any more, so there are no
+                // digests at all.
+                Err(_) => return
+            }
+        }
+        let (raw_sha256, raw_md5) = raw.finish();
+        let (body_sha256, body_md5) = body.finish();
+        *progress = Progress::Done(Digests {
+            raw_sha256: raw_sha256,
+            raw_md5: raw_md5,
+            body_sha256: body_sha256,
+            body_md5: body_md5
+        });
+    }
+
+    /// Returns the digests, if the body has been read to the end.
+    pub fn digests(&self) -> Option<Digests> {
+        match *self.progress.lock().unwrap() {
+            Progress::Done(digests) => Some(digests),
+            _ => None
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::error;
+use std::fmt;
 use std::io;
 
 use byteorder;
@@ -16,11 +18,69 @@ pub enum Error {
     /// Any IO error, either from directly reading files or from other libraries.
     IoError(io::Error),
     /// All-encompassing variant for anything that can't be a swf file.
-    NotSwf
+    NotSwf,
+    /// The file went over one of the limits set in `ParseOptions`.
+    LimitExceeded,
+    /// A compressed body decompressed to more than the file length in the
+    /// header, plus the slack set in `ParseOptions`.
+    BodyOverrun
+}
+
+impl Error {
+    /// Returns a short name for the variant, such as `"not-swf"`, for
+    /// tallying errors up and for scripts to match on.
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Error::IoError(_) => "io",
+            Error::NotSwf => "not-swf",
+            Error::LimitExceeded => "limit-exceeded",
+            Error::BodyOverrun => "body-overrun"
+        }
+    }
+}
+
+/// What `DecodedSwf` reads fail with on a body overrun, since `Read` can
+/// only return an `io::Error`. Converting it back gives `Error::BodyOverrun`.
+#[derive(Debug)]
+pub struct BodyOverrun;
+
+impl fmt::Display for BodyOverrun {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the decompressed body is longer than the file length")
+    }
+}
+
+impl error::Error for BodyOverrun {
+    fn description(&self) -> &str {
+        "body overrun"
+    }
+}
+
+/// What `DecodedSwf` reads fail with past `ParseOptions::max_decompressed()`.
+/// Converting it back gives `Error::LimitExceeded`.
+#[derive(Debug)]
+pub struct BodyTooLong;
+
+impl fmt::Display for BodyTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the decompressed body is longer than the options allow")
+    }
+}
+
+impl error::Error for BodyTooLong {
+    fn description(&self) -> &str {
+        "body too long"
+    }
 }
 
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Self {
+        if err.get_ref().map_or(false, |inner| inner.is::<BodyOverrun>()) {
+            return Error::BodyOverrun;
+        }
+        if err.get_ref().map_or(false, |inner| inner.is::<BodyTooLong>()) {
+            return Error::LimitExceeded;
+        }
         Error::IoError(err)
     }
 }
@@ -0,0 +1,373 @@
+//! Reading the parts of fonts that map glyphs to characters.
+//!
+//! Static text stores glyph indices rather than characters, so making sense
+//! of it, or writing new text, means going through the font's code table,
+//! and for layout, its advances. Glyph outlines aren't parsed.
+//!
+//! Fonts made for advanced anti-aliasing also come with a
+//! DefineFontAlignZones tag, and the text drawn in them with
+//! CSMTextSettings. Both are separate tags that only refer to the font or
+//! text by ID, so they're easy to lose; `fonts()` gathers them up so a
+//! report can show what a font relies on.
+
+use std::collections::HashMap;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use error::Error;
+use primitives::{read_f16, read_u16_at};
+use tag::{codes, Tag};
+use text::{EditText, StaticText, TextSettings};
+
+/// A DefineFont, DefineFont2 or DefineFont3, as far as text is concerned.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Font {
+    /// The character ID.
+    pub id: u16,
+    /// Which DefineFont this came from, from 1 to 3.
+    pub version: u8,
+    /// The font name. Empty for a DefineFont without a DefineFontInfo.
+    pub name: String,
+    /// Whether the font is bold.
+    pub bold: bool,
+    /// Whether the font is italic.
+    pub italic: bool,
+    /// The character each glyph draws, by glyph index. Empty for a
+    /// DefineFont without a DefineFontInfo.
+    pub codes: Vec<u16>,
+    /// How far each glyph moves the pen, in EM square units, if the font
+    /// has layout information.
+    pub advances: Option<Vec<i16>>,
+    /// The size of the EM square that glyphs and advances are measured in.
+    pub em_square: u32,
+    /// The font's DefineFontAlignZones, if it has one.
+    pub align_zones: Option<AlignZones>,
+    /// The CSMTextSettings of the text drawn in this font. Only filled in
+    /// by `fonts()`.
+    pub text_settings: Vec<TextSettings>
+}
+
+impl Font {
+    /// Parses a font, returning `None` if the tag isn't a DefineFont,
+    /// DefineFont2 or DefineFont3.
+    ///
+    /// A DefineFont only has outlines; its name and codes come from the
+    /// DefineFontInfo tag following it, which `add_info()` fills in.
+    pub fn parse(tag: &Tag) -> Result<Option<Self>, Error> {
+        let data = tag.data();
+        match tag.code() {
+            codes::DEFINE_FONT => Ok(Some(Font {
+                id: try!(read_u16_at(data, 0)),
+                version: 1,
+                name: String::new(),
+                bold: false,
+                italic: false,
+                codes: Vec::new(),
+                advances: None,
+                em_square: 1024,
+                align_zones: None,
+                text_settings: Vec::new()
+            })),
+            codes::DEFINE_FONT_2 | codes::DEFINE_FONT_3 => {
+                parse_font_2_or_3(data, tag.code() == codes::DEFINE_FONT_3).map(Some)
+            },
+            _ => Ok(None)
+        }
+    }
+
+    /// Fills in the name, style and codes from a DefineFontInfo or
+    /// DefineFontInfo2. Other tags are ignored.
+    pub fn add_info(&mut self, tag: &Tag) -> Result<(), Error> {
+        if tag.code() != codes::DEFINE_FONT_INFO && tag.code() != codes::DEFINE_FONT_INFO_2 {
+            return Ok(());
+        }
+        let mut reader = tag.data();
+        try!(reader.read_u16::<LittleEndian>());
+        self.name = try!(read_name(&mut reader));
+        let flags = try!(reader.read_u8());
+        if tag.code() == codes::DEFINE_FONT_INFO_2 {
+            // The language code.
+            try!(reader.read_u8());
+        }
+        self.bold = flags & 0x02 != 0;
+        self.italic = flags & 0x04 != 0;
+        // The codes take up the rest of the tag.
+        let wide_codes = flags & 0x01 != 0;
+        let count = reader.len() / if wide_codes { 2 } else { 1 };
+        self.codes = try!(read_codes(&mut reader, count, wide_codes));
+        Ok(())
+    }
+
+    /// Returns the glyph index for a character, if the font has it.
+    pub fn glyph_index(&self, c: char) -> Option<usize> {
+        let code = c as u32;
+        if code > 0xffff {
+            return None;
+        }
+        self.codes.iter().position(|&glyph| glyph as u32 == code)
+    }
+
+    /// Returns the character a glyph draws, if it's known.
+    pub fn glyph_char(&self, index: usize) -> Option<char> {
+        self.codes.get(index).and_then(|&code| ::std::char::from_u32(code as u32))
+    }
+}
+
+/// The alignment zones of a font, from a DefineFontAlignZones tag, which
+/// advanced anti-aliasing uses to line the strokes of each glyph up with
+/// the pixel grid.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AlignZones {
+    /// The ID of the font the zones are for.
+    pub font_id: u16,
+    /// How thick the font's strokes are: 0 for thin, 1 for medium and 2 for
+    /// thick.
+    pub thickness_hint: u8,
+    /// The zones of each glyph, by glyph index.
+    pub glyphs: Vec<GlyphZones>
+}
+
+/// The alignment zones of a single glyph.
+#[derive(Clone, PartialEq, Debug)]
+pub struct GlyphZones {
+    /// Each zone's position and size, in EM square units. There are
+    /// normally two, the horizontal one first.
+    pub zones: Vec<(f32, f32)>,
+    /// Whether the glyph is aligned horizontally.
+    pub x: bool,
+    /// Whether the glyph is aligned vertically.
+    pub y: bool
+}
+
+impl AlignZones {
+    /// Parses a DefineFontAlignZones tag, returning `None` for other tags.
+    pub fn parse(tag: &Tag) -> Result<Option<Self>, Error> {
+        if tag.code() != codes::DEFINE_FONT_ALIGN_ZONES {
+            return Ok(None);
+        }
+        let mut reader = tag.data();
+        let font_id = try!(reader.read_u16::<LittleEndian>());
+        let thickness_hint = try!(reader.read_u8()) >> 6;
+        // The glyph count isn't stored; there's one entry per glyph in the
+        // font, up to the end of the tag.
+        let mut glyphs = Vec::new();
+        while !reader.is_empty() {
+            let count = try!(reader.read_u8());
+            let mut zones = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let position = try!(read_f16(&mut reader));
+                let size = try!(read_f16(&mut reader));
+                zones.push((position, size));
+            }
+            let mask = try!(reader.read_u8());
+            glyphs.push(GlyphZones {
+                zones: zones,
+                x: mask & 0x01 != 0,
+                y: mask & 0x02 != 0
+            });
+        }
+        Ok(Some(AlignZones {
+            font_id: font_id,
+            thickness_hint: thickness_hint,
+            glyphs: glyphs
+        }))
+    }
+}
+
+/// Collects every font defined in a tag list, with any DefineFontInfo
+/// applied, and the alignment zones and text settings that go with it.
+pub fn fonts(tags: &[Tag]) -> Result<Vec<Font>, Error> {
+    let mut fonts = Vec::new();
+    let mut by_id = HashMap::new();
+    let mut text_fonts = HashMap::new();
+    let mut settings = Vec::new();
+    for tag in tags {
+        if let Some(font) = try!(Font::parse(tag)) {
+            by_id.insert(font.id, fonts.len());
+            fonts.push(font);
+        } else if tag.code() == codes::DEFINE_FONT_INFO || tag.code() == codes::DEFINE_FONT_INFO_2 {
+            if let Some(&index) = by_id.get(&try!(read_u16_at(tag.data(), 0))) {
+                try!(fonts[index].add_info(tag));
+            }
+        } else if let Some(zones) = try!(AlignZones::parse(tag)) {
+            if let Some(&index) = by_id.get(&zones.font_id) {
+                fonts[index].align_zones = Some(zones);
+            }
+        } else if let Some(field) = try!(EditText::parse(tag)) {
+            text_fonts.insert(field.id, field.font_id.into_iter().collect::<Vec<_>>());
+        } else if let Some(text) = try!(StaticText::parse(tag)) {
+            text_fonts.insert(text.id, text.records.iter().filter_map(|record| record.font_id).collect());
+        } else if let Some(parsed) = try!(TextSettings::parse(tag)) {
+            settings.push(parsed);
+        }
+    }
+
+    // The settings can only be matched up once the text they're for has
+    // been seen, and a text can use several fonts.
+    for parsed in settings {
+        let mut used: Vec<u16> = text_fonts.get(&parsed.text_id).cloned().unwrap_or_default();
+        used.sort();
+        used.dedup();
+        for font_id in used {
+            if let Some(&index) = by_id.get(&font_id) {
+                fonts[index].text_settings.push(parsed.clone());
+            }
+        }
+    }
+    Ok(fonts)
+}
+
+fn parse_font_2_or_3(data: &[u8], is_v3: bool) -> Result<Font, Error> {
+    let mut reader = data;
+    let id = try!(reader.read_u16::<LittleEndian>());
+    let flags = try!(reader.read_u8());
+    // The language code.
+    try!(reader.read_u8());
+    let name = try!(read_name(&mut reader));
+    let glyph_count = try!(reader.read_u16::<LittleEndian>()) as usize;
+
+    let has_layout = flags & 0x80 != 0;
+    let wide_offsets = flags & 0x08 != 0;
+    let wide_codes = flags & 0x04 != 0;
+    let mut font = Font {
+        id: id,
+        version: if is_v3 { 3 } else { 2 },
+        name: name,
+        bold: flags & 0x01 != 0,
+        italic: flags & 0x02 != 0,
+        codes: Vec::new(),
+        advances: None,
+        // DefineFont3 glyphs are drawn at 20 times the resolution.
+        em_square: if is_v3 { 20480 } else { 1024 },
+        align_zones: None,
+        text_settings: Vec::new()
+    };
+    if glyph_count == 0 {
+        return Ok(font);
+    }
+
+    // FONT overview:
+    // The offset table, then the offset of the code table, are both
+    // measured from the start of the offset table. The glyph shapes sit in
+    // between, and the layout, starting with the ascent, descent and
+    // leading, comes straight after the code table.
+    let table_start = data.len() - reader.len();
+    let code_table_offset = if wide_offsets {
+        match reader.get(glyph_count * 4..) {
+            Some(mut rest) => try!(rest.read_u32::<LittleEndian>()) as usize,
+            None => return Err(Error::NotSwf)
+        }
+    } else {
+        try!(read_u16_at(reader, glyph_count * 2)) as usize
+    };
+    let mut reader = match data.get(table_start + code_table_offset..) {
+        Some(rest) => rest,
+        None => return Err(Error::NotSwf)
+    };
+    font.codes = try!(read_codes(&mut reader, glyph_count, wide_codes));
+    if has_layout {
+        for _ in 0..3 {
+            try!(reader.read_i16::<LittleEndian>());
+        }
+        let mut advances = Vec::with_capacity(glyph_count);
+        for _ in 0..glyph_count {
+            advances.push(try!(reader.read_i16::<LittleEndian>()));
+        }
+        font.advances = Some(advances);
+    }
+    Ok(font)
+}
+
+/// Reads a font name, which has a length byte in front rather than a null
+/// at the end, though plenty of tools add a null anyway.
+fn read_name(reader: &mut &[u8]) -> Result<String, Error> {
+    let len = try!(reader.read_u8()) as usize;
+    if reader.len() < len {
+        return Err(Error::NotSwf);
+    }
+    let (name, rest) = reader.split_at(len);
+    *reader = rest;
+    let name = match name.iter().position(|&b| b == 0) {
+        Some(end) => &name[..end],
+        None => name
+    };
+    Ok(String::from_utf8_lossy(name).into_owned())
+}
+
+fn read_codes(reader: &mut &[u8], count: usize, wide: bool) -> Result<Vec<u16>, Error> {
+    let mut codes = Vec::with_capacity(count);
+    for _ in 0..count {
+        codes.push(if wide {
+            try!(reader.read_u16::<LittleEndian>())
+        } else {
+            try!(reader.read_u8()) as u16
+        });
+    }
+    Ok(codes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use primitives::{Matrix, Rect};
+    use tag::{codes, Tag};
+    use text::{GridFit, StaticText, TextRecord};
+
+    #[test]
+    fn test_fonts() {
+        let text = StaticText {
+            id: 2,
+            version: 1,
+            bounds: Rect { x_min: 0, x_max: 400, y_min: 0, y_max: 400 },
+            matrix: Matrix::identity(),
+            records: vec![TextRecord {
+                font_id: Some(1),
+                color: None,
+                x_offset: None,
+                y_offset: None,
+                height: Some(40),
+                glyphs: vec![(0, 20)]
+            }]
+        };
+        let tags = vec![
+            // Font 1, with glyphs for "H", "i" and "o" and no layout.
+            Tag::new(codes::DEFINE_FONT_2, vec![
+                1, 0, 0, 0, 1, b'A', 3, 0,
+                8, 0, 8, 0, 8, 0, 8, 0,
+                b'H', b'i', b'o'
+            ]),
+            // Medium strokes, and only the first glyph's zones, at 1.0 by
+            // 0.5 and 0.0 by 1.0, aligned both ways.
+            Tag::new(codes::DEFINE_FONT_ALIGN_ZONES, vec![
+                1, 0, 0x40,
+                2, 0x00, 0x3c, 0x00, 0x38, 0x00, 0x00, 0x00, 0x3c, 0x03
+            ]),
+            text.to_tag().unwrap(),
+            // Field 3 in font 1, 12 points high.
+            Tag::new(codes::DEFINE_EDIT_TEXT, vec![3, 0, 0, 0x01, 0, 1, 0, 240, 0, 0]),
+            // Pixel fitting and a thickness of 1.5 for the static text,
+            // subpixel fitting for the field, and settings for text that
+            // doesn't exist.
+            Tag::new(codes::CSM_TEXT_SETTINGS, vec![2, 0, 0x48, 0, 0, 0xc0, 0x3f, 0, 0, 0, 0, 0]),
+            Tag::new(codes::CSM_TEXT_SETTINGS, vec![3, 0, 0x50, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            Tag::new(codes::CSM_TEXT_SETTINGS, vec![9, 0, 0x48, 0, 0, 0, 0, 0, 0, 0, 0, 0])
+        ];
+
+        let fonts = fonts(&tags).unwrap();
+        assert_eq!(fonts.len(), 1);
+        assert_eq!(fonts[0].name, "A");
+        assert_eq!(fonts[0].codes, vec![b'H' as u16, b'i' as u16, b'o' as u16]);
+        assert_eq!(fonts[0].glyph_index('o'), Some(2));
+        assert_eq!(fonts[0].advances, None);
+        assert_eq!(fonts[0].align_zones, Some(AlignZones {
+            font_id: 1,
+            thickness_hint: 1,
+            glyphs: vec![GlyphZones { zones: vec![(1.0, 0.5), (0.0, 1.0)], x: true, y: true }]
+        }));
+        let settings: Vec<_> = fonts[0].text_settings.iter()
+            .map(|settings| (settings.text_id, settings.advanced, settings.grid_fit, settings.thickness))
+            .collect();
+        assert_eq!(settings, vec![(2, true, GridFit::Pixel, 1.5), (3, true, GridFit::Subpixel, 0.0)]);
+    }
+}
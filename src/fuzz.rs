@@ -0,0 +1,182 @@
+//! Seed inputs for fuzzers: small SWF files that are structurally valid, but
+//! sit on the edges of the format where readers tend to slip up.
+//!
+//! Every seed has a correct file length, and all but one end with an End
+//! tag, so a reader that rejects one has a bug, not a strict opinion.
+//! They're also handy as integration test inputs.
+//!
+//! Needs the `fuzz` feature.
+
+use std::io::Write;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+
+use primitives::BitWriter;
+use tag::codes;
+
+/// A single generated file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Seed {
+    /// What the file exercises, usable as a file name.
+    pub name: &'static str,
+    /// The complete file.
+    pub data: Vec<u8>
+}
+
+/// A header for `seed()`. The stage is given as its bit width and maximum
+/// coordinates, so RECTs can be wider than they need to be.
+struct Header {
+    version: u8,
+    nbits: u32,
+    x_max: i32,
+    y_max: i32,
+    frame_rate: u16,
+    frame_count: u16
+}
+
+const DEFAULT_HEADER: Header = Header {
+    version: 10,
+    nbits: 15,
+    x_max: 11000,
+    y_max: 8000,
+    frame_rate: 24 << 8,
+    frame_count: 1
+};
+
+/// Returns every seed, in a fixed order.
+pub fn seeds() -> Vec<Seed> {
+    let show_frame = tag(codes::SHOW_FRAME, &[], false);
+    let end = tag(codes::END, &[], false);
+    let one_frame = [&show_frame[..], &end[..]].concat();
+
+    let mut seeds = vec![
+        seed("minimal", &DEFAULT_HEADER, &one_frame, &[]),
+        seed("zero_frames", &Header { frame_count: 0, ..DEFAULT_HEADER }, &end, &[]),
+        seed("zero_frames_with_content", &Header { frame_count: 0, ..DEFAULT_HEADER }, &one_frame, &[]),
+        seed("max_frame_count", &Header { frame_count: 0xffff, ..DEFAULT_HEADER }, &one_frame, &[]),
+        seed("zero_frame_rate", &Header { frame_rate: 0, ..DEFAULT_HEADER }, &one_frame, &[]),
+        seed("fractional_frame_rate", &Header { frame_rate: 0x1880, ..DEFAULT_HEADER }, &one_frame, &[]),
+        seed("empty_stage", &Header { nbits: 0, x_max: 0, y_max: 0, ..DEFAULT_HEADER }, &one_frame, &[]),
+        // The widest RECT there is, holding the largest stage it can.
+        seed("max_nbits_rect", &Header { nbits: 31, x_max: 0x3fff_ffff, y_max: 0x3fff_ffff, ..DEFAULT_HEADER },
+             &one_frame, &[]),
+        // The widest RECT there is, holding an ordinary stage.
+        seed("padded_rect", &Header { nbits: 31, ..DEFAULT_HEADER }, &one_frame, &[]),
+        seed("version_1", &Header { version: 1, ..DEFAULT_HEADER }, &one_frame, &[]),
+        seed("version_255", &Header { version: 255, ..DEFAULT_HEADER }, &one_frame, &[]),
+        // Bytes after the End tag, counted in the file length, as some
+        // tools pad files out to a block size.
+        seed("trailing_padding", &DEFAULT_HEADER, &one_frame, &[0; 64])
+    ];
+
+    // Tags that would fit in a short header, written with a long one.
+    let background = [0xff, 0x00, 0x00];
+    let long_form = [
+        &tag(codes::SET_BACKGROUND_COLOR, &background, true)[..],
+        &tag(codes::SHOW_FRAME, &[], true)[..],
+        &tag(codes::END, &[], true)[..]
+    ].concat();
+    seeds.push(seed("long_form_tiny_tags", &DEFAULT_HEADER, &long_form, &[]));
+
+    // Payloads either side of the short header's limit of 62 bytes.
+    let metadata = |len: usize| vec![b'x'; len - 1].into_iter().chain(Some(0)).collect::<Vec<u8>>();
+    let boundary = [
+        &tag(codes::METADATA, &metadata(62), false)[..],
+        &tag(codes::METADATA, &metadata(63), true)[..],
+        &one_frame[..]
+    ].concat();
+    seeds.push(seed("short_header_boundary", &DEFAULT_HEADER, &boundary, &[]));
+
+    // A sprite with no frames at all, placed on the stage.
+    let sprite = [&[1, 0, 0, 0][..], &end[..]].concat();
+    let sprite_body = [
+        &tag(codes::DEFINE_SPRITE, &sprite, false)[..],
+        &tag(codes::PLACE_OBJECT_2, &[0x02, 1, 0, 1, 0], false)[..],
+        &one_frame[..]
+    ].concat();
+    seeds.push(seed("empty_sprite", &DEFAULT_HEADER, &sprite_body, &[]));
+
+    // A movie with no End tag at all, which plenty of real files lack.
+    seeds.push(seed("missing_end", &DEFAULT_HEADER, &show_frame, &[]));
+
+    let zlib = |name, header: &Header, tags: &[u8]| {
+        let uncompressed = seed(name, header, tags, &[]).data;
+        let mut encoder = ZlibEncoder::new(uncompressed[..8].to_vec(), Compression::Default);
+        encoder.write_all(&uncompressed[8..]).unwrap();
+        let mut data = encoder.finish().unwrap();
+        data[0] = b'C';
+        Seed { name: name, data: data }
+    };
+    seeds.push(zlib("zlib_minimal", &DEFAULT_HEADER, &one_frame));
+    seeds.push(zlib("zlib_zero_frames", &Header { frame_count: 0, ..DEFAULT_HEADER }, &end));
+    seeds.push(zlib("zlib_max_nbits_rect", &Header { nbits: 31, ..DEFAULT_HEADER }, &one_frame));
+    seeds
+}
+
+/// Assembles an uncompressed file from a header, the encoded tags and any
+/// bytes to put after them.
+fn seed(name: &'static str, header: &Header, tags: &[u8], trailing: &[u8]) -> Seed {
+    let mut rect = BitWriter::new();
+    rect.write_ub(5, header.nbits);
+    for &value in &[0, header.x_max, 0, header.y_max] {
+        rect.write_sb(header.nbits, value);
+    }
+
+    let mut body = rect.into_bytes();
+    body.write_u16::<LittleEndian>(header.frame_rate).unwrap();
+    body.write_u16::<LittleEndian>(header.frame_count).unwrap();
+    body.extend_from_slice(tags);
+    body.extend_from_slice(trailing);
+
+    let mut data = vec![b'F', b'W', b'S', header.version];
+    data.write_u32::<LittleEndian>(8 + body.len() as u32).unwrap();
+    data.extend(body);
+    Seed { name: name, data: data }
+}
+
+/// Encodes a single tag, with a long header if asked for or needed.
+fn tag(code: u16, data: &[u8], long_header: bool) -> Vec<u8> {
+    let mut out = Vec::new();
+    if long_header || data.len() >= 0x3f {
+        out.write_u16::<LittleEndian>((code << 6) | 0x3f).unwrap();
+        out.write_u32::<LittleEndian>(data.len() as u32).unwrap();
+    } else {
+        out.write_u16::<LittleEndian>((code << 6) | data.len() as u16).unwrap();
+    }
+    out.extend_from_slice(data);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::io::{Cursor, Read};
+
+    use SwfHeaders;
+    use tag;
+
+    #[test]
+    fn test_seeds_parse() {
+        let seeds = seeds();
+        let names: HashSet<_> = seeds.iter().map(|seed| seed.name).collect();
+        assert_eq!(names.len(), seeds.len());
+
+        for seed in seeds {
+            let (headers, mut decoded) = SwfHeaders::read_from(Cursor::new(seed.data.clone())).unwrap();
+            let mut body = Vec::new();
+            decoded.read_to_end(&mut body).unwrap();
+            assert!(tag::read_all(&body[..]).is_ok(), "{} has unreadable tags", seed.name);
+            if seed.name != "max_nbits_rect" && seed.name != "empty_stage" {
+                assert_eq!(headers.dimensions(), (550, 400), "{}", seed.name);
+            }
+            if seed.name.starts_with("zlib_") {
+                assert_eq!(seed.data[0], b'C');
+            } else {
+                assert_eq!(headers.file_length() as usize, seed.data.len(), "{}", seed.name);
+            }
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Guessing how interactive a movie is, for archives deciding whether to
+//! capture it as video or keep it running in an emulator.
+//!
+//! Nothing is run. The guess comes from what the movie contains: buttons,
+//! editable text fields, clip events, and scripts that mention the mouse or
+//! keyboard. It's a heuristic, and meant to be used as one: a movie with a
+//! single replay button comes out as an animation, which is usually what's
+//! wanted.
+
+use std::collections::HashSet;
+
+use abc::AbcFile;
+use avm1::{Action, Actions, PushValue};
+use error::Error;
+use tag::{self, codes, Tag};
+use text::EditText;
+
+/// Names scripts use to follow the mouse, in either ActionScript.
+const MOUSE_NAMES: &'static [&'static str] = &[
+    "_xmouse", "_ymouse", "onPress", "onRelease", "onReleaseOutside", "onRollOver", "onRollOut",
+    "onDragOver", "onDragOut", "onMouseDown", "onMouseUp", "onMouseMove", "startDrag", "hitTest",
+    "MouseEvent", "mouseX", "mouseY", "click", "mouseDown", "mouseUp", "mouseMove"
+];
+
+/// Names scripts use to read the keyboard, in either ActionScript.
+const KEY_NAMES: &'static [&'static str] = &[
+    "Key", "isDown", "getCode", "getAscii", "onKeyDown", "onKeyUp",
+    "KeyboardEvent", "keyDown", "keyUp", "keyCode", "charCode"
+];
+
+/// The ActionStartDrag action code.
+const ACTION_START_DRAG: u8 = 0x27;
+
+/// The PlaceObject2 and PlaceObject3 flag for clip actions.
+const PLACE_HAS_CLIP_ACTIONS: u8 = 0x80;
+
+/// The kinds of content `Interactivity::classify()` tells apart.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ContentKind {
+    /// Little or no interaction; fine to capture as video.
+    Animation,
+    /// Driven by the mouse or keyboard as it plays.
+    Game,
+    /// Built around text entry, such as forms and tools.
+    Application
+}
+
+/// What was found in a movie that suggests it's interactive.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Interactivity {
+    /// DefineButton and DefineButton2 tags.
+    pub buttons: usize,
+    /// Text fields the user can type into.
+    pub input_fields: usize,
+    /// Sprites placed with clip actions, such as `onClipEvent(mouseDown)`.
+    pub clip_actions: usize,
+    /// Distinct mouse-related names in the scripts, plus StartDrag actions.
+    pub mouse_references: usize,
+    /// Distinct keyboard-related names in the scripts.
+    pub key_references: usize
+}
+
+impl Interactivity {
+    /// Scans a tag list, including the tags inside sprites.
+    pub fn scan(tags: &[Tag]) -> Result<Self, Error> {
+        let mut scan = Scan {
+            found: Interactivity::default(),
+            names: HashSet::new()
+        };
+        try!(scan.walk(tags));
+        Ok(scan.found)
+    }
+
+    /// Weighs up what was found into a single number, where 0 means nothing
+    /// interactive at all. Typing and the keyboard count for the most, and
+    /// a single button for too little to matter, since plenty of animations
+    /// have one for replay.
+    pub fn score(&self) -> u32 {
+        (self.buttons * 2 + self.input_fields * 3 + self.clip_actions +
+         self.mouse_references + self.key_references * 2) as u32
+    }
+
+    /// Guesses what kind of content the movie is.
+    pub fn classify(&self) -> ContentKind {
+        if self.score() < 3 {
+            ContentKind::Animation
+        } else if self.input_fields >= 2 && self.key_references == 0 {
+            ContentKind::Application
+        } else if self.key_references > 0 || self.mouse_references > 0 || self.clip_actions > 0 {
+            ContentKind::Game
+        } else if self.input_fields > 0 {
+            ContentKind::Application
+        } else {
+            // Buttons and nothing else: menus and point-and-click.
+            ContentKind::Game
+        }
+    }
+}
+
+/// Scores how interactive a tag list is; see `Interactivity::score()`.
+pub fn interactivity_score(tags: &[Tag]) -> Result<u32, Error> {
+    Interactivity::scan(tags).map(|found| found.score())
+}
+
+struct Scan {
+    found: Interactivity,
+    names: HashSet<String>
+}
+
+impl Scan {
+    fn walk(&mut self, tags: &[Tag]) -> Result<(), Error> {
+        for tag in tags {
+            match tag.code() {
+                codes::DEFINE_BUTTON | codes::DEFINE_BUTTON_2 => self.found.buttons += 1,
+                codes::PLACE_OBJECT_2 | codes::PLACE_OBJECT_3 => {
+                    if tag.data().first().map_or(false, |&flags| flags & PLACE_HAS_CLIP_ACTIONS != 0) {
+                        self.found.clip_actions += 1;
+                    }
+                },
+                codes::DEFINE_SPRITE => {
+                    if tag.data().len() < 4 {
+                        return Err(Error::NotSwf);
+                    }
+                    try!(self.walk(&try!(tag::read_all(&tag.data()[4..]))));
+                },
+                codes::DO_ABC | codes::DO_ABC_DEFINE => {
+                    if let Some(abc) = try!(AbcFile::parse(tag)) {
+                        for string in &abc.strings {
+                            self.name(string);
+                        }
+                    }
+                },
+                _ => {}
+            }
+            if let Some(field) = try!(EditText::parse(tag)) {
+                if !field.read_only {
+                    self.found.input_fields += 1;
+                }
+            } else if let Some(actions) = Actions::from_tag(tag) {
+                try!(self.script(actions));
+            }
+        }
+        Ok(())
+    }
+
+    fn script(&mut self, actions: Actions) -> Result<(), Error> {
+        for action in actions {
+            match try!(action) {
+                Action::Simple(ACTION_START_DRAG) => self.found.mouse_references += 1,
+                Action::ConstantPool(constants) => {
+                    for constant in &constants {
+                        self.name(constant);
+                    }
+                },
+                Action::Push(values) => {
+                    for value in values {
+                        if let PushValue::Str(string) = value {
+                            self.name(&string);
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn name(&mut self, name: &str) {
+        if !self.names.insert(name.to_owned()) {
+            return;
+        }
+        if MOUSE_NAMES.contains(&name) {
+            self.found.mouse_references += 1;
+        } else if KEY_NAMES.contains(&name) {
+            self.found.key_references += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tag::{codes, Tag};
+
+    #[test]
+    fn test_classify() {
+        // A movie with only a replay button.
+        let button = Tag::new(codes::DEFINE_BUTTON_2, vec![1, 0, 0, 0, 0, 0]);
+        let replay = Interactivity::scan(&[button.clone()]).unwrap();
+        assert_eq!(replay.score(), 2);
+        assert_eq!(replay.classify(), ContentKind::Animation);
+
+        // Pushing "Key" and "isDown", then dragging, inside a sprite.
+        let mut sprite = vec![2, 0, 1, 0];
+        Tag::new(codes::DO_ACTION, vec![
+            0x96, 13, 0, 0, b'K', b'e', b'y', 0, 0, b'i', b's', b'D', b'o', b'w', b'n', 0,
+            0x27, 0x00
+        ]).write_to(&mut sprite).unwrap();
+        Tag::new(codes::END, vec![]).write_to(&mut sprite).unwrap();
+        let game = Interactivity::scan(&[button, Tag::new(codes::DEFINE_SPRITE, sprite)]).unwrap();
+        assert_eq!(game, Interactivity {
+            buttons: 1,
+            input_fields: 0,
+            clip_actions: 0,
+            mouse_references: 1,
+            key_references: 2
+        });
+        assert_eq!(game.classify(), ContentKind::Game);
+
+        // Two input fields, then a read-only one.
+        let field = |id, flags| Tag::new(codes::DEFINE_EDIT_TEXT, vec![id, 0, 0, flags, 0, 0]);
+        let form = vec![field(3, 0x00), field(4, 0x00), field(5, 0x08)];
+        assert_eq!(interactivity_score(&form).unwrap(), 6);
+        assert_eq!(Interactivity::scan(&form).unwrap().classify(), ContentKind::Application);
+    }
+}
@@ -17,15 +17,87 @@ extern crate byteorder;
 extern crate flate2;
 extern crate lzma;
 extern crate bit_range;
+extern crate xz2;
+#[cfg(feature = "digests")]
+extern crate md5;
+#[cfg(feature = "digests")]
+extern crate sha2;
+#[cfg(feature = "manifest")]
+extern crate serde;
+#[cfg(feature = "manifest")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "image")]
+extern crate image;
+#[cfg(feature = "ruffle")]
+extern crate swf;
 
+pub mod abc;
+pub mod assets;
+#[cfg(feature = "audio")]
+pub mod audio;
+pub mod avm1;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod bulk;
+pub mod button;
+pub mod carve;
+pub mod codec;
+pub mod compare;
+pub mod concat;
 mod decoded_swf;
+#[cfg(feature = "digests")]
+mod digests;
 mod error;
+pub mod font;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+pub mod interactivity;
+#[cfg(feature = "manifest")]
+pub mod manifest;
+pub mod merge;
+mod open_options;
+mod options;
+mod parser;
+pub mod primitives;
+pub mod progressive;
+pub mod recover;
+pub mod report;
+pub mod roundtrip;
+#[cfg(feature = "ruffle")]
+pub mod ruffle;
+pub mod seek;
+pub mod shape;
+pub mod sound;
+pub mod split;
+#[cfg(feature = "svg")]
+pub mod svg;
+mod swf_file;
+pub mod symbols;
+pub mod tag;
+pub mod tag_codes;
+pub mod text;
+pub mod thumbnail;
+pub mod timeline;
+pub mod validate;
+mod version;
+pub mod video;
+pub mod wrap;
+pub mod writer;
 
-use std::fs::File;
+use std::io;
+use std::io::Read;
 use std::path::Path;
 
-pub use decoded_swf::DecodedSwf;
+pub use decoded_swf::{Checkpoint, Chunks, CompressionInfo, DecodedSwf, ReadStatus, SharedBody};
+#[cfg(feature = "digests")]
+pub use digests::Digests;
 pub use error::Error;
+pub use open_options::OpenOptions;
+pub use options::{ParseOptions, QuirksProfile};
+pub use parser::Parser;
+pub use swf_file::SwfFile;
+pub use version::SwfVersion;
 
 use byteorder::{LittleEndian, ReadBytesExt};
 use bit_range::BitRange;
@@ -38,7 +110,7 @@ use bit_range::BitRange;
 /// an uncompressed SWF file. CWS was introduced with SWF 6, and specifies that
 /// all bytes beyond the first 8 are compressed using zlib. ZWS was added with
 /// SWF 13, and displays the same concept, but with LZMA instead of zlib.
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Signature {
     /// A signature of FWS, meaning an uncompressed SWF file.
     Uncompressed,
@@ -48,6 +120,95 @@ pub enum Signature {
     LzmaCompressed
 }
 
+impl Signature {
+    /// Returns true if Flash Player can read this compression in a file of
+    /// the given SWF version. zlib arrived with SWF 6, LZMA with SWF 13.
+    pub fn is_supported_by(&self, version: u8) -> bool {
+        match *self {
+            Signature::Uncompressed => true,
+            Signature::ZlibCompressed => version >= 6,
+            Signature::LzmaCompressed => version >= 13
+        }
+    }
+
+    /// Picks the compression likely to give the smallest file for a body of
+    /// `body_len` bytes (everything after the first 8) in the given SWF
+    /// version.
+    ///
+    /// That's the strongest one the version allows, except for tiny bodies,
+    /// where the compressed stream's own overhead outweighs anything saved.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use swf_headers::Signature;
+    /// assert_eq!(Signature::best_for(9, 100000), Signature::ZlibCompressed);
+    /// assert_eq!(Signature::best_for(13, 100000), Signature::LzmaCompressed);
+    /// assert_eq!(Signature::best_for(5, 100000), Signature::Uncompressed);
+    /// ```
+    pub fn best_for(version: u8, body_len: usize) -> Signature {
+        if body_len < 64 {
+            Signature::Uncompressed
+        } else if Signature::LzmaCompressed.is_supported_by(version) {
+            Signature::LzmaCompressed
+        } else if Signature::ZlibCompressed.is_supported_by(version) {
+            Signature::ZlibCompressed
+        } else {
+            Signature::Uncompressed
+        }
+    }
+}
+
+/// Which way round a stage is, as given by `SwfHeaders::orientation()`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Orientation {
+    /// Wider than it is tall.
+    Landscape,
+    /// Taller than it is wide.
+    Portrait,
+    /// As wide as it is tall.
+    Square
+}
+
+/// A header field outside the range real movies use, as given by
+/// `SwfHeaders::anomalies()`. Each holds the value found.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Anomaly {
+    /// A version newer than any Flash Player or AIR release wrote, past 43.
+    Version(u8),
+    /// A frame rate over 120fps, past what players keep up with.
+    FrameRate(u16),
+    /// A stage wider than 8192 pixels, the most Flash Player 11 draws.
+    Width(u32),
+    /// A stage taller than 8192 pixels.
+    Height(u32)
+}
+
+impl Anomaly {
+    /// Returns the name of the field, such as `"frame_rate"`, for grouping
+    /// by.
+    pub fn field(&self) -> &'static str {
+        match *self {
+            Anomaly::Version(_) => "version",
+            Anomaly::FrameRate(_) => "frame_rate",
+            Anomaly::Width(_) => "width",
+            Anomaly::Height(_) => "height"
+        }
+    }
+}
+
+/// How much of the start of a file `SwfHeaders::min_input_for_header()`
+/// says is needed.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum HeaderInput {
+    /// The headers can be decoded from the first this many bytes, and no
+    /// fewer.
+    Enough(usize),
+    /// The bytes given aren't enough, and the headers need at least this
+    /// many. Fetch them and ask again.
+    AtLeast(usize)
+}
+
 /// The primary struct, managing all the parsing and storage of SWF header
 /// information.
 #[derive(Copy, Clone, PartialEq, Debug)]
@@ -58,6 +219,7 @@ pub struct SwfHeaders {
     width: u32,
     height: u32,
     frame_rate: u16,
+    frame_rate_fraction: u8,
     frame_count: u16
 }
 
@@ -72,13 +234,37 @@ impl SwfHeaders {
     ///     // ...
     /// }
     pub fn open<T: AsRef<Path>>(path: T) -> Result<(Self, DecodedSwf), Error> {
-        Self::read_from(try!(File::open(path)))
+        Self::open_with_options(path, &ParseOptions::default())
+    }
+
+    /// Like open(), but with options for reading the body, and for which
+    /// files to open at all.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use swf_headers::{ParseOptions, SwfHeaders};
+    /// let options = ParseOptions::new().max_tag_len(1024 * 1024).max_file_size(64 * 1024 * 1024);
+    /// if let Ok((headers, decoded)) = SwfHeaders::open_with_options("example.swf", &options) {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn open_with_options<T: AsRef<Path>>(path: T, options: &ParseOptions)
+        -> Result<(Self, DecodedSwf), Error>
+    {
+        let (headers, mut decoded) = try!(Self::read_with_options(try!(decoded_swf::open_file(path.as_ref(), options)), options));
+        decoded_swf::set_origin(&mut decoded, path.as_ref(), options);
+        Ok((headers, decoded))
     }
 
     /// Takes a SWF file and parses its headers, returning the header struct
     /// along with a readable DecodedSwf if you wish to continue parsing the
     /// file.
     ///
+    /// Any stream will do in place of a file, since nothing here seeks, so
+    /// SWFs can be read straight from stdin or a socket. It has to be `Send`,
+    /// so that the DecodedSwf can be handed to another thread.
+    ///
     /// The vast bulk of SWF parsing happens in here. The code is documented,
     /// so you can read through the source if you want to understand how it
     /// all works.
@@ -93,7 +279,17 @@ impl SwfHeaders {
     ///     // ...
     /// }
     /// ```
-    pub fn read_from(mut file: File) -> Result<(Self, DecodedSwf), Error> {
+    pub fn read_from<R: Read + Send + 'static>(reader: R) -> Result<(Self, DecodedSwf), Error> {
+        Self::read_with_options(reader, &ParseOptions::default())
+    }
+
+    /// Like read_from(), but with options for reading the body.
+    ///
+    /// If the options have a body wrapper, the returned DecodedSwf reads
+    /// through it, and so does the rest of the header parsing in here.
+    pub fn read_with_options<R: Read + Send + 'static>(mut file: R, options: &ParseOptions)
+        -> Result<(Self, DecodedSwf), Error>
+    {
         // SWF header strcture overview:
         // Everything is little endian.
         //
@@ -107,7 +303,8 @@ impl SwfHeaders {
         // Frame count: u16
 
         // Get the signature
-        let sig = match try!(file.read_u8()) as char {
+        let sig_byte = try!(file.read_u8());
+        let sig = match sig_byte as char {
             'F' => Signature::Uncompressed,
             'C' => Signature::ZlibCompressed,
             'Z' => Signature::LzmaCompressed,
@@ -126,22 +323,37 @@ impl SwfHeaders {
         let file_length = try!(file.read_u32::<LittleEndian>());
 
         // From this point on (the 8th byte), the rest of the file will be likely compressed, so
-        // we have to work with a decoded copy.
-        let mut decoded = try!(DecodedSwf::decompress(file, sig));
+        // we have to work with a decoded copy. The digests, if wanted, need the bytes read so far.
+        let header = [sig_byte, 0x57, 0x53, version, file_length as u8, (file_length >> 8) as u8,
+                      (file_length >> 16) as u8, (file_length >> 24) as u8];
+        let mut decoded = try!(decoded_swf::decompress_with_options(file, sig, &header, options));
 
         // The logic for this is painful, so it'll be in its own function.
         let (width, height) = try!(parse_rect(&mut decoded));
 
-        // The frame rate is stored in the header as a fixed-point number. Unless it turns out that
-        // decimal points in frame rates are common, we won't bother dealing with it.
-        let frame_rate_lower = try!(decoded.read_u8());
-        let frame_rate_upper = try!(decoded.read_u8());
-        if frame_rate_lower != 0 {
-            panic!("swf_headers: Decimal points in frame rates not yet supported");
+        // The frame rate is stored in the header as an 8.8 fixed-point number, fraction first.
+        let frame_rate_fraction = try!(decoded.read_u8());
+        let frame_rate = try!(decoded.read_u8()) as u16;
+
+        let mut frame_count = try!(decoded.read_u16::<LittleEndian>());
+
+        // Flash Player plays files with junk in both of these, so the only
+        // way to know the real values is to read on.
+        let mut file_length = file_length;
+        if options.tolerates_bad_headers() {
+            let mut rest = Vec::new();
+            try!(decoded.read_to_end(&mut rest));
+            if let Some(frames) = count_frames(&rest) {
+                frame_count = frames;
+            }
+            let body_len = decoded_swf::refill(&mut decoded, rest);
+            file_length = (body_len + 8).min(u32::max_value() as u64) as u32;
         }
-        let frame_rate = frame_rate_upper as u16;
 
-        let frame_count = try!(decoded.read_u16::<LittleEndian>());
+        if (frame_rate == 0 && options.rejects_zero_frame_rate()) ||
+           (frame_count == 0 && options.rejects_zero_frame_count()) {
+            return Err(Error::NotSwf);
+        }
 
         Ok((SwfHeaders {
             signature: sig,
@@ -150,9 +362,156 @@ impl SwfHeaders {
             width: width,
             height: height,
             frame_rate: frame_rate,
+            frame_rate_fraction: frame_rate_fraction,
             frame_count: frame_count
         }, decoded))
     }
+
+    /// Parses the headers from a fixed buffer, without allocating or
+    /// reading anything, for embedded and other no-alloc contexts. Returns
+    /// the headers along with how many bytes they took up, which is where
+    /// the first tag starts.
+    ///
+    /// For an FWS file, `prefix` is simply its first 64 bytes. CWS and ZWS
+    /// bodies have to be decompressed by the caller: `prefix` is then the 8
+    /// bytes of file header as they are, followed by the start of the
+    /// decompressed body. Only the first `prefix_len(prefix[8])` bytes are
+    /// looked at, so a shorter file can be padded out with anything.
+    ///
+    /// Frame rates and counts are read the way `read_from()` reads them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use swf_headers::SwfHeaders;
+    /// let mut prefix = [0; 64];
+    /// // A 3x3 stage with 10-bit fields, at 24fps.
+    /// prefix[..18].copy_from_slice(&[b'F', b'W', b'S', 10, 20, 0, 0, 0,
+    ///                                0x50, 0x00, 0x1e, 0x00, 0x01, 0xe0, 0x00, 24, 1, 0]);
+    /// let (headers, len) = SwfHeaders::parse_prefix(&prefix).unwrap();
+    /// assert_eq!((headers.dimensions(), headers.frame_rate(), len), ((3, 3), 24, 18));
+    /// ```
+    pub fn parse_prefix(prefix: &[u8; 64]) -> Result<(Self, usize), Error> {
+        let signature = match prefix[0] {
+            b'F' => Signature::Uncompressed,
+            b'C' => Signature::ZlibCompressed,
+            b'Z' => Signature::LzmaCompressed,
+            _ => return Err(Error::NotSwf)
+        };
+        if prefix[1] != 0x57 || prefix[2] != 0x53 {
+            return Err(Error::NotSwf);
+        }
+        let file_length = try!((&prefix[4..8]).read_u32::<LittleEndian>());
+
+        // Only the maximums are kept, as in parse_rect().
+        let mut rect = primitives::BitReader::new(&prefix[8..]);
+        let nbits = try!(rect.read_ub(5));
+        let mut fields = [0; 4];
+        for field in &mut fields {
+            *field = try!(rect.read_ub(nbits));
+        }
+
+        let len = Self::prefix_len(prefix[8]);
+        Ok((SwfHeaders {
+            signature: signature,
+            version: prefix[3],
+            file_length: file_length,
+            width: fields[1],
+            height: fields[3],
+            frame_rate: prefix[len - 3] as u16,
+            frame_rate_fraction: prefix[len - 4],
+            frame_count: prefix[len - 2] as u16 | (prefix[len - 1] as u16) << 8
+        }, len))
+    }
+
+    /// Returns how many bytes `parse_prefix()` looks at, going by the first
+    /// byte of the stage RECT, the 9th of the file. It's never more than
+    /// 29, for a RECT with 31-bit fields.
+    pub fn prefix_len(rect_byte: u8) -> usize {
+        let nbits = (rect_byte >> 3) as usize;
+        8 + (5 + 4 * nbits + 7) / 8 + 4
+    }
+
+    /// Works out how many bytes from the start of a file are needed to
+    /// decode its headers, from however much of it has been fetched so
+    /// far, so a network fetcher can ask for a tight byte range rather than
+    /// guessing.
+    ///
+    /// For FWS files the answer is exact as soon as the 9th byte is known.
+    /// For CWS and ZWS files it takes decompressing what's there, and the
+    /// answer is the fewest bytes that decompress to the whole header,
+    /// which depends on how the compressor encoded it. Until then, each
+    /// `AtLeast` is a lower bound, so fetching in steps never overshoots.
+    /// A compressed stream that's corrupt rather than cut short looks the
+    /// same as a short one, so fetchers should give up at some point.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use swf_headers::{HeaderInput, SwfHeaders};
+    /// assert_eq!(SwfHeaders::min_input_for_header(b"FWS").unwrap(), HeaderInput::AtLeast(13));
+    /// let start = [b'F', b'W', b'S', 10, 20, 0, 0, 0, 0x50];
+    /// assert_eq!(SwfHeaders::min_input_for_header(&start).unwrap(), HeaderInput::Enough(18));
+    /// ```
+    pub fn min_input_for_header(start: &[u8]) -> Result<HeaderInput, Error> {
+        let sig = match start.first() {
+            Some(&b'F') => Signature::Uncompressed,
+            Some(&b'C') => Signature::ZlibCompressed,
+            Some(&b'Z') => Signature::LzmaCompressed,
+            Some(_) => return Err(Error::NotSwf),
+            None => return Ok(HeaderInput::AtLeast(13))
+        };
+        if start.get(1).map_or(false, |&byte| byte != 0x57) || start.get(2).map_or(false, |&byte| byte != 0x53) {
+            return Err(Error::NotSwf);
+        }
+        // The smallest header possible, with a RECT of a single byte: after
+        // the zlib header for CWS, and after the compressed length and
+        // properties for ZWS.
+        let at_least = match sig {
+            Signature::Uncompressed => 13,
+            Signature::ZlibCompressed => 8 + 2 + 1,
+            Signature::LzmaCompressed => 8 + 4 + 5 + 1
+        };
+        if start.len() < 9 {
+            return Ok(HeaderInput::AtLeast(at_least));
+        }
+        if sig == Signature::Uncompressed {
+            return Ok(HeaderInput::Enough(Self::prefix_len(start[8])));
+        }
+
+        // How much of the header the first `end` bytes decompress to, and
+        // its first byte. Anything the decompressor can't make sense of
+        // counts as nothing.
+        let decoded_len = |end: usize| -> (usize, u8) {
+            let mut decoded = [0; 21];
+            let filled = match DecodedSwf::decompress(io::Cursor::new(start[8..end].to_vec()), sig)
+                .and_then(|mut body| body.read_exact_or_eof(&mut decoded)) {
+                Ok(ReadStatus::Complete) => decoded.len(),
+                Ok(ReadStatus::Truncated(filled)) => filled,
+                Ok(ReadStatus::Eof) | Err(_) => 0
+            };
+            (filled, decoded[0])
+        };
+        let (filled, rect_byte) = decoded_len(start.len());
+        let needed = Self::prefix_len(rect_byte) - 8;
+        if filled == 0 || filled < needed {
+            return Ok(HeaderInput::AtLeast((start.len() + 1).max(at_least)));
+        }
+
+        // Decompressing more input never gives less output, so the fewest
+        // bytes that are enough can be searched for.
+        let (mut low, mut high) = (8, start.len());
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            let (filled, _) = decoded_len(mid);
+            if filled >= needed {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        Ok(HeaderInput::Enough(high))
+    }
     /// Returns the signature as an enum representing all valid values.
     pub fn signature(&self) -> Signature {
         self.signature
@@ -161,32 +520,180 @@ impl SwfHeaders {
     pub fn version(&self) -> u8 {
         self.version
     }
+    /// Returns the version number as a SwfVersion, for comparing against
+    /// named Flash Player releases.
+    pub fn swf_version(&self) -> SwfVersion {
+        SwfVersion::new(self.version)
+    }
     /// Returns the uncompressed total file length in bytes.
     pub fn file_length(&self) -> u32 {
         self.file_length
     }
+    /// Returns the length of everything after the first 8 bytes, once
+    /// decompressed: the rest of the header and the tags. This is what a
+    /// buffer for the whole decompressed body needs to hold, including the
+    /// part `read_from()` has already read. A file length too small to
+    /// cover the first 8 bytes gives 0.
+    pub fn expected_body_len(&self) -> u32 {
+        self.file_length.saturating_sub(8)
+    }
     /// Returns the dimensions in twips (the measurement unit flash uses, 1/20th of a pixel).
+    ///
+    /// Some generators write a zero-size stage, as a RECT with no bits per
+    /// field. That reads as (0, 0) rather than an error, and `validate`
+    /// warns about it as `empty-stage`.
     pub fn dimensions_twips(&self) -> (u32, u32) {
         (self.width, self.height)
     }
     /// Returns the dimensions in pixels (converted from twips, sometimes losing accuracy).
+    ///
+    /// Partial pixels are dropped, so 545 twips is 27 pixels. See
+    /// `dimensions_rounded()` and `dimensions_ceil()` for the alternatives.
     pub fn dimensions(&self) -> (u32, u32) {
         (self.width / 20, self.height / 20)
     }
-    /// Returns the frame rate (note: does not yet handle fractional framerates).
+    /// Returns the dimensions in pixels, rounded to the nearest whole pixel,
+    /// with halves rounding up. 545 twips is 27 pixels, and 550 is 28.
+    pub fn dimensions_rounded(&self) -> (u32, u32) {
+        ((self.width + 10) / 20, (self.height + 10) / 20)
+    }
+    /// Returns the dimensions in pixels, with any partial pixel counting as
+    /// a whole one, so that nothing on the stage gets cut off. 545 twips is
+    /// 28 pixels.
+    pub fn dimensions_ceil(&self) -> (u32, u32) {
+        ((self.width + 19) / 20, (self.height + 19) / 20)
+    }
+    /// Returns the stage's aspect ratio as width and height in lowest terms,
+    /// worked out from the exact size in twips.
+    ///
+    /// A zero-size stage gives (0, 0).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use swf_headers::SwfHeaders;
+    /// if let Ok((headers, _)) = SwfHeaders::open("example.swf") {
+    ///     // (4, 3) for a 640x480 stage.
+    ///     let (width, height) = headers.aspect_ratio();
+    /// }
+    /// ```
+    pub fn aspect_ratio(&self) -> (u32, u32) {
+        let (mut a, mut b) = (self.width, self.height);
+        while b != 0 {
+            let remainder = a % b;
+            a = b;
+            b = remainder;
+        }
+        if a == 0 {
+            (0, 0)
+        } else {
+            (self.width / a, self.height / a)
+        }
+    }
+    /// Returns which way round the stage is. A zero-size stage counts as
+    /// square.
+    pub fn orientation(&self) -> Orientation {
+        if self.width > self.height {
+            Orientation::Landscape
+        } else if self.width < self.height {
+            Orientation::Portrait
+        } else {
+            Orientation::Square
+        }
+    }
+    /// Returns the whole part of the frame rate. See `exact_frame_rate()` for
+    /// fractional frame rates.
     pub fn frame_rate(&self) -> u16 {
         self.frame_rate
     }
+    /// Returns the fractional part of the frame rate, in 256ths of a frame
+    /// per second.
+    pub fn frame_rate_fraction(&self) -> u8 {
+        self.frame_rate_fraction
+    }
+    /// Returns the frame rate including its fractional part, such as 29.5.
+    pub fn exact_frame_rate(&self) -> f32 {
+        self.frame_rate as f32 + self.frame_rate_fraction as f32 / 256.0
+    }
+    /// Returns the frame rate Flash Player actually plays at. A frame rate
+    /// of 0 in the header means the player's fallback of 12 fps.
+    pub fn nominal_frame_rate(&self) -> u16 {
+        if self.frame_rate == 0 { 12 } else { self.frame_rate }
+    }
     /// Returns the frame count.
     pub fn frame_count(&self) -> u16 {
         self.frame_count
     }
+    /// Returns the fields that are outside the range real movies use, in
+    /// the order they appear in the header. An empty list means nothing
+    /// looks unusual.
+    ///
+    /// These aren't errors: every one of them parses, and some plays. They
+    /// are a cheap first pass for finding odd files in a large collection.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use swf_headers::{Anomaly, SwfHeaders};
+    /// if let Ok((headers, _)) = SwfHeaders::open("example.swf") {
+    ///     for anomaly in headers.anomalies() {
+    ///         println!("unusual {}: {:?}", anomaly.field(), anomaly);
+    ///     }
+    /// }
+    /// ```
+    pub fn anomalies(&self) -> Vec<Anomaly> {
+        let mut anomalies = Vec::new();
+        if self.version > 43 {
+            anomalies.push(Anomaly::Version(self.version));
+        }
+        let (width, height) = self.dimensions();
+        if width > 8192 {
+            anomalies.push(Anomaly::Width(width));
+        }
+        if height > 8192 {
+            anomalies.push(Anomaly::Height(height));
+        }
+        if self.frame_rate > 120 {
+            anomalies.push(Anomaly::FrameRate(self.frame_rate));
+        }
+        anomalies
+    }
+}
+
+/// Counts the ShowFrame tags on the main timeline, saturating at the most a
+/// header can hold, or returns `None` if the tags can't be read.
+fn count_frames(mut tags: &[u8]) -> Option<u16> {
+    let mut frames = 0u16;
+    loop {
+        let (code, length) = match tag::read_header(&mut tags) {
+            Ok(Some(header)) => header,
+            Ok(None) => break,
+            Err(_) => return None
+        };
+        match code {
+            tag::codes::END => break,
+            tag::codes::SHOW_FRAME => frames = frames.saturating_add(1),
+            _ => {}
+        }
+        if length as usize > tags.len() {
+            return None;
+        }
+        tags = &tags[length as usize..];
+    }
+    Some(frames)
 }
 
 fn parse_rect<T: ReadBytesExt>(file: &mut T) -> Result<(u32, u32), Error> {
     let first_byte = try!(file.read_u8());
     let nbits = ((first_byte >> 3) & 0b0001_1111) as u32;
-    let nbytes = (5 + nbits * 4) / 8; // ?
+    if nbits == 0 {
+        // All four fields are empty, so the stage is zero-sized and there's
+        // nothing more to read.
+        return Ok((0, 0));
+    }
+    // The RECT is 5 + 4 * nbits bits long, which is never a whole number of
+    // bytes, so rounding down gives how many bytes follow the first one.
+    let nbytes = (5 + nbits * 4) / 8;
 
     let mut bytes = Vec::new();
     bytes.push(first_byte);
@@ -205,6 +712,7 @@ fn parse_rect<T: ReadBytesExt>(file: &mut T) -> Result<(u32, u32), Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs::File;
 
     // See tests/README.md for more information about these tests
 
@@ -244,6 +752,700 @@ mod tests {
         assert_eq!(headers.frame_count(), 29);
     }
 
+    #[test]
+    fn test_try_clone() {
+        use std::fs::{self, File};
+        use std::io::{Cursor, Read, Write};
+
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = [tag::Tag::new(tag::codes::DEFINE_BINARY_DATA, (0..250).collect())];
+        let bytes = writer::to_bytes(&headers, &tags).unwrap();
+        let path = ::std::env::temp_dir().join("swf_headers_test_try_clone.swf");
+        File::create(&path).unwrap().write_all(&bytes).unwrap();
+
+        let (_, mut decoded) = SwfHeaders::open(&path).unwrap();
+        let mut start = [0; 100];
+        decoded.read_exact(&mut start).unwrap();
+        let mut clone = decoded.try_clone().unwrap();
+        fs::remove_file(&path).unwrap();
+        let (mut rest, mut cloned_rest) = (Vec::new(), Vec::new());
+        decoded.read_to_end(&mut rest).unwrap();
+        clone.read_to_end(&mut cloned_rest).unwrap();
+        assert_eq!(rest.len(), 158);
+        assert_eq!(rest, cloned_rest);
+
+        let (_, streamed) = SwfHeaders::read_from(Cursor::new(bytes)).unwrap();
+        assert!(streamed.try_clone().is_err());
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        use std::io::{Cursor, Read};
+
+        for &signature in &[Signature::Uncompressed, Signature::ZlibCompressed] {
+            let headers = SwfHeaders {
+                signature: signature,
+                version: 10,
+                file_length: 0,
+                width: 200,
+                height: 200,
+                frame_rate: 24,
+                frame_rate_fraction: 0,
+                frame_count: 1
+            };
+            let tags = [tag::Tag::new(tag::codes::DEFINE_BINARY_DATA, (0..250).collect())];
+            let bytes = writer::to_bytes(&headers, &tags).unwrap();
+            let (_, mut decoded) = SwfHeaders::read_from(Cursor::new(bytes.clone())).unwrap();
+            let mut start = [0; 100];
+            decoded.read_exact(&mut start).unwrap();
+
+            let checkpoint = Checkpoint::from_bytes(&decoded.checkpoint().unwrap().to_bytes()).unwrap();
+            assert_eq!(checkpoint, decoded.checkpoint().unwrap());
+            let mut resumed = DecodedSwf::resume(Cursor::new(bytes.clone()), &checkpoint).unwrap();
+            let (mut rest, mut resumed_rest) = (Vec::new(), Vec::new());
+            decoded.read_to_end(&mut rest).unwrap();
+            resumed.read_to_end(&mut resumed_rest).unwrap();
+            assert_eq!(rest.len(), 158);
+            assert_eq!(rest, resumed_rest);
+
+            let mut other = bytes;
+            other[3] = 9;
+            assert!(DecodedSwf::resume(Cursor::new(other), &checkpoint).is_err());
+        }
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn send<T: Send>() {}
+        fn send_sync<T: Send + Sync>() {}
+
+        send_sync::<SwfHeaders>();
+        send_sync::<Signature>();
+        send_sync::<Error>();
+        send_sync::<ParseOptions>();
+        send_sync::<SharedBody>();
+        send_sync::<tag::Tag>();
+        send_sync::<validate::Finding>();
+        send_sync::<progressive::HeaderProgress>();
+        // Readers are only ever used from one thread at a time, but can move
+        // between threads.
+        send::<DecodedSwf>();
+        send::<Chunks<'static>>();
+        send::<tag::Tags<DecodedSwf>>();
+        send::<tag::WithDepth<SharedBody>>();
+        send::<avm1::Actions<'static>>();
+        send::<Parser>();
+        send::<writer::Streaming<::std::fs::File>>();
+    }
+
+    #[test]
+    fn test_body_wrapper() {
+        use std::fs;
+        use std::io::{Read, Write};
+        use std::sync::{Arc, Mutex};
+
+        struct Counting<R>(R, Arc<Mutex<usize>>);
+        impl<R: Read> Read for Counting<R> {
+            fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+                let read = try!(self.0.read(buf));
+                *self.1.lock().unwrap() += read;
+                Ok(read)
+            }
+        }
+
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = vec![tag::Tag::new(tag::codes::SHOW_FRAME, vec![])];
+        let path = ::std::env::temp_dir().join("swf_headers_test_body_wrapper.swf");
+        File::create(&path).unwrap().write_all(&writer::to_bytes(&headers, &tags).unwrap()).unwrap();
+
+        let count = Arc::new(Mutex::new(0));
+        let counter = count.clone();
+        let options = ParseOptions::new().wrap_body(move |body| Box::new(Counting(body, counter.clone())));
+        let (read, mut decoded) = SwfHeaders::open_with_options(&path, &options).unwrap();
+        let mut rest = Vec::new();
+        decoded.read_to_end(&mut rest).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.frame_count(), 1);
+        assert_eq!(*count.lock().unwrap(), read.expected_body_len() as usize);
+    }
+
+    #[test]
+    fn test_fractional_frame_rate() {
+        use std::io::Cursor;
+
+        let headers = SwfHeaders {
+            signature: Signature::Uncompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 29,
+            frame_rate_fraction: 0x80,
+            frame_count: 1
+        };
+        let data = writer::to_bytes(&headers, &[]).unwrap();
+        // The fraction is the low byte of the 8.8 fixed-point rate.
+        let rate_at = SwfHeaders::prefix_len(data[8]) - 4;
+        assert_eq!(&data[rate_at..rate_at + 2], &[0x80, 29]);
+
+        let (read, _) = SwfHeaders::read_from(Cursor::new(data.clone())).unwrap();
+        assert_eq!((read.frame_rate(), read.frame_rate_fraction()), (29, 0x80));
+        assert_eq!(read.exact_frame_rate(), 29.5);
+
+        let mut prefix = [0; 64];
+        prefix[..data.len()].copy_from_slice(&data);
+        let (parsed, _) = SwfHeaders::parse_prefix(&prefix).unwrap();
+        assert_eq!(parsed.exact_frame_rate(), 29.5);
+    }
+
+    #[test]
+    fn test_zero_frame_rate_and_count() {
+        use std::fs;
+        use std::io::Write;
+
+        let headers = SwfHeaders {
+            signature: Signature::Uncompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 0,
+            frame_rate_fraction: 0,
+            frame_count: 0
+        };
+        let path = ::std::env::temp_dir().join("swf_headers_test_zero_frames.swf");
+        File::create(&path).unwrap().write_all(&writer::to_bytes(&headers, &[]).unwrap()).unwrap();
+
+        let (lenient, _) = SwfHeaders::open(&path).unwrap();
+        assert_eq!(lenient.frame_rate(), 0);
+        assert_eq!(lenient.nominal_frame_rate(), 12);
+        assert_eq!(lenient.frame_count(), 0);
+
+        let strict_rate = ParseOptions::new().reject_zero_frame_rate(true);
+        let strict_count = ParseOptions::new().reject_zero_frame_count(true);
+        assert!(SwfHeaders::open_with_options(&path, &strict_rate).is_err());
+        assert!(SwfHeaders::open_with_options(&path, &strict_count).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_dimension_rounding() {
+        let headers = SwfHeaders {
+            signature: Signature::Uncompressed,
+            version: 10,
+            file_length: 0,
+            width: 545,
+            height: 550,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        assert_eq!(headers.dimensions(), (27, 27));
+        assert_eq!(headers.dimensions_rounded(), (27, 28));
+        assert_eq!(headers.dimensions_ceil(), (28, 28));
+
+        let exact = SwfHeaders { width: 540, height: 0, ..headers };
+        assert_eq!(exact.dimensions_rounded(), (27, 0));
+        assert_eq!(exact.dimensions_ceil(), (27, 0));
+    }
+
+    #[test]
+    fn test_aspect_ratio_and_orientation() {
+        let headers = SwfHeaders {
+            signature: Signature::Uncompressed,
+            version: 10,
+            file_length: 0,
+            width: 12800,
+            height: 9600,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        assert_eq!(headers.aspect_ratio(), (4, 3));
+        assert_eq!(headers.orientation(), Orientation::Landscape);
+
+        let portrait = SwfHeaders { width: 9600, height: 16000, ..headers };
+        assert_eq!(portrait.aspect_ratio(), (3, 5));
+        assert_eq!(portrait.orientation(), Orientation::Portrait);
+
+        let empty = SwfHeaders { width: 0, height: 0, ..headers };
+        assert_eq!(empty.aspect_ratio(), (0, 0));
+        assert_eq!(empty.orientation(), Orientation::Square);
+        assert_eq!(SwfHeaders { width: 500, height: 0, ..headers }.aspect_ratio(), (1, 0));
+    }
+
+    #[test]
+    fn test_anomalies() {
+        let headers = SwfHeaders {
+            signature: Signature::Uncompressed,
+            version: 43,
+            file_length: 0,
+            width: 8192 * 20,
+            height: 400 * 20,
+            frame_rate: 120,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        assert_eq!(headers.anomalies(), vec![]);
+
+        let odd = SwfHeaders { version: 255, height: 8193 * 20, frame_rate: 240, ..headers };
+        assert_eq!(odd.anomalies(), vec![Anomaly::Version(255), Anomaly::Height(8193), Anomaly::FrameRate(240)]);
+        assert_eq!(odd.anomalies()[2].field(), "frame_rate");
+    }
+
+    #[test]
+    fn test_zero_nbits_rect() {
+        use std::io::{Cursor, Read};
+
+        // A RECT of a single zero byte, then 24fps, 1 frame, and an End tag.
+        let bytes = vec![b'F', b'W', b'S', 8, 15, 0, 0, 0, 0x00, 0x00, 24, 1, 0, 0x00, 0x00];
+        let (headers, mut decoded) = SwfHeaders::read_from(Cursor::new(bytes)).unwrap();
+        assert_eq!(headers.dimensions_twips(), (0, 0));
+        assert_eq!(headers.dimensions(), (0, 0));
+        assert_eq!((headers.frame_rate(), headers.frame_count()), (24, 1));
+        let mut rest = Vec::new();
+        decoded.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, vec![0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_parse_prefix() {
+        use std::io::Cursor;
+
+        for &(width, height) in &[(11000, 8000), (0, 0), (0x3fff_ffff, 20)] {
+            let headers = SwfHeaders {
+                signature: Signature::Uncompressed,
+                version: 10,
+                file_length: 0,
+                width: width,
+                height: height,
+                frame_rate: 30,
+                frame_rate_fraction: 0,
+                frame_count: 300
+            };
+            let bytes = writer::to_bytes(&headers, &[]).unwrap();
+            let (expected, _) = SwfHeaders::read_from(Cursor::new(bytes.clone())).unwrap();
+
+            let mut prefix = [0xff; 64];
+            prefix[..bytes.len()].copy_from_slice(&bytes);
+            let (parsed, len) = SwfHeaders::parse_prefix(&prefix).unwrap();
+            assert_eq!(parsed, expected);
+            assert_eq!(len, SwfHeaders::prefix_len(bytes[8]));
+            // Only the End tag is left.
+            assert_eq!(len, bytes.len() - 2);
+
+            // A CWS file, decompressed by the caller.
+            prefix[0] = b'C';
+            assert_eq!(SwfHeaders::parse_prefix(&prefix).unwrap().0.signature(), Signature::ZlibCompressed);
+        }
+        assert_eq!(SwfHeaders::prefix_len(0xff), 29);
+
+        let mut prefix = [0; 64];
+        prefix[..3].copy_from_slice(b"FWX");
+        assert!(SwfHeaders::parse_prefix(&prefix).is_err());
+    }
+
+    #[test]
+    fn test_min_input_for_header() {
+        let headers = |signature| SwfHeaders {
+            signature: signature,
+            version: 10,
+            file_length: 0,
+            width: 11000,
+            height: 8000,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = [tag::Tag::new(tag::codes::DEFINE_BINARY_DATA, vec![0x55; 1000])];
+
+        let fws = writer::to_bytes(&headers(Signature::Uncompressed), &tags).unwrap();
+        assert_eq!(SwfHeaders::min_input_for_header(&fws[..4]).unwrap(), HeaderInput::AtLeast(13));
+        assert_eq!(SwfHeaders::min_input_for_header(&fws[..9]).unwrap(), HeaderInput::Enough(21));
+
+        let cws = writer::to_bytes(&headers(Signature::ZlibCompressed), &tags).unwrap();
+        let needed = match SwfHeaders::min_input_for_header(&cws).unwrap() {
+            HeaderInput::Enough(needed) => needed,
+            other => panic!("{:?}", other)
+        };
+        assert!(needed > 11 && needed < cws.len());
+        assert_eq!(SwfHeaders::min_input_for_header(&cws[..needed]).unwrap(), HeaderInput::Enough(needed));
+        assert_eq!(SwfHeaders::min_input_for_header(&cws[..needed - 1]).unwrap(), HeaderInput::AtLeast(needed));
+        assert_eq!(SwfHeaders::min_input_for_header(&cws[..8]).unwrap(), HeaderInput::AtLeast(11));
+        let (parsed, _) = SwfHeaders::read_from(::std::io::Cursor::new(cws[..needed].to_vec())).unwrap();
+        assert_eq!(parsed.dimensions(), (550, 400));
+
+        assert!(SwfHeaders::min_input_for_header(b"FX").is_err());
+        assert!(SwfHeaders::min_input_for_header(b"GWS").is_err());
+    }
+
+    #[test]
+    fn test_chunks() {
+        use std::io::Cursor;
+
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = [tag::Tag::new(tag::codes::DEFINE_BINARY_DATA, (0..250).collect())];
+        let bytes = writer::to_bytes(&headers, &tags).unwrap();
+        let (_, mut decoded) = SwfHeaders::read_from(Cursor::new(bytes.clone())).unwrap();
+        let chunks: Vec<Vec<u8>> = decoded.chunks(100).map(|chunk| chunk.unwrap()).collect();
+
+        // The tag's 6-byte header and payload, then the End tag.
+        assert_eq!(chunks.iter().map(|chunk| chunk.len()).collect::<Vec<_>>(), vec![100, 100, 58]);
+        assert_eq!(&chunks[0][..8], &[0xff, 0x15, 250, 0, 0, 0, 0, 1]);
+        assert_eq!(decoded.chunks(100).count(), 0);
+    }
+
+    #[test]
+    fn test_into_shared() {
+        use std::io::{Cursor, Read, Seek, SeekFrom};
+        use std::thread;
+
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = vec![tag::Tag::new(tag::codes::DEFINE_BINARY_DATA, (0..250).collect()),
+                        tag::Tag::new(tag::codes::SHOW_FRAME, vec![])];
+        let bytes = writer::to_bytes(&headers, &tags).unwrap();
+        let (_, decoded) = SwfHeaders::read_from(Cursor::new(bytes)).unwrap();
+        let body = decoded.into_shared().unwrap();
+
+        let workers: Vec<_> = (0..3).map(|_| {
+            let body = body.clone();
+            thread::spawn(move || tag::read_all(body).unwrap())
+        }).collect();
+        for worker in workers {
+            assert_eq!(worker.join().unwrap(), tags);
+        }
+
+        // Each clone has its own position.
+        let mut first = body.clone();
+        let mut rest = Vec::new();
+        first.seek(SeekFrom::End(-4)).unwrap();
+        first.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, vec![0x40, 0x00, 0x00, 0x00]);
+        assert_eq!((first.position(), body.position()), (body.as_slice().len() as u64, 0));
+        assert!(first.seek(SeekFrom::Current(-1000)).is_err());
+    }
+
+    #[test]
+    fn test_copy_body_to() {
+        use std::io::Cursor;
+
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = [tag::Tag::new(tag::codes::DEFINE_BINARY_DATA, vec![0x55; 20000])];
+        let mut bytes = writer::to_bytes(&headers, &tags).unwrap();
+        let (headers, mut decoded) = SwfHeaders::read_from(Cursor::new(bytes.clone())).unwrap();
+        let mut body = Vec::new();
+        let mut reported = Vec::new();
+        let copied = decoded.copy_body_to_with_progress(&mut body, &headers, |copied| reported.push(copied)).unwrap();
+        assert_eq!(copied, body.len() as u64);
+        assert_eq!(body.len(), 6 + 20000 + 2);
+        assert_eq!(reported.last(), Some(&copied));
+        assert!(reported.windows(2).all(|pair| pair[0] < pair[1]));
+        let uncompressed = writer::to_bytes(&SwfHeaders { signature: Signature::Uncompressed, ..headers }, &tags).unwrap();
+        assert_eq!(headers.expected_body_len() as usize, uncompressed.len() - 8);
+        assert_eq!(SwfHeaders { file_length: 3, ..headers }.expected_body_len(), 0);
+
+        // A file length one byte too long.
+        bytes[4] += 1;
+        let (headers, mut decoded) = SwfHeaders::read_from(Cursor::new(bytes)).unwrap();
+        assert!(decoded.copy_body_to(io::sink(), &headers).is_err());
+    }
+
+    #[test]
+    fn test_lzma_dict_size_limit() {
+        use std::io::Cursor;
+
+        let zws = |dict_size: u32| {
+            let mut bytes = vec![b'Z', b'W', b'S', 13, 100, 0, 0, 0, 20, 0, 0, 0, 0x5d];
+            bytes.extend_from_slice(&[dict_size as u8, (dict_size >> 8) as u8, (dict_size >> 16) as u8,
+                                      (dict_size >> 24) as u8]);
+            bytes.extend_from_slice(&[0; 20]);
+            Cursor::new(bytes)
+        };
+        let options = ParseOptions::new().max_lzma_dict_size(16 * 1024 * 1024);
+        match SwfHeaders::read_with_options(zws(0xffff_ffff), &options) {
+            Err(Error::LimitExceeded) => {},
+            other => panic!("{:?}", other.map(|(headers, _)| headers))
+        }
+        match SwfHeaders::read_with_options(zws(1 << 20), &options) {
+            Err(Error::LimitExceeded) => panic!("1MiB dictionary rejected"),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_body_overrun() {
+        use std::io::Cursor;
+
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = [tag::Tag::new(tag::codes::DEFINE_BINARY_DATA, vec![0; 100000])];
+        let mut bytes = writer::to_bytes(&headers, &tags).unwrap();
+        // Claim the body is only 100 bytes long.
+        bytes[4..8].copy_from_slice(&[108, 0, 0, 0]);
+
+        let options = ParseOptions::new().body_slack(1000);
+        let (_, mut decoded) = SwfHeaders::read_with_options(Cursor::new(bytes.clone()), &options).unwrap();
+        match decoded.skip(u64::max_value()) {
+            Err(Error::BodyOverrun) => {},
+            other => panic!("{:?}", other)
+        }
+        let (_, mut decoded) = SwfHeaders::read_with_options(Cursor::new(bytes), &options).unwrap();
+        let mut body = Vec::new();
+        assert!(decoded.read_to_end(&mut body).is_err());
+        assert_eq!(body.len(), 1100 - 10);
+
+        // A body exactly at the limit is fine.
+        let exact = ParseOptions::new().body_slack(100000 + 6 + 2 + 10 - 100);
+        let mut bytes = writer::to_bytes(&headers, &tags).unwrap();
+        bytes[4..8].copy_from_slice(&[108, 0, 0, 0]);
+        let (_, mut decoded) = SwfHeaders::read_with_options(Cursor::new(bytes), &exact).unwrap();
+        assert!(decoded.read_to_end(&mut Vec::new()).is_ok());
+        assert_eq!(Error::BodyOverrun.kind(), "body-overrun");
+    }
+
+    #[test]
+    fn test_tolerate_bad_headers() {
+        use std::io::Cursor;
+
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 60000
+        };
+        let show = tag::Tag::new(tag::codes::SHOW_FRAME, vec![]);
+        let tags = [tag::Tag::new(tag::codes::DEFINE_BINARY_DATA, vec![0; 1000]), show.clone(), show];
+        let real = writer::to_bytes(&SwfHeaders { signature: Signature::Uncompressed, ..headers }, &tags).unwrap();
+        // A protector's work: 60000 frames when there are 2, and a file
+        // length of 9.
+        let mut bytes = writer::to_bytes(&headers, &tags).unwrap();
+        bytes[4..8].copy_from_slice(&[9, 0, 0, 0]);
+        let header_len = SwfHeaders::prefix_len(real[8]);
+
+        let strict = ParseOptions::new().body_slack(0);
+        match SwfHeaders::read_with_options(Cursor::new(bytes.clone()), &strict) {
+            Err(Error::BodyOverrun) => {},
+            other => panic!("{:?}", other.map(|(headers, _)| headers))
+        }
+
+        let tolerant = strict.tolerate_bad_headers(true);
+        let (fixed, mut decoded) = SwfHeaders::read_with_options(Cursor::new(bytes), &tolerant).unwrap();
+        assert_eq!(fixed.file_length() as usize, real.len());
+        assert_eq!(fixed.frame_count(), 2);
+        let mut body = Vec::new();
+        assert_eq!(decoded.copy_body_to(&mut body, &fixed).unwrap() as usize, real.len() - header_len);
+        assert_eq!(&body[..], &real[header_len..]);
+    }
+
+    #[test]
+    fn test_tolerate_bad_headers_bomb() {
+        use std::io::Cursor;
+
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        // Tens of kilobytes of zlib claiming to be a 9-byte file.
+        let huge = vec![0; decoded_swf::UNTRUSTED_SLACK as usize + 1024];
+        let mut bytes = writer::to_bytes(&headers, &[tag::Tag::new(tag::codes::DEFINE_BINARY_DATA, huge)]).unwrap();
+        assert!(bytes.len() < 100 * 1024);
+        bytes[4..8].copy_from_slice(&[9, 0, 0, 0]);
+
+        let tolerant = ParseOptions::new().tolerate_bad_headers(true);
+        match SwfHeaders::read_with_options(Cursor::new(bytes.clone()), &tolerant) {
+            Err(Error::LimitExceeded) => {},
+            other => panic!("{:?}", other.map(|(headers, _)| headers))
+        }
+        let capped = tolerant.clone().max_decompressed(1024 * 1024);
+        assert_eq!(SwfHeaders::read_with_options(Cursor::new(bytes.clone()), &capped).err().unwrap().kind(),
+                   "limit-exceeded");
+        // A limit of its own lets the body through.
+        let roomy = tolerant.max_decompressed(64 * 1024 * 1024);
+        let (fixed, _) = SwfHeaders::read_with_options(Cursor::new(bytes), &roomy).unwrap();
+        assert!(fixed.file_length() as u64 > decoded_swf::UNTRUSTED_SLACK);
+    }
+
+    #[test]
+    fn test_compression_info() {
+        use std::io::Cursor;
+
+        let headers = |signature| SwfHeaders {
+            signature: signature,
+            version: 13,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let compression = |signature| {
+            let bytes = writer::to_bytes(&headers(signature), &[]).unwrap();
+            let decoded = DecodedSwf::decompress(Cursor::new(bytes[8..].to_vec()), signature).unwrap();
+            (bytes.len(), decoded.compression())
+        };
+
+        assert_eq!(compression(Signature::Uncompressed).1, Some(CompressionInfo::Uncompressed));
+        // The level in the header depends on the zlib implementation.
+        match compression(Signature::ZlibCompressed).1 {
+            Some(CompressionInfo::Zlib { window_bits: 15, has_dictionary: false, .. }) => {},
+            other => panic!("{:?}", other)
+        }
+        // LZMA preset 6.
+        let (len, lzma) = compression(Signature::LzmaCompressed);
+        assert_eq!(lzma, Some(CompressionInfo::Lzma {
+            compressed_len: len as u32 - 17,
+            lc: 3,
+            lp: 0,
+            pb: 2,
+            dict_size: 8 * 1024 * 1024
+        }));
+
+        // Peeking doesn't lose anything.
+        let bytes = writer::to_bytes(&headers(Signature::ZlibCompressed), &[]).unwrap();
+        let (read, mut decoded) = SwfHeaders::read_from(Cursor::new(bytes)).unwrap();
+        assert_eq!(read.dimensions(), (10, 10));
+        assert!(decoded.compression().is_some());
+        let mut rest = Vec::new();
+        decoded.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, vec![0, 0]);
+
+        let empty = DecodedSwf::decompress(Cursor::new(vec![0x78]), Signature::ZlibCompressed).unwrap();
+        assert_eq!(empty.compression(), None);
+    }
+
+    #[test]
+    fn test_read_from_stream() {
+        use std::io::{self, Read};
+
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = vec![tag::Tag::new(tag::codes::SHOW_FRAME, vec![])];
+        let bytes = writer::to_bytes(&headers, &tags).unwrap();
+        // Chaining hides the Cursor's Seek, like a pipe would.
+        let stream = io::empty().chain(io::Cursor::new(bytes.clone()));
+        let (read, body) = SwfHeaders::read_from(stream).unwrap();
+        assert_eq!(read.version(), 10);
+        assert_eq!(read.dimensions_twips(), (200, 200));
+        assert_eq!(tag::read_all(body).unwrap(), tags);
+    }
+
+    #[cfg(feature = "digests")]
+    #[test]
+    fn test_digests() {
+        use std::fs;
+        use std::io::{self, Read, Write};
+        use flate2::FlateReadExt;
+        use sha2::{Digest, Sha256};
+
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = vec![tag::Tag::new(tag::codes::SHOW_FRAME, vec![])];
+        let bytes = writer::to_bytes(&headers, &tags).unwrap();
+        let path = ::std::env::temp_dir().join("swf_headers_test_digests.swf");
+        {
+            let mut file = File::create(&path).unwrap();
+            file.write_all(&bytes).unwrap();
+            // Trailing junk still counts towards the raw digest.
+            file.write_all(b"junk").unwrap();
+        }
+
+        let options = ParseOptions::new().compute_digests(true);
+        assert!(format!("{:?}", options).contains("digests: true"));
+        let (_, mut decoded) = SwfHeaders::open_with_options(&path, &options).unwrap();
+        assert!(decoded.digests().is_none());
+        io::copy(&mut decoded, &mut io::sink()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let digests = decoded.digests().unwrap();
+        let mut raw = bytes.clone();
+        raw.extend_from_slice(b"junk");
+        assert_eq!(&digests.raw_sha256[..], &Sha256::digest(&raw)[..]);
+        let mut body = Vec::new();
+        (&bytes[8..]).zlib_decode().read_to_end(&mut body).unwrap();
+        assert_eq!(&digests.body_sha256[..], &Sha256::digest(&body)[..]);
+    }
+
     #[test]
     fn test_colourshift() {
         let (headers, _) = SwfHeaders::open("tests/colourshift.swf").unwrap();
@@ -0,0 +1,216 @@
+//! Building a manifest of a directory full of SWF files, for archives and
+//! the static sites that show them off. Only built with the `manifest`
+//! feature.
+//!
+//! Everything in here derives serde's `Serialize`, so a manifest can be
+//! written out with whichever serde format suits.
+
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::{ParseOptions, SwfHeaders};
+use error::Error;
+use report::SCHEMA_VERSION;
+use tag::{codes, Tags};
+
+/// A manifest of every SWF file found under a directory.
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct Manifest {
+    /// The layout of the manifest, from `report::SCHEMA_VERSION`.
+    pub schema: u32,
+    /// One entry per file, sorted by path.
+    pub entries: Vec<Entry>
+}
+
+/// Everything the manifest records about a single file.
+///
+/// Files that turn out not to be SWFs, or that are corrupt, still get an
+/// entry, with `error` set and whatever couldn't be read left as `None`.
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct Entry {
+    /// The path, relative to the manifest's directory.
+    pub path: String,
+    /// The size on disk in bytes.
+    pub size: u64,
+    /// The header fields.
+    pub headers: Option<HeaderInfo>,
+    /// The lowercase hex SHA-256 of the file as stored.
+    pub sha256: Option<String>,
+    /// The lowercase hex SHA-256 of the decompressed body, which is the same
+    /// however the file is compressed.
+    pub body_sha256: Option<String>,
+    /// What the movie uses, found by scanning its tags.
+    pub capabilities: Option<Capabilities>,
+    /// What went wrong reading the file, if anything did.
+    pub error: Option<String>
+}
+
+/// The header fields of a file.
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct HeaderInfo {
+    /// "FWS", "CWS" or "ZWS".
+    pub signature: String,
+    /// The SWF version.
+    pub version: u8,
+    /// The uncompressed length from the header.
+    pub file_length: u32,
+    /// The stage width in twips.
+    pub width_twips: u32,
+    /// The stage height in twips.
+    pub height_twips: u32,
+    /// The frame rate, as Flash Player would play it.
+    pub frame_rate: u16,
+    /// The frame count.
+    pub frame_count: u16
+}
+
+/// Which features of Flash a movie relies on, as far as its tags tell.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize)]
+pub struct Capabilities {
+    /// Contains ActionScript 3 bytecode, or says it does.
+    pub actionscript_3: bool,
+    /// Contains ActionScript 1 or 2 bytecode.
+    pub actionscript_2: bool,
+    /// Asks for network access when run locally.
+    pub use_network: bool,
+    /// Has a Metadata tag.
+    pub metadata: bool,
+    /// Has event or streaming sound.
+    pub sound: bool,
+    /// Has embedded video.
+    pub video: bool,
+    /// Has embedded fonts.
+    pub fonts: bool,
+    /// Has DefineBinaryData blobs.
+    pub binary_data: bool,
+    /// The number of tags on the main timeline.
+    pub tag_count: u32
+}
+
+/// Walks a directory, including subdirectories, and builds a manifest of
+/// every file ending in `.swf`.
+///
+/// Only failing to list a directory is an error. Problems with individual
+/// files are recorded in their entries instead, so one bad file doesn't
+/// spoil the whole manifest.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::manifest;
+/// if let Ok(manifest) = manifest::build("archive/") {
+///     for entry in &manifest.entries {
+///         println!("{} {:?}", entry.path, entry.body_sha256);
+///     }
+/// }
+/// ```
+pub fn build<P: AsRef<Path>>(dir: P) -> Result<Manifest, Error> {
+    let dir = dir.as_ref();
+    let mut entries = Vec::new();
+    try!(walk(dir, dir, &mut entries));
+    entries.sort_by(|a: &Entry, b: &Entry| a.path.cmp(&b.path));
+    Ok(Manifest {
+        schema: SCHEMA_VERSION,
+        entries: entries
+    })
+}
+
+fn walk(root: &Path, dir: &Path, entries: &mut Vec<Entry>) -> Result<(), Error> {
+    for item in try!(fs::read_dir(dir)) {
+        let path = try!(item).path();
+        if path.is_dir() {
+            try!(walk(root, &path, entries));
+        } else if path.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("swf")) {
+            let mut entry = entry(&path);
+            if let Ok(relative) = path.strip_prefix(root) {
+                entry.path = relative.to_string_lossy().into_owned();
+            }
+            entries.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// Builds the manifest entry for a single file, with its path as given.
+pub fn entry<P: AsRef<Path>>(path: P) -> Entry {
+    let path = path.as_ref();
+    let mut entry = Entry {
+        path: path.to_string_lossy().into_owned(),
+        size: fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0),
+        headers: None,
+        sha256: None,
+        body_sha256: None,
+        capabilities: None,
+        error: None
+    };
+    if let Err(err) = fill_entry(path, &mut entry) {
+        entry.error = Some(format!("{:?}", err));
+    }
+    entry
+}
+
+fn fill_entry(path: &Path, entry: &mut Entry) -> Result<(), Error> {
+    let options = ParseOptions::new().compute_digests(true);
+    let (headers, mut decoded) = try!(SwfHeaders::open_with_options(path, &options));
+    entry.headers = Some(HeaderInfo {
+        signature: match headers.signature() {
+            super::Signature::Uncompressed => "FWS",
+            super::Signature::ZlibCompressed => "CWS",
+            super::Signature::LzmaCompressed => "ZWS"
+        }.to_owned(),
+        version: headers.version(),
+        file_length: headers.file_length(),
+        width_twips: headers.dimensions_twips().0,
+        height_twips: headers.dimensions_twips().1,
+        frame_rate: headers.nominal_frame_rate(),
+        frame_count: headers.frame_count()
+    });
+
+    let mut capabilities = Capabilities::default();
+    let scanned = scan(&mut decoded, &mut capabilities);
+    entry.capabilities = Some(capabilities);
+
+    // Whatever's after the End tag, or after a broken tag, still gets
+    // hashed.
+    try!(io::copy(&mut decoded, &mut io::sink()));
+    if let Some(digests) = decoded.digests() {
+        entry.sha256 = Some(hex(&digests.raw_sha256));
+        entry.body_sha256 = Some(hex(&digests.body_sha256));
+    }
+    scanned
+}
+
+fn scan<R: io::Read>(body: R, capabilities: &mut Capabilities) -> Result<(), Error> {
+    for tag in Tags::new(body) {
+        let tag = try!(tag);
+        capabilities.tag_count += 1;
+        match tag.code() {
+            codes::FILE_ATTRIBUTES => {
+                let flags = tag.data().first().cloned().unwrap_or(0);
+                capabilities.actionscript_3 |= flags & 0x08 != 0;
+                capabilities.use_network |= flags & 0x01 != 0;
+            },
+            codes::DO_ABC | codes::DO_ABC_DEFINE => capabilities.actionscript_3 = true,
+            codes::DO_ACTION | codes::DO_INIT_ACTION => capabilities.actionscript_2 = true,
+            codes::METADATA => capabilities.metadata = true,
+            codes::DEFINE_SOUND | codes::SOUND_STREAM_HEAD | codes::SOUND_STREAM_HEAD_2 => capabilities.sound = true,
+            codes::DEFINE_VIDEO_STREAM => capabilities.video = true,
+            codes::DEFINE_FONT | codes::DEFINE_FONT_2 | codes::DEFINE_FONT_3 | codes::DEFINE_FONT_4 => {
+                capabilities.fonts = true;
+            },
+            codes::DEFINE_BINARY_DATA => capabilities.binary_data = true,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(out, "{:02x}", byte);
+    }
+    out
+}
@@ -0,0 +1,389 @@
+//! Stitching several SWFs together into one, one timeline after another.
+//!
+//! Character IDs are renumbered across all the movies so they
+//! don't collide, and the display list is cleared between movies so nothing
+//! from one episode lingers into the next.
+//!
+//! IDs are renumbered in define tags, tags that lead with a character ID,
+//! placements and removals (including inside sprites), the ExportAssets,
+//! ImportAssets and SymbolClass tables, the bitmap fills of shapes and
+//! morph shapes, the fonts of static and dynamic text, and the characters
+//! and sounds of buttons. A tag whose references can't be found, because it
+//! can't be parsed, is an error rather than being copied with stale IDs.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use super::SwfHeaders;
+use button;
+use error::Error;
+use primitives::{read_string, read_u16_at, write_u16_at};
+use shape;
+use tag::{self, codes, Tag};
+use text;
+use writer;
+
+/// Tags that only make sense once per file, so only the first movie's copy
+/// is kept.
+fn is_file_level_tag(code: u16) -> bool {
+    match code {
+        codes::FILE_ATTRIBUTES | codes::SET_BACKGROUND_COLOR | codes::METADATA |
+        codes::DEFINE_SCENE_AND_FRAME_LABEL_DATA => true,
+        _ => false
+    }
+}
+
+/// Opens each path in turn and merges them with `merge()`.
+pub fn merge_files<P: AsRef<Path>>(paths: &[P]) -> Result<Vec<u8>, Error> {
+    let mut movies = Vec::new();
+    for path in paths {
+        movies.push(try!(SwfHeaders::read_from(try!(File::open(path)))));
+    }
+    merge(movies)
+}
+
+/// Merges several movies into one SWF, playing each timeline in order.
+///
+/// The signature and frame rate come from the first movie. The version is
+/// the highest of them all, the stage is big enough to fit the biggest
+/// movie, and the frame count is the sum of the frames that were actually
+/// found.
+pub fn merge<R: Read>(movies: Vec<(SwfHeaders, R)>) -> Result<Vec<u8>, Error> {
+    let mut out_headers: Option<SwfHeaders> = None;
+    let mut out_tags = Vec::new();
+    let mut frame_count = 0u32;
+    let mut next_id = 1u32;
+
+    for (index, (headers, body)) in movies.into_iter().enumerate() {
+        let tags = try!(tag::read_all(body));
+
+        // Hand out fresh IDs in the order the characters are introduced.
+        let mut ids = HashMap::new();
+        for tag in &tags {
+            for id in introduced_ids(tag) {
+                if !ids.contains_key(&id) {
+                    if next_id > 0xffff {
+                        return Err(Error::NotSwf);
+                    }
+                    ids.insert(id, next_id as u16);
+                    next_id += 1;
+                }
+            }
+        }
+
+        if index > 0 {
+            // Clear whatever the previous movie left on stage.
+            let depths = occupied_depths(&out_tags);
+            for depth in depths {
+                let mut data = Vec::new();
+                try!(data.write_u16::<LittleEndian>(depth));
+                out_tags.push(Tag::new(codes::REMOVE_OBJECT_2, data));
+            }
+        }
+
+        for tag in tags {
+            if index > 0 && is_file_level_tag(tag.code()) {
+                continue;
+            }
+            if tag.code() == codes::SHOW_FRAME {
+                frame_count += 1;
+            }
+            out_tags.push(try!(remap_character_ids(&tag, &ids)));
+        }
+
+        out_headers = Some(match out_headers {
+            None => headers,
+            Some(merged) => {
+                let (width, height) = merged.dimensions_twips();
+                let (new_width, new_height) = headers.dimensions_twips();
+                SwfHeaders {
+                    version: merged.version.max(headers.version),
+                    width: width.max(new_width),
+                    height: height.max(new_height),
+                    ..merged
+                }
+            }
+        });
+    }
+
+    let headers = match out_headers {
+        Some(headers) => headers,
+        None => return Err(Error::NotSwf)
+    };
+    if frame_count > 0xffff {
+        return Err(Error::NotSwf);
+    }
+    let headers = SwfHeaders {
+        frame_count: frame_count as u16,
+        ..headers
+    };
+    writer::to_bytes(&headers, &out_tags)
+}
+
+/// Returns the IDs a tag brings into existence: the ID of a define tag, or
+/// the IDs an import binds.
+fn introduced_ids(tag: &Tag) -> Vec<u16> {
+    if let Some(id) = tag::character_id(tag) {
+        return vec![id];
+    }
+    match tag.code() {
+        codes::IMPORT_ASSETS | codes::IMPORT_ASSETS_2 => {
+            parse_import(tag).map(|(_, pairs)| pairs.into_iter().map(|(id, _)| id).collect())
+                             .unwrap_or_default()
+        },
+        _ => vec![]
+    }
+}
+
+/// Returns a copy of the tag with every character ID it mentions looked up
+/// in the map. IDs missing from the map are left alone.
+pub fn remap_character_ids(tag: &Tag, ids: &HashMap<u16, u16>) -> Result<Tag, Error> {
+    let mut data = tag.data().to_vec();
+    let map = |id: u16| *ids.get(&id).unwrap_or(&id);
+
+    match tag.code() {
+        codes::PLACE_OBJECT | codes::REMOVE_OBJECT | codes::DO_INIT_ACTION => {
+            try!(remap_at(&mut data, 0, &map));
+        },
+        codes::PLACE_OBJECT_2 => {
+            if data.first().map_or(false, |flags| flags & 0x02 != 0) {
+                try!(remap_at(&mut data, 3, &map));
+            }
+        },
+        codes::PLACE_OBJECT_3 => {
+            if data.len() < 4 {
+                return Err(Error::NotSwf);
+            }
+            let (flags, flags2) = (data[0], data[1]);
+            let has_character = flags & 0x02 != 0;
+            let has_image = flags2 & 0x10 != 0;
+            let has_class_name = flags2 & 0x08 != 0;
+            let mut pos = 4;
+            if has_class_name || (has_image && has_character) {
+                pos += match data[pos..].iter().position(|&b| b == 0) {
+                    Some(len) => len + 1,
+                    None => return Err(Error::NotSwf)
+                };
+            }
+            if has_character {
+                try!(remap_at(&mut data, pos, &map));
+            }
+        },
+        codes::EXPORT_ASSETS | codes::SYMBOL_CLASS => {
+            let pairs = try!(parse_symbol_list(&data));
+            data = encode_symbol_list(pairs.into_iter().map(|(id, name)| (map(id), name)));
+        },
+        codes::IMPORT_ASSETS | codes::IMPORT_ASSETS_2 => {
+            let (prefix, pairs) = try!(parse_import(tag));
+            data = prefix;
+            data.extend(encode_symbol_list(pairs.into_iter().map(|(id, name)| (map(id), name))));
+        },
+        codes::DEFINE_SPRITE => {
+            if data.len() < 4 {
+                return Err(Error::NotSwf);
+            }
+            let mut inner = data[..4].to_vec();
+            for nested in try!(tag::read_all(&data[4..])) {
+                try!(try!(remap_character_ids(&nested, ids)).write_to(&mut inner));
+            }
+            try!(Tag::new(codes::END, vec![]).write_to(&mut inner));
+            data = inner;
+            try!(remap_at(&mut data, 0, &map));
+        },
+        code if tag::defines_character(code) || tag::refers_to_character(code) => {
+            try!(remap_at(&mut data, 0, &map));
+            for offset in try!(reference_offsets(tag)) {
+                try!(remap_at(&mut data, offset, &map));
+            }
+        },
+        _ => {}
+    }
+    Ok(Tag::new(tag.code(), data))
+}
+
+/// Returns where the character IDs a define tag refers to, other than its
+/// own, are in its data.
+fn reference_offsets(tag: &Tag) -> Result<Vec<usize>, Error> {
+    match tag.code() {
+        codes::DEFINE_SHAPE | codes::DEFINE_SHAPE_2 | codes::DEFINE_SHAPE_3 | codes::DEFINE_SHAPE_4 |
+        codes::DEFINE_MORPH_SHAPE | codes::DEFINE_MORPH_SHAPE_2 => shape::bitmap_id_offsets(tag),
+        codes::DEFINE_TEXT | codes::DEFINE_TEXT_2 | codes::DEFINE_EDIT_TEXT => text::font_id_offsets(tag),
+        codes::DEFINE_BUTTON | codes::DEFINE_BUTTON_2 | codes::DEFINE_BUTTON_SOUND => {
+            button::character_id_offsets(tag)
+        },
+        _ => Ok(vec![])
+    }
+}
+
+fn remap_at<F: Fn(u16) -> u16>(data: &mut [u8], pos: usize, map: &F) -> Result<(), Error> {
+    let id = try!(read_u16_at(data, pos));
+    write_u16_at(data, pos, map(id))
+}
+
+/// Parses the `count, (id, name)*` list shared by ExportAssets, ImportAssets
+/// and SymbolClass.
+pub fn parse_symbol_list(data: &[u8]) -> Result<Vec<(u16, String)>, Error> {
+    let mut reader = data;
+    let count = try!(reader.read_u16::<LittleEndian>());
+    let mut pairs = Vec::new();
+    for _ in 0..count {
+        let id = try!(reader.read_u16::<LittleEndian>());
+        let name = try!(read_string(&mut reader));
+        pairs.push((id, name));
+    }
+    Ok(pairs)
+}
+
+fn encode_symbol_list<I: Iterator<Item = (u16, String)>>(pairs: I) -> Vec<u8> {
+    let pairs: Vec<_> = pairs.collect();
+    let mut data = Vec::new();
+    data.write_u16::<LittleEndian>(pairs.len() as u16).unwrap();
+    for (id, name) in pairs {
+        data.write_u16::<LittleEndian>(id).unwrap();
+        data.extend(name.into_bytes());
+        data.push(0);
+    }
+    data
+}
+
+/// Splits an ImportAssets/ImportAssets2 payload into everything before the
+/// symbol list (URL and reserved bytes) and the list itself.
+fn parse_import(tag: &Tag) -> Result<(Vec<u8>, Vec<(u16, String)>), Error> {
+    let data = tag.data();
+    let mut prefix_len = match data.iter().position(|&b| b == 0) {
+        Some(len) => len + 1,
+        None => return Err(Error::NotSwf)
+    };
+    if tag.code() == codes::IMPORT_ASSETS_2 {
+        prefix_len += 2;
+    }
+    if data.len() < prefix_len {
+        return Err(Error::NotSwf);
+    }
+    let pairs = try!(parse_symbol_list(&data[prefix_len..]));
+    Ok((data[..prefix_len].to_vec(), pairs))
+}
+
+/// Works out which depths are occupied after replaying every placement and
+/// removal in a root tag list.
+fn occupied_depths(tags: &[Tag]) -> BTreeSet<u16> {
+    let mut depths = BTreeSet::new();
+    for tag in tags {
+        let data = tag.data();
+        match tag.code() {
+            codes::PLACE_OBJECT | codes::PLACE_OBJECT_2 | codes::PLACE_OBJECT_3 => {
+                let offset = match tag.code() {
+                    codes::PLACE_OBJECT => 2,
+                    codes::PLACE_OBJECT_2 => 1,
+                    _ => 2
+                };
+                if let Ok(depth) = read_u16_at(data, offset) {
+                    depths.insert(depth);
+                }
+            },
+            codes::REMOVE_OBJECT | codes::REMOVE_OBJECT_2 => {
+                let offset = if tag.code() == codes::REMOVE_OBJECT { 2 } else { 0 };
+                if let Ok(depth) = read_u16_at(data, offset) {
+                    depths.remove(&depth);
+                }
+            },
+            _ => {}
+        }
+    }
+    depths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tag::{codes, Tag};
+
+    #[test]
+    fn test_remap_place_object_2() {
+        // Flags with HasCharacter set, depth 1, character 5.
+        let tag = Tag::new(codes::PLACE_OBJECT_2, vec![0x02, 1, 0, 5, 0]);
+        let mut ids = HashMap::new();
+        ids.insert(5, 300);
+        let remapped = remap_character_ids(&tag, &ids).unwrap();
+        assert_eq!(remapped.data(), &[0x02, 1, 0, 0x2c, 0x01]);
+    }
+
+    #[test]
+    fn test_remap_bitmap_fill() {
+        // Shape 7 with a single bitmap fill of character 5.
+        let tag = Tag::new(codes::DEFINE_SHAPE, vec![7, 0, 0x00, 0x01, 0x41, 5, 0, 0x00, 0x00, 0x10, 0x00]);
+        let mut ids = HashMap::new();
+        ids.insert(7, 1);
+        ids.insert(5, 2);
+        let remapped = remap_character_ids(&tag, &ids).unwrap();
+        assert_eq!(remapped.data(), &[1, 0, 0x00, 0x01, 0x41, 2, 0, 0x00, 0x00, 0x10, 0x00]);
+
+        // A shape whose fills can't be read can't have them rewritten.
+        let truncated = Tag::new(codes::DEFINE_SHAPE, tag.data()[..6].to_vec());
+        assert!(remap_character_ids(&truncated, &ids).is_err());
+    }
+
+    #[test]
+    fn test_remap_text_field_font() {
+        // Text field 11 in font 9, bound to the variable "v".
+        let tag = Tag::new(codes::DEFINE_EDIT_TEXT, vec![11, 0, 0x00, 0x01, 0x00, 9, 0, 0xf0, 0x00, b'v', 0]);
+        let mut ids = HashMap::new();
+        ids.insert(11, 1);
+        ids.insert(9, 2);
+        let remapped = remap_character_ids(&tag, &ids).unwrap();
+        assert_eq!(remapped.data(), &[1, 0, 0x00, 0x01, 0x00, 2, 0, 0xf0, 0x00, b'v', 0]);
+    }
+
+    #[test]
+    fn test_remap_button() {
+        let mut ids = HashMap::new();
+        ids.insert(3, 1);
+        ids.insert(5, 2);
+        ids.insert(7, 4);
+
+        // Button 3 showing character 5 in every state.
+        let tag = Tag::new(codes::DEFINE_BUTTON, vec![3, 0, 0x0f, 5, 0, 1, 0, 0x00, 0x00, 0x00]);
+        let remapped = remap_character_ids(&tag, &ids).unwrap();
+        assert_eq!(remapped.data(), &[1, 0, 0x0f, 2, 0, 1, 0, 0x00, 0x00, 0x00]);
+
+        // Sound 7 played with no options on the first transition, and none on
+        // the others.
+        let tag = Tag::new(codes::DEFINE_BUTTON_SOUND, vec![3, 0, 7, 0, 0x00, 0, 0, 0, 0, 0, 0]);
+        let remapped = remap_character_ids(&tag, &ids).unwrap();
+        assert_eq!(remapped.data(), &[1, 0, 4, 0, 0x00, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_remap_do_init_action() {
+        // Actions for sprite 6: just ActionEnd.
+        let tag = Tag::new(codes::DO_INIT_ACTION, vec![6, 0, 0]);
+        let mut ids = HashMap::new();
+        ids.insert(6, 2);
+        let remapped = remap_character_ids(&tag, &ids).unwrap();
+        assert_eq!(remapped.data(), &[2, 0, 0]);
+    }
+
+    #[test]
+    fn test_remap_symbol_class() {
+        let tag = Tag::new(codes::SYMBOL_CLASS, vec![1, 0, 7, 0, b'M', 0]);
+        let mut ids = HashMap::new();
+        ids.insert(7, 8);
+        let remapped = remap_character_ids(&tag, &ids).unwrap();
+        assert_eq!(parse_symbol_list(remapped.data()).unwrap(), vec![(8, "M".to_owned())]);
+    }
+
+    #[test]
+    fn test_occupied_depths() {
+        let tags = vec![
+            Tag::new(codes::PLACE_OBJECT_2, vec![0x02, 1, 0, 5, 0]),
+            Tag::new(codes::PLACE_OBJECT_2, vec![0x02, 2, 0, 5, 0]),
+            Tag::new(codes::REMOVE_OBJECT_2, vec![1, 0])
+        ];
+        assert_eq!(occupied_depths(&tags).into_iter().collect::<Vec<_>>(), vec![2]);
+    }
+}
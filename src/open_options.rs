@@ -0,0 +1,193 @@
+//! A single builder for opening SWF files, in the style of
+//! `std::fs::OpenOptions`.
+
+use std::io::Read;
+use std::path::Path;
+
+use super::SwfHeaders;
+use decoded_swf::DecodedSwf;
+use error::Error;
+use options::{ParseOptions, QuirksProfile};
+use swf_file::SwfFile;
+
+/// Options for opening a SWF file, and what to open it as.
+///
+/// Every setting from `ParseOptions` is here, with the ones most often
+/// wanted bundled up, so opening a file the way a service needs to is one
+/// chain of calls.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::OpenOptions;
+/// let options = OpenOptions::new().strict(true).max_decompressed(64 << 20);
+/// if let Ok((headers, decoded)) = options.open("example.swf") {
+///     // ...
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OpenOptions {
+    parse_options: ParseOptions
+}
+
+impl OpenOptions {
+    /// Creates options with the defaults of `ParseOptions::new()`.
+    pub fn new() -> Self {
+        OpenOptions::default()
+    }
+
+    /// Sets whether to accept only what the spec allows, as
+    /// `QuirksProfile::Strict` does, or to go back to the default
+    /// tolerances. Options set afterwards override either.
+    ///
+    /// Only the tolerance decisions a `QuirksProfile` makes are reset.
+    /// Limits meant to guard against hostile files, such as
+    /// `max_decompressed()`, are left alone, including the cap that
+    /// `QuirksProfile::FlashPlayer32` puts on it.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.parse_options = if strict {
+            self.parse_options.quirks(QuirksProfile::Strict)
+        } else {
+            let defaults = ParseOptions::new();
+            self.parse_options
+                .max_sprite_depth(defaults.sprite_depth_limit())
+                .body_slack(defaults.body_slack_limit())
+                .reject_zero_frame_count(defaults.rejects_zero_frame_count())
+                .reject_zero_frame_rate(defaults.rejects_zero_frame_rate())
+                .tolerate_bad_headers(defaults.tolerates_bad_headers())
+                .require_end_tag(defaults.requires_end_tag())
+        };
+        self
+    }
+
+    /// See `ParseOptions::quirks()`.
+    pub fn quirks(mut self, profile: QuirksProfile) -> Self {
+        self.parse_options = self.parse_options.quirks(profile);
+        self
+    }
+
+    /// See `ParseOptions::max_decompressed()`.
+    pub fn max_decompressed(mut self, len: u64) -> Self {
+        self.parse_options = self.parse_options.max_decompressed(len);
+        self
+    }
+
+    /// See `ParseOptions::max_file_size()`.
+    pub fn max_file_size(mut self, size: u64) -> Self {
+        self.parse_options = self.parse_options.max_file_size(size);
+        self
+    }
+
+    /// See `ParseOptions::max_tag_len()`.
+    pub fn max_tag_len(mut self, len: u32) -> Self {
+        self.parse_options = self.parse_options.max_tag_len(len);
+        self
+    }
+
+    /// See `ParseOptions::max_sprite_depth()`.
+    pub fn max_sprite_depth(mut self, depth: u32) -> Self {
+        self.parse_options = self.parse_options.max_sprite_depth(depth);
+        self
+    }
+
+    /// See `ParseOptions::max_lzma_dict_size()`.
+    pub fn max_lzma_dict_size(mut self, size: u32) -> Self {
+        self.parse_options = self.parse_options.max_lzma_dict_size(size);
+        self
+    }
+
+    /// See `ParseOptions::follow_symlinks()`.
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.parse_options = self.parse_options.follow_symlinks(follow);
+        self
+    }
+
+    /// See `ParseOptions::allow_special_files()`.
+    pub fn allow_special_files(mut self, allow: bool) -> Self {
+        self.parse_options = self.parse_options.allow_special_files(allow);
+        self
+    }
+
+    /// See `ParseOptions::compute_digests()`.
+    #[cfg(feature = "digests")]
+    pub fn compute_digests(mut self, enabled: bool) -> Self {
+        self.parse_options = self.parse_options.compute_digests(enabled);
+        self
+    }
+
+    /// Returns the options everything is parsed with, for the functions
+    /// that take a `ParseOptions`, such as `bulk::read_headers_with_options()`.
+    pub fn parse_options(&self) -> &ParseOptions {
+        &self.parse_options
+    }
+
+    /// Opens the file at `path` and parses its headers, as
+    /// `SwfHeaders::open_with_options()` does.
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<(SwfHeaders, DecodedSwf), Error> {
+        SwfHeaders::open_with_options(path, &self.parse_options)
+    }
+
+    /// Parses the headers from the start of `reader`, as
+    /// `SwfHeaders::read_with_options()` does.
+    pub fn read_from<R: Read + Send + 'static>(&self, reader: R) -> Result<(SwfHeaders, DecodedSwf), Error> {
+        SwfHeaders::read_with_options(reader, &self.parse_options)
+    }
+
+    /// Reads the whole file at `path` into an `SwfFile`.
+    pub fn open_swf_file<P: AsRef<Path>>(&self, path: P) -> Result<SwfFile, Error> {
+        SwfFile::open_with_options(path, &self.parse_options)
+    }
+}
+
+impl From<ParseOptions> for OpenOptions {
+    fn from(parse_options: ParseOptions) -> Self {
+        OpenOptions { parse_options: parse_options }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read};
+
+    use {Signature, SwfHeaders};
+    use tag::{codes, Tag};
+    use writer;
+
+    #[test]
+    fn test_open_options() {
+        let headers = |signature, frame_count| SwfHeaders {
+            signature: signature,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: frame_count
+        };
+        let tags = vec![Tag::new(codes::DEFINE_BINARY_DATA, vec![0; 1000])];
+        let empty = writer::to_bytes(&headers(Signature::ZlibCompressed, 0), &tags).unwrap();
+
+        let strict = OpenOptions::new().strict(true);
+        assert_eq!(strict.read_from(Cursor::new(empty.clone())).err().unwrap().kind(), "not-swf");
+        let lenient = strict.clone().strict(false);
+        assert!(!lenient.parse_options().rejects_zero_frame_count());
+        assert!(lenient.read_from(Cursor::new(empty.clone())).is_ok());
+
+        // Going back to the defaults keeps limits, whoever set them.
+        let emulator = OpenOptions::new().quirks(QuirksProfile::FlashPlayer32).strict(false);
+        assert_eq!(emulator.parse_options().decompressed_limit(), 256 * 1024 * 1024);
+        assert!(!emulator.parse_options().tolerates_bad_headers());
+        let capped = OpenOptions::new().max_decompressed(100).strict(true).strict(false);
+        assert_eq!(capped.parse_options().decompressed_limit(), 100);
+
+        // The limit holds for compressed and uncompressed bodies alike.
+        let full = writer::to_bytes(&headers(Signature::Uncompressed, 1), &tags).unwrap();
+        for data in vec![empty, full] {
+            let (_, mut decoded) = OpenOptions::new().max_decompressed(100).read_from(Cursor::new(data)).unwrap();
+            let err = decoded.read_to_end(&mut Vec::new()).unwrap_err();
+            assert_eq!(Error::from(err).kind(), "limit-exceeded");
+        }
+    }
+}
@@ -0,0 +1,398 @@
+use std::fmt;
+use std::io::Read;
+use std::sync::Arc;
+
+/// Sets of tolerance decisions, for `ParseOptions::quirks()`.
+///
+/// Each profile sets whether zero frame counts and rates are accepted,
+/// whether the file length and frame count in the header are trusted, how
+/// far past the file length a body can go, whether sprites can be nested,
+/// and whether the End tag can be missing. Limits meant to guard against
+/// hostile files, such as `max_tag_len()`, are left alone, except that a
+/// profile that distrusts the header also caps `max_decompressed()`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum QuirksProfile {
+    /// Accept what Flash Player 32, the last release, plays: zero frame
+    /// counts and rates, junk file lengths and frame counts, nested
+    /// sprites, and a missing End tag. For emulators and archives.
+    ///
+    /// With the file length not trusted, `max_decompressed()` is lowered to
+    /// 256MiB if it's any higher, so a zlib bomb can't take all the memory.
+    FlashPlayer32,
+    /// Accept only what the spec allows: no zero frame counts or rates,
+    /// bodies no longer than the file length says, sprites only on the main
+    /// timeline, and an End tag closing every timeline. For validators.
+    Strict
+}
+
+/// Limits and settings for reading the body of a SWF file.
+///
+/// The defaults accept anything a real SWF file could contain. Services
+/// handling untrusted files will want to tighten them, since a single tag
+/// header can claim a payload of up to 4GiB.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::ParseOptions;
+/// use swf_headers::tag::Tags;
+/// let options = ParseOptions::new().max_tag_len(16 * 1024 * 1024).max_sprite_depth(4);
+/// let body: &[u8] = &[0x40, 0x00];
+/// for tag in Tags::with_options(body, options) {
+///     // ...
+/// }
+/// ```
+#[derive(Clone)]
+pub struct ParseOptions {
+    max_tag_len: u32,
+    max_sprite_depth: u32,
+    max_lzma_dict_size: u32,
+    body_slack: u32,
+    max_decompressed: u64,
+    reject_zero_frame_count: bool,
+    reject_zero_frame_rate: bool,
+    tolerate_bad_headers: bool,
+    require_end_tag: bool,
+    max_file_size: u64,
+    follow_symlinks: bool,
+    allow_special_files: bool,
+    #[cfg(feature = "digests")]
+    digests: bool,
+    wrapper: Option<BodyWrapper>
+}
+
+/// A closure set with `ParseOptions::wrap_body()`.
+type BodyWrapper = Arc<dyn Fn(Box<dyn Read + Send>) -> Box<dyn Read + Send> + Send + Sync>;
+
+impl ParseOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        ParseOptions {
+            max_tag_len: u32::max_value(),
+            max_sprite_depth: 16,
+            max_lzma_dict_size: u32::max_value(),
+            body_slack: 1024 * 1024,
+            max_decompressed: u64::max_value(),
+            reject_zero_frame_count: false,
+            reject_zero_frame_rate: false,
+            tolerate_bad_headers: false,
+            require_end_tag: false,
+            max_file_size: u64::max_value(),
+            follow_symlinks: true,
+            allow_special_files: true,
+            #[cfg(feature = "digests")]
+            digests: false,
+            wrapper: None
+        }
+    }
+
+    /// Sets the longest tag payload, in bytes, to accept. Unlimited by
+    /// default.
+    pub fn max_tag_len(mut self, len: u32) -> Self {
+        self.max_tag_len = len;
+        self
+    }
+
+    /// Sets how deeply DefineSprite tags can be nested inside each other,
+    /// with a sprite on the main timeline counting as depth 1. Defaults to
+    /// 16.
+    ///
+    /// The spec doesn't allow nested sprites at all, but some tools write
+    /// them anyway, and anything recursing into them needs a limit.
+    pub fn max_sprite_depth(mut self, depth: u32) -> Self {
+        self.max_sprite_depth = depth;
+        self
+    }
+
+    /// Sets the largest LZMA dictionary, in bytes, to accept from a ZWS
+    /// file. Unlimited by default.
+    ///
+    /// The decoder allocates the dictionary the file asks for before
+    /// decoding anything, and the properties can ask for up to 4GiB, so a
+    /// 20-byte file can exhaust memory. Files asking for more than this get
+    /// `Error::LimitExceeded`.
+    pub fn max_lzma_dict_size(mut self, size: u32) -> Self {
+        self.max_lzma_dict_size = size;
+        self
+    }
+
+    /// Sets how far past the file length in the header a compressed body
+    /// can decompress to, in bytes, before reads fail with
+    /// `Error::BodyOverrun`. Defaults to 1MiB.
+    ///
+    /// Without a cap, a few kilobytes of zlib can inflate to gigabytes, and
+    /// anything scanning the tags would read them all. Plenty of real files
+    /// have a file length that's a little off, hence the slack.
+    /// Uncompressed bodies aren't capped, since they can't be any bigger
+    /// than the file.
+    pub fn body_slack(mut self, slack: u32) -> Self {
+        self.body_slack = slack;
+        self
+    }
+
+    /// Sets the most a body can decompress to, in bytes, whatever the file
+    /// length in the header says. Reads past it fail with
+    /// `Error::LimitExceeded`. Unlimited by default.
+    ///
+    /// Unlike `body_slack()`, this holds for uncompressed bodies and with
+    /// `tolerate_bad_headers()` too, so it bounds the memory anything
+    /// reading a whole body needs.
+    pub fn max_decompressed(mut self, len: u64) -> Self {
+        self.max_decompressed = len;
+        self
+    }
+
+    /// Sets whether a header with a frame count of 0 is an error.
+    ///
+    /// Some tools write 0 rather than counting their frames. Leniently (the
+    /// default), the header is accepted and `SwfHeaders::frame_count()`
+    /// reports the 0 as-is; strictly, `Error::NotSwf` is returned instead.
+    pub fn reject_zero_frame_count(mut self, reject: bool) -> Self {
+        self.reject_zero_frame_count = reject;
+        self
+    }
+
+    /// Sets whether a header with a frame rate of 0 is an error.
+    ///
+    /// Leniently (the default), the header is accepted and
+    /// `SwfHeaders::frame_rate()` reports the 0 as-is, while
+    /// `SwfHeaders::nominal_frame_rate()` gives the rate Flash Player
+    /// actually plays at; strictly, `Error::NotSwf` is returned instead.
+    pub fn reject_zero_frame_rate(mut self, reject: bool) -> Self {
+        self.reject_zero_frame_rate = reject;
+        self
+    }
+
+    /// Sets whether to distrust the file length and frame count in the
+    /// header, the way Flash Player does. Off by default.
+    ///
+    /// Protectors overwrite these with nonsense to trip up decompilers,
+    /// since the player copes without them. When
+    /// tolerating them, `SwfHeaders::read_with_options()` reads the whole
+    /// body into memory, and reports the length it decompressed to and the
+    /// number of ShowFrame tags on the main timeline instead. The frame
+    /// count from the header is kept if the tags can't be read.
+    ///
+    /// The file length can't cap a compressed body exactly when it isn't
+    /// trusted, so `body_slack()` doesn't apply. Unless `max_decompressed()`
+    /// is set, a compressed body is instead cut off at 16MiB past the file
+    /// length, with `Error::LimitExceeded`.
+    pub fn tolerate_bad_headers(mut self, tolerate: bool) -> Self {
+        self.tolerate_bad_headers = tolerate;
+        self
+    }
+
+    /// Returns true if the file length and frame count in the header are
+    /// recomputed from the body.
+    pub fn tolerates_bad_headers(&self) -> bool {
+        self.tolerate_bad_headers
+    }
+
+    /// Sets whether a timeline that runs out before its End tag is an
+    /// error, for `Tags`. Plenty of real files lack the final End tag, so
+    /// it isn't by default.
+    pub fn require_end_tag(mut self, require: bool) -> Self {
+        self.require_end_tag = require;
+        self
+    }
+
+    /// Returns true if a missing End tag is an error.
+    pub fn requires_end_tag(&self) -> bool {
+        self.require_end_tag
+    }
+
+    /// Sets all of the tolerance decisions at once, to match a player or
+    /// the spec. Options set afterwards override the profile's.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use swf_headers::{ParseOptions, QuirksProfile};
+    /// let emulator = ParseOptions::new().quirks(QuirksProfile::FlashPlayer32);
+    /// let validator = ParseOptions::new().quirks(QuirksProfile::Strict).max_sprite_depth(2);
+    /// assert!(validator.rejects_zero_frame_count());
+    /// ```
+    pub fn quirks(self, profile: QuirksProfile) -> Self {
+        let defaults = ParseOptions::new();
+        match profile {
+            QuirksProfile::FlashPlayer32 => ParseOptions {
+                max_sprite_depth: defaults.max_sprite_depth,
+                body_slack: defaults.body_slack,
+                reject_zero_frame_count: false,
+                reject_zero_frame_rate: false,
+                tolerate_bad_headers: true,
+                require_end_tag: false,
+                max_decompressed: self.max_decompressed.min(256 * 1024 * 1024),
+                ..self
+            },
+            QuirksProfile::Strict => ParseOptions {
+                max_sprite_depth: 1,
+                body_slack: 0,
+                reject_zero_frame_count: true,
+                reject_zero_frame_rate: true,
+                tolerate_bad_headers: false,
+                require_end_tag: true,
+                ..self
+            }
+        }
+    }
+
+    /// Returns true if a frame count of 0 is an error.
+    pub fn rejects_zero_frame_count(&self) -> bool {
+        self.reject_zero_frame_count
+    }
+
+    /// Returns true if a frame rate of 0 is an error.
+    pub fn rejects_zero_frame_rate(&self) -> bool {
+        self.reject_zero_frame_rate
+    }
+
+    /// Sets whether to compute SHA-256 and MD5 digests of both the file and
+    /// its decompressed body as they're read, for `DecodedSwf::digests()`.
+    /// Off by default, and only available with the `digests` feature.
+    #[cfg(feature = "digests")]
+    pub fn compute_digests(mut self, enabled: bool) -> Self {
+        self.digests = enabled;
+        self
+    }
+
+    /// Returns true if digests will be computed.
+    #[cfg(feature = "digests")]
+    pub fn digests_enabled(&self) -> bool {
+        self.digests
+    }
+
+    /// Sets the largest file, in bytes, that `SwfHeaders::open_with_options()`
+    /// and the `bulk` functions will open. Larger files get
+    /// `Error::LimitExceeded` before anything is read. Unlimited by default.
+    pub fn max_file_size(mut self, size: u64) -> Self {
+        self.max_file_size = size;
+        self
+    }
+
+    /// Returns the largest file that will be opened.
+    pub fn file_size_limit(&self) -> u64 {
+        self.max_file_size
+    }
+
+    /// Sets whether opening a path that's a symbolic link follows it, as it
+    /// does by default. When not, opening one is an `Error::IoError`, so a
+    /// scan of a directory users control can't be pointed elsewhere.
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Returns true if symbolic links are followed when opening.
+    pub fn follows_symlinks(&self) -> bool {
+        self.follow_symlinks
+    }
+
+    /// Sets whether paths that aren't regular files, such as devices, FIFOs
+    /// and sockets, can be opened, as they can by default. Reading a device
+    /// can return data forever, and opening a FIFO can block forever, so
+    /// scans of untrusted directories should turn this off. Opening one
+    /// then is an `Error::IoError`.
+    pub fn allow_special_files(mut self, allow: bool) -> Self {
+        self.allow_special_files = allow;
+        self
+    }
+
+    /// Returns true if paths other than regular files can be opened.
+    pub fn allows_special_files(&self) -> bool {
+        self.allow_special_files
+    }
+
+    /// Sets a function to wrap the decompressed body stream in, for counting
+    /// bytes, rate limiting, hashing, or anything else that wants to see
+    /// the data as it's read.
+    ///
+    /// The body starts just after the first 8 bytes of the file, so the
+    /// wrapper sees everything that was compressed, including the rest of
+    /// the header. Only `SwfHeaders::open_with_options()` and
+    /// `SwfHeaders::read_with_options()` use it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::io::Read;
+    /// use swf_headers::ParseOptions;
+    /// // Stop reading after 64MiB, however well the file compressed.
+    /// let options = ParseOptions::new().wrap_body(|body| Box::new(body.take(64 * 1024 * 1024)));
+    /// ```
+    pub fn wrap_body<F>(mut self, wrapper: F) -> Self
+        where F: Fn(Box<dyn Read + Send>) -> Box<dyn Read + Send> + Send + Sync + 'static
+    {
+        self.wrapper = Some(Arc::new(wrapper));
+        self
+    }
+
+    /// Runs the body wrapper on a stream, returning the stream untouched if
+    /// there's no wrapper.
+    pub fn wrap(&self, body: Box<dyn Read + Send>) -> Box<dyn Read + Send> {
+        match self.wrapper {
+            Some(ref wrapper) => wrapper(body),
+            None => body
+        }
+    }
+
+    /// Returns true if a body wrapper has been set.
+    pub fn has_wrapper(&self) -> bool {
+        self.wrapper.is_some()
+    }
+
+    /// Returns the longest tag payload accepted.
+    pub fn tag_len_limit(&self) -> u32 {
+        self.max_tag_len
+    }
+
+    /// Returns the deepest DefineSprite nesting accepted.
+    pub fn sprite_depth_limit(&self) -> u32 {
+        self.max_sprite_depth
+    }
+
+    /// Returns the largest LZMA dictionary accepted.
+    pub fn lzma_dict_size_limit(&self) -> u32 {
+        self.max_lzma_dict_size
+    }
+
+    /// Returns how far past the file length a compressed body can go.
+    pub fn body_slack_limit(&self) -> u32 {
+        self.body_slack
+    }
+
+    /// Returns the most a body can decompress to.
+    pub fn decompressed_limit(&self) -> u64 {
+        self.max_decompressed
+    }
+}
+
+impl fmt::Debug for ParseOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("ParseOptions");
+        debug
+            .field("max_tag_len", &self.max_tag_len)
+            .field("max_sprite_depth", &self.max_sprite_depth)
+            .field("max_lzma_dict_size", &self.max_lzma_dict_size)
+            .field("body_slack", &self.body_slack)
+            .field("max_decompressed", &self.max_decompressed)
+            .field("reject_zero_frame_count", &self.reject_zero_frame_count)
+            .field("reject_zero_frame_rate", &self.reject_zero_frame_rate)
+            .field("tolerate_bad_headers", &self.tolerate_bad_headers)
+            .field("require_end_tag", &self.require_end_tag)
+            .field("max_file_size", &self.max_file_size)
+            .field("follow_symlinks", &self.follow_symlinks)
+            .field("allow_special_files", &self.allow_special_files);
+        #[cfg(feature = "digests")]
+        debug.field("digests", &self.digests);
+        debug
+            .field("wrapper", &self.wrapper.as_ref().map(|_| "Fn"))
+            .finish()
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions::new()
+    }
+}
@@ -0,0 +1,205 @@
+//! Reading the headers of many files in a row without allocating for each.
+//!
+//! `SwfHeaders::read_from()` sets up a fresh decompressor and buffers for
+//! every file, which is most of the time spent on small ones. A `Parser`
+//! keeps its buffers and zlib state between files, so scanning a large
+//! collection allocates once up front. Only the headers are read; for the
+//! body, use `read_from()`.
+
+use std::io::{self, Read};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use flate2::{Decompress, Flush, Status};
+use lzma;
+
+use super::{Signature, SwfHeaders};
+use error::Error;
+use options::ParseOptions;
+
+/// How much compressed input is read at a time. zlib gets the headers out
+/// of the first few dozen bytes, so this rarely fills up.
+const CHUNK_LEN: usize = 256;
+
+/// Reads the headers of one file after another, reusing its buffers.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::fs::File;
+/// use swf_headers::Parser;
+/// let mut parser = Parser::new();
+/// for path in &["a.swf", "b.swf", "c.swf"] {
+///     if let Ok(file) = File::open(path) {
+///         match parser.parse(file) {
+///             Ok(headers) => println!("{}: {:?}", path, headers.dimensions()),
+///             Err(err) => println!("{}: {}", path, err.kind())
+///         }
+///     }
+/// }
+/// ```
+pub struct Parser {
+    // The decompressed start of the current file, as parse_prefix() wants.
+    prefix: [u8; 64],
+    // Compressed input on its way to the decompressor.
+    input: Vec<u8>,
+    zlib: Decompress,
+    lzma_dict_size_limit: u32
+}
+
+impl Parser {
+    /// Creates a parser, with the LZMA dictionary size limit of
+    /// `ParseOptions::default()`.
+    pub fn new() -> Self {
+        Self::with_options(&ParseOptions::default())
+    }
+
+    /// Creates a parser that rejects LZMA dictionaries larger than `options`
+    /// allow. The other options only matter for reading bodies, so they're
+    /// ignored.
+    pub fn with_options(options: &ParseOptions) -> Self {
+        Parser {
+            prefix: [0; 64],
+            input: vec![0; CHUNK_LEN],
+            zlib: Decompress::new(true),
+            lzma_dict_size_limit: options.lzma_dict_size_limit()
+        }
+    }
+
+    /// Reads the headers from the start of `reader`, giving the same result
+    /// as `SwfHeaders::read_from()`.
+    ///
+    /// The reader is left somewhere after the headers. For compressed files,
+    /// that's however far the decompressor needed to read.
+    pub fn parse<R: Read>(&mut self, mut reader: R) -> Result<SwfHeaders, Error> {
+        try!(fill(&mut reader, &mut self.prefix[..8]));
+        if self.prefix[1] != 0x57 || self.prefix[2] != 0x53 {
+            return Err(Error::NotSwf);
+        }
+        let sig = match self.prefix[0] {
+            b'F' => Signature::Uncompressed,
+            b'C' => Signature::ZlibCompressed,
+            b'Z' => Signature::LzmaCompressed,
+            _ => return Err(Error::NotSwf)
+        };
+
+        match sig {
+            Signature::Uncompressed => try!(fill_prefix(reader, &mut self.prefix)),
+            Signature::ZlibCompressed => try!(self.inflate_prefix(reader)),
+            Signature::LzmaCompressed => {
+                // The compressed length, then the properties and dictionary
+                // size, checked before the decoder allocates anything.
+                try!(fill(&mut reader, &mut self.input[..9]));
+                let dict_size = (&self.input[5..9]).read_u32::<LittleEndian>().unwrap();
+                if dict_size > self.lzma_dict_size_limit {
+                    return Err(Error::LimitExceeded);
+                }
+                let start = io::Cursor::new(&self.input[..9]);
+                let decoder = try!(lzma::Reader::from(start.chain(reader)));
+                try!(fill_prefix(decoder, &mut self.prefix));
+            }
+        }
+        SwfHeaders::parse_prefix(&self.prefix).map(|(headers, _)| headers)
+    }
+
+    /// Decompresses the rest of a zlib header, a chunk of input at a time.
+    fn inflate_prefix<R: Read>(&mut self, mut reader: R) -> Result<(), Error> {
+        self.zlib.reset(true);
+        let mut filled = 8;
+        let mut wanted = 9;
+        while filled < wanted {
+            let read = try!(reader.read(&mut self.input));
+            if read == 0 {
+                return Err(Error::NotSwf);
+            }
+            let mut chunk = &self.input[..read];
+            while !chunk.is_empty() && filled < wanted {
+                let (before_in, before_out) = (self.zlib.total_in(), self.zlib.total_out());
+                let status = match self.zlib.decompress(chunk, &mut self.prefix[filled..], Flush::None) {
+                    Ok(status) => status,
+                    Err(_) => return Err(Error::NotSwf)
+                };
+                let consumed = (self.zlib.total_in() - before_in) as usize;
+                let produced = (self.zlib.total_out() - before_out) as usize;
+                chunk = &chunk[consumed..];
+                filled += produced;
+                if filled > 8 {
+                    wanted = SwfHeaders::prefix_len(self.prefix[8]);
+                }
+                if status == Status::StreamEnd && filled < wanted {
+                    return Err(Error::NotSwf);
+                }
+                if consumed == 0 && produced == 0 {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads a decompressed header into `prefix`, after its first 8 bytes.
+fn fill_prefix<R: Read>(mut reader: R, prefix: &mut [u8; 64]) -> Result<(), Error> {
+    try!(fill(&mut reader, &mut prefix[8..9]));
+    let len = SwfHeaders::prefix_len(prefix[8]);
+    fill(&mut reader, &mut prefix[9..len])
+}
+
+/// Fills `buf`, taking running out of input as not being a SWF, the way
+/// `read_from()` does.
+fn fill<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), Error> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => return Err(Error::NotSwf),
+            Ok(read) => filled += read,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {},
+            Err(err) => return Err(err.into())
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use {Signature, SwfHeaders};
+    use tag::{codes, Tag};
+    use writer;
+
+    #[test]
+    fn test_parse_many() {
+        let tags = vec![Tag::new(codes::SHOW_FRAME, vec![])];
+        let mut files = Vec::new();
+        for &(signature, width) in &[(Signature::ZlibCompressed, 550), (Signature::Uncompressed, 1),
+                                     (Signature::ZlibCompressed, 0x3fff_ffff / 20)] {
+            files.push(writer::to_bytes(&SwfHeaders {
+                signature: signature,
+                version: 10,
+                file_length: 0,
+                width: width * 20,
+                height: 400 * 20,
+                frame_rate: 30,
+                frame_rate_fraction: 0,
+                frame_count: 1
+            }, &tags).unwrap());
+        }
+
+        let mut parser = Parser::new();
+        for file in &files {
+            let (expected, _) = SwfHeaders::read_from(Cursor::new(file.clone())).unwrap();
+            assert_eq!(parser.parse(&file[..]).unwrap(), expected);
+        }
+        assert!(parser.parse(&b"XWS\x0a"[..]).is_err());
+        assert!(parser.parse(&files[0][..12]).is_err());
+        // Still fine after failing.
+        assert_eq!(parser.parse(&files[0][..]).unwrap().dimensions(), (550, 400));
+    }
+}
@@ -0,0 +1,718 @@
+//! Readers and writers for the small building blocks the SWF spec is built
+//! out of: bit fields, RECTs, matrices, colors, color transforms, strings and
+//! variable-length integers.
+
+use std::io::{self, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use error::Error;
+
+/// Reads bit fields out of a byte slice, most significant bit first, the way
+/// the spec packs them.
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize
+}
+
+impl<'a> BitReader<'a> {
+    /// Creates a reader starting at the first bit of the slice.
+    pub fn new(data: &'a [u8]) -> Self {
+        BitReader {
+            data: data,
+            pos: 0
+        }
+    }
+
+    /// Reads an unsigned bit field of up to 32 bits.
+    pub fn read_ub(&mut self, nbits: u32) -> Result<u32, Error> {
+        let mut value = 0u32;
+        for _ in 0..nbits {
+            let byte = match self.data.get(self.pos / 8) {
+                Some(byte) => *byte,
+                None => return Err(Error::NotSwf)
+            };
+            let bit = (byte >> (7 - self.pos % 8)) & 1;
+            value = (value << 1) | bit as u32;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    /// Reads a signed (two's complement) bit field of up to 32 bits.
+    pub fn read_sb(&mut self, nbits: u32) -> Result<i32, Error> {
+        let value = try!(self.read_ub(nbits));
+        if nbits == 0 || nbits == 32 {
+            return Ok(value as i32);
+        }
+        let shift = 32 - nbits;
+        Ok(((value << shift) as i32) >> shift)
+    }
+
+    /// Reads a signed 16.16 fixed-point bit field.
+    pub fn read_fb(&mut self, nbits: u32) -> Result<f64, Error> {
+        Ok(try!(self.read_sb(nbits)) as f64 / 65536.0)
+    }
+
+    /// Reads a single bit as a flag.
+    pub fn read_flag(&mut self) -> Result<bool, Error> {
+        Ok(try!(self.read_ub(1)) == 1)
+    }
+
+    /// Reads a byte-aligned u8, skipping to the next byte boundary first.
+    pub fn read_u8(&mut self) -> Result<u8, Error> {
+        self.align();
+        Ok(try!(self.read_ub(8)) as u8)
+    }
+
+    /// Reads a byte-aligned little-endian u16, skipping to the next byte
+    /// boundary first.
+    pub fn read_u16(&mut self) -> Result<u16, Error> {
+        let low = try!(self.read_u8()) as u16;
+        let high = try!(self.read_u8()) as u16;
+        Ok(low | (high << 8))
+    }
+
+    /// Skips ahead to the next byte boundary.
+    pub fn align(&mut self) {
+        self.pos = (self.pos + 7) / 8 * 8;
+    }
+
+    /// Returns the number of whole bytes touched so far, counting a partially
+    /// read byte as consumed.
+    pub fn byte_pos(&self) -> usize {
+        (self.pos + 7) / 8
+    }
+}
+
+/// Packs bit fields into bytes, most significant bit first.
+pub struct BitWriter {
+    data: Vec<u8>,
+    nbits: usize
+}
+
+impl BitWriter {
+    /// Creates an empty writer.
+    pub fn new() -> Self {
+        BitWriter {
+            data: Vec::new(),
+            nbits: 0
+        }
+    }
+
+    /// Writes the low `nbits` bits of an unsigned value.
+    pub fn write_ub(&mut self, nbits: u32, value: u32) {
+        for i in (0..nbits).rev() {
+            if self.nbits % 8 == 0 {
+                self.data.push(0);
+            }
+            let bit = ((value >> i) & 1) as u8;
+            let last = self.data.len() - 1;
+            self.data[last] |= bit << (7 - self.nbits % 8);
+            self.nbits += 1;
+        }
+    }
+
+    /// Writes the low `nbits` bits of a signed value.
+    pub fn write_sb(&mut self, nbits: u32, value: i32) {
+        self.write_ub(nbits, value as u32);
+    }
+
+    /// Writes a single bit.
+    pub fn write_flag(&mut self, value: bool) {
+        self.write_ub(1, value as u32);
+    }
+
+    /// Consumes the writer, padding the last byte with zero bits.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Returns the fewest bits a signed bit field needs to hold every value
+/// given.
+pub fn signed_bits_needed(values: &[i32]) -> u32 {
+    values.iter().map(|&value| {
+        if value == 0 {
+            0
+        } else if value > 0 {
+            33 - value.leading_zeros()
+        } else {
+            33 - (!value).leading_zeros()
+        }
+    }).max().unwrap_or(0)
+}
+
+/// Clamps a value to what a signed bit field of `nbits` bits can hold.
+fn clamp_signed(value: i32, nbits: u32) -> i32 {
+    let max = (1 << (nbits - 1)) - 1;
+    value.max(-max - 1).min(max)
+}
+
+/// Encodes a RECT with its minimums at zero, as used for the stage size in
+/// the header. Values are in twips.
+///
+/// A RECT field can be at most 31 bits, so a width or height of 2^30 twips
+/// or more is an error.
+pub fn encode_rect(width: u32, height: u32) -> Result<Vec<u8>, Error> {
+    if width >= 1 << 30 || height >= 1 << 30 {
+        return Err(Error::IoError(io::Error::new(io::ErrorKind::InvalidInput, "stage too large for a RECT")));
+    }
+    let (width, height) = (width as i32, height as i32);
+    let nbits = signed_bits_needed(&[width, height]);
+    let mut writer = BitWriter::new();
+    writer.write_ub(5, nbits);
+    writer.write_sb(nbits, 0);
+    writer.write_sb(nbits, width);
+    writer.write_sb(nbits, 0);
+    writer.write_sb(nbits, height);
+    Ok(writer.into_bytes())
+}
+
+/// A MATRIX, the 2D affine transform used for placing characters.
+///
+/// Scale and rotation are 16.16 fixed point in the file, which an f64 holds
+/// exactly. Translation is in twips.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Matrix {
+    /// Horizontal scale.
+    pub scale_x: f64,
+    /// Vertical scale.
+    pub scale_y: f64,
+    /// First rotate/skew term.
+    pub rotate_skew_0: f64,
+    /// Second rotate/skew term.
+    pub rotate_skew_1: f64,
+    /// Horizontal translation in twips.
+    pub translate_x: i32,
+    /// Vertical translation in twips.
+    pub translate_y: i32
+}
+
+impl Matrix {
+    /// Returns the identity matrix, which leaves things where they are.
+    pub fn identity() -> Self {
+        Matrix {
+            scale_x: 1.0,
+            scale_y: 1.0,
+            rotate_skew_0: 0.0,
+            rotate_skew_1: 0.0,
+            translate_x: 0,
+            translate_y: 0
+        }
+    }
+
+    /// Reads a MATRIX from the current bit position.
+    ///
+    /// The reader is left just after the last field, not byte-aligned, so
+    /// callers reading further fields should call `align()` themselves.
+    pub fn read(reader: &mut BitReader) -> Result<Self, Error> {
+        let mut matrix = Matrix::identity();
+        if try!(reader.read_flag()) {
+            let nbits = try!(reader.read_ub(5));
+            matrix.scale_x = try!(reader.read_fb(nbits));
+            matrix.scale_y = try!(reader.read_fb(nbits));
+        }
+        if try!(reader.read_flag()) {
+            let nbits = try!(reader.read_ub(5));
+            matrix.rotate_skew_0 = try!(reader.read_fb(nbits));
+            matrix.rotate_skew_1 = try!(reader.read_fb(nbits));
+        }
+        let nbits = try!(reader.read_ub(5));
+        matrix.translate_x = try!(reader.read_sb(nbits));
+        matrix.translate_y = try!(reader.read_sb(nbits));
+        Ok(matrix)
+    }
+
+    /// Writes a MATRIX, leaving out the scale and rotate terms when they're
+    /// the identity, and using the fewest bits each field needs.
+    ///
+    /// A MATRIX field can be at most 31 bits, so values past that, such as
+    /// a scale of 16384 or more, are clamped to the nearest that fits.
+    pub fn write(&self, writer: &mut BitWriter) {
+        let fixed = |value: f64| clamp_signed((value * 65536.0).round() as i32, 31);
+
+        let has_scale = self.scale_x != 1.0 || self.scale_y != 1.0;
+        writer.write_flag(has_scale);
+        if has_scale {
+            let values = [fixed(self.scale_x), fixed(self.scale_y)];
+            let nbits = signed_bits_needed(&values);
+            writer.write_ub(5, nbits);
+            writer.write_sb(nbits, values[0]);
+            writer.write_sb(nbits, values[1]);
+        }
+
+        let has_rotate = self.rotate_skew_0 != 0.0 || self.rotate_skew_1 != 0.0;
+        writer.write_flag(has_rotate);
+        if has_rotate {
+            let values = [fixed(self.rotate_skew_0), fixed(self.rotate_skew_1)];
+            let nbits = signed_bits_needed(&values);
+            writer.write_ub(5, nbits);
+            writer.write_sb(nbits, values[0]);
+            writer.write_sb(nbits, values[1]);
+        }
+
+        let translate = [clamp_signed(self.translate_x, 31), clamp_signed(self.translate_y, 31)];
+        let nbits = signed_bits_needed(&translate);
+        writer.write_ub(5, nbits);
+        writer.write_sb(nbits, translate[0]);
+        writer.write_sb(nbits, translate[1]);
+    }
+}
+
+/// A RECT, in twips.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Rect {
+    /// The left edge.
+    pub x_min: i32,
+    /// The right edge.
+    pub x_max: i32,
+    /// The top edge.
+    pub y_min: i32,
+    /// The bottom edge.
+    pub y_max: i32
+}
+
+impl Rect {
+    /// Reads a RECT from the current bit position.
+    pub fn read(reader: &mut BitReader) -> Result<Self, Error> {
+        let nbits = try!(reader.read_ub(5));
+        Ok(Rect {
+            x_min: try!(reader.read_sb(nbits)),
+            x_max: try!(reader.read_sb(nbits)),
+            y_min: try!(reader.read_sb(nbits)),
+            y_max: try!(reader.read_sb(nbits))
+        })
+    }
+
+    /// Writes a RECT, using the fewest bits the fields need.
+    pub fn write(&self, writer: &mut BitWriter) {
+        let nbits = signed_bits_needed(&[self.x_min, self.x_max, self.y_min, self.y_max]);
+        writer.write_ub(5, nbits);
+        writer.write_sb(nbits, self.x_min);
+        writer.write_sb(nbits, self.x_max);
+        writer.write_sb(nbits, self.y_min);
+        writer.write_sb(nbits, self.y_max);
+    }
+
+    /// Returns the width in twips.
+    pub fn width(&self) -> i32 {
+        self.x_max - self.x_min
+    }
+
+    /// Returns the height in twips.
+    pub fn height(&self) -> i32 {
+        self.y_max - self.y_min
+    }
+}
+
+/// An RGB or RGBA color. Colors read from plain RGB fields are fully opaque.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Rgba {
+    /// Red.
+    pub r: u8,
+    /// Green.
+    pub g: u8,
+    /// Blue.
+    pub b: u8,
+    /// Alpha, where 255 is opaque.
+    pub a: u8
+}
+
+impl Rgba {
+    /// Reads a 3-byte RGB record.
+    pub fn read_rgb<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let mut bytes = [0; 3];
+        try!(read_exact(reader, &mut bytes));
+        Ok(Rgba { r: bytes[0], g: bytes[1], b: bytes[2], a: 255 })
+    }
+
+    /// Reads a 4-byte RGBA record.
+    pub fn read_rgba<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let mut bytes = [0; 4];
+        try!(read_exact(reader, &mut bytes));
+        Ok(Rgba { r: bytes[0], g: bytes[1], b: bytes[2], a: bytes[3] })
+    }
+}
+
+fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), Error> {
+    for byte in buf.iter_mut() {
+        *byte = try!(reader.read_u8());
+    }
+    Ok(())
+}
+
+/// A CXFORM or CXFORMWITHALPHA, transforming colors channel by channel.
+///
+/// Each channel becomes `channel * mult + add`, clamped to 0-255. The
+/// multipliers are 8.8 fixed point in the file, which an f32 holds exactly.
+/// Plain CXFORMs have no alpha terms, so they read back with the identity
+/// values there.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ColorTransform {
+    /// Red multiplier.
+    pub r_mult: f32,
+    /// Green multiplier.
+    pub g_mult: f32,
+    /// Blue multiplier.
+    pub b_mult: f32,
+    /// Alpha multiplier.
+    pub a_mult: f32,
+    /// Red offset.
+    pub r_add: i16,
+    /// Green offset.
+    pub g_add: i16,
+    /// Blue offset.
+    pub b_add: i16,
+    /// Alpha offset.
+    pub a_add: i16
+}
+
+impl ColorTransform {
+    /// Returns the identity transform, which leaves colors alone.
+    pub fn identity() -> Self {
+        ColorTransform {
+            r_mult: 1.0,
+            g_mult: 1.0,
+            b_mult: 1.0,
+            a_mult: 1.0,
+            r_add: 0,
+            g_add: 0,
+            b_add: 0,
+            a_add: 0
+        }
+    }
+
+    /// Reads a CXFORM (`with_alpha == false`) or CXFORMWITHALPHA from the
+    /// current bit position.
+    pub fn read(reader: &mut BitReader, with_alpha: bool) -> Result<Self, Error> {
+        let has_add = try!(reader.read_flag());
+        let has_mult = try!(reader.read_flag());
+        let nbits = try!(reader.read_ub(4));
+        let channels = if with_alpha { 4 } else { 3 };
+
+        let mut transform = ColorTransform::identity();
+        if has_mult {
+            let mut mults = [1.0; 4];
+            for mult in mults.iter_mut().take(channels) {
+                *mult = try!(reader.read_sb(nbits)) as f32 / 256.0;
+            }
+            transform.r_mult = mults[0];
+            transform.g_mult = mults[1];
+            transform.b_mult = mults[2];
+            transform.a_mult = mults[3];
+        }
+        if has_add {
+            let mut adds = [0; 4];
+            for add in adds.iter_mut().take(channels) {
+                *add = try!(reader.read_sb(nbits)) as i16;
+            }
+            transform.r_add = adds[0];
+            transform.g_add = adds[1];
+            transform.b_add = adds[2];
+            transform.a_add = adds[3];
+        }
+        Ok(transform)
+    }
+
+    /// Writes a CXFORM or CXFORMWITHALPHA, leaving out the multiply or add
+    /// terms when they're the identity. Alpha terms are dropped when
+    /// `with_alpha` is false.
+    ///
+    /// The fields can be at most 15 bits, so adds are clamped to -16384 to
+    /// 16383 and multipliers to just under 64, which make no difference to
+    /// the 8-bit channels they're applied to.
+    pub fn write(&self, writer: &mut BitWriter, with_alpha: bool) {
+        let channels = if with_alpha { 4 } else { 3 };
+        let mults: Vec<i32> = [self.r_mult, self.g_mult, self.b_mult, self.a_mult].iter()
+            .take(channels).map(|&mult| clamp_signed((mult * 256.0).round() as i32, 15)).collect();
+        let adds: Vec<i32> = [self.r_add, self.g_add, self.b_add, self.a_add].iter()
+            .take(channels).map(|&add| clamp_signed(add as i32, 15)).collect();
+        let has_mult = mults.iter().any(|&mult| mult != 256);
+        let has_add = adds.iter().any(|&add| add != 0);
+
+        let mut values = Vec::new();
+        if has_mult {
+            values.extend_from_slice(&mults);
+        }
+        if has_add {
+            values.extend_from_slice(&adds);
+        }
+        let nbits = signed_bits_needed(&values);
+
+        writer.write_flag(has_add);
+        writer.write_flag(has_mult);
+        writer.write_ub(4, nbits);
+        for value in values {
+            writer.write_sb(nbits, value);
+        }
+    }
+
+    /// Applies the transform to a color.
+    pub fn apply(&self, color: Rgba) -> Rgba {
+        fn channel(value: u8, mult: f32, add: i16) -> u8 {
+            let result = value as f32 * mult + add as f32;
+            result.max(0.0).min(255.0) as u8
+        }
+        Rgba {
+            r: channel(color.r, self.r_mult, self.r_add),
+            g: channel(color.g, self.g_mult, self.g_add),
+            b: channel(color.b, self.b_mult, self.b_add),
+            a: channel(color.a, self.a_mult, self.a_add)
+        }
+    }
+}
+
+/// Reads a little-endian u16 at a byte offset into a slice.
+pub fn read_u16_at(data: &[u8], pos: usize) -> Result<u16, Error> {
+    match (data.get(pos), data.get(pos + 1)) {
+        (Some(&low), Some(&high)) => Ok((low as u16) | ((high as u16) << 8)),
+        _ => Err(Error::NotSwf)
+    }
+}
+
+/// Overwrites a little-endian u16 at a byte offset into a slice.
+pub fn write_u16_at(data: &mut [u8], pos: usize, value: u16) -> Result<(), Error> {
+    if data.len() < pos + 2 {
+        return Err(Error::NotSwf);
+    }
+    data[pos] = value as u8;
+    data[pos + 1] = (value >> 8) as u8;
+    Ok(())
+}
+
+/// Reads a null-terminated STRING.
+///
+/// The spec says these are UTF-8 from SWF 6 onwards and whatever the locale
+/// felt like before that, so invalid sequences are replaced rather than
+/// treated as errors.
+pub fn read_string<R: Read>(reader: &mut R) -> Result<String, Error> {
+    let mut bytes = Vec::new();
+    loop {
+        match try!(reader.read_u8()) {
+            0 => break,
+            byte => bytes.push(byte)
+        }
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Reads an EncodedU32, the variable-length integer used by newer tags.
+pub fn read_encoded_u32<R: Read>(reader: &mut R) -> Result<u32, Error> {
+    let mut value = 0u32;
+    for i in 0..5 {
+        let byte = try!(reader.read_u8());
+        value |= ((byte & 0x7f) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    Ok(value)
+}
+
+/// Writes an EncodedU32 in as few bytes as it fits in.
+pub fn write_encoded_u32<W: Write>(writer: &mut W, mut value: u32) -> Result<(), Error> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            try!(writer.write_all(&[byte]));
+            return Ok(());
+        }
+        try!(writer.write_all(&[byte | 0x80]));
+    }
+}
+
+/// Reads a little-endian FLOAT16, the half-precision float font alignment
+/// zones use.
+pub fn read_f16<R: Read>(reader: &mut R) -> Result<f32, Error> {
+    let bits = try!(reader.read_u16::<LittleEndian>());
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    let value = match exponent {
+        // Subnormal numbers, with no implicit leading one.
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => ::std::f32::INFINITY,
+        0x1f => ::std::f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15)
+    };
+    Ok(sign * value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bits_roundtrip() {
+        let mut writer = BitWriter::new();
+        writer.write_ub(5, 17);
+        writer.write_sb(7, -20);
+        writer.write_flag(true);
+        writer.write_ub(32, 0xdeadbeef);
+        let bytes = writer.into_bytes();
+
+        let mut reader = BitReader::new(&bytes);
+        assert_eq!(reader.read_ub(5).unwrap(), 17);
+        assert_eq!(reader.read_sb(7).unwrap(), -20);
+        assert_eq!(reader.read_flag().unwrap(), true);
+        assert_eq!(reader.read_ub(32).unwrap(), 0xdeadbeef);
+        assert!(reader.read_ub(8).is_err());
+    }
+
+    #[test]
+    fn test_read_f16() {
+        let read = |bytes: [u8; 2]| read_f16(&mut &bytes[..]).unwrap();
+        assert_eq!(read([0x00, 0x3c]), 1.0);
+        assert_eq!(read([0x00, 0xc0]), -2.0);
+        assert_eq!(read([0x00, 0x38]), 0.5);
+        assert_eq!(read([0x01, 0x00]), 2f32.powi(-24));
+        assert_eq!(read([0x00, 0x7c]), ::std::f32::INFINITY);
+    }
+
+    #[test]
+    fn test_signed_bits_needed() {
+        assert_eq!(signed_bits_needed(&[0]), 0);
+        assert_eq!(signed_bits_needed(&[1]), 2);
+        assert_eq!(signed_bits_needed(&[-1]), 1);
+        assert_eq!(signed_bits_needed(&[6000, 6000]), 14);
+    }
+
+    #[test]
+    fn test_encode_rect() {
+        let bytes = encode_rect(11000, 8000).unwrap();
+        let mut reader = BitReader::new(&bytes);
+        let nbits = reader.read_ub(5).unwrap();
+        assert_eq!(nbits, 15);
+        assert_eq!(reader.read_sb(nbits).unwrap(), 0);
+        assert_eq!(reader.read_sb(nbits).unwrap(), 11000);
+        assert_eq!(reader.read_sb(nbits).unwrap(), 0);
+        assert_eq!(reader.read_sb(nbits).unwrap(), 8000);
+        assert_eq!(bytes.len(), 9);
+
+        let largest = (1 << 30) - 1;
+        let bytes = encode_rect(largest, 20).unwrap();
+        let mut reader = BitReader::new(&bytes);
+        let nbits = reader.read_ub(5).unwrap();
+        assert_eq!(nbits, 31);
+        assert_eq!(reader.read_sb(nbits).unwrap(), 0);
+        assert_eq!(reader.read_sb(nbits).unwrap(), largest as i32);
+        assert!(encode_rect(1 << 30, 20).is_err());
+        assert!(encode_rect(20, u32::max_value()).is_err());
+    }
+
+    #[test]
+    fn test_read_matrix() {
+        // Scale of 2.0 in both directions, no rotation, translated (20, -20).
+        let mut writer = BitWriter::new();
+        writer.write_flag(true);
+        writer.write_ub(5, 19);
+        writer.write_sb(19, 2 << 16);
+        writer.write_sb(19, 2 << 16);
+        writer.write_flag(false);
+        writer.write_ub(5, 6);
+        writer.write_sb(6, 20);
+        writer.write_sb(6, -20);
+        let bytes = writer.into_bytes();
+
+        let matrix = Matrix::read(&mut BitReader::new(&bytes)).unwrap();
+        assert_eq!(matrix.scale_x, 2.0);
+        assert_eq!(matrix.scale_y, 2.0);
+        assert_eq!(matrix.rotate_skew_0, 0.0);
+        assert_eq!(matrix.translate_x, 20);
+        assert_eq!(matrix.translate_y, -20);
+    }
+
+    #[test]
+    fn test_matrix_roundtrip() {
+        let matrix = Matrix {
+            scale_x: 0.5,
+            scale_y: -3.25,
+            rotate_skew_0: 0.125,
+            rotate_skew_1: -0.125,
+            translate_x: -4000,
+            translate_y: 17
+        };
+        let mut writer = BitWriter::new();
+        matrix.write(&mut writer);
+        let bytes = writer.into_bytes();
+        assert_eq!(Matrix::read(&mut BitReader::new(&bytes)).unwrap(), matrix);
+
+        let mut writer = BitWriter::new();
+        Matrix::identity().write(&mut writer);
+        assert_eq!(writer.into_bytes(), vec![0]);
+    }
+
+    #[test]
+    fn test_color_transform_roundtrip() {
+        let transform = ColorTransform {
+            r_mult: 0.5,
+            g_mult: 1.0,
+            b_mult: 2.0,
+            a_mult: 0.25,
+            r_add: -255,
+            g_add: 0,
+            b_add: 10,
+            a_add: 0
+        };
+        let mut writer = BitWriter::new();
+        transform.write(&mut writer, true);
+        let bytes = writer.into_bytes();
+        assert_eq!(ColorTransform::read(&mut BitReader::new(&bytes), true).unwrap(), transform);
+
+        // Without alpha, the alpha terms don't survive.
+        let mut writer = BitWriter::new();
+        transform.write(&mut writer, false);
+        let bytes = writer.into_bytes();
+        let read = ColorTransform::read(&mut BitReader::new(&bytes), false).unwrap();
+        assert_eq!(read.a_mult, 1.0);
+        assert_eq!(read.b_mult, 2.0);
+
+        let color = Rgba { r: 200, g: 100, b: 100, a: 255 };
+        assert_eq!(transform.apply(color), Rgba { r: 0, g: 100, b: 210, a: 63 });
+    }
+
+    #[test]
+    fn test_write_clamps_fields() {
+        // An add of 32767 needs 16 bits, one more than a CXFORM has.
+        let transform = ColorTransform {
+            r_add: 32767,
+            g_add: -32768,
+            b_add: 16383,
+            r_mult: 100.0,
+            ..ColorTransform::identity()
+        };
+        let mut writer = BitWriter::new();
+        transform.write(&mut writer, false);
+        let bytes = writer.into_bytes();
+        assert_eq!(BitReader::new(&bytes).read_ub(6).unwrap() & 0x0f, 15);
+        let read = ColorTransform::read(&mut BitReader::new(&bytes), false).unwrap();
+        assert_eq!((read.r_add, read.g_add, read.b_add), (16383, -16384, 16383));
+        assert_eq!(read.r_mult, 16383.0 / 256.0);
+        assert_eq!(transform.apply(Rgba { r: 1, g: 255, b: 0, a: 255 }), read.apply(Rgba { r: 1, g: 255, b: 0, a: 255 }));
+
+        let matrix = Matrix {
+            scale_x: 20000.0,
+            translate_x: i32::max_value(),
+            translate_y: -(1 << 30),
+            ..Matrix::identity()
+        };
+        let mut writer = BitWriter::new();
+        matrix.write(&mut writer);
+        let bytes = writer.into_bytes();
+        let read = Matrix::read(&mut BitReader::new(&bytes)).unwrap();
+        assert_eq!(read.scale_x, ((1 << 30) - 1) as f64 / 65536.0);
+        assert_eq!(read.scale_y, 1.0);
+        assert_eq!((read.translate_x, read.translate_y), ((1 << 30) - 1, -(1 << 30)));
+    }
+
+    #[test]
+    fn test_encoded_u32() {
+        let bytes = [0xe5, 0x8e, 0x26];
+        assert_eq!(read_encoded_u32(&mut &bytes[..]).unwrap(), 624485);
+        let mut written = Vec::new();
+        write_encoded_u32(&mut written, 624485).unwrap();
+        assert_eq!(written, bytes);
+    }
+}
@@ -0,0 +1,248 @@
+//! Reading the headers bit by bit as a file arrives, for upload UIs that
+//! want to show what they can over a slow connection.
+//!
+//! Feed `HeaderProgress` bytes as they come in, and it says how far
+//! through the headers it's got. Each field can be read as soon as its
+//! stage is reached, before the rest have arrived.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use swf_headers::progressive::{HeaderProgress, HeaderStage};
+//! let mut progress = HeaderProgress::new();
+//! assert_eq!(progress.feed(b"FWS\x0a").unwrap(), HeaderStage::Version);
+//! assert_eq!(progress.version(), Some(10));
+//! assert_eq!(progress.feed(&[20, 0, 0, 0, 0x50, 0x00, 0x1e, 0x00, 0x01, 0xe0]).unwrap(),
+//!            HeaderStage::Dimensions);
+//! assert_eq!(progress.dimensions(), Some((3, 3)));
+//! assert_eq!(progress.feed(&[0x00, 24, 1, 0]).unwrap(), HeaderStage::Complete);
+//! assert_eq!(progress.headers().unwrap().frame_count(), 1);
+//! ```
+
+use std::io::Cursor;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use decoded_swf::{DecodedSwf, ReadStatus};
+use error::Error;
+use super::{Signature, SwfHeaders};
+
+/// How much of a compressed file is buffered while waiting for its headers
+/// to decompress. Real compressors get the headers out well before this.
+const MAX_BUFFERED: usize = 64 * 1024;
+
+/// How far through the headers a `HeaderProgress` has got, in the order
+/// the fields arrive. Each stage includes the ones before it.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum HeaderStage {
+    /// Nothing useful yet.
+    Nothing,
+    /// The signature, and so the compression.
+    Signature,
+    /// The SWF version.
+    Version,
+    /// The file length.
+    FileLength,
+    /// The stage dimensions.
+    Dimensions,
+    /// The frame rate.
+    FrameRate,
+    /// Everything, ending with the frame count.
+    Complete
+}
+
+/// Headers read as far as the bytes fed so far allow.
+#[derive(Clone, Debug)]
+pub struct HeaderProgress {
+    // The file so far, until the headers are complete, then just its first
+    // 8 bytes.
+    raw: Vec<u8>,
+    // The headers decoded so far, with everything not yet known as 0.
+    headers: Option<SwfHeaders>,
+    stage: HeaderStage
+}
+
+impl HeaderProgress {
+    /// Starts with nothing known.
+    pub fn new() -> Self {
+        HeaderProgress {
+            raw: Vec::new(),
+            headers: None,
+            stage: HeaderStage::Nothing
+        }
+    }
+
+    /// Takes the next bytes of the file, returning how far through the
+    /// headers that gets. Anything after the headers is ignored.
+    ///
+    /// Returns `Error::NotSwf` once the bytes can't be the start of a SWF
+    /// file, and goes on doing so.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<HeaderStage, Error> {
+        if self.stage == HeaderStage::Complete {
+            return Ok(self.stage);
+        }
+        self.raw.extend_from_slice(bytes);
+        let signature = match self.raw.first() {
+            Some(&b'F') => Signature::Uncompressed,
+            Some(&b'C') => Signature::ZlibCompressed,
+            Some(&b'Z') => Signature::LzmaCompressed,
+            Some(_) => return Err(Error::NotSwf),
+            None => return Ok(self.stage)
+        };
+        if self.raw.get(1).map_or(false, |&byte| byte != 0x57) ||
+           self.raw.get(2).map_or(false, |&byte| byte != 0x53) {
+            return Err(Error::NotSwf);
+        }
+
+        // The headers as far as they go, decompressed after the 8th byte.
+        let mut prefix = [0; 64];
+        let mut known = self.raw.len().min(8);
+        prefix[..known].copy_from_slice(&self.raw[..known]);
+        if self.raw.len() > 8 {
+            if signature == Signature::Uncompressed {
+                let end = self.raw.len().min(prefix.len());
+                prefix[8..end].copy_from_slice(&self.raw[8..end]);
+                known = end;
+            } else {
+                known += try!(self.decompress(signature, &mut prefix[8..37]));
+            }
+        }
+
+        let rect_len = if known > 8 { SwfHeaders::prefix_len(prefix[8]) - 12 } else { 0 };
+        self.stage = match known {
+            0 => HeaderStage::Nothing,
+            1..=3 => HeaderStage::Signature,
+            4..=7 => HeaderStage::Version,
+            _ if rect_len == 0 || known < 8 + rect_len => HeaderStage::FileLength,
+            _ if known < 8 + rect_len + 2 => HeaderStage::Dimensions,
+            _ if known < 8 + rect_len + 4 => HeaderStage::FrameRate,
+            _ => HeaderStage::Complete
+        };
+        self.headers = if self.stage >= HeaderStage::Dimensions {
+            Some(try!(SwfHeaders::parse_prefix(&prefix)).0)
+        } else {
+            None
+        };
+        if self.stage == HeaderStage::Complete {
+            self.raw.truncate(8);
+        } else if self.raw.len() > MAX_BUFFERED {
+            return Err(Error::NotSwf);
+        }
+        Ok(self.stage)
+    }
+
+    /// Decompresses what's been fed after the 8th byte into `out`,
+    /// returning how much of it was filled. A stream that's cut short fills
+    /// what it can.
+    fn decompress(&self, signature: Signature, out: &mut [u8]) -> Result<usize, Error> {
+        let body = Cursor::new(self.raw[8..].to_vec());
+        let filled = match DecodedSwf::decompress(body, signature)
+            .and_then(|mut body| body.read_exact_or_eof(out)) {
+            Ok(ReadStatus::Complete) => out.len(),
+            Ok(ReadStatus::Truncated(filled)) => filled,
+            // Too little to even start decompressing.
+            Ok(ReadStatus::Eof) | Err(Error::IoError(_)) => 0,
+            Err(err) => return Err(err)
+        };
+        Ok(filled)
+    }
+
+    /// Returns how far through the headers it's got.
+    pub fn stage(&self) -> HeaderStage {
+        self.stage
+    }
+
+    /// Returns the signature, once known.
+    pub fn signature(&self) -> Option<Signature> {
+        match self.raw.first() {
+            Some(&b'F') => Some(Signature::Uncompressed),
+            Some(&b'C') => Some(Signature::ZlibCompressed),
+            Some(&b'Z') => Some(Signature::LzmaCompressed),
+            _ => None
+        }
+    }
+
+    /// Returns the SWF version, once known.
+    pub fn version(&self) -> Option<u8> {
+        match self.headers {
+            Some(headers) => Some(headers.version()),
+            None if self.stage >= HeaderStage::Version => Some(self.raw[3]),
+            None => None
+        }
+    }
+
+    /// Returns the file length, once known.
+    pub fn file_length(&self) -> Option<u32> {
+        match self.headers {
+            Some(headers) => Some(headers.file_length()),
+            None if self.stage >= HeaderStage::FileLength => (&self.raw[4..8]).read_u32::<LittleEndian>().ok(),
+            None => None
+        }
+    }
+
+    /// Returns the stage dimensions in pixels, once known.
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        self.headers.map(|headers| headers.dimensions())
+    }
+
+    /// Returns the frame rate, once known.
+    pub fn frame_rate(&self) -> Option<u16> {
+        self.headers.and_then(|headers| {
+            if self.stage >= HeaderStage::FrameRate { Some(headers.frame_rate()) } else { None }
+        })
+    }
+
+    /// Returns the complete headers, once everything's known.
+    pub fn headers(&self) -> Option<SwfHeaders> {
+        if self.stage == HeaderStage::Complete { self.headers } else { None }
+    }
+}
+
+impl Default for HeaderProgress {
+    fn default() -> Self {
+        HeaderProgress::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::{LittleEndian, ReadBytesExt};
+    use tag::{codes, Tag};
+    use writer;
+    use {Signature, SwfHeaders};
+
+    #[test]
+    fn test_byte_by_byte() {
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 11000,
+            height: 8000,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 7
+        };
+        let file = writer::to_bytes(&headers, &[Tag::new(codes::SHOW_FRAME, vec![])]).unwrap();
+        let mut progress = HeaderProgress::new();
+        let mut last = HeaderStage::Nothing;
+        for (i, byte) in file.iter().enumerate() {
+            let stage = progress.feed(&[*byte]).unwrap();
+            assert!(stage >= last);
+            match i {
+                0 => assert_eq!(stage, HeaderStage::Signature),
+                3 => assert_eq!(progress.version(), Some(10)),
+                _ => {}
+            }
+            last = stage;
+        }
+        assert_eq!(last, HeaderStage::Complete);
+        assert_eq!(progress.signature(), Some(Signature::ZlibCompressed));
+        assert_eq!(progress.file_length(), Some((&file[4..8]).read_u32::<LittleEndian>().unwrap()));
+        let parsed = progress.headers().unwrap();
+        assert_eq!((parsed.dimensions(), parsed.frame_rate(), parsed.frame_count()), ((550, 400), 24, 7));
+
+        assert!(HeaderProgress::new().feed(b"FWX").is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! Guessing the stage size of files whose stage RECT has rotted.
+//!
+//! A RECT starts with 5 bits saying how wide its four fields are, so a
+//! single flipped bit there throws off the size, the frame rate and count
+//! after it, and where the tags start. `recover_stage()` tries every width
+//! and keeps whichever reading makes the most sense of the bytes around
+//! it. It's a guess, and says so.
+
+use error::Error;
+use primitives::{BitReader, Rect};
+use tag_codes;
+use super::SwfHeaders;
+
+/// The largest stage side, in twips, that counts as plausible: 8192
+/// pixels, well past what authoring tools allow.
+const MAX_STAGE_TWIPS: i32 = 8192 * 20;
+
+/// A reading of the stage RECT and the fields after it.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct StageGuess {
+    /// The RECT, in twips.
+    pub rect: Rect,
+    /// The field width the RECT was read with.
+    pub nbits: u32,
+    /// How many bytes of the body the RECT, frame rate and frame count take
+    /// up, which is where the first tag starts.
+    pub header_len: usize,
+    /// The frame rate, as the 8.8 fixed-point value it's stored as.
+    pub frame_rate: u16,
+    /// The frame count.
+    pub frame_count: u16,
+    /// True if `nbits` isn't what the file says, so the RECT is a guess.
+    pub guessed: bool
+}
+
+/// Returns true if a RECT looks like a real stage: starting at the origin,
+/// and neither empty nor absurdly big.
+pub fn is_plausible(rect: &Rect) -> bool {
+    let side = |len: i32| len > 0 && len <= MAX_STAGE_TWIPS;
+    rect.x_min == 0 && rect.y_min == 0 && side(rect.width()) && side(rect.height())
+}
+
+/// Reads the stage RECT at the start of `body`, the decompressed bytes after
+/// the 8-byte file header, trying other field widths if it doesn't look
+/// like a real stage.
+///
+/// Every width is tried, and each reading that gives a plausible stage is
+/// scored on whether it's in whole pixels, whether the frame rate and count
+/// are sensible, and whether a known tag follows. The RECT as stored wins
+/// ties, so intact files come back as they are. Returns `None` if no width
+/// gives a plausible stage.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::recover;
+/// // A 550x400 stage whose field width has flipped from 15 to 13.
+/// let body = [0x68, 0x00, 0x05, 0x5f, 0x00, 0x00, 0x0f, 0xa0, 0x00, 0x00, 0x18, 0x01, 0x00, 0x43, 0x02];
+/// let guess = recover::recover_stage(&body).unwrap().unwrap();
+/// assert_eq!((guess.rect.x_max, guess.rect.y_max, guess.guessed), (11000, 8000, true));
+/// ```
+pub fn recover_stage(body: &[u8]) -> Result<Option<StageGuess>, Error> {
+    let stored_nbits = match body.first() {
+        Some(&byte) => (byte >> 3) as u32,
+        None => return Err(Error::NotSwf)
+    };
+    // The stored width goes first, so it wins ties.
+    let mut best: Option<(u32, StageGuess)> = None;
+    for nbits in Some(stored_nbits).into_iter().chain(1..32) {
+        let guess = match read_stage(body, nbits, stored_nbits) {
+            Some(ref guess) if is_plausible(&guess.rect) => *guess,
+            _ => continue
+        };
+        let score = score(body, &guess);
+        if best.map_or(true, |(best_score, _)| score > best_score) {
+            best = Some((score, guess));
+        }
+    }
+    Ok(best.map(|(_, guess)| guess))
+}
+
+/// Reads the RECT with `nbits`-wide fields, whatever the first 5 bits say,
+/// along with the frame rate and count after it.
+fn read_stage(body: &[u8], nbits: u32, stored_nbits: u32) -> Option<StageGuess> {
+    let mut reader = BitReader::new(body);
+    reader.read_ub(5).ok();
+    let mut fields = [0; 4];
+    for field in &mut fields {
+        *field = match reader.read_sb(nbits) {
+            Ok(value) => value,
+            Err(_) => return None
+        };
+    }
+    let header_len = SwfHeaders::prefix_len((nbits << 3) as u8) - 8;
+    let after = match body.get(header_len - 4..header_len) {
+        Some(after) => after,
+        None => return None
+    };
+    Some(StageGuess {
+        rect: Rect { x_min: fields[0], x_max: fields[1], y_min: fields[2], y_max: fields[3] },
+        nbits: nbits,
+        header_len: header_len,
+        frame_rate: after[0] as u16 | (after[1] as u16) << 8,
+        frame_count: after[2] as u16 | (after[3] as u16) << 8,
+        guessed: nbits != stored_nbits
+    })
+}
+
+/// Scores how much sense a plausible reading makes of the bytes around it.
+fn score(body: &[u8], guess: &StageGuess) -> u32 {
+    let mut score = 0;
+    // Authoring tools size stages in whole pixels.
+    if guess.rect.x_max % 20 == 0 && guess.rect.y_max % 20 == 0 {
+        score += 1;
+    }
+    let fps = guess.frame_rate >> 8;
+    if fps >= 1 && fps <= 120 {
+        score += 1;
+    }
+    if guess.frame_count >= 1 {
+        score += 1;
+    }
+    // The first tag is the best evidence the header ended where it should.
+    if let (Some(&low), Some(&high)) = (body.get(guess.header_len), body.get(guess.header_len + 1)) {
+        let code = (low as u16 | (high as u16) << 8) >> 6;
+        if tag_codes::lookup(code).is_some() {
+            score += 2;
+        }
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primitives::Rect;
+    use tag::{codes, Tag};
+    use writer;
+    use {Signature, SwfHeaders};
+
+    #[test]
+    fn test_recover_stage() {
+        let headers = SwfHeaders {
+            signature: Signature::Uncompressed,
+            version: 10,
+            file_length: 0,
+            width: 11000,
+            height: 8000,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = [Tag::new(codes::SET_BACKGROUND_COLOR, vec![0, 0, 0]), Tag::new(codes::SHOW_FRAME, vec![])];
+        let file = writer::to_bytes(&headers, &tags).unwrap();
+        let intact = recover_stage(&file[8..]).unwrap().unwrap();
+        assert!(!intact.guessed);
+        assert_eq!(intact.rect, Rect { x_min: 0, x_max: 11000, y_min: 0, y_max: 8000 });
+        assert_eq!((intact.frame_rate, intact.frame_count), (24 << 8, 1));
+
+        // Every single-bit flip in the field width is recovered from.
+        for bit in 3..8 {
+            let mut body = file[8..].to_vec();
+            body[0] ^= 1 << bit;
+            let guess = recover_stage(&body).unwrap().unwrap();
+            assert!(guess.guessed);
+            assert_eq!((guess.rect, guess.header_len, guess.nbits), (intact.rect, intact.header_len, intact.nbits));
+        }
+
+        assert!(!is_plausible(&Rect { x_min: 0, x_max: 0, y_min: 0, y_max: 8000 }));
+        assert_eq!(recover_stage(&[0x00]).unwrap(), None);
+    }
+}
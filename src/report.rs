@@ -0,0 +1,211 @@
+//! Writing scan results as NDJSON, one JSON object per line.
+//!
+//! This is the format `swfinfo --format ndjson` prints, so services doing
+//! their own scanning can hand out exactly the same records as the command
+//! line tool.
+
+use std::io::Write;
+use std::path::Path;
+
+use error::Error;
+use validate::Finding;
+use super::{Signature, SwfHeaders};
+
+/// Identifies the layout of everything this crate serializes: the JSON
+/// records here, manifests, and the CLI's CSV. It goes up whenever a field
+/// is renamed, removed or changes meaning, but not when one is added, so
+/// archives can tell whether old output needs converting.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A single line of a report.
+#[derive(Debug)]
+pub enum Record {
+    /// The headers of a file that was read.
+    Headers {
+        /// The file, as it was named.
+        path: String,
+        /// What was read from it.
+        headers: SwfHeaders
+    },
+    /// A file that couldn't be read, and why.
+    Unreadable {
+        /// The file, as it was named.
+        path: String,
+        /// What went wrong.
+        error: Error
+    },
+    /// Something `validate` found wrong with a file.
+    Finding {
+        /// The file, as it was named.
+        path: String,
+        /// What was found.
+        finding: Finding
+    }
+}
+
+impl Record {
+    /// Reads the headers of a file, giving either a `Headers` or an
+    /// `Unreadable` record.
+    pub fn scan<P: AsRef<Path>>(path: P) -> Self {
+        let path_string = path.as_ref().to_string_lossy().into_owned();
+        match SwfHeaders::open(path) {
+            Ok((headers, _)) => Record::Headers {
+                path: path_string,
+                headers: headers
+            },
+            Err(err) => Record::Unreadable {
+                path: path_string,
+                error: err
+            }
+        }
+    }
+
+    /// Returns the path of the file the record is about.
+    pub fn path(&self) -> &str {
+        match *self {
+            Record::Headers { ref path, .. } |
+            Record::Unreadable { ref path, .. } |
+            Record::Finding { ref path, .. } => path
+        }
+    }
+
+    /// Returns true for `Unreadable` records.
+    pub fn is_unreadable(&self) -> bool {
+        match *self {
+            Record::Unreadable { .. } => true,
+            _ => false
+        }
+    }
+
+    /// Encodes the record as a single-line JSON object.
+    ///
+    /// Every object has a `schema` field holding `SCHEMA_VERSION`, and a
+    /// `file` field. Headers add `version`,
+    /// `compression` (`"none"`, `"zlib"` or `"lzma"`), `length`, `width`
+    /// and `height` in pixels, `fps` and `frames`. Unreadable files add
+    /// `error`, and findings add `severity`, `code`, `offset` (possibly
+    /// `null`) and `message`.
+    pub fn to_json(&self) -> String {
+        let mut fields = vec![
+            format!("\"schema\": {}", SCHEMA_VERSION),
+            format!("\"file\": {}", json_string(self.path()))
+        ];
+        match *self {
+            Record::Headers { ref headers, .. } => {
+                let compression = match headers.signature() {
+                    Signature::Uncompressed => "none",
+                    Signature::ZlibCompressed => "zlib",
+                    Signature::LzmaCompressed => "lzma"
+                };
+                let (width, height) = headers.dimensions();
+                fields.push(format!("\"version\": {}", headers.version()));
+                fields.push(format!("\"compression\": \"{}\"", compression));
+                fields.push(format!("\"length\": {}", headers.file_length()));
+                fields.push(format!("\"width\": {}", width));
+                fields.push(format!("\"height\": {}", height));
+                fields.push(format!("\"fps\": {}", headers.nominal_frame_rate()));
+                fields.push(format!("\"frames\": {}", headers.frame_count()));
+            },
+            Record::Unreadable { ref error, .. } => {
+                fields.push(format!("\"error\": {}", json_string(&format!("{:?}", error))));
+            },
+            Record::Finding { ref finding, .. } => {
+                let offset = finding.offset.map_or("null".to_string(), |offset| offset.to_string());
+                fields.push(format!("\"severity\": \"{}\"", finding.severity.as_str()));
+                fields.push(format!("\"code\": \"{}\"", finding.code));
+                fields.push(format!("\"offset\": {}", offset));
+                fields.push(format!("\"message\": {}", json_string(&finding.message)));
+            }
+        }
+        format!("{{{}}}", fields.join(", "))
+    }
+}
+
+/// Writes each record as a line of JSON, returning how many were written.
+///
+/// The writer is flushed after every line, so whatever is reading the
+/// other end sees records as soon as they're ready, even when the iterator
+/// takes its time producing them.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::report::{self, Record};
+/// let paths = ["intro.swf", "game.swf"];
+/// let mut out = Vec::new();
+/// report::write_ndjson(paths.iter().map(|path| Record::scan(path)), &mut out).unwrap();
+/// assert_eq!(String::from_utf8(out).unwrap().lines().count(), 2);
+/// ```
+pub fn write_ndjson<I, W>(records: I, mut writer: W) -> Result<u64, Error>
+    where I: IntoIterator<Item = Record>, W: Write
+{
+    let mut count = 0;
+    for record in records {
+        try!(writeln!(writer, "{}", record.to_json()));
+        try!(writer.flush());
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Quotes and escapes a string for JSON.
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use {Error, Signature, SwfHeaders};
+    use validate::{Finding, Severity};
+
+    #[test]
+    fn test_write_ndjson() {
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 9,
+            file_length: 1234,
+            width: 11000,
+            height: 8000,
+            frame_rate: 0,
+            frame_rate_fraction: 0,
+            frame_count: 3
+        };
+        let records = vec![
+            Record::Headers { path: "a.swf".to_string(), headers: headers },
+            Record::Unreadable { path: "b \"quoted\".swf".to_string(), error: Error::NotSwf },
+            Record::Finding {
+                path: "a.swf".to_string(),
+                finding: Finding {
+                    severity: Severity::Warning,
+                    code: "stage-origin",
+                    message: "off\tcentre".to_string(),
+                    offset: None
+                }
+            }
+        ];
+        let mut out = Vec::new();
+        assert_eq!(write_ndjson(records, &mut out).unwrap(), 3);
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+{\"schema\": 1, \"file\": \"a.swf\", \"version\": 9, \"compression\": \"zlib\", \"length\": 1234, \"width\": 550, \
+\"height\": 400, \"fps\": 12, \"frames\": 3}
+{\"schema\": 1, \"file\": \"b \\\"quoted\\\".swf\", \"error\": \"NotSwf\"}
+{\"schema\": 1, \"file\": \"a.swf\", \"severity\": \"warning\", \"code\": \"stage-origin\", \"offset\": null, \
+\"message\": \"off\\tcentre\"}
+");
+    }
+}
@@ -0,0 +1,276 @@
+//! Checking that a file survives being read and written back out.
+//!
+//! Anything rewriting SWFs on top of this crate relies on the reader and
+//! writer agreeing about every byte they don't mean to change. `verify()`
+//! reads a file, writes it straight back out with `writer`, and reports
+//! everywhere the two differ, region by region.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use decoded_swf::DecodedSwf;
+use error::Error;
+use primitives::{BitReader, Rect};
+use tag::{self, codes, Tag};
+use writer;
+use super::{Signature, SwfHeaders};
+
+/// A part of the decompressed file.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Region {
+    /// The version and file length in the first 8 bytes. The signature is
+    /// left out, since it only says how the rest was compressed.
+    FileHeader,
+    /// The stage RECT, frame rate and frame count.
+    MovieHeader,
+    /// A tag, header and all, by its position among the top-level tags (0
+    /// for the first) and its code. The End tag counts.
+    Tag {
+        /// The position of the tag.
+        index: usize,
+        /// The tag code.
+        code: u16
+    },
+    /// Anything after the End tag.
+    Trailing
+}
+
+/// Where a region sits in a file.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Span {
+    /// The offset of the first byte, in the decompressed file.
+    pub offset: u64,
+    /// The length in bytes.
+    pub len: u64
+}
+
+/// One region that didn't come through unchanged.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Divergence {
+    /// The region in question.
+    pub region: Region,
+    /// Where it is in the original, or `None` if only the rewrite has it.
+    pub original: Option<Span>,
+    /// Where it is in the rewrite, or `None` if it was dropped.
+    pub rewritten: Option<Span>,
+    /// How far into the region the first differing byte is. 0 if the
+    /// region is missing from either side.
+    pub first_difference: u64
+}
+
+/// Everything `verify()` found.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Report {
+    /// The decompressed length of the original.
+    pub original_len: u64,
+    /// The decompressed length of the rewrite.
+    pub rewritten_len: u64,
+    /// The regions that differ, in the order they appear in the original,
+    /// followed by any only the rewrite has.
+    pub divergences: Vec<Divergence>
+}
+
+impl Report {
+    /// Returns true if the rewrite matched the original byte for byte,
+    /// compression aside.
+    pub fn is_exact(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// Reads a file, writes it back out, and compares the two.
+///
+/// Both are compared decompressed, so a file only has to come back the
+/// same modulo compression. The usual reasons it doesn't are a stage RECT
+/// with more bits than it needs, a missing End tag, and junk after it. Tags come back exactly as they were, header
+/// form included, whether or not this crate knows what they are.
+///
+/// Returns an error if the file can't be read as a SWF in the first place.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::roundtrip;
+/// if let Ok(report) = roundtrip::verify("example.swf") {
+///     for divergence in &report.divergences {
+///         println!("{:?} changed", divergence.region);
+///     }
+/// }
+/// ```
+pub fn verify<P: AsRef<Path>>(path: P) -> Result<Report, Error> {
+    verify_from(try!(File::open(path)))
+}
+
+/// Like `verify()`, but reads the file from any stream.
+pub fn verify_from<R: Read>(mut reader: R) -> Result<Report, Error> {
+    let mut raw = Vec::new();
+    try!(reader.read_to_end(&mut raw));
+    if raw.len() < 8 {
+        return Err(Error::NotSwf);
+    }
+    let signature = match raw[0] {
+        b'F' => Signature::Uncompressed,
+        b'C' => Signature::ZlibCompressed,
+        b'Z' => Signature::LzmaCompressed,
+        _ => return Err(Error::NotSwf)
+    };
+
+    // Everything is compared uncompressed, so the original is turned into
+    // an FWS file to match what gets written back out.
+    let mut original = raw[..8].to_vec();
+    original[0] = b'F';
+    let mut decoded = try!(DecodedSwf::decompress(Cursor::new(raw[8..].to_vec()), signature));
+    try!(decoded.read_to_end(&mut original));
+
+    let (headers, body) = try!(SwfHeaders::read_from(Cursor::new(original.clone())));
+    let tags = try!(tag::read_all(body));
+    let rewritten = try!(writer::to_bytes(&headers, &tags));
+
+    let original_regions = try!(regions(&original));
+    let rewritten_regions = try!(regions(&rewritten));
+    let rewritten_spans: HashMap<Region, Span> = rewritten_regions.iter().cloned().collect();
+
+    let mut divergences = Vec::new();
+    for &(region, span) in &original_regions {
+        match rewritten_spans.get(&region) {
+            Some(&other) => {
+                let a = &original[span.offset as usize..(span.offset + span.len) as usize];
+                let b = &rewritten[other.offset as usize..(other.offset + other.len) as usize];
+                if let Some(first) = first_difference(a, b) {
+                    divergences.push(Divergence {
+                        region: region,
+                        original: Some(span),
+                        rewritten: Some(other),
+                        first_difference: first as u64
+                    });
+                }
+            },
+            None => divergences.push(Divergence {
+                region: region,
+                original: Some(span),
+                rewritten: None,
+                first_difference: 0
+            })
+        }
+    }
+    let original_spans: HashMap<Region, Span> = original_regions.iter().cloned().collect();
+    for &(region, span) in &rewritten_regions {
+        if !original_spans.contains_key(&region) {
+            divergences.push(Divergence {
+                region: region,
+                original: None,
+                rewritten: Some(span),
+                first_difference: 0
+            });
+        }
+    }
+
+    Ok(Report {
+        original_len: original.len() as u64,
+        rewritten_len: rewritten.len() as u64,
+        divergences: divergences
+    })
+}
+
+/// Splits an uncompressed file into its regions.
+fn regions(bytes: &[u8]) -> Result<Vec<(Region, Span)>, Error> {
+    let mut regions = vec![(Region::FileHeader, Span { offset: 0, len: 8 })];
+    let mut bits = BitReader::new(&bytes[8..]);
+    try!(Rect::read(&mut bits));
+    let header_end = 8 + bits.byte_pos() + 4;
+    if header_end > bytes.len() {
+        return Err(Error::NotSwf);
+    }
+    regions.push((Region::MovieHeader, Span { offset: 8, len: header_end as u64 - 8 }));
+
+    let mut reader = &bytes[header_end..];
+    let mut index = 0;
+    loop {
+        let start = bytes.len() - reader.len();
+        let tag = match try!(Tag::read_from(&mut reader)) {
+            Some(tag) => tag,
+            None => break
+        };
+        let len = bytes.len() - reader.len() - start;
+        regions.push((Region::Tag { index: index, code: tag.code() }, Span { offset: start as u64, len: len as u64 }));
+        index += 1;
+        if tag.code() == codes::END {
+            break;
+        }
+    }
+    if !reader.is_empty() {
+        let offset = bytes.len() - reader.len();
+        regions.push((Region::Trailing, Span { offset: offset as u64, len: reader.len() as u64 }));
+    }
+    Ok(regions)
+}
+
+fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    match a.iter().zip(b).position(|(x, y)| x != y) {
+        Some(pos) => Some(pos),
+        None if a.len() != b.len() => Some(a.len().min(b.len())),
+        None => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use {Signature, SwfHeaders};
+    use tag::{codes, Tag};
+    use writer;
+
+    fn movie(signature: Signature) -> Vec<u8> {
+        let headers = SwfHeaders {
+            signature: signature,
+            version: 10,
+            file_length: 0,
+            width: 11000,
+            height: 8000,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = vec![Tag::new(codes::SET_BACKGROUND_COLOR, vec![255, 255, 255]), Tag::new(codes::SHOW_FRAME, vec![])];
+        writer::to_bytes(&headers, &tags).unwrap()
+    }
+
+    #[test]
+    fn test_exact() {
+        for &signature in &[Signature::Uncompressed, Signature::ZlibCompressed] {
+            let report = verify_from(Cursor::new(movie(signature))).unwrap();
+            assert!(report.is_exact());
+            assert_eq!(report.original_len, report.rewritten_len);
+        }
+    }
+
+    #[test]
+    fn test_divergences() {
+        let original = movie(Signature::Uncompressed);
+        // The same file, but with half a frame on top of the frame rate,
+        // SetBackgroundColor in a long header, and some junk after the End
+        // tag. The fraction and the long header are kept, so only the junk
+        // and the file length it adds count.
+        let header_end = original.len() - 9;
+        let mut file = original[..header_end].to_vec();
+        file[header_end - 4] = 0x80;
+        file.extend_from_slice(&[0x7f, 0x02, 3, 0, 0, 0, 255, 255, 255]);
+        file.extend_from_slice(&original[original.len() - 4..]);
+        file.extend_from_slice(b"junk");
+        let file_length = file.len() as u32;
+        file[4] = file_length as u8;
+
+        let report = verify_from(Cursor::new(file)).unwrap();
+        assert!(!report.is_exact());
+        assert_eq!(report.original_len, report.rewritten_len + 4);
+        let regions: Vec<Region> = report.divergences.iter().map(|divergence| divergence.region).collect();
+        assert_eq!(regions, vec![Region::FileHeader, Region::Trailing]);
+        assert_eq!(report.divergences[0].first_difference, 4);
+        assert_eq!(report.divergences[1].original, Some(Span { offset: header_end as u64 + 13, len: 4 }));
+        assert_eq!(report.divergences[1].rewritten, None);
+    }
+}
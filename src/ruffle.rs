@@ -0,0 +1,84 @@
+//! Conversions between this crate's header types and those of the `swf`
+//! crate, the parser behind the Ruffle emulator, so the two can be mixed
+//! without copying fields by hand.
+//!
+//! Needs the `ruffle` feature.
+//!
+//! The conversions lose what one side has and the other doesn't. `swf`
+//! keeps where the stage starts, while `SwfHeaders` keeps only the stage's
+//! size. `swf::Header` has no file length, so it's read from the
+//! `swf::HeaderExt` that `swf::decompress_swf()` returns alongside it.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! extern crate swf;
+//! extern crate swf_headers;
+//! use std::fs;
+//! use swf_headers::SwfHeaders;
+//! # fn main() {
+//! if let Ok(data) = fs::read("example.swf") {
+//!     let swf_buf = swf::decompress_swf(&data[..]).unwrap();
+//!     let headers = SwfHeaders::from(&swf_buf.header);
+//!     let header: swf::Header = headers.into();
+//! }
+//! # }
+//! ```
+
+use swf::{Compression, Fixed8, Header, HeaderExt, Rectangle, Twips};
+
+use super::{Signature, SwfHeaders};
+
+impl From<Signature> for Compression {
+    fn from(signature: Signature) -> Self {
+        match signature {
+            Signature::Uncompressed => Compression::None,
+            Signature::ZlibCompressed => Compression::Zlib,
+            Signature::LzmaCompressed => Compression::Lzma
+        }
+    }
+}
+
+impl From<Compression> for Signature {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::None => Signature::Uncompressed,
+            Compression::Zlib => Signature::ZlibCompressed,
+            Compression::Lzma => Signature::LzmaCompressed
+        }
+    }
+}
+
+impl From<SwfHeaders> for Header {
+    fn from(headers: SwfHeaders) -> Self {
+        Header {
+            compression: headers.signature.into(),
+            version: headers.version,
+            stage_size: Rectangle {
+                x_min: Twips::new(0),
+                x_max: Twips::new(headers.width as i32),
+                y_min: Twips::new(0),
+                y_max: Twips::new(headers.height as i32)
+            },
+            frame_rate: Fixed8::from_bits((headers.frame_rate << 8 | headers.frame_rate_fraction as u16) as i16),
+            num_frames: headers.frame_count
+        }
+    }
+}
+
+impl<'a> From<&'a HeaderExt> for SwfHeaders {
+    fn from(header: &'a HeaderExt) -> Self {
+        let stage = header.stage_size();
+        let extent = |min: Twips, max: Twips| (max.get() - min.get()).max(0) as u32;
+        SwfHeaders {
+            signature: header.compression().into(),
+            version: header.version(),
+            file_length: header.uncompressed_len() as u32,
+            width: extent(stage.x_min, stage.x_max),
+            height: extent(stage.y_min, stage.y_max),
+            frame_rate: (header.frame_rate().get() as u16) >> 8,
+            frame_rate_fraction: header.frame_rate().get() as u8,
+            frame_count: header.num_frames()
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Finding where each frame starts, so resumable and streaming players can
+//! start partway through a movie.
+//!
+//! A frame's tags run from just after the previous ShowFrame up to and
+//! including its own. Offsets are into the decompressed file, counting the
+//! 8-byte file header, so they line up with `roundtrip::Span`s and with
+//! what a decompressed copy of the file has on disk.
+//!
+//! Starting at a frame's offset gives its tags, but not the definitions and
+//! display list changes from the frames before it. `split::split_frames()`
+//! builds a standalone movie when that matters.
+
+use std::io::{self, Read};
+use std::path::Path;
+
+use decoded_swf;
+use error::Error;
+use tag::{self, codes};
+use super::SwfHeaders;
+
+/// The offsets where each frame of the main timeline starts.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FrameIndex {
+    offsets: Vec<u64>
+}
+
+impl FrameIndex {
+    /// Indexes a file.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use swf_headers::seek::FrameIndex;
+    /// if let Ok(index) = FrameIndex::open("example.swf") {
+    ///     println!("frame 100 starts at byte {:?}", index.offset_of_frame(100));
+    /// }
+    /// ```
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let (_, mut body) = try!(decoded_swf::open_body(path));
+        let mut rect_byte = [0; 1];
+        try!(body.read_exact(&mut rect_byte));
+        let header_len = SwfHeaders::prefix_len(rect_byte[0]);
+        try!(body.skip(header_len as u64 - 9));
+        FrameIndex::build(body, header_len as u64)
+    }
+
+    /// Indexes the tags in `body`, which should be positioned at the first
+    /// tag, `body_offset` bytes into the decompressed file.
+    ///
+    /// Payloads are skipped rather than read into memory, so this is cheap
+    /// even for long movies.
+    pub fn build<R: Read>(mut body: R, body_offset: u64) -> Result<Self, Error> {
+        let mut offsets = vec![body_offset];
+        let mut pos = body_offset;
+        loop {
+            let mut counted = CountingReader { inner: &mut body, read: 0 };
+            let (code, length) = match try!(tag::read_header(&mut counted)) {
+                Some(header) => header,
+                None => break
+            };
+            if code == codes::END {
+                break;
+            }
+            let skipped = try!(io::copy(&mut (&mut counted).take(length as u64), &mut io::sink()));
+            if skipped < length as u64 {
+                return Err(Error::NotSwf);
+            }
+            pos += counted.read;
+            if code == codes::SHOW_FRAME {
+                offsets.push(pos);
+            }
+        }
+        // Whatever follows the last ShowFrame never gets shown.
+        offsets.pop();
+        Ok(FrameIndex { offsets: offsets })
+    }
+
+    /// Returns the offset where the tags of zero-based frame `n` begin, or
+    /// `None` if the movie doesn't have that many frames.
+    pub fn offset_of_frame(&self, n: u16) -> Option<u64> {
+        self.offsets.get(n as usize).cloned()
+    }
+
+    /// Returns the offset of the frame playing `seconds` into the movie, at
+    /// the frame rate Flash Player would use. Times past the end give
+    /// `None`.
+    pub fn offset_at_time(&self, seconds: f64, headers: &SwfHeaders) -> Option<u64> {
+        if seconds < 0.0 {
+            return None;
+        }
+        let frame = (seconds * headers.nominal_frame_rate() as f64).floor();
+        if frame >= self.offsets.len() as f64 {
+            return None;
+        }
+        self.offsets.get(frame as usize).cloned()
+    }
+
+    /// Returns the number of frames found.
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Returns true if the movie has no complete frames at all.
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+}
+
+/// Counts what's read through it, so tag headers of either length can be
+/// measured.
+struct CountingReader<R> {
+    inner: R,
+    read: u64
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = try!(self.inner.read(buf));
+        self.read += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    use tag::{codes, Tag};
+    use writer;
+    use {Signature, SwfHeaders};
+
+    #[test]
+    fn test_frame_offsets() {
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 2,
+            frame_rate_fraction: 0,
+            frame_count: 2
+        };
+        let show = Tag::new(codes::SHOW_FRAME, vec![]);
+        // A long-header payload, then two frames, then a DoAction that's
+        // never shown.
+        let tags = vec![
+            Tag::new(codes::DEFINE_BINARY_DATA, vec![0; 100]),
+            show.clone(),
+            Tag::new(codes::SET_BACKGROUND_COLOR, vec![0, 0, 0]),
+            show,
+            Tag::new(codes::DO_ACTION, vec![0])
+        ];
+        let path = env::temp_dir().join("swf_headers_test_frame_offsets.swf");
+        File::create(&path).unwrap().write_all(&writer::to_bytes(&headers, &tags).unwrap()).unwrap();
+        let index = FrameIndex::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let uncompressed = SwfHeaders { signature: Signature::Uncompressed, ..headers };
+        let file = writer::to_bytes(&uncompressed, &tags).unwrap();
+        let first = SwfHeaders::prefix_len(file[8]) as u64;
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.offset_of_frame(0), Some(first));
+        assert_eq!(index.offset_of_frame(1), Some(first + 6 + 100 + 2));
+        assert_eq!(&file[first as usize + 108..first as usize + 110], &[0x43, 0x02]);
+        assert_eq!(index.offset_of_frame(2), None);
+        assert_eq!(index.offset_at_time(0.75, &headers), index.offset_of_frame(1));
+        assert_eq!(index.offset_at_time(1.0, &headers), None);
+    }
+}
@@ -0,0 +1,841 @@
+//! Parsing DefineShape through DefineShape4: bounds, fill and line styles,
+//! and the edge records that make up the outlines. Morph shapes only have
+//! their headers parsed, though `bitmap_id_offsets()` finds the bitmap
+//! fills in both kinds.
+
+use error::Error;
+use primitives::{BitReader, Matrix, Rect, Rgba};
+use tag::{codes, Tag};
+
+/// A parsed DefineShape, DefineShape2, DefineShape3 or DefineShape4 tag.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Shape {
+    /// The character ID.
+    pub id: u16,
+    /// Which DefineShape this came from, from 1 to 4.
+    pub version: u8,
+    /// The bounds of the shape, including strokes.
+    pub bounds: Rect,
+    /// The bounds of the shape, excluding strokes. DefineShape4 only.
+    pub edge_bounds: Option<Rect>,
+    /// Whether any strokes ignore scaling. DefineShape4 only.
+    pub uses_non_scaling_strokes: bool,
+    /// Whether any strokes scale. DefineShape4 only.
+    pub uses_scaling_strokes: bool,
+    /// The initial fill styles.
+    pub fill_styles: Vec<FillStyle>,
+    /// The initial line styles.
+    pub line_styles: Vec<LineStyle>,
+    /// The shape records, if they were asked for.
+    pub records: Option<Vec<ShapeRecord>>
+}
+
+/// The header of a DefineMorphShape or DefineMorphShape2 tag. A morph shape
+/// is drawn somewhere between its start and end shapes, picked by the ratio
+/// it's placed with.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct MorphShape {
+    /// The character ID.
+    pub id: u16,
+    /// Which DefineMorphShape this came from, 1 or 2.
+    pub version: u8,
+    /// The bounds at ratio 0, including strokes.
+    pub start_bounds: Rect,
+    /// The bounds at ratio 65535, including strokes.
+    pub end_bounds: Rect,
+    /// The start and end bounds excluding strokes. DefineMorphShape2 only.
+    pub edge_bounds: Option<(Rect, Rect)>,
+    /// Whether any strokes ignore scaling. DefineMorphShape2 only.
+    pub uses_non_scaling_strokes: bool,
+    /// Whether any strokes scale. DefineMorphShape2 only.
+    pub uses_scaling_strokes: bool
+}
+
+/// A single SHAPERECORD, with all positions as deltas in twips.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ShapeRecord {
+    /// Changes the current styles and/or moves the pen.
+    StyleChange(StyleChange),
+    /// A straight line to a point relative to the pen.
+    StraightEdge {
+        /// Horizontal delta.
+        delta_x: i32,
+        /// Vertical delta.
+        delta_y: i32
+    },
+    /// A quadratic curve through a control point to an anchor point, each
+    /// relative to the point before it.
+    CurvedEdge {
+        /// Horizontal delta to the control point.
+        control_delta_x: i32,
+        /// Vertical delta to the control point.
+        control_delta_y: i32,
+        /// Horizontal delta from the control point to the anchor.
+        anchor_delta_x: i32,
+        /// Vertical delta from the control point to the anchor.
+        anchor_delta_y: i32
+    }
+}
+
+/// The contents of a STYLECHANGERECORD. Style indices are 1-based, with 0
+/// meaning "no style".
+#[derive(Clone, PartialEq, Debug)]
+pub struct StyleChange {
+    /// Moves the pen to an absolute position, in twips.
+    pub move_to: Option<(i32, i32)>,
+    /// The new fill style for the left side of edges.
+    pub fill_style_0: Option<u32>,
+    /// The new fill style for the right side of edges.
+    pub fill_style_1: Option<u32>,
+    /// The new line style.
+    pub line_style: Option<u32>,
+    /// New fill and line style arrays, replacing the old ones.
+    pub new_styles: Option<(Vec<FillStyle>, Vec<LineStyle>)>
+}
+
+/// A FILLSTYLE.
+#[derive(Clone, PartialEq, Debug)]
+pub enum FillStyle {
+    /// A solid color.
+    Solid(Rgba),
+    /// A linear gradient.
+    LinearGradient(Gradient),
+    /// A radial gradient.
+    RadialGradient(Gradient),
+    /// A radial gradient with its focal point moved off-center, given as a
+    /// fraction of the radius from -1.0 to 1.0.
+    FocalGradient(Gradient, f32),
+    /// A bitmap fill.
+    Bitmap {
+        /// The character ID of the bitmap.
+        id: u16,
+        /// The transform from the bitmap to the shape, where one bitmap pixel
+        /// is one twip before transforming.
+        matrix: Matrix,
+        /// Whether the bitmap tiles, rather than clamping its edge pixels.
+        repeating: bool,
+        /// Whether the bitmap is smoothed when scaled.
+        smoothed: bool
+    }
+}
+
+/// What a gradient does outside of its 0-255 ratio range.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SpreadMode {
+    /// Extends the end colors.
+    Pad,
+    /// Mirrors the gradient back and forth.
+    Reflect,
+    /// Repeats the gradient.
+    Repeat
+}
+
+/// The color space a gradient interpolates in.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum InterpolationMode {
+    /// Plain RGB interpolation.
+    Normal,
+    /// Linear RGB interpolation.
+    Linear
+}
+
+/// A GRADIENT, laid out over the square from (-16384, -16384) to
+/// (16384, 16384) twips and then transformed into place.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Gradient {
+    /// The transform from gradient space to shape space.
+    pub matrix: Matrix,
+    /// What happens past either end of the gradient.
+    pub spread: SpreadMode,
+    /// The color space used between stops.
+    pub interpolation: InterpolationMode,
+    /// The color stops, each with a ratio from 0 to 255 along the gradient.
+    pub stops: Vec<(u8, Rgba)>
+}
+
+/// How the ends of a line are drawn.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CapStyle {
+    /// Rounded ends.
+    Round,
+    /// Flat ends, flush with the end points.
+    None,
+    /// Flat ends, extended past the end points by half the width.
+    Square
+}
+
+/// How corners between line segments are drawn.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum JoinStyle {
+    /// Rounded corners.
+    Round,
+    /// Cut-off corners.
+    Bevel,
+    /// Sharp corners, beveled beyond the given multiple of the line width.
+    Miter(f32)
+}
+
+/// A LINESTYLE or LINESTYLE2. Everything beyond the width and color only
+/// exists in DefineShape4, and is left at Flash Player's defaults for older
+/// shapes.
+#[derive(Clone, PartialEq, Debug)]
+pub struct LineStyle {
+    /// The width in twips.
+    pub width: u16,
+    /// The color. Lines filled with something other than a solid color are
+    /// reported as opaque black here, with the real fill in `fill`.
+    pub color: Rgba,
+    /// The fill used instead of a plain color, if any.
+    pub fill: Option<FillStyle>,
+    /// The cap at the start of the line.
+    pub start_cap: CapStyle,
+    /// The cap at the end of the line.
+    pub end_cap: CapStyle,
+    /// How corners are drawn.
+    pub join: JoinStyle,
+    /// Whether the width ignores horizontal scaling.
+    pub no_h_scale: bool,
+    /// Whether the width ignores vertical scaling.
+    pub no_v_scale: bool,
+    /// Whether the line is snapped to whole pixels.
+    pub pixel_hinting: bool,
+    /// Whether closed paths are left without a join where they meet.
+    pub no_close: bool
+}
+
+impl Shape {
+    /// Parses a shape's header and style arrays, returning `None` if the tag
+    /// isn't a DefineShape. Shape records are skipped.
+    pub fn parse(tag: &Tag) -> Result<Option<Self>, Error> {
+        parse_shape(tag, false)
+    }
+
+    /// Parses a shape including all of its shape records, returning `None` if
+    /// the tag isn't a DefineShape.
+    pub fn parse_with_records(tag: &Tag) -> Result<Option<Self>, Error> {
+        parse_shape(tag, true)
+    }
+}
+
+impl MorphShape {
+    /// Parses a morph shape's header, returning `None` if the tag isn't a
+    /// DefineMorphShape. The styles and edges are skipped.
+    pub fn parse(tag: &Tag) -> Result<Option<Self>, Error> {
+        let version = match tag.code() {
+            codes::DEFINE_MORPH_SHAPE => 1,
+            codes::DEFINE_MORPH_SHAPE_2 => 2,
+            _ => return Ok(None)
+        };
+        let mut reader = BitReader::new(tag.data());
+
+        let id = try!(reader.read_u16());
+        let start_bounds = try!(Rect::read(&mut reader));
+        reader.align();
+        let end_bounds = try!(Rect::read(&mut reader));
+        let mut shape = MorphShape {
+            id: id,
+            version: version,
+            start_bounds: start_bounds,
+            end_bounds: end_bounds,
+            edge_bounds: None,
+            uses_non_scaling_strokes: false,
+            uses_scaling_strokes: false
+        };
+        if version == 2 {
+            reader.align();
+            let start_edge_bounds = try!(Rect::read(&mut reader));
+            reader.align();
+            let end_edge_bounds = try!(Rect::read(&mut reader));
+            shape.edge_bounds = Some((start_edge_bounds, end_edge_bounds));
+            let flags = try!(reader.read_u8());
+            shape.uses_non_scaling_strokes = flags & 0x02 != 0;
+            shape.uses_scaling_strokes = flags & 0x01 != 0;
+        }
+        Ok(Some(shape))
+    }
+
+    /// Returns the bounds at a placement's morph ratio, interpolated linearly
+    /// between the start and end bounds.
+    pub fn bounds_at(&self, ratio: u16) -> Rect {
+        let lerp = |start: i32, end: i32| {
+            start + ((end - start) as i64 * ratio as i64 / 65535) as i32
+        };
+        Rect {
+            x_min: lerp(self.start_bounds.x_min, self.end_bounds.x_min),
+            x_max: lerp(self.start_bounds.x_max, self.end_bounds.x_max),
+            y_min: lerp(self.start_bounds.y_min, self.end_bounds.y_min),
+            y_max: lerp(self.start_bounds.y_max, self.end_bounds.y_max)
+        }
+    }
+}
+
+/// Collects the headers of every morph shape defined in a tag list.
+pub fn morph_shapes(tags: &[Tag]) -> Result<Vec<MorphShape>, Error> {
+    let mut shapes = Vec::new();
+    for tag in tags {
+        if let Some(shape) = try!(MorphShape::parse(tag)) {
+            shapes.push(shape);
+        }
+    }
+    Ok(shapes)
+}
+
+/// Returns which DefineShape a tag code is, or `None` if it isn't one.
+pub fn shape_version(code: u16) -> Option<u8> {
+    match code {
+        codes::DEFINE_SHAPE => Some(1),
+        codes::DEFINE_SHAPE_2 => Some(2),
+        codes::DEFINE_SHAPE_3 => Some(3),
+        codes::DEFINE_SHAPE_4 => Some(4),
+        _ => None
+    }
+}
+
+fn parse_shape(tag: &Tag, with_records: bool) -> Result<Option<Shape>, Error> {
+    let version = match shape_version(tag.code()) {
+        Some(version) => version,
+        None => return Ok(None)
+    };
+    let mut reader = BitReader::new(tag.data());
+
+    let id = try!(reader.read_u16());
+    let bounds = try!(Rect::read(&mut reader));
+    let mut edge_bounds = None;
+    let mut uses_non_scaling_strokes = false;
+    let mut uses_scaling_strokes = false;
+    if version == 4 {
+        reader.align();
+        edge_bounds = Some(try!(Rect::read(&mut reader)));
+        let flags = try!(reader.read_u8());
+        uses_non_scaling_strokes = flags & 0x02 != 0;
+        uses_scaling_strokes = flags & 0x01 != 0;
+    }
+
+    let fill_styles = try!(read_fill_styles(&mut reader, version));
+    let line_styles = try!(read_line_styles(&mut reader, version));
+
+    let records = if with_records {
+        Some(try!(read_shape_records(&mut reader, version)))
+    } else {
+        None
+    };
+
+    Ok(Some(Shape {
+        id: id,
+        version: version,
+        bounds: bounds,
+        edge_bounds: edge_bounds,
+        uses_non_scaling_strokes: uses_non_scaling_strokes,
+        uses_scaling_strokes: uses_scaling_strokes,
+        fill_styles: fill_styles,
+        line_styles: line_styles,
+        records: records
+    }))
+}
+
+/// Reads the SHAPERECORDs of a SHAPE or SHAPEWITHSTYLE, starting at the
+/// NumFillBits/NumLineBits byte, up to and including the end record.
+pub fn read_shape_records(reader: &mut BitReader, version: u8) -> Result<Vec<ShapeRecord>, Error> {
+    read_shape_records_with(reader, version, &mut |reader| {
+        let fills = try!(read_fill_styles(reader, version));
+        let lines = try!(read_line_styles(reader, version));
+        Ok((fills, lines))
+    })
+}
+
+/// Reads shape records, with `read_styles` reading the style arrays of each
+/// StyleChange that has new ones.
+fn read_shape_records_with<F>(reader: &mut BitReader, version: u8, read_styles: &mut F)
+    -> Result<Vec<ShapeRecord>, Error>
+    where F: FnMut(&mut BitReader) -> Result<(Vec<FillStyle>, Vec<LineStyle>), Error>
+{
+    reader.align();
+    let mut fill_bits = try!(reader.read_ub(4));
+    let mut line_bits = try!(reader.read_ub(4));
+    let mut records = Vec::new();
+
+    loop {
+        let is_edge = try!(reader.read_flag());
+        if !is_edge {
+            let flags = try!(reader.read_ub(5));
+            if flags == 0 {
+                break;
+            }
+            let has_new_styles = flags & 0x10 != 0;
+            let has_line_style = flags & 0x08 != 0;
+            let has_fill_style_1 = flags & 0x04 != 0;
+            let has_fill_style_0 = flags & 0x02 != 0;
+            let has_move_to = flags & 0x01 != 0;
+
+            let move_to = if has_move_to {
+                let nbits = try!(reader.read_ub(5));
+                Some((try!(reader.read_sb(nbits)), try!(reader.read_sb(nbits))))
+            } else {
+                None
+            };
+            let fill_style_0 = if has_fill_style_0 { Some(try!(reader.read_ub(fill_bits))) } else { None };
+            let fill_style_1 = if has_fill_style_1 { Some(try!(reader.read_ub(fill_bits))) } else { None };
+            let line_style = if has_line_style { Some(try!(reader.read_ub(line_bits))) } else { None };
+
+            // DefineShape (version 1) uses this bit for something else
+            // entirely, so it never carries new styles there.
+            let new_styles = if has_new_styles && version > 1 {
+                let (fills, lines) = try!(read_styles(reader));
+                reader.align();
+                fill_bits = try!(reader.read_ub(4));
+                line_bits = try!(reader.read_ub(4));
+                Some((fills, lines))
+            } else {
+                None
+            };
+
+            records.push(ShapeRecord::StyleChange(StyleChange {
+                move_to: move_to,
+                fill_style_0: fill_style_0,
+                fill_style_1: fill_style_1,
+                line_style: line_style,
+                new_styles: new_styles
+            }));
+        } else {
+            let is_straight = try!(reader.read_flag());
+            let nbits = try!(reader.read_ub(4)) + 2;
+            if is_straight {
+                let is_general = try!(reader.read_flag());
+                let (delta_x, delta_y) = if is_general {
+                    (try!(reader.read_sb(nbits)), try!(reader.read_sb(nbits)))
+                } else if try!(reader.read_flag()) {
+                    (0, try!(reader.read_sb(nbits)))
+                } else {
+                    (try!(reader.read_sb(nbits)), 0)
+                };
+                records.push(ShapeRecord::StraightEdge {
+                    delta_x: delta_x,
+                    delta_y: delta_y
+                });
+            } else {
+                records.push(ShapeRecord::CurvedEdge {
+                    control_delta_x: try!(reader.read_sb(nbits)),
+                    control_delta_y: try!(reader.read_sb(nbits)),
+                    anchor_delta_x: try!(reader.read_sb(nbits)),
+                    anchor_delta_y: try!(reader.read_sb(nbits))
+                });
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// Returns where the character ID of each bitmap fill is in the data of a
+/// DefineShape or DefineMorphShape, so that the IDs can be rewritten in
+/// place. That includes fills in line styles, and in new styles partway
+/// through the outline. Other tags have none.
+///
+/// Fills of 0xffff, which Flash writes for a bitmap that's been deleted,
+/// aren't references, so they're left out.
+pub fn bitmap_id_offsets(tag: &Tag) -> Result<Vec<usize>, Error> {
+    let data = tag.data();
+    let mut offsets = Vec::new();
+    if let Some(version) = shape_version(tag.code()) {
+        let mut reader = BitReader::new(data);
+        try!(reader.read_u16());
+        try!(Rect::read(&mut reader));
+        if version == 4 {
+            reader.align();
+            try!(Rect::read(&mut reader));
+            try!(reader.read_u8());
+        }
+        try!(find_bitmap_fills(&mut reader, version, &mut offsets));
+        try!(read_shape_records_with(&mut reader, version, &mut |reader| {
+            try!(find_bitmap_fills(reader, version, &mut offsets));
+            Ok((Vec::new(), Vec::new()))
+        }));
+    } else if let Some(shape) = try!(MorphShape::parse(tag)) {
+        // The styles come after the header and the u32 offset to the end
+        // edges, which MorphShape::parse() stops short of.
+        let mut reader = BitReader::new(data);
+        try!(reader.read_u16());
+        for _ in 0..if shape.version == 2 { 4 } else { 2 } {
+            reader.align();
+            try!(Rect::read(&mut reader));
+        }
+        if shape.version == 2 {
+            try!(reader.read_u8());
+        }
+        for _ in 0..4 {
+            try!(reader.read_u8());
+        }
+        try!(find_morph_bitmap_fills(&mut reader, shape.version, &mut offsets));
+    }
+    offsets.retain(|&offset| data.get(offset..offset + 2) != Some(&[0xff, 0xff][..]));
+    Ok(offsets)
+}
+
+/// Reads a FILLSTYLEARRAY and LINESTYLEARRAY, noting where each bitmap
+/// fill's ID is.
+fn find_bitmap_fills(reader: &mut BitReader, version: u8, offsets: &mut Vec<usize>) -> Result<(), Error> {
+    for _ in 0..try!(read_style_count(reader, version)) {
+        reader.align();
+        let pos = reader.byte_pos();
+        if let FillStyle::Bitmap { .. } = try!(read_fill_style(reader, version)) {
+            offsets.push(pos + 1);
+        }
+    }
+    for _ in 0..try!(read_style_count(reader, version)) {
+        reader.align();
+        let pos = reader.byte_pos();
+        let style = try!(read_line_style(reader, version));
+        if let Some(FillStyle::Bitmap { .. }) = style.fill {
+            // The width and flags come first, then the miter limit if
+            // there is one, then the fill's type.
+            let miter = if let JoinStyle::Miter(_) = style.join { 2 } else { 0 };
+            offsets.push(pos + 4 + miter + 1);
+        }
+    }
+    Ok(())
+}
+
+/// Like `find_bitmap_fills()`, for the MORPHFILLSTYLEARRAY and
+/// MORPHLINESTYLEARRAY of a morph shape, where every style has a start and
+/// an end.
+fn find_morph_bitmap_fills(reader: &mut BitReader, version: u8, offsets: &mut Vec<usize>) -> Result<(), Error> {
+    for _ in 0..try!(read_style_count(reader, 2)) {
+        try!(find_morph_bitmap_fill(reader, offsets));
+    }
+    for _ in 0..try!(read_style_count(reader, 2)) {
+        // The start and end widths.
+        try!(reader.read_u16());
+        try!(reader.read_u16());
+        if version == 1 {
+            try!(skip_bytes(reader, 8));
+            continue;
+        }
+        // The same flags as a LINESTYLE2.
+        let flags = try!(reader.read_u16());
+        if (flags >> 4) & 0x03 == 2 {
+            try!(reader.read_u16());
+        }
+        if flags & 0x08 != 0 {
+            try!(find_morph_bitmap_fill(reader, offsets));
+        } else {
+            try!(skip_bytes(reader, 8));
+        }
+    }
+    Ok(())
+}
+
+/// Reads a MORPHFILLSTYLE, noting where its ID is if it's a bitmap fill.
+fn find_morph_bitmap_fill(reader: &mut BitReader, offsets: &mut Vec<usize>) -> Result<(), Error> {
+    reader.align();
+    let pos = reader.byte_pos();
+    match try!(reader.read_u8()) {
+        0x00 => skip_bytes(reader, 8),
+        kind @ 0x10 | kind @ 0x12 | kind @ 0x13 => {
+            reader.align();
+            try!(Matrix::read(reader));
+            reader.align();
+            try!(Matrix::read(reader));
+            // Each stop has a start and end ratio and color.
+            let stops = try!(reader.read_u8()) & 0x0f;
+            try!(skip_bytes(reader, stops as usize * 10));
+            if kind == 0x13 {
+                try!(skip_bytes(reader, 4));
+            }
+            Ok(())
+        },
+        0x40..=0x43 => {
+            offsets.push(pos + 1);
+            try!(reader.read_u16());
+            reader.align();
+            try!(Matrix::read(reader));
+            reader.align();
+            try!(Matrix::read(reader));
+            Ok(())
+        },
+        _ => Err(Error::NotSwf)
+    }
+}
+
+fn skip_bytes(reader: &mut BitReader, len: usize) -> Result<(), Error> {
+    for _ in 0..len {
+        try!(reader.read_u8());
+    }
+    Ok(())
+}
+
+fn read_style_count(reader: &mut BitReader, version: u8) -> Result<usize, Error> {
+    let count = try!(reader.read_u8());
+    if count == 0xff && version >= 2 {
+        Ok(try!(reader.read_u16()) as usize)
+    } else {
+        Ok(count as usize)
+    }
+}
+
+/// Reads a FILLSTYLEARRAY for the given DefineShape version (1 to 4).
+///
+/// Morph shapes and font glyphs use the same layout with their own twists,
+/// so this is only for DefineShape tags.
+pub fn read_fill_styles(reader: &mut BitReader, version: u8) -> Result<Vec<FillStyle>, Error> {
+    let count = try!(read_style_count(reader, version));
+    let mut styles = Vec::with_capacity(count);
+    for _ in 0..count {
+        styles.push(try!(read_fill_style(reader, version)));
+    }
+    Ok(styles)
+}
+
+/// Reads a LINESTYLEARRAY for the given DefineShape version (1 to 4).
+pub fn read_line_styles(reader: &mut BitReader, version: u8) -> Result<Vec<LineStyle>, Error> {
+    let count = try!(read_style_count(reader, version));
+    let mut styles = Vec::with_capacity(count);
+    for _ in 0..count {
+        styles.push(try!(read_line_style(reader, version)));
+    }
+    Ok(styles)
+}
+
+/// Reads a single LINESTYLE, or a LINESTYLE2 for DefineShape4.
+pub fn read_line_style(reader: &mut BitReader, version: u8) -> Result<LineStyle, Error> {
+    let width = try!(reader.read_u16());
+    let mut style = LineStyle {
+        width: width,
+        color: Rgba { r: 0, g: 0, b: 0, a: 255 },
+        fill: None,
+        start_cap: CapStyle::Round,
+        end_cap: CapStyle::Round,
+        join: JoinStyle::Round,
+        no_h_scale: false,
+        no_v_scale: false,
+        pixel_hinting: false,
+        no_close: false
+    };
+    if version < 4 {
+        style.color = try!(read_color(reader, version));
+        return Ok(style);
+    }
+
+    // LINESTYLE2 overview:
+    // StartCapStyle: UB2, JoinStyle: UB2, HasFillFlag: UB1, NoHScaleFlag: UB1,
+    // NoVScaleFlag: UB1, PixelHintingFlag: UB1, Reserved: UB5, NoClose: UB1,
+    // EndCapStyle: UB2. Then a u16 miter limit if it's a miter join, then
+    // either a FILLSTYLE or an RGBA.
+    let start_cap = try!(reader.read_ub(2));
+    let join = try!(reader.read_ub(2));
+    let has_fill = try!(reader.read_flag());
+    style.no_h_scale = try!(reader.read_flag());
+    style.no_v_scale = try!(reader.read_flag());
+    style.pixel_hinting = try!(reader.read_flag());
+    try!(reader.read_ub(5));
+    style.no_close = try!(reader.read_flag());
+    let end_cap = try!(reader.read_ub(2));
+
+    style.start_cap = try!(cap_style(start_cap));
+    style.end_cap = try!(cap_style(end_cap));
+    style.join = match join {
+        0 => JoinStyle::Round,
+        1 => JoinStyle::Bevel,
+        2 => JoinStyle::Miter(try!(reader.read_u16()) as f32 / 256.0),
+        _ => return Err(Error::NotSwf)
+    };
+
+    if has_fill {
+        let fill = try!(read_fill_style(reader, version));
+        if let FillStyle::Solid(color) = fill {
+            style.color = color;
+        }
+        style.fill = Some(fill);
+    } else {
+        style.color = try!(read_color(reader, version));
+    }
+    Ok(style)
+}
+
+fn cap_style(value: u32) -> Result<CapStyle, Error> {
+    match value {
+        0 => Ok(CapStyle::Round),
+        1 => Ok(CapStyle::None),
+        2 => Ok(CapStyle::Square),
+        _ => Err(Error::NotSwf)
+    }
+}
+
+/// Reads a single FILLSTYLE.
+pub fn read_fill_style(reader: &mut BitReader, version: u8) -> Result<FillStyle, Error> {
+    match try!(reader.read_u8()) {
+        0x00 => Ok(FillStyle::Solid(try!(read_color(reader, version)))),
+        kind @ 0x10 | kind @ 0x12 | kind @ 0x13 => {
+            reader.align();
+            let matrix = try!(Matrix::read(reader));
+            let gradient = try!(read_gradient(reader, matrix, version));
+            Ok(match kind {
+                0x10 => FillStyle::LinearGradient(gradient),
+                0x12 => FillStyle::RadialGradient(gradient),
+                _ => {
+                    // The focal point is signed 8.8 fixed point.
+                    let focal_point = try!(reader.read_u16()) as i16 as f32 / 256.0;
+                    FillStyle::FocalGradient(gradient, focal_point)
+                }
+            })
+        },
+        kind @ 0x40..=0x43 => {
+            let id = try!(reader.read_u16());
+            reader.align();
+            Ok(FillStyle::Bitmap {
+                id: id,
+                matrix: try!(Matrix::read(reader)),
+                repeating: kind & 0x01 == 0,
+                smoothed: kind & 0x02 == 0
+            })
+        },
+        _ => Err(Error::NotSwf)
+    }
+}
+
+fn read_gradient(reader: &mut BitReader, matrix: Matrix, version: u8) -> Result<Gradient, Error> {
+    reader.align();
+    let spread = match try!(reader.read_ub(2)) {
+        0 => SpreadMode::Pad,
+        1 => SpreadMode::Reflect,
+        2 => SpreadMode::Repeat,
+        _ => return Err(Error::NotSwf)
+    };
+    let interpolation = match try!(reader.read_ub(2)) {
+        0 => InterpolationMode::Normal,
+        1 => InterpolationMode::Linear,
+        _ => return Err(Error::NotSwf)
+    };
+    let count = try!(reader.read_ub(4));
+    let mut stops = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let ratio = try!(reader.read_u8());
+        stops.push((ratio, try!(read_color(reader, version))));
+    }
+    Ok(Gradient {
+        matrix: matrix,
+        spread: spread,
+        interpolation: interpolation,
+        stops: stops
+    })
+}
+
+// DefineShape and DefineShape2 use RGB, later versions RGBA.
+fn read_color(reader: &mut BitReader, version: u8) -> Result<Rgba, Error> {
+    let r = try!(reader.read_u8());
+    let g = try!(reader.read_u8());
+    let b = try!(reader.read_u8());
+    let a = if version >= 3 { try!(reader.read_u8()) } else { 255 };
+    Ok(Rgba { r: r, g: g, b: b, a: a })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primitives::{BitWriter, Rect, Rgba};
+    use tag::{codes, Tag};
+
+    #[test]
+    fn test_square() {
+        // DefineShape3 with ID 1, bounds (0, 0)-(20, 20), one solid red fill,
+        // no line styles, and a 20 twip square drawn with it.
+        let mut writer = BitWriter::new();
+        writer.write_ub(16, 0x0100);
+        writer.write_ub(5, 6);
+        for value in &[0, 20, 0, 20] {
+            writer.write_sb(6, *value);
+        }
+        writer.write_ub(3, 0);
+        for byte in &[1, 0x00, 0xff, 0x00, 0x00, 0xff, 0] {
+            writer.write_ub(8, *byte);
+        }
+        writer.write_ub(4, 1);
+        writer.write_ub(4, 0);
+        // Style change: fill style 1 on the right, no move.
+        writer.write_flag(false);
+        writer.write_ub(5, 0b00100);
+        writer.write_ub(1, 1);
+        // Four edges: right, down, left, up.
+        for &(vertical, delta) in &[(false, 20), (true, 20), (false, -20), (true, -20)] {
+            writer.write_flag(true);
+            writer.write_flag(true);
+            writer.write_ub(4, 4);
+            writer.write_flag(false);
+            writer.write_flag(vertical);
+            writer.write_sb(6, delta);
+        }
+        writer.write_flag(false);
+        writer.write_ub(5, 0);
+        let tag = Tag::new(codes::DEFINE_SHAPE_3, writer.into_bytes());
+
+        let shape = Shape::parse_with_records(&tag).unwrap().unwrap();
+        assert_eq!(shape.id, 1);
+        assert_eq!(shape.version, 3);
+        assert_eq!(shape.bounds.width(), 20);
+        assert_eq!(shape.fill_styles, vec![FillStyle::Solid(Rgba { r: 255, g: 0, b: 0, a: 255 })]);
+        assert!(shape.line_styles.is_empty());
+
+        let records = shape.records.unwrap();
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[2], ShapeRecord::StraightEdge { delta_x: 0, delta_y: 20 });
+        assert_eq!(records[3], ShapeRecord::StraightEdge { delta_x: -20, delta_y: 0 });
+    }
+
+    #[test]
+    fn test_gradient_and_line_style_2() {
+        // A reflected linear gradient with two stops, identity matrix.
+        let bytes = [0x10, 0x00, 0x42, 0, 0, 0, 0, 0xff, 255, 255, 255, 0x80, 0];
+        let mut reader = BitReader::new(&bytes);
+        match read_fill_style(&mut reader, 3).unwrap() {
+            FillStyle::LinearGradient(gradient) => {
+                assert_eq!(gradient.spread, SpreadMode::Reflect);
+                assert_eq!(gradient.interpolation, InterpolationMode::Normal);
+                assert_eq!(gradient.stops, vec![
+                    (0, Rgba { r: 0, g: 0, b: 0, a: 0xff }),
+                    (255, Rgba { r: 255, g: 255, b: 0x80, a: 0 })
+                ]);
+            },
+            other => panic!("expected a linear gradient, got {:?}", other)
+        }
+
+        // 1px wide, square start cap, miter join with limit 3, no-close, with
+        // a solid green fill.
+        let bytes = [20, 0, 0b1010_1000, 0b0000_0100, 0x00, 0x03, 0x00, 0, 255, 0, 255];
+        let mut reader = BitReader::new(&bytes);
+        let style = read_line_style(&mut reader, 4).unwrap();
+        assert_eq!(style.width, 20);
+        assert_eq!(style.start_cap, CapStyle::Square);
+        assert_eq!(style.end_cap, CapStyle::Round);
+        assert_eq!(style.join, JoinStyle::Miter(3.0));
+        assert!(style.no_close);
+        assert_eq!(style.color, Rgba { r: 0, g: 255, b: 0, a: 255 });
+        assert_eq!(style.fill, Some(FillStyle::Solid(style.color)));
+    }
+
+    #[test]
+    fn test_morph_shape() {
+        // DefineMorphShape2 with ID 3, growing from (0, 0)-(20, 20) to
+        // (0, 0)-(40, 60), with non-scaling strokes.
+        let mut data = vec![3, 0];
+        for &(x_max, y_max) in &[(20, 20), (40, 60), (20, 20), (40, 60)] {
+            let mut writer = BitWriter::new();
+            Rect { x_min: 0, x_max: x_max, y_min: 0, y_max: y_max }.write(&mut writer);
+            data.extend(writer.into_bytes());
+        }
+        data.push(0x02);
+        // The offset to the end edges, then one bitmap fill of character 5
+        // and no line styles.
+        data.extend(&[0, 0, 0, 0, 1]);
+        let fill_pos = data.len();
+        data.extend(&[0x41, 5, 0, 0x00, 0x00, 0]);
+        let tag = Tag::new(codes::DEFINE_MORPH_SHAPE_2, data);
+        assert_eq!(bitmap_id_offsets(&tag).unwrap(), vec![fill_pos + 1]);
+
+        let shape = MorphShape::parse(&tag).unwrap().unwrap();
+        assert_eq!(shape.id, 3);
+        assert_eq!(shape.end_bounds, Rect { x_min: 0, x_max: 40, y_min: 0, y_max: 60 });
+        assert_eq!(shape.edge_bounds.map(|bounds| bounds.0), Some(shape.start_bounds));
+        assert!(shape.uses_non_scaling_strokes && !shape.uses_scaling_strokes);
+        assert_eq!(shape.bounds_at(0), shape.start_bounds);
+        assert_eq!(shape.bounds_at(32768), Rect { x_min: 0, x_max: 30, y_min: 0, y_max: 40 });
+        assert_eq!(shape.bounds_at(65535), shape.end_bounds);
+    }
+}
@@ -0,0 +1,434 @@
+//! Reassembling streaming sound from SoundStreamBlock tags, and working out
+//! when event sounds play.
+//!
+//! Streaming sound is split into one block per frame, each with a small
+//! header of its own in front of the audio data. Only MP3 streams are
+//! reassembled for now, since they're by far the most common and the only
+//! ones that make a usable file once put back together.
+//!
+//! Event sounds are started, and stopped, by StartSound tags on the
+//! timeline, each with a SOUNDINFO saying how to play it. `cue_sheet()`
+//! lists them by frame, which is what's needed to line extracted sounds up
+//! with the animation again.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use assets::{Sound, SoundFormat};
+use error::Error;
+use merge::parse_symbol_list;
+use primitives::read_string;
+use tag::{codes, Tag};
+
+/// The sample rates a two-bit rate code stands for.
+const SAMPLE_RATES: [u32; 4] = [5512, 11025, 22050, 44100];
+
+/// The parameters of a timeline's streaming sound, from its SoundStreamHead
+/// or SoundStreamHead2.
+///
+/// The stream is what the blocks hold; the playback parameters are only
+/// the player's advice to the sound card, and can differ. Audio sync
+/// tooling wants `samples_per_frame` and `latency_seek` to line an
+/// extracted stream up with the frames it plays over.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct StreamHead {
+    /// The sample rate to play at, in Hz.
+    pub playback_rate: u32,
+    /// Whether to play 16-bit samples rather than 8-bit ones.
+    pub playback_16_bit: bool,
+    /// Whether to play in stereo.
+    pub playback_stereo: bool,
+    /// How the blocks are encoded.
+    pub format: SoundFormat,
+    /// The sample rate of the blocks, in Hz.
+    pub stream_rate: u32,
+    /// Whether the blocks hold 16-bit samples. Only meaningful for
+    /// uncompressed streams.
+    pub stream_16_bit: bool,
+    /// Whether the blocks are stereo.
+    pub stream_stereo: bool,
+    /// The average number of samples per channel in each frame's block.
+    pub samples_per_frame: u16,
+    /// For MP3, how many samples of encoder delay to skip at the start. Some
+    /// encoders leave it out when it's zero.
+    pub latency_seek: Option<i16>
+}
+
+impl StreamHead {
+    /// Parses a SoundStreamHead or SoundStreamHead2, returning `None` for
+    /// other tags.
+    pub fn parse(tag: &Tag) -> Result<Option<Self>, Error> {
+        if tag.code() != codes::SOUND_STREAM_HEAD && tag.code() != codes::SOUND_STREAM_HEAD_2 {
+            return Ok(None);
+        }
+        // SOUNDSTREAMHEAD overview:
+        // Reserved: UB4, PlaybackSoundRate: UB2, PlaybackSoundSize: UB1,
+        // PlaybackSoundType: UB1, StreamSoundCompression: UB4,
+        // StreamSoundRate: UB2, StreamSoundSize: UB1, StreamSoundType: UB1,
+        // StreamSoundSampleCount: u16, then LatencySeek: i16 for MP3.
+        let mut reader = tag.data();
+        let playback = try!(reader.read_u8());
+        let stream = try!(reader.read_u8());
+        let samples_per_frame = try!(reader.read_u16::<LittleEndian>());
+        let format = SoundFormat::from_code(stream >> 4);
+        let latency_seek = if format == SoundFormat::Mp3 {
+            reader.read_i16::<LittleEndian>().ok()
+        } else {
+            None
+        };
+        Ok(Some(StreamHead {
+            playback_rate: SAMPLE_RATES[((playback >> 2) & 0x03) as usize],
+            playback_16_bit: playback & 0x02 != 0,
+            playback_stereo: playback & 0x01 != 0,
+            format: format,
+            stream_rate: SAMPLE_RATES[((stream >> 2) & 0x03) as usize],
+            stream_16_bit: stream & 0x02 != 0,
+            stream_stereo: stream & 0x01 != 0,
+            samples_per_frame: samples_per_frame,
+            latency_seek: latency_seek
+        }))
+    }
+}
+
+/// Finds the head of a single timeline's streaming sound, returning `None`
+/// if it has none.
+pub fn stream_head(tags: &[Tag]) -> Result<Option<StreamHead>, Error> {
+    for tag in tags {
+        if let Some(head) = try!(StreamHead::parse(tag)) {
+            return Ok(Some(head));
+        }
+    }
+    Ok(None)
+}
+
+/// A reassembled MP3 stream, along with what's needed to play it back
+/// gaplessly.
+///
+/// MP3 encoders add silence to the start and end of the audio. SWF files
+/// record how much via SeekSamples, so a player that trims `skip_samples`
+/// from the start and `padding_samples` from the end of the decoded audio
+/// gets exactly the `sample_count` samples the movie plays, with no clicks
+/// between loops or clips. Most MP3 tools accept these as the encoder delay
+/// and padding.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Mp3Stream {
+    /// The MP3 frames, back to back with the block headers removed.
+    pub data: Vec<u8>,
+    /// The sample rate in Hz.
+    pub sample_rate: u32,
+    /// Whether the stream is stereo.
+    pub stereo: bool,
+    /// The number of samples per channel the movie actually plays.
+    pub sample_count: u32,
+    /// The number of samples per channel to skip at the start.
+    pub skip_samples: u32,
+    /// The number of samples per channel to drop at the end.
+    pub padding_samples: u32
+}
+
+/// Pulls the MP3 stream out of a single timeline's tags, returning `None` if
+/// the timeline has no streaming sound, or it isn't MP3.
+///
+/// Sprites have their own streams, so pass them in separately.
+pub fn mp3_stream(tags: &[Tag]) -> Result<Option<Mp3Stream>, Error> {
+    let head = match try!(stream_head(tags)) {
+        Some(ref head) if head.format == SoundFormat::Mp3 => *head,
+        _ => return Ok(None)
+    };
+    let latency_seek = head.latency_seek.unwrap_or(0);
+
+    let mut data = Vec::new();
+    let mut sample_count = 0u32;
+    let mut first_seek = None;
+    for tag in tags {
+        if tag.code() != codes::SOUND_STREAM_BLOCK {
+            continue;
+        }
+        let mut reader = tag.data();
+        let block_samples = try!(reader.read_u16::<LittleEndian>());
+        let seek_samples = try!(reader.read_i16::<LittleEndian>());
+        // Empty blocks pad out frames with no audio, and their SeekSamples
+        // mean nothing.
+        if block_samples == 0 && reader.is_empty() {
+            continue;
+        }
+        if first_seek.is_none() {
+            first_seek = Some(seek_samples);
+        }
+        sample_count += block_samples as u32;
+        data.extend_from_slice(reader);
+    }
+
+    // The first block's SeekSamples is how far into its first frame the
+    // movie starts playing, which is the encoder delay. The head's
+    // LatencySeek says the same thing, and is the fallback for streams
+    // whose blocks leave it out.
+    let skip_samples = match first_seek {
+        Some(seek) if seek > 0 => seek as u32,
+        _ => latency_seek.max(0) as u32
+    };
+    let decoded_samples = mp3_sample_count(&data);
+    let padding_samples = decoded_samples.saturating_sub(skip_samples + sample_count);
+
+    Ok(Some(Mp3Stream {
+        data: data,
+        sample_rate: head.stream_rate,
+        stereo: head.stream_stereo,
+        sample_count: sample_count,
+        skip_samples: skip_samples,
+        padding_samples: padding_samples
+    }))
+}
+
+/// How an event sound is played, from the SOUNDINFO in a StartSound,
+/// StartSound2 or DefineButtonSound.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SoundInfo {
+    /// Whether this stops the sound rather than starting it.
+    pub stop: bool,
+    /// Whether the sound is left alone if it's already playing, rather than
+    /// started again on top of itself.
+    pub no_multiple: bool,
+    /// The sample to start from, in 44kHz samples.
+    pub in_point: Option<u32>,
+    /// The sample to stop at, in 44kHz samples.
+    pub out_point: Option<u32>,
+    /// How many times to play the sound.
+    pub loops: Option<u16>,
+    /// The volume envelope, if the sound has one.
+    pub envelope: Vec<EnvelopePoint>
+}
+
+/// A point on a sound's volume envelope. The volume changes linearly from
+/// one point to the next.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct EnvelopePoint {
+    /// Where the point is, in 44kHz samples from the start of the sound.
+    pub position: u32,
+    /// The left channel's volume, from 0 to 32768.
+    pub left: u16,
+    /// The right channel's volume, from 0 to 32768.
+    pub right: u16
+}
+
+impl SoundInfo {
+    /// Reads a SOUNDINFO.
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let flags = try!(reader.read_u8());
+        let in_point = if flags & 0x01 != 0 { Some(try!(reader.read_u32::<LittleEndian>())) } else { None };
+        let out_point = if flags & 0x02 != 0 { Some(try!(reader.read_u32::<LittleEndian>())) } else { None };
+        let loops = if flags & 0x04 != 0 { Some(try!(reader.read_u16::<LittleEndian>())) } else { None };
+        let mut envelope = Vec::new();
+        if flags & 0x08 != 0 {
+            for _ in 0..try!(reader.read_u8()) {
+                envelope.push(EnvelopePoint {
+                    position: try!(reader.read_u32::<LittleEndian>()),
+                    left: try!(reader.read_u16::<LittleEndian>()),
+                    right: try!(reader.read_u16::<LittleEndian>())
+                });
+            }
+        }
+        Ok(SoundInfo {
+            stop: flags & 0x20 != 0,
+            no_multiple: flags & 0x10 != 0,
+            in_point: in_point,
+            out_point: out_point,
+            loops: loops,
+            envelope: envelope
+        })
+    }
+}
+
+/// An event sound being started or stopped on a frame.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SoundCue {
+    /// The frame, counting from 0.
+    pub frame: u16,
+    /// The ID of the DefineSound. For StartSound2, this is looked up from
+    /// the class name, and is `None` if no SymbolClass gives it one.
+    pub sound_id: Option<u16>,
+    /// The ActionScript 3 class of the sound, for StartSound2.
+    pub class_name: Option<String>,
+    /// How long the sound is in seconds, before any in and out points or
+    /// loops, if it's defined in the same tag list.
+    pub duration: Option<f64>,
+    /// How the sound is played.
+    pub info: SoundInfo
+}
+
+/// Lists every event sound started or stopped on a single timeline, in
+/// order, as a cue sheet for re-creating the movie's audio.
+///
+/// Sprites have their own timelines, so pass them in separately, along
+/// with the rest of the movie's tags if their sounds are defined there.
+/// Sounds started by buttons aren't on any timeline, so they aren't
+/// included.
+pub fn cue_sheet(tags: &[Tag]) -> Result<Vec<SoundCue>, Error> {
+    let mut durations = HashMap::new();
+    let mut classes = HashMap::new();
+    for tag in tags {
+        if let Some(sound) = try!(Sound::from_tag(tag)) {
+            durations.insert(sound.id, sound.sample_count as f64 / sound.sample_rate as f64);
+        } else if tag.code() == codes::SYMBOL_CLASS {
+            for (id, name) in try!(parse_symbol_list(tag.data())) {
+                classes.insert(name, id);
+            }
+        }
+    }
+
+    let mut cues = Vec::new();
+    let mut frame = 0;
+    for tag in tags {
+        let mut reader = tag.data();
+        let (sound_id, class_name) = match tag.code() {
+            codes::SHOW_FRAME => {
+                frame += 1;
+                continue;
+            },
+            codes::START_SOUND => (Some(try!(reader.read_u16::<LittleEndian>())), None),
+            codes::START_SOUND_2 => {
+                let name = try!(read_string(&mut reader));
+                (classes.get(&name).cloned(), Some(name))
+            },
+            _ => continue
+        };
+        cues.push(SoundCue {
+            frame: frame,
+            sound_id: sound_id,
+            class_name: class_name,
+            duration: sound_id.and_then(|id| durations.get(&id).cloned()),
+            info: try!(SoundInfo::read(&mut reader))
+        });
+    }
+    Ok(cues)
+}
+
+/// Counts the samples per channel in a run of MPEG audio layer III frames,
+/// stopping at anything that isn't a valid frame header.
+pub fn mp3_sample_count(data: &[u8]) -> u32 {
+    const MPEG1_BITRATES: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+    const MPEG2_BITRATES: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+    let mut pos = 0;
+    let mut samples = 0;
+    while pos + 4 <= data.len() {
+        let header = &data[pos..pos + 4];
+        // Frame sync, then layer III.
+        if header[0] != 0xff || header[1] & 0xe0 != 0xe0 || (header[1] >> 1) & 0x03 != 0x01 {
+            break;
+        }
+        let version = (header[1] >> 3) & 0x03;
+        let bitrate_index = (header[2] >> 4) as usize;
+        let rate_index = ((header[2] >> 2) & 0x03) as usize;
+        let padding = ((header[2] >> 1) & 0x01) as usize;
+        if version == 1 || bitrate_index == 0 || bitrate_index == 15 || rate_index == 3 {
+            break;
+        }
+        let (bitrate, sample_rate, frame_samples) = match version {
+            // MPEG 1
+            3 => (MPEG1_BITRATES[bitrate_index], [44100, 48000, 32000][rate_index], 1152),
+            // MPEG 2
+            2 => (MPEG2_BITRATES[bitrate_index], [22050, 24000, 16000][rate_index], 576),
+            // MPEG 2.5
+            _ => (MPEG2_BITRATES[bitrate_index], [11025, 12000, 8000][rate_index], 576)
+        };
+        let length = (frame_samples / 8 * bitrate * 1000 / sample_rate) as usize + padding;
+        pos += length;
+        samples += frame_samples;
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tag::{codes, Tag};
+
+    // A silent-looking MPEG 1 layer III frame at 32kbps and 32kHz, which is
+    // exactly 144 bytes.
+    fn frame() -> Vec<u8> {
+        let mut frame = vec![0xff, 0xfb, 0x18, 0xc4];
+        frame.resize(144, 0);
+        frame
+    }
+
+    #[test]
+    fn test_reassemble() {
+        let block = |samples: u16, seek: i16, frames: usize| {
+            let mut data = vec![samples as u8, (samples >> 8) as u8, seek as u8, (seek >> 8) as u8];
+            for _ in 0..frames {
+                data.extend(frame());
+            }
+            Tag::new(codes::SOUND_STREAM_BLOCK, data)
+        };
+        let tags = vec![
+            // MP3, 44kHz, 16-bit stereo, 1000 samples per frame.
+            Tag::new(codes::SOUND_STREAM_HEAD_2, vec![0x0f, 0x2f, 0xe8, 0x03, 0x40, 0x02]),
+            block(1152, 576, 1),
+            Tag::new(codes::SHOW_FRAME, vec![]),
+            Tag::new(codes::SOUND_STREAM_BLOCK, vec![0, 0, 0, 0]),
+            block(1000, 0, 2)
+        ];
+        let stream = mp3_stream(&tags).unwrap().unwrap();
+        assert_eq!(stream.data.len(), 144 * 3);
+        assert_eq!(stream.sample_rate, 44100);
+        assert!(stream.stereo);
+        assert_eq!(stream.sample_count, 2152);
+        assert_eq!(stream.skip_samples, 576);
+        assert_eq!(stream.padding_samples, 1152 * 3 - 576 - 2152);
+        assert_eq!(stream_head(&tags).unwrap().unwrap().latency_seek, Some(576));
+    }
+
+    #[test]
+    fn test_stream_head() {
+        // Played back at 44kHz 16-bit stereo, from an 11kHz mono ADPCM
+        // stream with 460 samples a frame.
+        let head = StreamHead::parse(&Tag::new(codes::SOUND_STREAM_HEAD, vec![0x0f, 0x16, 0xcc, 0x01])).unwrap().unwrap();
+        assert_eq!(head, StreamHead {
+            playback_rate: 44100,
+            playback_16_bit: true,
+            playback_stereo: true,
+            format: SoundFormat::Adpcm,
+            stream_rate: 11025,
+            stream_16_bit: true,
+            stream_stereo: false,
+            samples_per_frame: 460,
+            latency_seek: None
+        });
+        assert_eq!(StreamHead::parse(&Tag::new(codes::SHOW_FRAME, vec![])).unwrap(), None);
+    }
+
+    #[test]
+    fn test_cue_sheet() {
+        let tags = vec![
+            // Sound 1: a second of 11kHz 8-bit mono samples, exported to
+            // ActionScript as "Beep".
+            Tag::new(codes::DEFINE_SOUND, vec![1, 0, 0x04, 0x11, 0x2b, 0, 0]),
+            Tag::new(codes::SYMBOL_CLASS, vec![1, 0, 1, 0, b'B', b'e', b'e', b'p', 0]),
+            Tag::new(codes::SHOW_FRAME, vec![]),
+            // Looped three times, fading out of the left channel.
+            Tag::new(codes::START_SOUND, vec![
+                1, 0, 0x0c, 3, 0, 2,
+                0, 0, 0, 0, 0, 0x80, 0, 0x80,
+                0x44, 0xac, 0, 0, 0, 0, 0, 0x80
+            ]),
+            Tag::new(codes::SHOW_FRAME, vec![]),
+            Tag::new(codes::START_SOUND, vec![1, 0, 0x20]),
+            Tag::new(codes::START_SOUND_2, vec![b'B', b'e', b'e', b'p', 0, 0x10]),
+            Tag::new(codes::START_SOUND_2, vec![b'X', 0, 0x00])
+        ];
+        let cues = cue_sheet(&tags).unwrap();
+        let summary: Vec<_> = cues.iter().map(|cue| (cue.frame, cue.sound_id, cue.duration)).collect();
+        assert_eq!(summary, vec![(1, Some(1), Some(1.0)), (2, Some(1), Some(1.0)), (2, Some(1), Some(1.0)), (2, None, None)]);
+        assert_eq!(cues[0].info.loops, Some(3));
+        assert_eq!(cues[0].info.envelope, vec![
+            EnvelopePoint { position: 0, left: 32768, right: 32768 },
+            EnvelopePoint { position: 44100, left: 0, right: 32768 }
+        ]);
+        assert!(cues[1].info.stop);
+        assert_eq!(cues[2].class_name, Some("Beep".to_string()));
+        assert!(cues[2].info.no_multiple && !cues[2].info.stop);
+    }
+}
@@ -0,0 +1,236 @@
+//! Splitting one SWF into several smaller ones, by frame range or by scene.
+//!
+//! Each output file gets its own copy of every definition it might need, and
+//! the display list as it stood at the start of its range, so it plays
+//! correctly on its own.
+
+use std::io::Read;
+use std::ops::Range;
+
+use super::SwfHeaders;
+use error::Error;
+use primitives::{read_encoded_u32, read_string};
+use tag::{self, codes, Tag, Tags};
+use writer;
+
+/// A scene, as declared by a DefineSceneAndFrameLabelData tag.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Scene {
+    name: String,
+    frames: Range<u16>
+}
+
+impl Scene {
+    /// Returns the scene name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Returns the range of (zero-based) frames the scene covers.
+    pub fn frames(&self) -> Range<u16> {
+        self.frames.clone()
+    }
+}
+
+/// Reads a SWF body and writes one SWF per frame range.
+///
+/// Ranges are zero-based and clamped to the frames actually present. The
+/// outputs keep the signature and version of the original.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::SwfHeaders;
+/// use swf_headers::split;
+/// if let Ok((headers, decoded)) = SwfHeaders::open("example.swf") {
+///     let parts = split::split_frames(&headers, decoded, &[0..10, 10..20]).unwrap();
+///     // ...
+/// }
+/// ```
+pub fn split_frames<R: Read>(headers: &SwfHeaders, body: R, ranges: &[Range<u16>])
+        -> Result<Vec<Vec<u8>>, Error> {
+    let frames = tag::group_frames(try!(tag::read_all(body)));
+    ranges.iter().map(|range| build_range(headers, &frames, range.clone())).collect()
+}
+
+/// Reads a SWF body and writes one SWF per scene.
+///
+/// Movies without any scene information are treated as one big scene.
+pub fn split_scenes<R: Read>(headers: &SwfHeaders, body: R) -> Result<Vec<(Scene, Vec<u8>)>, Error> {
+    let tags = try!(tag::read_all(body));
+    let scenes = try!(scenes(&tags));
+    let frames = tag::group_frames(tags);
+
+    let mut out = Vec::new();
+    for scene in scenes {
+        let swf = try!(build_range(headers, &frames, scene.frames()));
+        out.push((scene, swf));
+    }
+    Ok(out)
+}
+
+/// Lists the scenes declared in a tag list, with their frame ranges worked
+/// out from the offsets of the scenes that follow them.
+pub fn scenes(tags: &[Tag]) -> Result<Vec<Scene>, Error> {
+    let frame_count = tags.iter().filter(|tag| tag.code() == codes::SHOW_FRAME).count() as u16;
+
+    let data = match tags.iter().find(|tag| tag.code() == codes::DEFINE_SCENE_AND_FRAME_LABEL_DATA) {
+        Some(tag) => tag.data(),
+        None => return Ok(vec![Scene {
+            name: "Scene 1".to_owned(),
+            frames: 0..frame_count
+        }])
+    };
+
+    let mut reader = data;
+    let count = try!(read_encoded_u32(&mut reader));
+    let mut starts = Vec::new();
+    for _ in 0..count {
+        let offset = try!(read_encoded_u32(&mut reader));
+        let name = try!(read_string(&mut reader));
+        starts.push((offset as u16, name));
+    }
+
+    let mut scenes = Vec::new();
+    for (i, &(start, ref name)) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map(|next| next.0).unwrap_or(frame_count);
+        scenes.push(Scene {
+            name: name.clone(),
+            frames: start..end
+        });
+    }
+    Ok(scenes)
+}
+
+/// Reads a SWF body and lists its frame labels, as the (zero-based) frame
+/// each is on, the label, and whether it's a named anchor, which browsers
+/// can link to with `#label` on the end of the URL.
+///
+/// Only the main timeline is included, since labels inside sprites can't
+/// be linked to.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::SwfHeaders;
+/// use swf_headers::split;
+/// if let Ok((_, decoded)) = SwfHeaders::open("example.swf") {
+///     for (frame, label, is_anchor) in split::frame_labels(decoded).unwrap() {
+///         if is_anchor {
+///             println!("#{} goes to frame {}", label, frame);
+///         }
+///     }
+/// }
+/// ```
+pub fn frame_labels<R: Read>(body: R) -> Result<Vec<(u16, String, bool)>, Error> {
+    let mut labels = Vec::new();
+    let mut frame = 0u16;
+    for tag in Tags::new(body) {
+        let tag = try!(tag);
+        match tag.code() {
+            codes::SHOW_FRAME => frame = frame.wrapping_add(1),
+            codes::FRAME_LABEL => {
+                let mut reader = tag.data();
+                let label = try!(read_string(&mut reader));
+                // SWF 6 added an optional flag byte after the name.
+                let is_anchor = reader.first() == Some(&1);
+                labels.push((frame, label, is_anchor));
+            },
+            _ => {}
+        }
+    }
+    Ok(labels)
+}
+
+fn build_range(headers: &SwfHeaders, frames: &[Vec<Tag>], range: Range<u16>) -> Result<Vec<u8>, Error> {
+    let end = (range.end as usize).min(frames.len());
+    let start = (range.start as usize).min(end);
+
+    let mut tags = Vec::new();
+
+    // Everything before the range gets flattened into the start of the first
+    // frame: definitions are kept as-is, and the display list changes are
+    // replayed back to back with no ShowFrame in between, which leaves the
+    // display list in the same state it was in when the range began. Scripts,
+    // sounds and labels from those frames are dropped.
+    for frame in &frames[..start] {
+        for tag in frame {
+            if !tag::is_frame_local_tag(tag.code()) && !is_scene_tag(tag.code()) {
+                tags.push(tag.clone());
+            }
+        }
+    }
+
+    for frame in &frames[start..end] {
+        for tag in frame {
+            if !is_scene_tag(tag.code()) {
+                tags.push(tag.clone());
+            }
+        }
+    }
+
+    let headers = SwfHeaders {
+        frame_count: (end - start) as u16,
+        ..*headers
+    };
+    writer::to_bytes(&headers, &tags)
+}
+
+// The scene table refers to frame numbers in the original movie, so it would
+// be wrong in every output.
+fn is_scene_tag(code: u16) -> bool {
+    code == codes::DEFINE_SCENE_AND_FRAME_LABEL_DATA
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tag::{codes, Tag};
+
+    fn scene_tag() -> Tag {
+        // Two scenes, "a" at frame 0 and "b" at frame 2, and no labels.
+        Tag::new(codes::DEFINE_SCENE_AND_FRAME_LABEL_DATA,
+                 vec![2, 0, b'a', 0, 2, b'b', 0, 0])
+    }
+
+    #[test]
+    fn test_scenes() {
+        let tags = vec![
+            scene_tag(),
+            Tag::new(codes::SHOW_FRAME, vec![]),
+            Tag::new(codes::SHOW_FRAME, vec![]),
+            Tag::new(codes::SHOW_FRAME, vec![])
+        ];
+        let scenes = scenes(&tags).unwrap();
+        assert_eq!(scenes.len(), 2);
+        assert_eq!(scenes[0].name(), "a");
+        assert_eq!(scenes[0].frames(), 0..2);
+        assert_eq!(scenes[1].name(), "b");
+        assert_eq!(scenes[1].frames(), 2..3);
+    }
+
+    #[test]
+    fn test_frame_labels() {
+        let mut body = Vec::new();
+        for tag in &[
+            Tag::new(codes::FRAME_LABEL, b"intro\0".to_vec()),
+            Tag::new(codes::SHOW_FRAME, vec![]),
+            Tag::new(codes::SHOW_FRAME, vec![]),
+            Tag::new(codes::FRAME_LABEL, b"menu\0\x01".to_vec()),
+            Tag::new(codes::SHOW_FRAME, vec![])
+        ] {
+            tag.write_to(&mut body).unwrap();
+        }
+        assert_eq!(frame_labels(&body[..]).unwrap(), vec![
+            (0, "intro".to_string(), false),
+            (2, "menu".to_string(), true)
+        ]);
+    }
+
+    #[test]
+    fn test_no_scene_tag() {
+        let tags = vec![Tag::new(codes::SHOW_FRAME, vec![])];
+        let scenes = scenes(&tags).unwrap();
+        assert_eq!(scenes.len(), 1);
+        assert_eq!(scenes[0].frames(), 0..1);
+    }
+}
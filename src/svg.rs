@@ -0,0 +1,328 @@
+//! Exporting shapes as SVG documents. Only built with the `svg` feature.
+//!
+//! Solid and gradient fills and solid strokes are converted. Bitmap fills
+//! have no equivalent without the bitmap itself, so they're drawn as flat
+//! grey.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use error::Error;
+use primitives::{Matrix, Rgba};
+use shape::{FillStyle, Gradient, LineStyle, Shape, ShapeRecord, SpreadMode};
+use tag::Tag;
+
+type Point = (i32, i32);
+
+#[derive(Clone, Copy)]
+struct Edge {
+    from: Point,
+    control: Option<Point>,
+    to: Point
+}
+
+impl Edge {
+    fn reversed(&self) -> Self {
+        Edge {
+            from: self.to,
+            control: self.control,
+            to: self.from
+        }
+    }
+}
+
+/// The edges using each style, collected between two sets of new styles.
+struct StyleGroup {
+    fill_styles: Vec<FillStyle>,
+    line_styles: Vec<LineStyle>,
+    fills: HashMap<u32, Vec<Edge>>,
+    lines: HashMap<u32, Vec<Edge>>
+}
+
+impl StyleGroup {
+    fn new(fill_styles: Vec<FillStyle>, line_styles: Vec<LineStyle>) -> Self {
+        StyleGroup {
+            fill_styles: fill_styles,
+            line_styles: line_styles,
+            fills: HashMap::new(),
+            lines: HashMap::new()
+        }
+    }
+}
+
+/// Parses a DefineShape tag and converts it to an SVG document, returning
+/// `None` if the tag isn't a shape.
+pub fn tag_to_svg(tag: &Tag) -> Result<Option<String>, Error> {
+    Ok(try!(Shape::parse_with_records(tag)).map(|shape| to_svg(&shape)))
+}
+
+/// Converts a shape to an SVG document, with one SVG unit per pixel.
+///
+/// The shape needs to have been parsed with `Shape::parse_with_records()`,
+/// otherwise the document will be empty.
+pub fn to_svg(shape: &Shape) -> String {
+    let groups = collect_groups(shape);
+    let bounds = shape.bounds;
+
+    let mut defs = String::new();
+    let mut body = String::new();
+    let mut gradient_count = 0;
+
+    for group in &groups {
+        let mut fill_indices: Vec<_> = group.fills.keys().cloned().collect();
+        fill_indices.sort();
+        for index in fill_indices {
+            let style = match group.fill_styles.get(index as usize - 1) {
+                Some(style) => style,
+                None => continue
+            };
+            let fill = match *style {
+                FillStyle::Solid(color) => paint("fill", color),
+                FillStyle::LinearGradient(ref gradient) => {
+                    gradient_count += 1;
+                    write_gradient(&mut defs, gradient_count, gradient, false);
+                    format!("fill=\"url(#gradient{})\"", gradient_count)
+                },
+                FillStyle::RadialGradient(ref gradient) | FillStyle::FocalGradient(ref gradient, _) => {
+                    gradient_count += 1;
+                    write_gradient(&mut defs, gradient_count, gradient, true);
+                    format!("fill=\"url(#gradient{})\"", gradient_count)
+                },
+                FillStyle::Bitmap { .. } => "fill=\"#808080\"".to_owned()
+            };
+            let path = fill_path(&group.fills[&index]);
+            let _ = writeln!(body, "  <path d=\"{}\" {} fill-rule=\"evenodd\"/>", path, fill);
+        }
+
+        let mut line_indices: Vec<_> = group.lines.keys().cloned().collect();
+        line_indices.sort();
+        for index in line_indices {
+            let style = match group.line_styles.get(index as usize - 1) {
+                Some(style) => style,
+                None => continue
+            };
+            let path = line_path(&group.lines[&index]);
+            let stroke = paint("stroke", style.color);
+            let _ = writeln!(body,
+                "  <path d=\"{}\" fill=\"none\" {} stroke-width=\"{}\" \
+                 stroke-linecap=\"round\" stroke-linejoin=\"round\"/>",
+                path, stroke, px(style.width.max(1) as i32));
+        }
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\">",
+        px(bounds.width()), px(bounds.height()),
+        px(bounds.x_min), px(bounds.y_min), px(bounds.width()), px(bounds.height()));
+    if !defs.is_empty() {
+        out.push_str("  <defs>\n");
+        out.push_str(&defs);
+        out.push_str("  </defs>\n");
+    }
+    out.push_str(&body);
+    out.push_str("</svg>\n");
+    out
+}
+
+/// Walks the shape records, sorting every edge into the fill and line
+/// styles it uses.
+fn collect_groups(shape: &Shape) -> Vec<StyleGroup> {
+    let mut groups = vec![StyleGroup::new(shape.fill_styles.clone(), shape.line_styles.clone())];
+    let records = match shape.records {
+        Some(ref records) => records,
+        None => return groups
+    };
+
+    let mut pen = (0, 0);
+    let (mut fill_0, mut fill_1, mut line) = (0, 0, 0);
+
+    for record in records {
+        let edge = match *record {
+            ShapeRecord::StyleChange(ref change) => {
+                if let Some((ref fills, ref lines)) = change.new_styles {
+                    groups.push(StyleGroup::new(fills.clone(), lines.clone()));
+                    fill_0 = 0;
+                    fill_1 = 0;
+                    line = 0;
+                }
+                if let Some(point) = change.move_to {
+                    pen = point;
+                }
+                fill_0 = change.fill_style_0.unwrap_or(fill_0);
+                fill_1 = change.fill_style_1.unwrap_or(fill_1);
+                line = change.line_style.unwrap_or(line);
+                continue;
+            },
+            ShapeRecord::StraightEdge { delta_x, delta_y } => Edge {
+                from: pen,
+                control: None,
+                to: (pen.0 + delta_x, pen.1 + delta_y)
+            },
+            ShapeRecord::CurvedEdge { control_delta_x, control_delta_y, anchor_delta_x, anchor_delta_y } => {
+                let control = (pen.0 + control_delta_x, pen.1 + control_delta_y);
+                Edge {
+                    from: pen,
+                    control: Some(control),
+                    to: (control.0 + anchor_delta_x, control.1 + anchor_delta_y)
+                }
+            }
+        };
+        pen = edge.to;
+
+        let group = groups.last_mut().unwrap();
+        // Fill style 1 is on the right of the edge as drawn, fill style 0 on
+        // the left, so fill 0 edges get flipped to wind the same way.
+        if fill_1 != 0 {
+            group.fills.entry(fill_1).or_insert_with(Vec::new).push(edge);
+        }
+        if fill_0 != 0 {
+            group.fills.entry(fill_0).or_insert_with(Vec::new).push(edge.reversed());
+        }
+        if line != 0 {
+            group.lines.entry(line).or_insert_with(Vec::new).push(edge);
+        }
+    }
+    groups
+}
+
+/// Chains a fill's edges into closed contours. Edges arrive in no useful
+/// order, so each contour is found by following edges end to start.
+fn fill_path(edges: &[Edge]) -> String {
+    let mut starts: HashMap<Point, Vec<usize>> = HashMap::new();
+    for (i, edge) in edges.iter().enumerate() {
+        starts.entry(edge.from).or_insert_with(Vec::new).push(i);
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut d = String::new();
+    for first in 0..edges.len() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+        let start = edges[first].from;
+        let _ = write!(d, "M{} {}", px(start.0), px(start.1));
+        write_segment(&mut d, &edges[first]);
+
+        let mut current = edges[first].to;
+        while current != start {
+            let next = starts.get(&current).and_then(|candidates| {
+                candidates.iter().cloned().find(|&i| !used[i])
+            });
+            match next {
+                Some(i) => {
+                    used[i] = true;
+                    write_segment(&mut d, &edges[i]);
+                    current = edges[i].to;
+                },
+                None => break
+            }
+        }
+        d.push('Z');
+    }
+    d
+}
+
+/// Lines keep the order they were drawn in, only moving the pen when an
+/// edge doesn't continue from the previous one.
+fn line_path(edges: &[Edge]) -> String {
+    let mut d = String::new();
+    let mut pen = None;
+    for edge in edges {
+        if pen != Some(edge.from) {
+            let _ = write!(d, "M{} {}", px(edge.from.0), px(edge.from.1));
+        }
+        write_segment(&mut d, edge);
+        pen = Some(edge.to);
+    }
+    d
+}
+
+fn write_segment(d: &mut String, edge: &Edge) {
+    let _ = match edge.control {
+        Some(control) => write!(d, "Q{} {} {} {}",
+                                px(control.0), px(control.1), px(edge.to.0), px(edge.to.1)),
+        None => write!(d, "L{} {}", px(edge.to.0), px(edge.to.1))
+    };
+}
+
+fn write_gradient(defs: &mut String, id: usize, gradient: &Gradient, radial: bool) {
+    // Gradients are defined over a 32768 twip square centered on the origin.
+    let transform = svg_matrix(&gradient.matrix);
+    let spread = match gradient.spread {
+        SpreadMode::Pad => "pad",
+        SpreadMode::Reflect => "reflect",
+        SpreadMode::Repeat => "repeat"
+    };
+    let _ = if radial {
+        writeln!(defs,
+            "    <radialGradient id=\"gradient{}\" gradientUnits=\"userSpaceOnUse\" \
+             cx=\"0\" cy=\"0\" r=\"819.2\" spreadMethod=\"{}\" gradientTransform=\"{}\">",
+            id, spread, transform)
+    } else {
+        writeln!(defs,
+            "    <linearGradient id=\"gradient{}\" gradientUnits=\"userSpaceOnUse\" \
+             x1=\"-819.2\" y1=\"0\" x2=\"819.2\" y2=\"0\" spreadMethod=\"{}\" gradientTransform=\"{}\">",
+            id, spread, transform)
+    };
+    for &(ratio, color) in &gradient.stops {
+        let _ = writeln!(defs,
+            "      <stop offset=\"{}\" stop-color=\"#{:02x}{:02x}{:02x}\" stop-opacity=\"{}\"/>",
+            ratio as f64 / 255.0, color.r, color.g, color.b, color.a as f64 / 255.0);
+    }
+    let _ = writeln!(defs, "    </{}>", if radial { "radialGradient" } else { "linearGradient" });
+}
+
+fn svg_matrix(matrix: &Matrix) -> String {
+    format!("matrix({} {} {} {} {} {})",
+            matrix.scale_x, matrix.rotate_skew_0, matrix.rotate_skew_1, matrix.scale_y,
+            px(matrix.translate_x), px(matrix.translate_y))
+}
+
+fn paint(attribute: &str, color: Rgba) -> String {
+    let mut out = format!("{}=\"#{:02x}{:02x}{:02x}\"", attribute, color.r, color.g, color.b);
+    if color.a != 255 {
+        let _ = write!(out, " {}-opacity=\"{}\"", attribute, color.a as f64 / 255.0);
+    }
+    out
+}
+
+fn px(twips: i32) -> f64 {
+    twips as f64 / 20.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primitives::{Rect, Rgba};
+    use shape::{FillStyle, Shape, ShapeRecord, StyleChange};
+
+    #[test]
+    fn test_square() {
+        let edge = |delta_x, delta_y| ShapeRecord::StraightEdge { delta_x: delta_x, delta_y: delta_y };
+        let shape = Shape {
+            id: 1,
+            version: 3,
+            bounds: Rect { x_min: 0, x_max: 200, y_min: 0, y_max: 200 },
+            edge_bounds: None,
+            uses_non_scaling_strokes: false,
+            uses_scaling_strokes: false,
+            fill_styles: vec![FillStyle::Solid(Rgba { r: 255, g: 0, b: 0, a: 255 })],
+            line_styles: vec![],
+            records: Some(vec![
+                ShapeRecord::StyleChange(StyleChange {
+                    move_to: None,
+                    fill_style_0: None,
+                    fill_style_1: Some(1),
+                    line_style: None,
+                    new_styles: None
+                }),
+                edge(200, 0), edge(0, 200), edge(-200, 0), edge(0, -200)
+            ])
+        };
+        let svg = to_svg(&shape);
+        assert!(svg.contains("viewBox=\"0 0 10 10\""));
+        assert!(svg.contains("d=\"M0 0L10 0L10 10L0 10L0 0Z\" fill=\"#ff0000\""));
+    }
+}
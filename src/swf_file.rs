@@ -0,0 +1,192 @@
+//! One handle on a whole SWF file, for when the headers are only the start.
+//!
+//! `SwfHeaders::read_from()` hands back the headers and a stream of the
+//! body, and everything past that, such as the tags, the assets or a
+//! validation report, means passing the stream on to the right module. An
+//! `SwfFile` keeps the lot together, working each part out the first time
+//! it's asked for.
+
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{Anomaly, SwfHeaders};
+use assets::{self, Assets};
+use decoded_swf::{self, DecodedSwf};
+use error::Error;
+use options::ParseOptions;
+use tag::{Tag, Tags};
+use validate::{self, Finding};
+
+/// A SWF file, read into memory, with its headers parsed.
+///
+/// The tags are read the first time `tags()` or `assets()` needs them, and
+/// kept from then on. The file is kept as it was read, compressed or not,
+/// so `validate()` can check it byte for byte, and `body()` can decompress
+/// it again as often as needed.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::SwfFile;
+/// if let Ok(mut swf) = SwfFile::open("example.swf") {
+///     println!("{}x{}", swf.headers().dimensions().0, swf.headers().dimensions().1);
+///     if let Ok(tags) = swf.tags() {
+///         println!("{} tags", tags.len());
+///     }
+///     for finding in swf.validate() {
+///         println!("{}", finding);
+///     }
+/// }
+/// ```
+pub struct SwfFile {
+    raw: Arc<[u8]>,
+    headers: SwfHeaders,
+    warnings: Vec<Anomaly>,
+    options: ParseOptions,
+    // The body from parsing the headers, until something takes it.
+    decoded: Option<DecodedSwf>,
+    tags: Option<Vec<Tag>>
+}
+
+impl SwfFile {
+    /// Reads the file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::open_with_options(path, &ParseOptions::default())
+    }
+
+    /// Like `open()`, but opening and parsing the file with `options`.
+    pub fn open_with_options<P: AsRef<Path>>(path: P, options: &ParseOptions) -> Result<Self, Error> {
+        Self::read_with_options(try!(decoded_swf::open_file(path.as_ref(), options)), options)
+    }
+
+    /// Reads a whole file from `reader`.
+    pub fn read_from<R: Read>(reader: R) -> Result<Self, Error> {
+        Self::read_with_options(reader, &ParseOptions::default())
+    }
+
+    /// Like `read_from()`, but parsing with `options`. A stream longer than
+    /// `ParseOptions::max_file_size()` allows is `Error::LimitExceeded`.
+    pub fn read_with_options<R: Read>(reader: R, options: &ParseOptions) -> Result<Self, Error> {
+        let limit = options.file_size_limit();
+        let mut raw = Vec::new();
+        try!(reader.take(limit.saturating_add(1)).read_to_end(&mut raw));
+        if raw.len() as u64 > limit {
+            return Err(Error::LimitExceeded);
+        }
+
+        let raw: Arc<[u8]> = raw.into();
+        let (headers, decoded) = try!(SwfHeaders::read_with_options(Cursor::new(raw.clone()), options));
+        Ok(SwfFile {
+            raw: raw,
+            warnings: headers.anomalies(),
+            headers: headers,
+            options: options.clone(),
+            decoded: Some(decoded),
+            tags: None
+        })
+    }
+
+    /// Returns the parsed headers.
+    pub fn headers(&self) -> &SwfHeaders {
+        &self.headers
+    }
+
+    /// Returns anything unusual about the headers, as
+    /// `SwfHeaders::anomalies()` finds it.
+    pub fn warnings(&self) -> &[Anomaly] {
+        &self.warnings
+    }
+
+    /// Returns the file as it was read.
+    pub fn data(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// Returns a stream of the decompressed body, starting just after the
+    /// headers, as `SwfHeaders::read_from()` would.
+    ///
+    /// The first call hands over the stream the headers were parsed from.
+    /// Later ones decompress the file again.
+    pub fn body(&mut self) -> Result<DecodedSwf, Error> {
+        match self.decoded.take() {
+            Some(decoded) => Ok(decoded),
+            None => SwfHeaders::read_with_options(Cursor::new(self.raw.clone()), &self.options)
+                .map(|(_, decoded)| decoded)
+        }
+    }
+
+    /// Returns the main timeline's tags, reading them the first time.
+    ///
+    /// A file whose tags can't all be read gives the error each time,
+    /// rather than some of the tags.
+    pub fn tags(&mut self) -> Result<&[Tag], Error> {
+        if self.tags.is_none() {
+            let body = try!(self.body());
+            let tags: Vec<Tag> = try!(Tags::with_options(body, self.options.clone()).collect());
+            self.tags = Some(tags);
+        }
+        Ok(self.tags.as_ref().unwrap())
+    }
+
+    /// Collects every bitmap, event sound and binary blob in the file.
+    pub fn assets(&mut self) -> Result<Assets, Error> {
+        assets::collect(try!(self.tags()))
+    }
+
+    /// Checks the file against the spec, as `validate::validate()` does.
+    pub fn validate(&self) -> Vec<Finding> {
+        validate::validate_bytes(&self.raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read};
+
+    use {Anomaly, ParseOptions, Signature, SwfHeaders};
+    use tag::{codes, Tag};
+    use writer;
+
+    #[test]
+    fn test_swf_file() {
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 11000,
+            height: 8000,
+            frame_rate: 240,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = vec![
+            Tag::new(codes::FILE_ATTRIBUTES, vec![0, 0, 0, 0]),
+            Tag::new(codes::DEFINE_BINARY_DATA, vec![1, 0, 0, 0, 0, 0, b'h', b'i']),
+            Tag::new(codes::SHOW_FRAME, vec![])
+        ];
+        let data = writer::to_bytes(&headers, &tags).unwrap();
+
+        let mut swf = SwfFile::read_from(Cursor::new(data.clone())).unwrap();
+        assert_eq!(swf.headers().dimensions(), (550, 400));
+        assert_eq!(swf.warnings(), &[Anomaly::FrameRate(240)][..]);
+        assert_eq!(swf.data(), &data[..]);
+        assert_eq!(swf.tags().unwrap(), &tags[..]);
+        // The tags are kept, so this doesn't need the body again.
+        let assets = swf.assets().unwrap();
+        assert_eq!(assets.binary_data[0].data, b"hi".to_vec());
+        assert!(assets.bitmaps.is_empty() && assets.sounds.is_empty());
+        assert_eq!(swf.validate(), Vec::new());
+
+        // The body comes back whole, even after the tags were read from it.
+        let mut body = Vec::new();
+        swf.body().unwrap().read_to_end(&mut body).unwrap();
+        // FileAttributes, DefineBinaryData, ShowFrame and End.
+        assert_eq!(body.len(), 6 + 10 + 2 + 2);
+
+        let limited = ParseOptions::new().max_file_size(data.len() as u64 - 1);
+        assert_eq!(SwfFile::read_with_options(Cursor::new(data), &limited).err().unwrap().kind(),
+                   "limit-exceeded");
+    }
+}
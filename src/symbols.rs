@@ -0,0 +1,497 @@
+//! Pulling a single exported symbol out of a movie into a SWF of its own.
+//!
+//! A symbol takes along every character it depends on. The references
+//! followed are placements inside sprites, bitmap fills in shapes, fonts in
+//! static and dynamic text, the characters making up buttons and their
+//! sounds, and JPEGTables for DefineBits. Tags that add to a character, such
+//! as DefineFontInfo or DefineScalingGrid, come along with it. Morph shapes
+//! are copied, but their bitmap fills aren't followed.
+//!
+//! The same references are used by `character_sizes()` to work out what each
+//! character costs, for finding what makes a movie large.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use super::SwfHeaders;
+use button::Button;
+use error::Error;
+use merge::parse_symbol_list;
+use primitives::{read_u16_at, BitReader};
+use shape::{FillStyle, Shape, ShapeRecord};
+use tag::{self, codes, Tag, Tags};
+use text::{EditText, StaticText};
+use timeline::Placement;
+use writer;
+
+/// Reads a SWF body and writes a SWF containing only the character exported
+/// as `name`, along with everything it depends on.
+///
+/// The new movie keeps the original's headers, FileAttributes and
+/// background color, exports the character under the same name, and has a
+/// single frame with the character placed at the origin if it's something
+/// that can be placed. Returns `None` if nothing defined in the movie is
+/// exported under that name.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::SwfHeaders;
+/// use swf_headers::symbols;
+/// if let Ok((headers, decoded)) = SwfHeaders::open("example.swf") {
+///     if let Some(swf) = symbols::extract_symbol(&headers, decoded, "logo_mc").unwrap() {
+///         // ...
+///     }
+/// }
+/// ```
+pub fn extract_symbol<R: Read>(headers: &SwfHeaders, body: R, name: &str) -> Result<Option<Vec<u8>>, Error> {
+    let tags = try!(tag::read_all(body));
+
+    let mut exported = None;
+    for tag in tags.iter().filter(|tag| tag.code() == codes::EXPORT_ASSETS) {
+        let symbols = try!(parse_symbol_list(tag.data()));
+        if let Some(&(id, _)) = symbols.iter().find(|&&(_, ref symbol)| symbol == name) {
+            exported = Some(id);
+            break;
+        }
+    }
+
+    // Only the first definition of an ID counts, the same as in the player.
+    let mut definitions = HashMap::new();
+    let mut companions = HashMap::new();
+    for (index, tag) in tags.iter().enumerate() {
+        if let Some(id) = tag::character_id(tag) {
+            definitions.entry(id).or_insert(index);
+        } else if tag::refers_to_character(tag.code()) {
+            if let Ok(id) = read_u16_at(tag.data(), 0) {
+                companions.entry(id).or_insert_with(Vec::new).push(index);
+            }
+        }
+    }
+    let id = match exported {
+        Some(id) if definitions.contains_key(&id) => id,
+        _ => return Ok(None)
+    };
+
+    let mut needed = HashSet::new();
+    let mut pending = vec![id];
+    while let Some(id) = pending.pop() {
+        if !needed.insert(id) {
+            continue;
+        }
+        if let Some(&index) = definitions.get(&id) {
+            pending.extend(try!(dependencies(&tags[index])));
+        }
+        for &index in companions.get(&id).map_or(&[][..], |indices| &indices[..]) {
+            pending.extend(try!(dependencies(&tags[index])));
+        }
+    }
+    let uses_jpeg_tables = needed.iter().any(|id| {
+        definitions.get(id).map_or(false, |&index| tags[index].code() == codes::DEFINE_BITS)
+    });
+
+    let mut out = Vec::new();
+    for (index, tag) in tags.iter().enumerate() {
+        let keep = match tag.code() {
+            codes::FILE_ATTRIBUTES | codes::SET_BACKGROUND_COLOR => true,
+            codes::JPEG_TABLES => uses_jpeg_tables,
+            _ => match tag::character_id(tag) {
+                Some(id) => needed.contains(&id) && definitions[&id] == index,
+                None => tag::refers_to_character(tag.code()) &&
+                        read_u16_at(tag.data(), 0).map(|id| needed.contains(&id)).unwrap_or(false)
+            }
+        };
+        if keep {
+            out.push(tag.clone());
+        }
+    }
+
+    let mut export = Vec::new();
+    try!(export.write_u16::<LittleEndian>(1));
+    try!(export.write_u16::<LittleEndian>(id));
+    export.extend_from_slice(name.as_bytes());
+    export.push(0);
+    out.push(Tag::new(codes::EXPORT_ASSETS, export));
+    if is_placeable(tags[definitions[&id]].code()) {
+        // HasCharacter, at depth 1.
+        let mut place = vec![0x02, 1, 0];
+        try!(place.write_u16::<LittleEndian>(id));
+        out.push(Tag::new(codes::PLACE_OBJECT_2, place));
+    }
+    out.push(Tag::new(codes::SHOW_FRAME, vec![]));
+
+    let headers = SwfHeaders {
+        frame_count: 1,
+        ..*headers
+    };
+    writer::to_bytes(&headers, &out).map(Some)
+}
+
+/// Returns the IDs of the characters a tag refers to, as far as the module
+/// docs say they're followed. IDs may be repeated.
+pub fn dependencies(tag: &Tag) -> Result<Vec<u16>, Error> {
+    let data = tag.data();
+    match tag.code() {
+        codes::DEFINE_SPRITE => {
+            let mut ids = Vec::new();
+            for nested in Tags::new(data.get(4..).unwrap_or(&[])) {
+                if let Some(Placement { character_id: Some(id), .. }) = try!(Placement::parse(&try!(nested))) {
+                    ids.push(id);
+                }
+            }
+            Ok(ids)
+        },
+        codes::DEFINE_SHAPE | codes::DEFINE_SHAPE_2 | codes::DEFINE_SHAPE_3 | codes::DEFINE_SHAPE_4 => {
+            let shape = match try!(Shape::parse_with_records(tag)) {
+                Some(shape) => shape,
+                None => return Ok(vec![])
+            };
+            let mut fills: Vec<&FillStyle> = shape.fill_styles.iter().collect();
+            fills.extend(shape.line_styles.iter().filter_map(|style| style.fill.as_ref()));
+            for record in shape.records.iter().flat_map(|records| records) {
+                if let ShapeRecord::StyleChange(ref change) = *record {
+                    if let Some((ref fill_styles, ref line_styles)) = change.new_styles {
+                        fills.extend(fill_styles);
+                        fills.extend(line_styles.iter().filter_map(|style| style.fill.as_ref()));
+                    }
+                }
+            }
+            Ok(fills.into_iter().filter_map(|fill| match *fill {
+                FillStyle::Bitmap { id, .. } if id != 0xffff => Some(id),
+                _ => None
+            }).collect())
+        },
+        codes::DEFINE_TEXT | codes::DEFINE_TEXT_2 => {
+            let text = try!(StaticText::parse(tag));
+            Ok(text.iter().flat_map(|text| &text.records).filter_map(|record| record.font_id).collect())
+        },
+        codes::DEFINE_EDIT_TEXT => {
+            Ok(try!(EditText::parse(tag)).and_then(|field| field.font_id).into_iter().collect())
+        },
+        codes::DEFINE_BUTTON | codes::DEFINE_BUTTON_2 => {
+            Ok(try!(Button::parse(tag)).map(|button| button.characters()).unwrap_or_default())
+        },
+        codes::DEFINE_BUTTON_SOUND => {
+            let mut reader = BitReader::new(data);
+            try!(reader.read_u16());
+            let mut ids = Vec::new();
+            for _ in 0..4 {
+                let id = try!(reader.read_u16());
+                if id != 0 {
+                    ids.push(id);
+                    try!(skip_sound_info(&mut reader));
+                }
+            }
+            Ok(ids)
+        },
+        _ => Ok(vec![])
+    }
+}
+
+/// Skips over a SOUNDINFO.
+fn skip_sound_info(reader: &mut BitReader) -> Result<(), Error> {
+    let flags = try!(reader.read_u8());
+    let mut skip = 0;
+    if flags & 0x01 != 0 {
+        skip += 4;
+    }
+    if flags & 0x02 != 0 {
+        skip += 4;
+    }
+    if flags & 0x04 != 0 {
+        skip += 2;
+    }
+    for _ in 0..skip {
+        try!(reader.read_u8());
+    }
+    if flags & 0x08 != 0 {
+        let points = try!(reader.read_u8());
+        for _ in 0..points as u32 * 8 {
+            try!(reader.read_u8());
+        }
+    }
+    Ok(())
+}
+
+/// What a character costs in bytes, as found by `character_sizes()`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CharacterSize {
+    /// The character ID.
+    pub id: u16,
+    /// The code of the tag defining it.
+    pub code: u16,
+    /// The bytes taken by its define tag and the tags adding to it, such as
+    /// DefineFontInfo or StartSound, headers included.
+    pub own_bytes: usize,
+    /// `own_bytes` plus those of every character in `exclusive`: what
+    /// removing the character would save.
+    pub total_bytes: usize,
+    /// The characters it depends on that nothing else uses, directly or
+    /// through other characters, in ID order.
+    pub exclusive: Vec<u16>,
+    /// The character this one is exclusive to, if any. Following these up
+    /// gives the nesting for a treemap.
+    pub parent: Option<u16>
+}
+
+/// Works out the byte cost of every character defined in a tag list, in the
+/// order they're defined.
+///
+/// A dependency only counts towards a character if nothing else refers to
+/// it. Anything the main timeline places or exports counts as used from
+/// outside, so it's never exclusive to another character.
+pub fn character_sizes(tags: &[Tag]) -> Result<Vec<CharacterSize>, Error> {
+    let mut order = Vec::new();
+    let mut sizes: HashMap<u16, (u16, usize)> = HashMap::new();
+    let mut depends_on: HashMap<u16, Vec<u16>> = HashMap::new();
+    let mut used_outside = HashSet::new();
+    for tag in tags {
+        let id = match tag::character_id(tag) {
+            Some(id) => {
+                // Only the first definition of an ID counts, the same as in
+                // the player.
+                if sizes.contains_key(&id) {
+                    continue;
+                }
+                order.push(id);
+                sizes.insert(id, (tag.code(), 0));
+                id
+            },
+            None if tag::refers_to_character(tag.code()) => match read_u16_at(tag.data(), 0) {
+                Ok(id) => id,
+                Err(_) => continue
+            },
+            None => {
+                if tag.code() == codes::EXPORT_ASSETS || tag.code() == codes::SYMBOL_CLASS {
+                    used_outside.extend(try!(parse_symbol_list(tag.data())).into_iter().map(|(id, _)| id));
+                } else if let Some(Placement { character_id: Some(id), .. }) = try!(Placement::parse(tag)) {
+                    used_outside.insert(id);
+                }
+                continue;
+            }
+        };
+        if let Some(size) = sizes.get_mut(&id) {
+            size.1 += tag.encoded_len();
+        }
+        depends_on.entry(id).or_insert_with(Vec::new).extend(try!(dependencies(tag)));
+    }
+
+    let mut users: HashMap<u16, HashSet<u16>> = HashMap::new();
+    for (&id, ids) in &mut depends_on {
+        ids.retain(|dependency| *dependency != id && sizes.contains_key(dependency));
+        ids.sort();
+        ids.dedup();
+        for &dependency in ids.iter() {
+            users.entry(dependency).or_insert_with(HashSet::new).insert(id);
+        }
+    }
+
+    // A character takes along a dependency once everything using it has
+    // been taken along.
+    let mut exclusive_sets = HashMap::new();
+    for &id in &order {
+        let mut taken = HashSet::new();
+        taken.insert(id);
+        loop {
+            let candidates: Vec<u16> = taken.iter()
+                .flat_map(|user| depends_on.get(user).map_or(&[][..], |ids| &ids[..]))
+                .cloned()
+                .collect();
+            let before = taken.len();
+            for dependency in candidates {
+                if !taken.contains(&dependency) && !used_outside.contains(&dependency) &&
+                   users[&dependency].iter().all(|user| taken.contains(user)) {
+                    taken.insert(dependency);
+                }
+            }
+            if taken.len() == before {
+                break;
+            }
+        }
+        taken.remove(&id);
+        exclusive_sets.insert(id, taken);
+    }
+
+    Ok(order.iter().map(|&id| {
+        let (code, own_bytes) = sizes[&id];
+        let mut exclusive: Vec<u16> = exclusive_sets[&id].iter().cloned().collect();
+        exclusive.sort();
+        // Exclusive sets nest, so the closest owner is the smallest one.
+        let parent = order.iter()
+            .filter(|owner| exclusive_sets[*owner].contains(&id))
+            .min_by_key(|owner| exclusive_sets[*owner].len())
+            .cloned();
+        CharacterSize {
+            id: id,
+            code: code,
+            own_bytes: own_bytes,
+            total_bytes: own_bytes + exclusive.iter().map(|id| sizes[id].1).sum::<usize>(),
+            exclusive: exclusive,
+            parent: parent
+        }
+    }).collect())
+}
+
+/// Returns true for characters that can go on the display list by
+/// themselves.
+fn is_placeable(code: u16) -> bool {
+    match code {
+        codes::DEFINE_SHAPE | codes::DEFINE_SHAPE_2 | codes::DEFINE_SHAPE_3 | codes::DEFINE_SHAPE_4 |
+        codes::DEFINE_MORPH_SHAPE | codes::DEFINE_MORPH_SHAPE_2 | codes::DEFINE_SPRITE |
+        codes::DEFINE_BUTTON | codes::DEFINE_BUTTON_2 | codes::DEFINE_TEXT | codes::DEFINE_TEXT_2 |
+        codes::DEFINE_EDIT_TEXT | codes::DEFINE_VIDEO_STREAM => true,
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use {Signature, SwfHeaders};
+    use tag::{self, codes, Tag};
+    use writer;
+
+    fn headers() -> SwfHeaders {
+        SwfHeaders {
+            signature: Signature::Uncompressed,
+            version: 8,
+            file_length: 0,
+            width: 2000,
+            height: 2000,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        }
+    }
+
+    #[test]
+    fn test_extract_symbol() {
+        let mut sprite = vec![3, 0, 1, 0];
+        Tag::new(codes::PLACE_OBJECT_2, vec![0x02, 1, 0, 2, 0]).write_to(&mut sprite).unwrap();
+        Tag::new(codes::SHOW_FRAME, vec![]).write_to(&mut sprite).unwrap();
+        Tag::new(codes::END, vec![]).write_to(&mut sprite).unwrap();
+
+        let tags = vec![
+            Tag::new(codes::SET_BACKGROUND_COLOR, vec![0, 0, 0]),
+            Tag::new(codes::JPEG_TABLES, vec![0xff, 0xd8, 0xff, 0xd9]),
+            Tag::new(codes::DEFINE_BITS_LOSSLESS, vec![1, 0, 3, 1, 0, 1, 0, 0]),
+            // Shape 2, filled with bitmap 1.
+            Tag::new(codes::DEFINE_SHAPE, vec![2, 0, 0, 1, 0x41, 1, 0, 0, 0, 0x10, 0]),
+            Tag::new(codes::DEFINE_SHAPE, vec![4, 0, 0, 0, 0, 0, 0]),
+            Tag::new(codes::DEFINE_SPRITE, sprite.clone()),
+            Tag::new(codes::DEFINE_SCALING_GRID, vec![3, 0, 0]),
+            Tag::new(codes::EXPORT_ASSETS, vec![2, 0, 3, 0, b'l', b'o', b'g', b'o', 0, 4, 0, b'x', 0]),
+            Tag::new(codes::SHOW_FRAME, vec![])
+        ];
+        let (_, body) = SwfHeaders::read_from(Cursor::new(writer::to_bytes(&headers(), &tags).unwrap())).unwrap();
+
+        let extracted = extract_symbol(&headers(), body, "logo").unwrap().unwrap();
+        let (new_headers, body) = SwfHeaders::read_from(Cursor::new(extracted)).unwrap();
+        assert_eq!(new_headers.frame_count(), 1);
+        let codes: Vec<u16> = tag::read_all(body).unwrap().iter().map(|tag| tag.code()).collect();
+        assert_eq!(codes, vec![
+            codes::SET_BACKGROUND_COLOR,
+            codes::DEFINE_BITS_LOSSLESS,
+            codes::DEFINE_SHAPE,
+            codes::DEFINE_SPRITE,
+            codes::DEFINE_SCALING_GRID,
+            codes::EXPORT_ASSETS,
+            codes::PLACE_OBJECT_2,
+            codes::SHOW_FRAME
+        ]);
+
+        let (_, body) = SwfHeaders::read_from(Cursor::new(writer::to_bytes(&headers(), &tags).unwrap())).unwrap();
+        assert!(extract_symbol(&headers(), body, "missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_extract_text_keeps_anti_aliasing() {
+        let tags = vec![
+            // Font 6, with no glyphs, and its alignment zones.
+            Tag::new(codes::DEFINE_FONT_2, vec![6, 0, 0, 0, 1, b'A', 0, 0]),
+            Tag::new(codes::DEFINE_FONT_ALIGN_ZONES, vec![6, 0, 0x40]),
+            // Text 5 in font 6, then its settings, and settings for text
+            // that isn't extracted.
+            Tag::new(codes::DEFINE_TEXT, vec![5, 0, 0, 0, 1, 1, 0x88, 6, 0, 12, 0, 1, 0x80, 0]),
+            Tag::new(codes::CSM_TEXT_SETTINGS, vec![5, 0, 0x48, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            Tag::new(codes::CSM_TEXT_SETTINGS, vec![7, 0, 0x48, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            Tag::new(codes::EXPORT_ASSETS, vec![1, 0, 5, 0, b't', 0]),
+            Tag::new(codes::SHOW_FRAME, vec![])
+        ];
+        let (_, body) = SwfHeaders::read_from(Cursor::new(writer::to_bytes(&headers(), &tags).unwrap())).unwrap();
+
+        let extracted = extract_symbol(&headers(), body, "t").unwrap().unwrap();
+        let (_, body) = SwfHeaders::read_from(Cursor::new(extracted)).unwrap();
+        let extracted = tag::read_all(body).unwrap();
+        let codes: Vec<u16> = extracted.iter().map(|tag| tag.code()).collect();
+        assert_eq!(codes, vec![
+            codes::DEFINE_FONT_2,
+            codes::DEFINE_FONT_ALIGN_ZONES,
+            codes::DEFINE_TEXT,
+            codes::CSM_TEXT_SETTINGS,
+            codes::EXPORT_ASSETS,
+            codes::PLACE_OBJECT_2,
+            codes::SHOW_FRAME
+        ]);
+        assert_eq!(extracted[3], tags[3]);
+    }
+
+    #[test]
+    fn test_character_sizes() {
+        // Bitmap 1 fills shapes 2 and 4, which sprite 3 places. Bitmap 5 is
+        // exported, so it belongs to nothing even though shape 6 uses it.
+        let mut sprite = vec![3, 0, 1, 0];
+        Tag::new(codes::PLACE_OBJECT_2, vec![0x02, 1, 0, 2, 0]).write_to(&mut sprite).unwrap();
+        Tag::new(codes::PLACE_OBJECT_2, vec![0x02, 2, 0, 4, 0]).write_to(&mut sprite).unwrap();
+        Tag::new(codes::END, vec![]).write_to(&mut sprite).unwrap();
+        let sprite_len = sprite.len() + 2;
+        let tags = vec![
+            Tag::new(codes::DEFINE_BITS_LOSSLESS, vec![1, 0, 3, 1, 0, 1, 0, 0]),
+            Tag::new(codes::DEFINE_SHAPE, vec![2, 0, 0, 1, 0x41, 1, 0, 0, 0, 0x10, 0]),
+            Tag::new(codes::DEFINE_SHAPE, vec![4, 0, 0, 1, 0x41, 1, 0, 0, 0, 0x10, 0]),
+            Tag::new(codes::DEFINE_SPRITE, sprite),
+            Tag::new(codes::DEFINE_BITS_LOSSLESS, vec![5, 0, 3, 1, 0, 1, 0, 0]),
+            Tag::new(codes::DEFINE_SHAPE, vec![6, 0, 0, 1, 0x41, 5, 0, 0, 0, 0x10, 0]),
+            Tag::new(codes::EXPORT_ASSETS, vec![1, 0, 5, 0, b'b', 0]),
+            Tag::new(codes::PLACE_OBJECT_2, vec![0x02, 1, 0, 3, 0])
+        ];
+
+        let sizes = character_sizes(&tags).unwrap();
+        assert_eq!(sizes.iter().map(|size| size.id).collect::<Vec<_>>(), vec![1, 2, 4, 3, 5, 6]);
+        assert_eq!(sizes[0].own_bytes, 10);
+        assert_eq!(sizes[0].parent, Some(3));
+        assert_eq!(sizes[1].parent, Some(3));
+        assert!(sizes[1].exclusive.is_empty());
+        assert_eq!(sizes[3], CharacterSize {
+            id: 3,
+            code: codes::DEFINE_SPRITE,
+            own_bytes: sprite_len,
+            total_bytes: sprite_len + 10 + 13 + 13,
+            exclusive: vec![1, 2, 4],
+            parent: None
+        });
+        assert_eq!((sizes[4].parent, sizes[5].total_bytes), (None, 13));
+    }
+
+    #[test]
+    fn test_text_and_button_dependencies() {
+        // DefineText 5 with an empty RECT and matrix, 1-bit glyphs and
+        // advances, and one record in font 6 with a single glyph.
+        let text = Tag::new(codes::DEFINE_TEXT, vec![5, 0, 0, 0, 1, 1, 0x88, 6, 0, 12, 0, 1, 0x80, 0]);
+        assert_eq!(dependencies(&text).unwrap(), vec![6]);
+
+        // DefineButton2 7 with an up state of character 8 and an over state
+        // of character 9, both with an empty color transform.
+        let button = Tag::new(codes::DEFINE_BUTTON_2, vec![
+            7, 0, 0, 0, 0,
+            0x01, 8, 0, 1, 0, 0, 0,
+            0x02, 9, 0, 1, 0, 0, 0,
+            0
+        ]);
+        assert_eq!(dependencies(&button).unwrap(), vec![8, 9]);
+    }
+}
@@ -0,0 +1,711 @@
+//! Reading and writing the tags that make up everything after the header.
+
+use std::io::{self, Cursor, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use error::Error;
+use options::ParseOptions;
+use primitives::read_u16_at;
+
+/// Tag codes used throughout the crate.
+///
+/// This is nowhere near the full list in the spec, just the ones this crate
+/// needs to tell apart. `tag_codes` has the full list, with names.
+pub mod codes {
+    /// Marks the end of a tag list.
+    pub const END: u16 = 0;
+    /// Marks the end of a frame.
+    pub const SHOW_FRAME: u16 = 1;
+    /// DefineShape.
+    pub const DEFINE_SHAPE: u16 = 2;
+    /// PlaceObject, the original display list placement tag.
+    pub const PLACE_OBJECT: u16 = 4;
+    /// RemoveObject, removing a character at a depth.
+    pub const REMOVE_OBJECT: u16 = 5;
+    /// DefineBits, a JPEG relying on JPEGTables.
+    pub const DEFINE_BITS: u16 = 6;
+    /// DefineButton.
+    pub const DEFINE_BUTTON: u16 = 7;
+    /// JPEGTables, the encoding tables shared by DefineBits tags.
+    pub const JPEG_TABLES: u16 = 8;
+    /// SetBackgroundColor.
+    pub const SET_BACKGROUND_COLOR: u16 = 9;
+    /// DefineFont.
+    pub const DEFINE_FONT: u16 = 10;
+    /// DefineText.
+    pub const DEFINE_TEXT: u16 = 11;
+    /// DoAction, an AVM1 script for the current frame.
+    pub const DO_ACTION: u16 = 12;
+    /// DefineFontInfo.
+    pub const DEFINE_FONT_INFO: u16 = 13;
+    /// DefineSound.
+    pub const DEFINE_SOUND: u16 = 14;
+    /// StartSound, starting an event sound.
+    pub const START_SOUND: u16 = 15;
+    /// DefineButtonSound.
+    pub const DEFINE_BUTTON_SOUND: u16 = 17;
+    /// SoundStreamHead.
+    pub const SOUND_STREAM_HEAD: u16 = 18;
+    /// SoundStreamBlock, a chunk of streaming sound for the current frame.
+    pub const SOUND_STREAM_BLOCK: u16 = 19;
+    /// DefineBitsLossless.
+    pub const DEFINE_BITS_LOSSLESS: u16 = 20;
+    /// DefineBitsJPEG2.
+    pub const DEFINE_BITS_JPEG_2: u16 = 21;
+    /// DefineShape2.
+    pub const DEFINE_SHAPE_2: u16 = 22;
+    /// DefineButtonCxform.
+    pub const DEFINE_BUTTON_CXFORM: u16 = 23;
+    /// PlaceObject2.
+    pub const PLACE_OBJECT_2: u16 = 26;
+    /// RemoveObject2.
+    pub const REMOVE_OBJECT_2: u16 = 28;
+    /// DefineShape3.
+    pub const DEFINE_SHAPE_3: u16 = 32;
+    /// DefineText2.
+    pub const DEFINE_TEXT_2: u16 = 33;
+    /// DefineButton2.
+    pub const DEFINE_BUTTON_2: u16 = 34;
+    /// DefineBitsJPEG3.
+    pub const DEFINE_BITS_JPEG_3: u16 = 35;
+    /// DefineBitsLossless2.
+    pub const DEFINE_BITS_LOSSLESS_2: u16 = 36;
+    /// DefineEditText.
+    pub const DEFINE_EDIT_TEXT: u16 = 37;
+    /// DefineSprite, a nested timeline.
+    pub const DEFINE_SPRITE: u16 = 39;
+    /// ProductInfo, naming the compiler that built the file, and when.
+    pub const PRODUCT_INFO: u16 = 41;
+    /// FrameLabel, naming the current frame.
+    pub const FRAME_LABEL: u16 = 43;
+    /// SoundStreamHead2.
+    pub const SOUND_STREAM_HEAD_2: u16 = 45;
+    /// DefineMorphShape.
+    pub const DEFINE_MORPH_SHAPE: u16 = 46;
+    /// DefineFont2.
+    pub const DEFINE_FONT_2: u16 = 48;
+    /// ExportAssets.
+    pub const EXPORT_ASSETS: u16 = 56;
+    /// ImportAssets.
+    pub const IMPORT_ASSETS: u16 = 57;
+    /// EnableDebugger, allowing the SWF 5 debugger to connect.
+    pub const ENABLE_DEBUGGER: u16 = 58;
+    /// DoInitAction, an AVM1 script run once for a sprite.
+    pub const DO_INIT_ACTION: u16 = 59;
+    /// DefineVideoStream.
+    pub const DEFINE_VIDEO_STREAM: u16 = 60;
+    /// VideoFrame.
+    pub const VIDEO_FRAME: u16 = 61;
+    /// DefineFontInfo2.
+    pub const DEFINE_FONT_INFO_2: u16 = 62;
+    /// EnableDebugger2, allowing the debugger to connect.
+    pub const ENABLE_DEBUGGER_2: u16 = 64;
+    /// FileAttributes.
+    pub const FILE_ATTRIBUTES: u16 = 69;
+    /// PlaceObject3.
+    pub const PLACE_OBJECT_3: u16 = 70;
+    /// ImportAssets2.
+    pub const IMPORT_ASSETS_2: u16 = 71;
+    /// DoABCDefine, the undocumented predecessor of DoABC.
+    pub const DO_ABC_DEFINE: u16 = 72;
+    /// DefineFontAlignZones.
+    pub const DEFINE_FONT_ALIGN_ZONES: u16 = 73;
+    /// CSMTextSettings.
+    pub const CSM_TEXT_SETTINGS: u16 = 74;
+    /// DefineFont3.
+    pub const DEFINE_FONT_3: u16 = 75;
+    /// SymbolClass.
+    pub const SYMBOL_CLASS: u16 = 76;
+    /// Metadata, an XMP document describing the file.
+    pub const METADATA: u16 = 77;
+    /// DefineScalingGrid.
+    pub const DEFINE_SCALING_GRID: u16 = 78;
+    /// DoABC, an AVM2 bytecode block.
+    pub const DO_ABC: u16 = 82;
+    /// DefineShape4.
+    pub const DEFINE_SHAPE_4: u16 = 83;
+    /// DefineMorphShape2.
+    pub const DEFINE_MORPH_SHAPE_2: u16 = 84;
+    /// DefineSceneAndFrameLabelData, listing scenes and frame labels.
+    pub const DEFINE_SCENE_AND_FRAME_LABEL_DATA: u16 = 86;
+    /// DefineBinaryData.
+    pub const DEFINE_BINARY_DATA: u16 = 87;
+    /// DefineFontName.
+    pub const DEFINE_FONT_NAME: u16 = 88;
+    /// StartSound2, starting an event sound by class name.
+    pub const START_SOUND_2: u16 = 89;
+    /// DefineBitsJPEG4.
+    pub const DEFINE_BITS_JPEG_4: u16 = 90;
+    /// DefineFont4.
+    pub const DEFINE_FONT_4: u16 = 91;
+    /// EnableTelemetry, allowing profilers such as Adobe Scout to connect.
+    pub const ENABLE_TELEMETRY: u16 = 93;
+}
+
+/// A single tag, made up of its code and its raw payload.
+///
+/// Nothing inside the payload is parsed here; that's left to whatever cares
+/// about that particular kind of tag. That goes for codes this crate has
+/// never heard of too, and since a tag read from a file also remembers
+/// which header form it was written with, writing it back out gives the
+/// same bytes it was read from.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Tag {
+    code: u16,
+    data: Vec<u8>,
+    long_header: bool
+}
+
+impl Tag {
+    /// Creates a tag from a code and a payload. It'll be written with the
+    /// short header whenever the payload fits in it.
+    pub fn new(code: u16, data: Vec<u8>) -> Self {
+        let long_header = data.len() >= 0x3f;
+        Tag {
+            code: code,
+            data: data,
+            long_header: long_header
+        }
+    }
+
+    /// Makes the tag use the long header even if its payload would fit in
+    /// the short one, for matching a file written by a tool that did.
+    pub fn with_long_header(mut self) -> Self {
+        self.long_header = true;
+        self
+    }
+
+    /// Reads a single tag, header and all.
+    ///
+    /// Returns `Ok(None)` if the reader is already at EOF, since plenty of
+    /// SWF files in the wild just stop without bothering with an End tag.
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Option<Self>, Error> {
+        Tag::read_with_options(reader, &ParseOptions::default())
+    }
+
+    /// Reads a single tag like `read_from()`, returning
+    /// `Error::LimitExceeded` if its length is over the limit in `options`.
+    ///
+    /// The length is checked before any of the payload is read.
+    pub fn read_with_options<R: Read>(reader: &mut R, options: &ParseOptions) -> Result<Option<Self>, Error> {
+        let (code, length, long_header) = match try!(read_raw_header(reader)) {
+            Some(header) => header,
+            None => return Ok(None)
+        };
+        if length > options.tag_len_limit() {
+            return Err(Error::LimitExceeded);
+        }
+
+        let mut data = Vec::new();
+        try!(reader.by_ref().take(length as u64).read_to_end(&mut data));
+        if data.len() != length as usize {
+            return Err(Error::NotSwf);
+        }
+
+        Ok(Some(Tag {
+            code: code,
+            data: data,
+            long_header: long_header
+        }))
+    }
+
+    /// Writes the tag out, using the same header form it was read with, or
+    /// for new tags, the short one whenever the payload fits in it.
+    ///
+    /// A code needs to fit in 10 bits and a length in 32, so a tag with a
+    /// code over 1023 or a payload of 4GiB or more is an error, and nothing
+    /// is written.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let length = self.data.len();
+        if self.code > 0x3ff || length as u64 > u32::max_value() as u64 {
+            return Err(Error::IoError(io::Error::new(io::ErrorKind::InvalidInput, "tag code or length too large")));
+        }
+        if !self.long_header {
+            try!(writer.write_u16::<LittleEndian>((self.code << 6) | length as u16));
+        } else {
+            try!(writer.write_u16::<LittleEndian>((self.code << 6) | 0x3f));
+            try!(writer.write_u32::<LittleEndian>(length as u32));
+        }
+        try!(writer.write_all(&self.data));
+        Ok(())
+    }
+
+    /// Returns the number of bytes this tag takes up once written.
+    pub fn encoded_len(&self) -> usize {
+        let header = if self.long_header { 6 } else { 2 };
+        header + self.data.len()
+    }
+
+    /// Returns true if the tag has a long header, either because it was
+    /// read with one or because its payload doesn't fit in a short one.
+    pub fn was_long_header(&self) -> bool {
+        self.long_header
+    }
+
+    /// Returns the tag code.
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+    /// Returns the raw payload.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+    /// Consumes the tag, returning the raw payload.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Reads just a tag header, returning the code and payload length and
+/// leaving the reader at the start of the payload.
+///
+/// Returns `Ok(None)` if the reader is already at EOF. Paired with
+/// `DecodedSwf::skip()`, this walks past uninteresting tags without reading
+/// their payloads into memory.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::SwfHeaders;
+/// use swf_headers::tag::{self, codes};
+/// if let Ok((headers, mut decoded)) = SwfHeaders::open("example.swf") {
+///     while let Some((code, length)) = tag::read_header(&mut decoded).unwrap() {
+///         if code == codes::END {
+///             break;
+///         }
+///         decoded.skip(length as u64).unwrap();
+///     }
+/// }
+/// ```
+pub fn read_header<R: Read>(reader: &mut R) -> Result<Option<(u16, u32)>, Error> {
+    Ok(try!(read_raw_header(reader)).map(|(code, length, _)| (code, length)))
+}
+
+/// Like `read_header()`, but also says whether the header was a long one.
+fn read_raw_header<R: Read>(reader: &mut R) -> Result<Option<(u16, u32, bool)>, Error> {
+    // Tag header overview:
+    // The first u16 packs the code into the upper 10 bits and the length
+    // into the lower 6. A length of 0x3f means the real length follows as
+    // a u32, which is what the spec calls a "long" tag header.
+    let mut first = [0; 1];
+    if try!(reader.read(&mut first)) == 0 {
+        return Ok(None);
+    }
+    let second = try!(reader.read_u8());
+    let code_and_length = (first[0] as u16) | ((second as u16) << 8);
+
+    let code = code_and_length >> 6;
+    let mut length = (code_and_length & 0x3f) as u32;
+    let long_header = length == 0x3f;
+    if long_header {
+        length = try!(reader.read_u32::<LittleEndian>());
+    }
+    Ok(Some((code, length, long_header)))
+}
+
+/// An iterator over the tags of a SWF body.
+///
+/// Stops at the first End tag (without yielding it), at EOF, or after the
+/// first error. EOF is an error itself with `ParseOptions::require_end_tag()`.
+pub struct Tags<R: Read> {
+    reader: R,
+    options: ParseOptions,
+    done: bool,
+    recurse: bool,
+    // The bodies of the sprites currently being read, innermost last.
+    sprites: Vec<Cursor<Vec<u8>>>,
+    depth: u32
+}
+
+impl<R: Read> Tags<R> {
+    /// Creates an iterator reading tags from the given reader, which should
+    /// be positioned just after the header (such as a `DecodedSwf` fresh out
+    /// of `SwfHeaders::read_from()`).
+    pub fn new(reader: R) -> Self {
+        Tags::with_options(reader, ParseOptions::default())
+    }
+
+    /// Creates an iterator that enforces the limits in `options`.
+    ///
+    /// DefineSprite tags are checked all the way down as they're read, so
+    /// both limits hold for every nested tag too, and anything recursing
+    /// into the sprites afterwards is safe.
+    pub fn with_options(reader: R, options: ParseOptions) -> Self {
+        Tags {
+            reader: reader,
+            options: options,
+            done: false,
+            recurse: false,
+            sprites: Vec::new(),
+            depth: 0
+        }
+    }
+
+    /// Makes the iterator descend into DefineSprite tags.
+    ///
+    /// Each DefineSprite is yielded as usual, followed by the tags inside
+    /// it, so every tag in the file turns up in a single pass. The End tags
+    /// closing each sprite are skipped, just like the top-level one. Use
+    /// `with_depth()` to tell which sprite a tag belongs to.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use swf_headers::SwfHeaders;
+    /// use swf_headers::tag::Tags;
+    /// if let Ok((_, decoded)) = SwfHeaders::open("example.swf") {
+    ///     for tag in Tags::new(decoded).recurse_into_sprites(true).with_depth() {
+    ///         let (depth, tag) = tag.unwrap();
+    ///         println!("{}{}", "  ".repeat(depth as usize), tag.code());
+    ///     }
+    /// }
+    /// ```
+    pub fn recurse_into_sprites(mut self, recurse: bool) -> Self {
+        self.recurse = recurse;
+        self
+    }
+
+    /// Turns the iterator into one yielding each tag along with how many
+    /// sprites it's nested in, 0 for top-level tags.
+    pub fn with_depth(self) -> WithDepth<R> {
+        WithDepth { tags: self }
+    }
+
+    fn read_tag(&mut self) -> Result<Option<Tag>, Error> {
+        let tag = try!(Tag::read_with_options(&mut self.reader, &self.options));
+        if let Some(ref tag) = tag {
+            if tag.code() == codes::DEFINE_SPRITE {
+                try!(check_sprite(tag.data(), &self.options, 1));
+            }
+        }
+        Ok(tag)
+    }
+}
+
+/// Checks the limits against the tags nested inside a DefineSprite payload.
+///
+/// Only limit errors are reported. Malformed sprites are left for whatever
+/// reads them properly to complain about.
+fn check_sprite(data: &[u8], options: &ParseOptions, depth: u32) -> Result<(), Error> {
+    if depth > options.sprite_depth_limit() {
+        return Err(Error::LimitExceeded);
+    }
+    let mut reader = data.get(4..).unwrap_or(&[]);
+    loop {
+        match Tag::read_with_options(&mut reader, options) {
+            Ok(Some(tag)) => {
+                if tag.code() == codes::END {
+                    return Ok(());
+                }
+                if tag.code() == codes::DEFINE_SPRITE {
+                    try!(check_sprite(tag.data(), options, depth + 1));
+                }
+            },
+            Err(Error::LimitExceeded) => return Err(Error::LimitExceeded),
+            _ => return Ok(())
+        }
+    }
+}
+
+impl<R: Read> Iterator for Tags<R> {
+    type Item = Result<Tag, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.done {
+                return None;
+            }
+            let result = match self.sprites.last_mut() {
+                Some(sprite) => Tag::read_with_options(sprite, &self.options),
+                None => self.read_tag()
+            };
+            match result {
+                Ok(Some(ref tag)) if tag.code() != codes::END => {},
+                Ok(None) if self.options.requires_end_tag() => {
+                    self.done = true;
+                    return Some(Err(Error::NotSwf));
+                },
+                Ok(_) => {
+                    // The end of a sprite carries on with whatever it was
+                    // nested in.
+                    if self.sprites.pop().is_none() {
+                        self.done = true;
+                    }
+                    continue;
+                },
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+
+            let tag = result.unwrap().unwrap();
+            self.depth = self.sprites.len() as u32;
+            if self.recurse && tag.code() == codes::DEFINE_SPRITE {
+                // The sprite ID and frame count come before the tags.
+                let body = tag.data().get(4..).unwrap_or(&[]).to_vec();
+                self.sprites.push(Cursor::new(body));
+            }
+            return Some(Ok(tag));
+        }
+    }
+}
+
+/// An iterator over tags and how deeply they're nested, made by
+/// `Tags::with_depth()`.
+pub struct WithDepth<R: Read> {
+    tags: Tags<R>
+}
+
+impl<R: Read> Iterator for WithDepth<R> {
+    type Item = Result<(u32, Tag), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tag = self.tags.next();
+        let depth = self.tags.depth;
+        tag.map(|tag| tag.map(|tag| (depth, tag)))
+    }
+}
+
+/// Reads every tag from the reader into a vector.
+pub fn read_all<R: Read>(reader: R) -> Result<Vec<Tag>, Error> {
+    Tags::new(reader).collect()
+}
+
+/// Returns true for tags that change the display list.
+pub fn is_display_list_tag(code: u16) -> bool {
+    match code {
+        codes::PLACE_OBJECT | codes::PLACE_OBJECT_2 | codes::PLACE_OBJECT_3 |
+        codes::REMOVE_OBJECT | codes::REMOVE_OBJECT_2 => true,
+        _ => false
+    }
+}
+
+/// Returns true for tags that only mean anything on the frame they appear in,
+/// such as scripts, sounds and labels.
+pub fn is_frame_local_tag(code: u16) -> bool {
+    match code {
+        codes::SHOW_FRAME | codes::DO_ACTION | codes::START_SOUND |
+        codes::START_SOUND_2 | codes::SOUND_STREAM_BLOCK | codes::FRAME_LABEL => true,
+        _ => false
+    }
+}
+
+/// Returns true for tags that define a new character, whose ID is always
+/// the first u16 of the payload.
+pub fn defines_character(code: u16) -> bool {
+    match code {
+        codes::DEFINE_SHAPE | codes::DEFINE_BITS | codes::DEFINE_BUTTON |
+        codes::DEFINE_FONT | codes::DEFINE_TEXT | codes::DEFINE_SOUND |
+        codes::DEFINE_BITS_LOSSLESS | codes::DEFINE_BITS_JPEG_2 | codes::DEFINE_SHAPE_2 |
+        codes::DEFINE_SHAPE_3 | codes::DEFINE_TEXT_2 | codes::DEFINE_BUTTON_2 |
+        codes::DEFINE_BITS_JPEG_3 | codes::DEFINE_BITS_LOSSLESS_2 | codes::DEFINE_EDIT_TEXT |
+        codes::DEFINE_SPRITE | codes::DEFINE_MORPH_SHAPE | codes::DEFINE_FONT_2 |
+        codes::DEFINE_VIDEO_STREAM | codes::DEFINE_FONT_3 | codes::DEFINE_SHAPE_4 |
+        codes::DEFINE_MORPH_SHAPE_2 | codes::DEFINE_BINARY_DATA | codes::DEFINE_BITS_JPEG_4 |
+        codes::DEFINE_FONT_4 => true,
+        _ => false
+    }
+}
+
+/// Returns true for tags whose payload starts with the ID of a character
+/// defined elsewhere, such as DefineFontInfo or StartSound.
+pub fn refers_to_character(code: u16) -> bool {
+    match code {
+        codes::DEFINE_FONT_INFO | codes::START_SOUND | codes::DEFINE_BUTTON_SOUND |
+        codes::DEFINE_BUTTON_CXFORM | codes::VIDEO_FRAME | codes::DEFINE_FONT_INFO_2 |
+        codes::DEFINE_FONT_ALIGN_ZONES | codes::CSM_TEXT_SETTINGS |
+        codes::DEFINE_SCALING_GRID | codes::DEFINE_FONT_NAME => true,
+        _ => false
+    }
+}
+
+/// Returns the character ID a define tag introduces, if it is one.
+pub fn character_id(tag: &Tag) -> Option<u16> {
+    if defines_character(tag.code()) {
+        read_u16_at(tag.data(), 0).ok()
+    } else {
+        None
+    }
+}
+
+/// Splits a tag list into frames, each ending with its ShowFrame tag.
+///
+/// Any trailing tags without a ShowFrame end up in a final, unterminated
+/// frame.
+pub fn group_frames(tags: Vec<Tag>) -> Vec<Vec<Tag>> {
+    let mut frames = Vec::new();
+    let mut current = Vec::new();
+    for tag in tags {
+        let is_show_frame = tag.code() == codes::SHOW_FRAME;
+        current.push(tag);
+        if is_show_frame {
+            frames.push(current);
+            current = Vec::new();
+        }
+    }
+    if !current.is_empty() {
+        frames.push(current);
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_and_long_headers() {
+        let short = Tag::new(9, vec![0xff, 0xff, 0xff]);
+        let long = Tag::new(87, vec![0; 100]);
+
+        let mut buf = Vec::new();
+        short.write_to(&mut buf).unwrap();
+        long.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len(), short.encoded_len() + long.encoded_len());
+
+        let tags = read_all(&buf[..]).unwrap();
+        assert_eq!(tags, vec![short, long]);
+
+        // Codes only have 10 bits.
+        let mut buf = Vec::new();
+        assert!(Tag::new(1023, vec![]).write_to(&mut buf).is_ok());
+        assert!(Tag::new(1024, vec![]).write_to(&mut buf).is_err());
+        assert_eq!(buf, vec![0xc0, 0xff]);
+    }
+
+    #[test]
+    fn test_passthrough() {
+        // An unknown code with a needlessly long header, then a DoABC with
+        // a short one.
+        let buf = [0xff, 0x3f, 2, 0, 0, 0, 0xde, 0xad, 0x82, 0x14, 0x01, 0x02];
+        let tags = read_all(&buf[..]).unwrap();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].code(), 0xff);
+        assert!(tags[0].was_long_header());
+        assert!(!tags[1].was_long_header());
+        assert_ne!(tags[0], Tag::new(0xff, vec![0xde, 0xad]));
+        assert_eq!(tags[0], Tag::new(0xff, vec![0xde, 0xad]).with_long_header());
+
+        let mut out = Vec::new();
+        for tag in &tags {
+            tag.write_to(&mut out).unwrap();
+            assert_eq!(tag.encoded_len(), tag.data().len() + if tag.was_long_header() { 6 } else { 2 });
+        }
+        assert_eq!(&out[..], &buf[..]);
+    }
+
+    #[test]
+    fn test_stops_at_end() {
+        let mut buf = Vec::new();
+        Tag::new(codes::SHOW_FRAME, vec![]).write_to(&mut buf).unwrap();
+        Tag::new(codes::END, vec![]).write_to(&mut buf).unwrap();
+        Tag::new(codes::SHOW_FRAME, vec![]).write_to(&mut buf).unwrap();
+        assert_eq!(read_all(&buf[..]).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_recurse_into_sprites() {
+        let mut inner = vec![2, 0, 1, 0];
+        Tag::new(codes::SHOW_FRAME, vec![]).write_to(&mut inner).unwrap();
+        Tag::new(codes::END, vec![]).write_to(&mut inner).unwrap();
+        let mut outer = vec![1, 0, 1, 0];
+        Tag::new(codes::DEFINE_SPRITE, inner).write_to(&mut outer).unwrap();
+        Tag::new(codes::SHOW_FRAME, vec![]).write_to(&mut outer).unwrap();
+        let mut buf = Vec::new();
+        Tag::new(codes::DEFINE_SPRITE, outer).write_to(&mut buf).unwrap();
+        Tag::new(codes::SHOW_FRAME, vec![]).write_to(&mut buf).unwrap();
+
+        let flat: Vec<(u32, u16)> = Tags::new(&buf[..]).with_depth()
+            .map(|tag| tag.map(|(depth, tag)| (depth, tag.code())).unwrap())
+            .collect();
+        assert_eq!(flat, vec![(0, codes::DEFINE_SPRITE), (0, codes::SHOW_FRAME)]);
+
+        let nested: Vec<(u32, u16)> = Tags::new(&buf[..]).recurse_into_sprites(true).with_depth()
+            .map(|tag| tag.map(|(depth, tag)| (depth, tag.code())).unwrap())
+            .collect();
+        assert_eq!(nested, vec![
+            (0, codes::DEFINE_SPRITE),
+            (1, codes::DEFINE_SPRITE),
+            (2, codes::SHOW_FRAME),
+            (1, codes::SHOW_FRAME),
+            (0, codes::SHOW_FRAME)
+        ]);
+
+        // A sprite with a truncated tag inside.
+        let broken = [0xc7, 0x09, 1, 0, 1, 0, 0x43, 0x02, 0];
+        let tags: Vec<_> = Tags::new(&broken[..]).recurse_into_sprites(true).collect();
+        assert_eq!(tags.len(), 2);
+        assert!(tags[1].is_err());
+    }
+
+    #[test]
+    fn test_truncated_tag() {
+        let buf = [0x05, 0x00, 0x01];
+        assert!(read_all(&buf[..]).is_err());
+    }
+
+    #[test]
+    fn test_limits() {
+        use error::Error;
+        use options::ParseOptions;
+
+        // A long header claiming a 4GiB payload.
+        let buf = [0x3f, 0x00, 0xff, 0xff, 0xff, 0xff];
+        let options = ParseOptions::new().max_tag_len(1024);
+        match Tags::with_options(&buf[..], options).next() {
+            Some(Err(Error::LimitExceeded)) => {},
+            other => panic!("expected LimitExceeded, got {:?}", other)
+        }
+
+        // A sprite inside a sprite.
+        let mut inner = vec![2, 0, 1, 0];
+        Tag::new(codes::SHOW_FRAME, vec![]).write_to(&mut inner).unwrap();
+        let mut outer = vec![1, 0, 1, 0];
+        Tag::new(codes::DEFINE_SPRITE, inner).write_to(&mut outer).unwrap();
+        let mut buf = Vec::new();
+        Tag::new(codes::DEFINE_SPRITE, outer).write_to(&mut buf).unwrap();
+
+        assert!(read_all(&buf[..]).is_ok());
+        let options = ParseOptions::new().max_sprite_depth(1);
+        match Tags::with_options(&buf[..], options).next() {
+            Some(Err(Error::LimitExceeded)) => {},
+            other => panic!("expected LimitExceeded, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_quirks() {
+        use error::Error;
+        use options::{ParseOptions, QuirksProfile};
+
+        // A sprite with no End tag, and no End tag after it either.
+        let mut sprite = vec![1, 0, 1, 0];
+        Tag::new(codes::SHOW_FRAME, vec![]).write_to(&mut sprite).unwrap();
+        let mut buf = Vec::new();
+        Tag::new(codes::DEFINE_SPRITE, sprite).write_to(&mut buf).unwrap();
+
+        let player = ParseOptions::new().quirks(QuirksProfile::FlashPlayer32);
+        assert_eq!(player.decompressed_limit(), 256 * 1024 * 1024);
+        let smaller = ParseOptions::new().max_decompressed(1024).quirks(QuirksProfile::FlashPlayer32);
+        assert_eq!(smaller.decompressed_limit(), 1024);
+        let tags = Tags::with_options(&buf[..], player).recurse_into_sprites(true);
+        assert_eq!(tags.map(|tag| tag.unwrap().code()).collect::<Vec<_>>(),
+                   vec![codes::DEFINE_SPRITE, codes::SHOW_FRAME]);
+
+        let strict = ParseOptions::new().quirks(QuirksProfile::Strict);
+        let mut tags = Tags::with_options(&buf[..], strict).recurse_into_sprites(true);
+        assert_eq!(tags.next().unwrap().unwrap().code(), codes::DEFINE_SPRITE);
+        assert_eq!(tags.next().unwrap().unwrap().code(), codes::SHOW_FRAME);
+        match tags.next() {
+            Some(Err(Error::NotSwf)) => {},
+            other => panic!("expected NotSwf, got {:?}", other)
+        }
+        assert!(tags.next().is_none());
+
+        // Later options win over the profile's.
+        let relaxed = ParseOptions::new().quirks(QuirksProfile::Strict).require_end_tag(false);
+        assert!(relaxed.rejects_zero_frame_count() && !relaxed.requires_end_tag());
+    }
+}
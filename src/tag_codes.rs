@@ -0,0 +1,162 @@
+//! A registry of tag codes, with their names from the spec and the SWF
+//! version that introduced them.
+//!
+//! `tag::codes` has constants for the tags this crate works with. This is
+//! the complete list, for tools that need to print or look up any tag.
+
+/// Everything known about one tag code.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TagCode {
+    /// The numeric code.
+    pub code: u16,
+    /// The name, spelled the way the spec spells it, such as
+    /// `"DefineBitsJPEG2"`.
+    pub name: &'static str,
+    /// The SWF version the tag first appeared in.
+    pub since: u8,
+    /// False for tags Adobe never documented, which turn up in files
+    /// anyway.
+    pub documented: bool
+}
+
+macro_rules! tag_code {
+    ($code:expr, $name:expr, $since:expr) => {
+        TagCode { code: $code, name: $name, since: $since, documented: true }
+    };
+    ($code:expr, $name:expr, $since:expr, undocumented) => {
+        TagCode { code: $code, name: $name, since: $since, documented: false }
+    };
+}
+
+/// Every known tag code, sorted by code.
+pub static ALL: &'static [TagCode] = &[
+    tag_code!(0, "End", 1),
+    tag_code!(1, "ShowFrame", 1),
+    tag_code!(2, "DefineShape", 1),
+    tag_code!(4, "PlaceObject", 1),
+    tag_code!(5, "RemoveObject", 1),
+    tag_code!(6, "DefineBits", 1),
+    tag_code!(7, "DefineButton", 1),
+    tag_code!(8, "JPEGTables", 1),
+    tag_code!(9, "SetBackgroundColor", 1),
+    tag_code!(10, "DefineFont", 1),
+    tag_code!(11, "DefineText", 1),
+    tag_code!(12, "DoAction", 3),
+    tag_code!(13, "DefineFontInfo", 1),
+    tag_code!(14, "DefineSound", 1),
+    tag_code!(15, "StartSound", 1),
+    tag_code!(17, "DefineButtonSound", 2),
+    tag_code!(18, "SoundStreamHead", 1),
+    tag_code!(19, "SoundStreamBlock", 1),
+    tag_code!(20, "DefineBitsLossless", 2),
+    tag_code!(21, "DefineBitsJPEG2", 2),
+    tag_code!(22, "DefineShape2", 2),
+    tag_code!(23, "DefineButtonCxform", 2),
+    tag_code!(24, "Protect", 2),
+    tag_code!(26, "PlaceObject2", 3),
+    tag_code!(28, "RemoveObject2", 3),
+    tag_code!(32, "DefineShape3", 3),
+    tag_code!(33, "DefineText2", 3),
+    tag_code!(34, "DefineButton2", 3),
+    tag_code!(35, "DefineBitsJPEG3", 3),
+    tag_code!(36, "DefineBitsLossless2", 3),
+    tag_code!(37, "DefineEditText", 4),
+    tag_code!(39, "DefineSprite", 3),
+    tag_code!(41, "ProductInfo", 9, undocumented),
+    tag_code!(43, "FrameLabel", 3),
+    tag_code!(45, "SoundStreamHead2", 3),
+    tag_code!(46, "DefineMorphShape", 3),
+    tag_code!(48, "DefineFont2", 3),
+    tag_code!(56, "ExportAssets", 5),
+    tag_code!(57, "ImportAssets", 5),
+    tag_code!(58, "EnableDebugger", 5),
+    tag_code!(59, "DoInitAction", 6),
+    tag_code!(60, "DefineVideoStream", 6),
+    tag_code!(61, "VideoFrame", 6),
+    tag_code!(62, "DefineFontInfo2", 6),
+    tag_code!(64, "EnableDebugger2", 6),
+    tag_code!(65, "ScriptLimits", 7),
+    tag_code!(66, "SetTabIndex", 7),
+    tag_code!(69, "FileAttributes", 8),
+    tag_code!(70, "PlaceObject3", 8),
+    tag_code!(71, "ImportAssets2", 8),
+    tag_code!(72, "DoABCDefine", 9, undocumented),
+    tag_code!(73, "DefineFontAlignZones", 8),
+    tag_code!(74, "CSMTextSettings", 8),
+    tag_code!(75, "DefineFont3", 8),
+    tag_code!(76, "SymbolClass", 9),
+    tag_code!(77, "Metadata", 1),
+    tag_code!(78, "DefineScalingGrid", 8),
+    tag_code!(82, "DoABC", 9),
+    tag_code!(83, "DefineShape4", 8),
+    tag_code!(84, "DefineMorphShape2", 8),
+    tag_code!(86, "DefineSceneAndFrameLabelData", 9),
+    tag_code!(87, "DefineBinaryData", 9),
+    tag_code!(88, "DefineFontName", 9),
+    tag_code!(89, "StartSound2", 9),
+    tag_code!(90, "DefineBitsJPEG4", 10),
+    tag_code!(91, "DefineFont4", 10),
+    tag_code!(93, "EnableTelemetry", 19)
+];
+
+/// Looks up a tag code.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::tag_codes;
+/// let info = tag_codes::lookup(83).unwrap();
+/// assert_eq!(info.name, "DefineShape4");
+/// assert_eq!(info.since, 8);
+/// ```
+pub fn lookup(code: u16) -> Option<&'static TagCode> {
+    ALL.binary_search_by_key(&code, |tag_code| tag_code.code).ok().map(|index| &ALL[index])
+}
+
+/// Looks up a tag by name. Case doesn't matter, so `"doabc"` finds DoABC.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::tag_codes;
+/// assert_eq!(tag_codes::by_name("DoABC").map(|info| info.code), Some(82));
+/// assert_eq!(tag_codes::by_name("jpegtables").map(|info| info.code), Some(8));
+/// ```
+pub fn by_name(name: &str) -> Option<&'static TagCode> {
+    ALL.iter().find(|tag_code| tag_code.name.eq_ignore_ascii_case(name))
+}
+
+/// Returns the name of a tag code, if it's a known one.
+pub fn name(code: u16) -> Option<&'static str> {
+    lookup(code).map(|tag_code| tag_code.name)
+}
+
+/// Returns the code for a tag name, if it's a known one.
+pub fn code(name: &str) -> Option<u16> {
+    by_name(name).map(|tag_code| tag_code.code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    use tag::codes;
+
+    #[test]
+    fn test_registry() {
+        assert!(ALL.windows(2).all(|pair| pair[0].code < pair[1].code));
+        let names: HashSet<String> = ALL.iter().map(|tag_code| tag_code.name.to_lowercase()).collect();
+        assert_eq!(names.len(), ALL.len());
+
+        for tag_code in ALL {
+            assert_eq!(lookup(tag_code.code), Some(tag_code));
+            assert_eq!(code(tag_code.name), Some(tag_code.code));
+        }
+        assert_eq!(name(codes::DEFINE_SCENE_AND_FRAME_LABEL_DATA), Some("DefineSceneAndFrameLabelData"));
+        assert_eq!(name(codes::CSM_TEXT_SETTINGS), Some("CSMTextSettings"));
+        assert_eq!(lookup(codes::DO_ABC_DEFINE).map(|tag_code| tag_code.documented), Some(false));
+        assert_eq!(name(3), None);
+        assert_eq!(code("DefineShape5"), None);
+    }
+}
@@ -0,0 +1,683 @@
+//! Parsing text: DefineEditText, the tag behind dynamic and input text
+//! fields, and DefineText, for static text.
+//!
+//! Localization tools care about three things here: the text a field starts
+//! with, the variable it's bound to, and the font it needs. `EditText`
+//! exposes those, and `with_initial_text()` writes a field back out with
+//! new text and everything else left alone. Static text is laid out glyph
+//! by glyph in advance, which `StaticText` reads and writes, and
+//! `Translator` puts the two together to translate a whole movie.
+
+use std::collections::HashMap;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use error::Error;
+use font::Font;
+use primitives::{read_string, read_u16_at, signed_bits_needed, BitReader, BitWriter, Matrix, Rect, Rgba};
+use tag::{codes, Tag};
+use writer::TagAction;
+
+/// A parsed DefineEditText tag.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EditText {
+    /// The character ID.
+    pub id: u16,
+    /// The bounds of the field.
+    pub bounds: Rect,
+    /// The font, as the ID of a DefineFont2 or DefineFont3.
+    pub font_id: Option<u16>,
+    /// The font, as the name of an ActionScript 3 class.
+    pub font_class: Option<String>,
+    /// The font height in twips.
+    pub font_height: Option<u16>,
+    /// The text color.
+    pub color: Option<Rgba>,
+    /// The most characters the field accepts.
+    pub max_length: Option<u16>,
+    /// The ActionScript variable whose value the field shows, or an empty
+    /// string for none.
+    pub variable_name: String,
+    /// The text the field starts out with.
+    pub initial_text: Option<String>,
+    /// Whether the text is a subset of HTML rather than plain text.
+    pub is_html: bool,
+    /// Whether the text is drawn with the embedded font's outlines rather
+    /// than a device font.
+    pub uses_outlines: bool,
+    /// Whether the field can hold more than one line.
+    pub multiline: bool,
+    /// Whether long lines wrap.
+    pub word_wrap: bool,
+    /// Whether the field hides what's typed into it.
+    pub password: bool,
+    /// Whether the field can't be edited.
+    pub read_only: bool,
+    /// Whether the field was converted from static text by the authoring
+    /// tool.
+    pub was_static: bool
+}
+
+impl EditText {
+    /// Parses a text field, returning `None` if the tag isn't a
+    /// DefineEditText.
+    pub fn parse(tag: &Tag) -> Result<Option<Self>, Error> {
+        match try!(parse_edit_text(tag)) {
+            Some((edit_text, _)) => Ok(Some(edit_text)),
+            None => Ok(None)
+        }
+    }
+}
+
+/// Returns a copy of a DefineEditText with its initial text replaced,
+/// returning `None` if the tag isn't a DefineEditText.
+///
+/// HTML fields take HTML, so escaping is up to the caller.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::tag::{codes, Tag};
+/// use swf_headers::text::{self, EditText};
+/// // Field 1 with an empty RECT, bound to "title", saying "Hello".
+/// let tag = Tag::new(codes::DEFINE_EDIT_TEXT, b"\x01\0\0\x80\0title\0Hello\0".to_vec());
+/// let translated = text::with_initial_text(&tag, "Bonjour").unwrap().unwrap();
+/// let field = EditText::parse(&translated).unwrap().unwrap();
+/// assert_eq!(field.initial_text, Some("Bonjour".to_string()));
+/// assert_eq!(field.variable_name, "title");
+/// ```
+pub fn with_initial_text(tag: &Tag, text: &str) -> Result<Option<Tag>, Error> {
+    let (flags_pos, text_pos) = match try!(parse_edit_text(tag)) {
+        Some((_, positions)) => positions,
+        None => return Ok(None)
+    };
+    let mut data = tag.data()[..text_pos].to_vec();
+    // Sets HasText.
+    data[flags_pos] |= 0x80;
+    data.extend_from_slice(text.as_bytes());
+    data.push(0);
+    Ok(Some(Tag::new(codes::DEFINE_EDIT_TEXT, data)))
+}
+
+/// Collects every text field defined in a tag list.
+pub fn edit_texts(tags: &[Tag]) -> Result<Vec<EditText>, Error> {
+    let mut fields = Vec::new();
+    for tag in tags {
+        if let Some(field) = try!(EditText::parse(tag)) {
+            fields.push(field);
+        }
+    }
+    Ok(fields)
+}
+
+/// Returns where the font ID is in the data of a DefineEditText, or of
+/// each record that changes font in a DefineText or DefineText2, so that
+/// the IDs can be rewritten in place. Other tags have none.
+pub fn font_id_offsets(tag: &Tag) -> Result<Vec<usize>, Error> {
+    if let Some((field, (flags_pos, _))) = try!(parse_edit_text(tag)) {
+        // The font ID comes straight after the two bytes of flags.
+        return Ok(field.font_id.map(|_| flags_pos + 2).into_iter().collect());
+    }
+    Ok(try!(parse_static_text(tag)).map(|(_, offsets)| offsets).unwrap_or_default())
+}
+
+/// A parsed DefineText or DefineText2.
+#[derive(Clone, PartialEq, Debug)]
+pub struct StaticText {
+    /// The character ID.
+    pub id: u16,
+    /// Which DefineText this came from, 1 or 2. DefineText2 has colors with
+    /// alpha.
+    pub version: u8,
+    /// The bounds of the text.
+    pub bounds: Rect,
+    /// The transform from the text's own space to the character's.
+    pub matrix: Matrix,
+    /// The runs of glyphs making up the text.
+    pub records: Vec<TextRecord>
+}
+
+/// A TEXTRECORD: a run of glyphs, along with any change of style before it.
+/// Styles carry on from one record to the next until they're changed.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TextRecord {
+    /// The font to switch to.
+    pub font_id: Option<u16>,
+    /// The color to switch to.
+    pub color: Option<Rgba>,
+    /// Where to move the pen horizontally, in twips.
+    pub x_offset: Option<i16>,
+    /// Where to move the pen vertically, in twips.
+    pub y_offset: Option<i16>,
+    /// The text height in twips, which is always given along with the
+    /// font.
+    pub height: Option<u16>,
+    /// Each glyph, as its index in the font and how far it moves the pen
+    /// in twips.
+    pub glyphs: Vec<(u32, i32)>
+}
+
+impl StaticText {
+    /// Parses static text, returning `None` if the tag isn't a DefineText
+    /// or DefineText2.
+    pub fn parse(tag: &Tag) -> Result<Option<Self>, Error> {
+        match try!(parse_static_text(tag)) {
+            Some((text, _)) => Ok(Some(text)),
+            None => Ok(None)
+        }
+    }
+
+    /// Encodes the text as a DefineText or DefineText2 tag, using as few
+    /// bits for the glyphs as they need.
+    ///
+    /// Returns `Error::NotSwf` if a record has more than 255 glyphs.
+    pub fn to_tag(&self) -> Result<Tag, Error> {
+        let mut data = vec![self.id as u8, (self.id >> 8) as u8];
+        let mut writer = BitWriter::new();
+        self.bounds.write(&mut writer);
+        data.extend(writer.into_bytes());
+        let mut writer = BitWriter::new();
+        self.matrix.write(&mut writer);
+        data.extend(writer.into_bytes());
+
+        let glyphs = self.records.iter().flat_map(|record| &record.glyphs);
+        let glyph_bits = glyphs.clone().map(|&(index, _)| 32 - index.leading_zeros()).max().unwrap_or(0);
+        let advance_bits = signed_bits_needed(&glyphs.map(|&(_, advance)| advance).collect::<Vec<i32>>());
+        data.push(glyph_bits as u8);
+        data.push(advance_bits as u8);
+
+        for record in &self.records {
+            if record.glyphs.len() > 255 {
+                return Err(Error::NotSwf);
+            }
+            let mut flags = 0x80;
+            let mut fields = Vec::new();
+            if let Some(font_id) = record.font_id {
+                flags |= 0x08;
+                fields.extend_from_slice(&[font_id as u8, (font_id >> 8) as u8]);
+            }
+            if let Some(color) = record.color {
+                flags |= 0x04;
+                fields.extend_from_slice(&[color.r, color.g, color.b]);
+                if self.version == 2 {
+                    fields.push(color.a);
+                }
+            }
+            for &(flag, offset) in &[(0x01, record.x_offset), (0x02, record.y_offset)] {
+                if let Some(offset) = offset {
+                    flags |= flag;
+                    fields.extend_from_slice(&[offset as u8, (offset >> 8) as u8]);
+                }
+            }
+            if record.font_id.is_some() {
+                let height = record.height.unwrap_or(0);
+                fields.extend_from_slice(&[height as u8, (height >> 8) as u8]);
+            }
+            data.push(flags);
+            data.extend(fields);
+            data.push(record.glyphs.len() as u8);
+            let mut writer = BitWriter::new();
+            for &(index, advance) in &record.glyphs {
+                writer.write_ub(glyph_bits, index);
+                writer.write_sb(advance_bits, advance);
+            }
+            data.extend(writer.into_bytes());
+        }
+        data.push(0);
+
+        let code = if self.version == 2 { codes::DEFINE_TEXT_2 } else { codes::DEFINE_TEXT };
+        Ok(Tag::new(code, data))
+    }
+}
+
+/// How text with advanced anti-aliasing is snapped to the pixel grid.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GridFit {
+    /// Not at all.
+    None,
+    /// To whole pixels, which suits left-aligned dynamic text.
+    Pixel,
+    /// To LCD subpixels.
+    Subpixel
+}
+
+/// A parsed CSMTextSettings tag, which tunes the advanced anti-aliasing of
+/// one DefineText, DefineText2 or DefineEditText.
+///
+/// The tag only refers to the text by ID, so anything that copies or drops
+/// text has to copy or drop these along with it, or the text quietly goes
+/// back to being rendered the normal way.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TextSettings {
+    /// The ID of the text the settings are for.
+    pub text_id: u16,
+    /// Whether the text uses advanced anti-aliasing at all.
+    pub advanced: bool,
+    /// How glyphs are snapped to the pixel grid.
+    pub grid_fit: GridFit,
+    /// How much thicker to draw the glyphs, from -200 to 200, or 0 for the
+    /// default.
+    pub thickness: f32,
+    /// How much sharper to draw the glyph edges, from -400 to 400, or 0 for
+    /// the default.
+    pub sharpness: f32
+}
+
+impl TextSettings {
+    /// Parses a CSMTextSettings tag, returning `None` for other tags.
+    pub fn parse(tag: &Tag) -> Result<Option<Self>, Error> {
+        if tag.code() != codes::CSM_TEXT_SETTINGS {
+            return Ok(None);
+        }
+        let mut reader = tag.data();
+        let text_id = try!(reader.read_u16::<LittleEndian>());
+        let flags = try!(reader.read_u8());
+        let grid_fit = match (flags >> 3) & 0x07 {
+            0 => GridFit::None,
+            1 => GridFit::Pixel,
+            2 => GridFit::Subpixel,
+            _ => return Err(Error::NotSwf)
+        };
+        Ok(Some(TextSettings {
+            text_id: text_id,
+            advanced: flags >> 6 == 1,
+            grid_fit: grid_fit,
+            thickness: try!(reader.read_f32::<LittleEndian>()),
+            sharpness: try!(reader.read_f32::<LittleEndian>())
+        }))
+    }
+}
+
+/// Collects the CSMTextSettings in a tag list.
+pub fn text_settings(tags: &[Tag]) -> Result<Vec<TextSettings>, Error> {
+    let mut settings = Vec::new();
+    for tag in tags {
+        if let Some(parsed) = try!(TextSettings::parse(tag)) {
+            settings.push(parsed);
+        }
+    }
+    Ok(settings)
+}
+
+/// Translates the text in a movie, as a `writer::transform()` callback.
+///
+/// Text fields are translated when their whole initial text is in the
+/// map. Static text is translated a record at a time, which usually means
+/// a line at a time, so the map needs an entry for each line. The new text
+/// is laid out with the advances from the font, starting where the old
+/// text started. Nothing is done about text that comes out wider than
+/// before, and text can only be translated into characters the font
+/// already has glyphs for; anything else is left as it was, and listed in
+/// `untranslatable()`.
+///
+/// Only the text tags themselves are replaced, and they keep their IDs, so
+/// the DefineFontAlignZones and CSMTextSettings belonging to them stay
+/// where they are and still apply.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use std::io::Cursor;
+/// use swf_headers::text::Translator;
+/// use swf_headers::writer;
+/// let mut translations = HashMap::new();
+/// translations.insert("Start".to_string(), "Commencer".to_string());
+/// let mut translator = Translator::new(&translations);
+/// let mut translated = Cursor::new(Vec::new());
+/// if writer::transform("game.swf", &mut translated, |tag| translator.translate(tag)).is_ok() {
+///     for text in translator.untranslatable() {
+///         println!("The fonts don't have everything \"{}\" needs", text);
+///     }
+/// }
+/// ```
+pub struct Translator<'a> {
+    translations: &'a HashMap<String, String>,
+    fonts: HashMap<u16, Font>,
+    translated: usize,
+    untranslatable: Vec<String>
+}
+
+impl<'a> Translator<'a> {
+    /// Creates a translator from a map of original text to translated text.
+    pub fn new(translations: &'a HashMap<String, String>) -> Self {
+        Translator {
+            translations: translations,
+            fonts: HashMap::new(),
+            translated: 0,
+            untranslatable: Vec::new()
+        }
+    }
+
+    /// Translates a single tag. Fonts have to be passed in too, before the
+    /// text that uses them, which they always are in a well-formed file.
+    /// Tags that can't be parsed are left alone.
+    pub fn translate(&mut self, tag: &Tag) -> TagAction {
+        match self.translate_tag(tag) {
+            Ok(Some(tag)) => TagAction::Replace(vec![tag]),
+            _ => TagAction::Keep
+        }
+    }
+
+    /// Returns how many fields and records have been translated so far.
+    pub fn translated(&self) -> usize {
+        self.translated
+    }
+
+    /// Returns the translations that couldn't be used because the font was
+    /// missing some of their characters.
+    pub fn untranslatable(&self) -> &[String] {
+        &self.untranslatable
+    }
+
+    fn translate_tag(&mut self, tag: &Tag) -> Result<Option<Tag>, Error> {
+        if let Some(font) = try!(Font::parse(tag)) {
+            self.fonts.insert(font.id, font);
+            return Ok(None);
+        }
+        if tag.code() == codes::DEFINE_FONT_INFO || tag.code() == codes::DEFINE_FONT_INFO_2 {
+            if let Some(font) = self.fonts.get_mut(&try!(read_u16_at(tag.data(), 0))) {
+                try!(font.add_info(tag));
+            }
+            return Ok(None);
+        }
+
+        if let Some(field) = try!(EditText::parse(tag)) {
+            return match field.initial_text.and_then(|text| self.translations.get(&text)) {
+                Some(translation) => {
+                    self.translated += 1;
+                    with_initial_text(tag, translation)
+                },
+                None => Ok(None)
+            };
+        }
+
+        let mut text = match try!(StaticText::parse(tag)) {
+            Some(text) => text,
+            None => return Ok(None)
+        };
+        let mut changed = false;
+        let mut font = None;
+        let mut height = 0;
+        for record in &mut text.records {
+            if let Some(font_id) = record.font_id {
+                font = self.fonts.get(&font_id);
+            }
+            if let Some(new_height) = record.height {
+                height = new_height;
+            }
+            let font = match font {
+                Some(font) => font,
+                None => continue
+            };
+            let original: Option<String> = record.glyphs.iter().map(|&(index, _)| font.glyph_char(index as usize)).collect();
+            let translation = match original.and_then(|original| self.translations.get(&original)) {
+                Some(translation) => translation,
+                None => continue
+            };
+            match lay_out(font, height, translation) {
+                Some(glyphs) => {
+                    record.glyphs = glyphs;
+                    self.translated += 1;
+                    changed = true;
+                },
+                None => self.untranslatable.push(translation.clone())
+            }
+        }
+        if changed {
+            text.to_tag().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Lays out a line of text in a font, returning `None` if the font is
+/// missing a glyph or advances, or there are too many glyphs for a record.
+fn lay_out(font: &Font, height: u16, text: &str) -> Option<Vec<(u32, i32)>> {
+    let advances = match font.advances {
+        Some(ref advances) => advances,
+        None => return None
+    };
+    let mut glyphs = Vec::new();
+    for c in text.chars() {
+        let index = match font.glyph_index(c) {
+            Some(index) => index,
+            None => return None
+        };
+        let advance = (advances[index] as f64 * height as f64 / font.em_square as f64).round() as i32;
+        glyphs.push((index as u32, advance));
+    }
+    if glyphs.len() > 255 {
+        return None;
+    }
+    Some(glyphs)
+}
+
+/// Parses a DefineText or DefineText2, also returning where the font ID of
+/// each record that changes font is.
+fn parse_static_text(tag: &Tag) -> Result<Option<(StaticText, Vec<usize>)>, Error> {
+    let version = match tag.code() {
+        codes::DEFINE_TEXT => 1,
+        codes::DEFINE_TEXT_2 => 2,
+        _ => return Ok(None)
+    };
+    let mut reader = BitReader::new(tag.data());
+    let id = try!(reader.read_u16());
+    let bounds = try!(Rect::read(&mut reader));
+    reader.align();
+    let matrix = try!(Matrix::read(&mut reader));
+    let glyph_bits = try!(reader.read_u8()) as u32;
+    let advance_bits = try!(reader.read_u8()) as u32;
+
+    let mut records = Vec::new();
+    let mut font_offsets = Vec::new();
+    loop {
+        let flags = try!(reader.read_u8());
+        if flags == 0 {
+            break;
+        }
+        let has_font = flags & 0x08 != 0;
+        let font_id = if has_font {
+            font_offsets.push(reader.byte_pos());
+            Some(try!(reader.read_u16()))
+        } else {
+            None
+        };
+        let color = if flags & 0x04 == 0 {
+            None
+        } else {
+            let (r, g, b) = (try!(reader.read_u8()), try!(reader.read_u8()), try!(reader.read_u8()));
+            let a = if version == 2 { try!(reader.read_u8()) } else { 255 };
+            Some(Rgba { r: r, g: g, b: b, a: a })
+        };
+        let x_offset = if flags & 0x01 != 0 { Some(try!(reader.read_u16()) as i16) } else { None };
+        let y_offset = if flags & 0x02 != 0 { Some(try!(reader.read_u16()) as i16) } else { None };
+        let height = if has_font { Some(try!(reader.read_u16())) } else { None };
+        let glyph_count = try!(reader.read_u8());
+        let mut glyphs = Vec::with_capacity(glyph_count as usize);
+        for _ in 0..glyph_count {
+            glyphs.push((try!(reader.read_ub(glyph_bits)), try!(reader.read_sb(advance_bits))));
+        }
+        records.push(TextRecord {
+            font_id: font_id,
+            color: color,
+            x_offset: x_offset,
+            y_offset: y_offset,
+            height: height,
+            glyphs: glyphs
+        });
+    }
+
+    Ok(Some((StaticText {
+        id: id,
+        version: version,
+        bounds: bounds,
+        matrix: matrix,
+        records: records
+    }, font_offsets)))
+}
+
+/// Parses a DefineEditText, also returning where its first flags byte is
+/// and where its initial text starts (or would start).
+fn parse_edit_text(tag: &Tag) -> Result<Option<(EditText, (usize, usize))>, Error> {
+    if tag.code() != codes::DEFINE_EDIT_TEXT {
+        return Ok(None);
+    }
+    let data = tag.data();
+    let mut bits = BitReader::new(data);
+    let id = try!(bits.read_u16());
+    let bounds = try!(Rect::read(&mut bits));
+    let flags_pos = bits.byte_pos();
+
+    // DEFINEEDITTEXT overview:
+    // After the RECT, two bytes of flags saying which of the optional
+    // fields follow: FontID, FontClass, FontHeight, TextColor, MaxLength,
+    // then the layout. VariableName is always there, and InitialText comes
+    // last.
+    let mut reader = match data.get(flags_pos..) {
+        Some(rest) => rest,
+        None => return Err(Error::NotSwf)
+    };
+    let flags = try!(reader.read_u8());
+    let flags2 = try!(reader.read_u8());
+    let has_font = flags & 0x01 != 0;
+    let has_font_class = flags2 & 0x80 != 0;
+
+    let font_id = if has_font { Some(try!(reader.read_u16::<LittleEndian>())) } else { None };
+    let font_class = if has_font_class { Some(try!(read_string(&mut reader))) } else { None };
+    let font_height = if has_font || has_font_class {
+        Some(try!(reader.read_u16::<LittleEndian>()))
+    } else {
+        None
+    };
+    let color = if flags & 0x04 != 0 { Some(try!(Rgba::read_rgba(&mut reader))) } else { None };
+    let max_length = if flags & 0x02 != 0 { Some(try!(reader.read_u16::<LittleEndian>())) } else { None };
+    if flags2 & 0x20 != 0 {
+        // Align, the left and right margins, indent and leading.
+        for _ in 0..9 {
+            try!(reader.read_u8());
+        }
+    }
+    let variable_name = try!(read_string(&mut reader));
+    let text_pos = data.len() - reader.len();
+    let initial_text = if flags & 0x80 != 0 { Some(try!(read_string(&mut reader))) } else { None };
+
+    Ok(Some((EditText {
+        id: id,
+        bounds: bounds,
+        font_id: font_id,
+        font_class: font_class,
+        font_height: font_height,
+        color: color,
+        max_length: max_length,
+        variable_name: variable_name,
+        initial_text: initial_text,
+        is_html: flags2 & 0x02 != 0,
+        uses_outlines: flags2 & 0x01 != 0,
+        multiline: flags & 0x20 != 0,
+        word_wrap: flags & 0x40 != 0,
+        password: flags & 0x10 != 0,
+        read_only: flags & 0x08 != 0,
+        was_static: flags2 & 0x04 != 0
+    }, (flags_pos, text_pos))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tag::{codes, Tag};
+
+    #[test]
+    fn test_edit_text() {
+        let tag = Tag::new(codes::DEFINE_EDIT_TEXT, vec![
+            4, 0,
+            // An empty RECT.
+            0,
+            // HasText, Multiline, HasTextColor, HasFont, then HasLayout,
+            // HTML and UseOutlines.
+            0xa5, 0x23,
+            7, 0,
+            240, 0,
+            0x11, 0x22, 0x33, 0xff,
+            0, 0, 0, 0, 0, 0, 0, 0, 0,
+            b'_', b'r', b'o', b'o', b't', b'.', b'n', 0,
+            b'<', b'b', b'>', b'H', b'i', b'<', b'/', b'b', b'>', 0
+        ]);
+        let field = EditText::parse(&tag).unwrap().unwrap();
+        assert_eq!(field.id, 4);
+        assert_eq!(field.font_id, Some(7));
+        assert_eq!(field.font_class, None);
+        assert_eq!(field.font_height, Some(240));
+        assert_eq!(field.color, Some(Rgba { r: 0x11, g: 0x22, b: 0x33, a: 0xff }));
+        assert_eq!(field.variable_name, "_root.n");
+        assert_eq!(field.initial_text, Some("<b>Hi</b>".to_string()));
+        assert!(field.is_html && field.uses_outlines && field.multiline);
+        assert!(!field.word_wrap && !field.read_only);
+
+        let translated = with_initial_text(&tag, "<b>Salut</b>").unwrap().unwrap();
+        let translated = EditText::parse(&translated).unwrap().unwrap();
+        assert_eq!(translated.initial_text, Some("<b>Salut</b>".to_string()));
+        assert_eq!(EditText { initial_text: None, ..translated }, EditText { initial_text: None, ..field });
+
+        assert_eq!(EditText::parse(&Tag::new(codes::SHOW_FRAME, vec![])).unwrap(), None);
+    }
+
+    #[test]
+    fn test_translate() {
+        // Font 1, with layout, and glyphs for "H", "i" and "o" advancing
+        // half, a quarter and half an EM. The glyph shapes are left out, as
+        // they aren't read.
+        let font = Tag::new(codes::DEFINE_FONT_2, vec![
+            1, 0, 0x80, 0, 1, b'A', 3, 0,
+            8, 0, 8, 0, 8, 0, 8, 0,
+            b'H', b'i', b'o',
+            0, 0, 0, 0, 0, 0,
+            0, 2, 0, 1, 0, 2
+        ]);
+        let record = |font_id, y_offset, glyphs| TextRecord {
+            font_id: font_id,
+            color: None,
+            x_offset: None,
+            y_offset: y_offset,
+            height: font_id.map(|_| 40),
+            glyphs: glyphs
+        };
+        let text = StaticText {
+            id: 2,
+            version: 1,
+            bounds: Rect { x_min: 0, x_max: 400, y_min: 0, y_max: 400 },
+            matrix: Matrix::identity(),
+            records: vec![
+                record(Some(1), None, vec![(0, 20), (1, 10)]),
+                record(None, Some(40), vec![(1, 10), (0, 20)])
+            ]
+        };
+        let text_tag = text.to_tag().unwrap();
+        assert_eq!(StaticText::parse(&text_tag).unwrap(), Some(text.clone()));
+        let field = Tag::new(codes::DEFINE_EDIT_TEXT, b"\x03\0\0\x80\0\0Hi\0".to_vec());
+
+        let mut translations = HashMap::new();
+        translations.insert("Hi".to_string(), "oHo".to_string());
+        translations.insert("iH".to_string(), "Hey".to_string());
+        let mut translator = Translator::new(&translations);
+        assert_eq!(translator.translate(&font), TagAction::Keep);
+        let translated = match translator.translate(&text_tag) {
+            TagAction::Replace(tags) => StaticText::parse(&tags[0]).unwrap().unwrap(),
+            other => panic!("expected the text to be replaced, got {:?}", other)
+        };
+        assert_eq!(translated.records[0].glyphs, vec![(2, 20), (0, 20), (2, 20)]);
+        assert_eq!(translated.records[1], text.records[1]);
+        match translator.translate(&field) {
+            TagAction::Replace(tags) => {
+                let field = EditText::parse(&tags[0]).unwrap().unwrap();
+                assert_eq!(field.initial_text, Some("oHo".to_string()));
+            },
+            other => panic!("expected the field to be replaced, got {:?}", other)
+        }
+        let settings = Tag::new(codes::CSM_TEXT_SETTINGS, vec![2, 0, 0x48, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(translator.translate(&settings), TagAction::Keep);
+        assert_eq!(translator.translated(), 2);
+        assert_eq!(translator.untranslatable(), &["Hey".to_string()]);
+    }
+}
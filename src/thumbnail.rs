@@ -0,0 +1,83 @@
+//! Everything needed to draw a static preview of a movie's first frame,
+//! short of actually drawing it.
+
+use std::io::Read;
+
+use super::SwfHeaders;
+use assets::{Bitmap, BitmapKind};
+use error::Error;
+use primitives::Rgba;
+use tag::{codes, Tag, Tags};
+use timeline::{DisplayList, DisplayObject};
+
+/// A structured description of the first frame of a movie.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Thumbnail {
+    /// The stage size in twips.
+    pub stage_twips: (u32, u32),
+    /// The background color, if the movie sets one.
+    pub background: Option<Rgba>,
+    /// The objects on the display list at the end of the first frame, from
+    /// the bottom depth up.
+    pub objects: Vec<DisplayObject>,
+    /// Every bitmap defined by the end of the first frame, and so available
+    /// to anything on it.
+    pub bitmaps: Vec<Bitmap>,
+    /// The JPEGTables payload, needed to decode `BitmapKind::Jpeg` bitmaps.
+    pub jpeg_tables: Option<Vec<u8>>
+}
+
+/// Reads a SWF body up to the end of its first frame and gathers what a
+/// thumbnailer needs.
+///
+/// Only the first frame is ever read, so this stays cheap on huge files.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::SwfHeaders;
+/// use swf_headers::thumbnail;
+/// if let Ok((headers, decoded)) = SwfHeaders::open("example.swf") {
+///     let thumb = thumbnail::first_frame(&headers, decoded).unwrap();
+///     println!("{} objects on the first frame", thumb.objects.len());
+/// }
+/// ```
+pub fn first_frame<R: Read>(headers: &SwfHeaders, body: R) -> Result<Thumbnail, Error> {
+    let mut background = None;
+    let mut list = DisplayList::new();
+    let mut bitmaps = Vec::new();
+    let mut jpeg_tables = None;
+
+    for tag in Tags::new(body) {
+        let tag: Tag = try!(tag);
+        match tag.code() {
+            codes::SHOW_FRAME => break,
+            codes::SET_BACKGROUND_COLOR => {
+                background = Some(try!(Rgba::read_rgb(&mut tag.data())));
+            },
+            codes::JPEG_TABLES => {
+                jpeg_tables = Some(tag.into_data());
+            },
+            _ => {
+                if let Some(bitmap) = try!(Bitmap::from_tag(&tag)) {
+                    bitmaps.push(bitmap);
+                } else {
+                    try!(list.apply(&tag));
+                }
+            }
+        }
+    }
+
+    // The tables are only worth handing over if something needs them.
+    if !bitmaps.iter().any(|bitmap| bitmap.kind == BitmapKind::Jpeg) {
+        jpeg_tables = None;
+    }
+
+    Ok(Thumbnail {
+        stage_twips: headers.dimensions_twips(),
+        background: background,
+        objects: list.objects().cloned().collect(),
+        bitmaps: bitmaps,
+        jpeg_tables: jpeg_tables
+    })
+}
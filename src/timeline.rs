@@ -0,0 +1,370 @@
+//! A lightweight model of the display list, replaying PlaceObject and
+//! RemoveObject tags frame by frame.
+//!
+//! This doesn't render anything or run any scripts; it only keeps track of
+//! which character sits at which depth, and how it's transformed.
+//! `frame_scripts()` says which frames would run scripts, without looking
+//! at what's in them.
+
+use std::collections::BTreeMap;
+use std::collections::btree_map::Values;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use error::Error;
+use primitives::{read_string, read_u16_at, BitReader, ColorTransform, Matrix};
+use tag::{codes, Tag};
+
+/// The contents of a single PlaceObject, PlaceObject2 or PlaceObject3 tag.
+///
+/// Fields the tag didn't set are `None`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Placement {
+    /// The depth being placed at or modified.
+    pub depth: u16,
+    /// Whether this modifies (or replaces) whatever is already at the depth.
+    pub is_move: bool,
+    /// The character being placed.
+    pub character_id: Option<u16>,
+    /// The transform to apply.
+    pub matrix: Option<Matrix>,
+    /// The color transform to apply.
+    pub color_transform: Option<ColorTransform>,
+    /// The morph ratio, for morph shapes and video.
+    pub ratio: Option<u16>,
+    /// The instance name.
+    pub name: Option<String>,
+    /// The depth up to which this object acts as a mask.
+    pub clip_depth: Option<u16>
+}
+
+impl Placement {
+    /// Parses a placement tag, returning `None` for any other kind of tag.
+    pub fn parse(tag: &Tag) -> Result<Option<Self>, Error> {
+        let data = tag.data();
+        match tag.code() {
+            codes::PLACE_OBJECT => {
+                let character_id = try!(read_u16_at(data, 0));
+                let depth = try!(read_u16_at(data, 2));
+                let mut bits = BitReader::new(&data[4..]);
+                let matrix = try!(Matrix::read(&mut bits));
+                // The color transform is optional, and only there if there
+                // are bytes left over after the matrix.
+                bits.align();
+                let color_transform = if bits.byte_pos() < data.len() - 4 {
+                    Some(try!(ColorTransform::read(&mut bits, false)))
+                } else {
+                    None
+                };
+                Ok(Some(Placement {
+                    depth: depth,
+                    is_move: false,
+                    character_id: Some(character_id),
+                    matrix: Some(matrix),
+                    color_transform: color_transform,
+                    ratio: None,
+                    name: None,
+                    clip_depth: None
+                }))
+            },
+            codes::PLACE_OBJECT_2 | codes::PLACE_OBJECT_3 => {
+                parse_place_object_2_or_3(data, tag.code() == codes::PLACE_OBJECT_3).map(Some)
+            },
+            _ => Ok(None)
+        }
+    }
+}
+
+fn parse_place_object_2_or_3(data: &[u8], is_v3: bool) -> Result<Placement, Error> {
+    let mut reader = data;
+    let flags = try!(reader.read_u8());
+    let flags2 = if is_v3 { try!(reader.read_u8()) } else { 0 };
+    let depth = try!(reader.read_u16::<LittleEndian>());
+
+    let has_clip_depth = flags & 0x40 != 0;
+    let has_name = flags & 0x20 != 0;
+    let has_ratio = flags & 0x10 != 0;
+    let has_color_transform = flags & 0x08 != 0;
+    let has_matrix = flags & 0x04 != 0;
+    let has_character = flags & 0x02 != 0;
+    let is_move = flags & 0x01 != 0;
+    let has_image = flags2 & 0x10 != 0;
+    let has_class_name = flags2 & 0x08 != 0;
+
+    if has_class_name || (has_image && has_character) {
+        try!(read_string(&mut reader));
+    }
+    let character_id = if has_character {
+        Some(try!(reader.read_u16::<LittleEndian>()))
+    } else {
+        None
+    };
+    let matrix = if has_matrix {
+        let mut bits = BitReader::new(reader);
+        let matrix = try!(Matrix::read(&mut bits));
+        reader = &reader[bits.byte_pos()..];
+        Some(matrix)
+    } else {
+        None
+    };
+    let color_transform = if has_color_transform {
+        let mut bits = BitReader::new(reader);
+        let color_transform = try!(ColorTransform::read(&mut bits, true));
+        reader = &reader[bits.byte_pos()..];
+        Some(color_transform)
+    } else {
+        None
+    };
+    let ratio = if has_ratio {
+        Some(try!(reader.read_u16::<LittleEndian>()))
+    } else {
+        None
+    };
+    let name = if has_name {
+        Some(try!(read_string(&mut reader)))
+    } else {
+        None
+    };
+    let clip_depth = if has_clip_depth {
+        Some(try!(reader.read_u16::<LittleEndian>()))
+    } else {
+        None
+    };
+
+    Ok(Placement {
+        depth: depth,
+        is_move: is_move,
+        character_id: character_id,
+        matrix: matrix,
+        color_transform: color_transform,
+        ratio: ratio,
+        name: name,
+        clip_depth: clip_depth
+    })
+}
+
+/// A character instance sitting on the display list.
+#[derive(Clone, PartialEq, Debug)]
+pub struct DisplayObject {
+    /// The depth it sits at.
+    pub depth: u16,
+    /// The character it's an instance of.
+    pub character_id: u16,
+    /// Its current transform.
+    pub matrix: Matrix,
+    /// Its color transform, if one was ever set.
+    pub color_transform: Option<ColorTransform>,
+    /// Its morph ratio, if one was ever set.
+    pub ratio: Option<u16>,
+    /// Its instance name, if it has one.
+    pub name: Option<String>,
+    /// The depth up to which it masks, if it's a mask.
+    pub clip_depth: Option<u16>
+}
+
+/// The display list of a single timeline, ordered by depth.
+#[derive(Clone, PartialEq, Debug)]
+pub struct DisplayList {
+    objects: BTreeMap<u16, DisplayObject>
+}
+
+impl DisplayList {
+    /// Creates an empty display list.
+    pub fn new() -> Self {
+        DisplayList {
+            objects: BTreeMap::new()
+        }
+    }
+
+    /// Applies a tag to the display list. Tags that don't touch the display
+    /// list are ignored.
+    pub fn apply(&mut self, tag: &Tag) -> Result<(), Error> {
+        match tag.code() {
+            codes::REMOVE_OBJECT => {
+                self.objects.remove(&try!(read_u16_at(tag.data(), 2)));
+            },
+            codes::REMOVE_OBJECT_2 => {
+                self.objects.remove(&try!(read_u16_at(tag.data(), 0)));
+            },
+            _ => {
+                if let Some(placement) = try!(Placement::parse(tag)) {
+                    self.place(placement);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn place(&mut self, placement: Placement) {
+        let existing = self.objects.remove(&placement.depth);
+        let object = match (existing, placement.is_move, placement.character_id) {
+            // Modifying, and possibly swapping the character of, an existing
+            // object. Anything the tag doesn't mention carries over.
+            (Some(old), true, character_id) => DisplayObject {
+                depth: placement.depth,
+                character_id: character_id.unwrap_or(old.character_id),
+                matrix: placement.matrix.unwrap_or(old.matrix),
+                color_transform: placement.color_transform.or(old.color_transform),
+                ratio: placement.ratio.or(old.ratio),
+                name: placement.name.or(old.name),
+                clip_depth: placement.clip_depth.or(old.clip_depth)
+            },
+            // A fresh placement, replacing anything that was there.
+            (_, _, Some(character_id)) => DisplayObject {
+                depth: placement.depth,
+                character_id: character_id,
+                matrix: placement.matrix.unwrap_or_else(Matrix::identity),
+                color_transform: placement.color_transform,
+                ratio: placement.ratio,
+                name: placement.name,
+                clip_depth: placement.clip_depth
+            },
+            // Modifying an empty depth, or placing nothing. Flash Player
+            // ignores these, so we do too.
+            (old, _, None) => match old {
+                Some(old) => old,
+                None => return
+            }
+        };
+        self.objects.insert(placement.depth, object);
+    }
+
+    /// Returns the object at a depth, if there is one.
+    pub fn get(&self, depth: u16) -> Option<&DisplayObject> {
+        self.objects.get(&depth)
+    }
+
+    /// Returns every object, from the bottom depth up.
+    pub fn objects<'a>(&'a self) -> Values<'a, u16, DisplayObject> {
+        self.objects.values()
+    }
+
+    /// Returns the number of objects on the display list.
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Returns true if nothing is on the display list.
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+/// Replays a tag list and returns the display list as it stands at every
+/// ShowFrame, in frame order.
+pub fn frames(tags: &[Tag]) -> Result<Vec<DisplayList>, Error> {
+    let mut list = DisplayList::new();
+    let mut frames = Vec::new();
+    for tag in tags {
+        if tag.code() == codes::SHOW_FRAME {
+            frames.push(list.clone());
+        } else {
+            try!(list.apply(tag));
+        }
+    }
+    Ok(frames)
+}
+
+/// The scripts attached to a single frame.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct FrameScripts {
+    /// The number of DoAction tags, run when the frame is shown.
+    pub actions: usize,
+    /// The number of DoInitAction tags, run before the frame's DoActions.
+    pub init_actions: usize,
+    /// The number of DoABC and DoABCDefine tags, loaded on the frame.
+    pub abc: usize
+}
+
+impl FrameScripts {
+    /// Returns true if any code comes with the frame.
+    ///
+    /// ActionScript 3 code loaded on an earlier frame can still hook this
+    /// one with `addFrameScript()`, which only running it would show, so
+    /// once a movie has loaded ABC, false doesn't rule code out.
+    pub fn has_code(&self) -> bool {
+        self.actions > 0 || self.init_actions > 0 || self.abc > 0
+    }
+}
+
+/// Lists the scripts attached to each frame of a single timeline, in frame
+/// order, so players can tell where scripted interactivity starts.
+///
+/// Sprites have their own timelines, so pass them in separately.
+pub fn frame_scripts(tags: &[Tag]) -> Vec<FrameScripts> {
+    let mut frames = Vec::new();
+    let mut frame = FrameScripts::default();
+    for tag in tags {
+        match tag.code() {
+            codes::SHOW_FRAME => {
+                frames.push(frame);
+                frame = FrameScripts::default();
+            },
+            codes::DO_ACTION => frame.actions += 1,
+            codes::DO_INIT_ACTION => frame.init_actions += 1,
+            codes::DO_ABC | codes::DO_ABC_DEFINE => frame.abc += 1,
+            _ => {}
+        }
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tag::{codes, Tag};
+
+    // PlaceObject2 with HasCharacter and HasMatrix, an identity-ish matrix
+    // translated by (1, 1), at depth 1.
+    fn place(character_id: u8) -> Tag {
+        Tag::new(codes::PLACE_OBJECT_2, vec![0x06, 1, 0, character_id, 0, 0b0000_0100, 0b1010_0000])
+    }
+
+    #[test]
+    fn test_place_and_remove() {
+        let tags = vec![
+            place(3),
+            Tag::new(codes::SHOW_FRAME, vec![]),
+            // Move to depth 1 with a new name only.
+            Tag::new(codes::PLACE_OBJECT_2, vec![0x21, 1, 0, b'a', 0]),
+            Tag::new(codes::SHOW_FRAME, vec![]),
+            Tag::new(codes::REMOVE_OBJECT_2, vec![1, 0]),
+            Tag::new(codes::SHOW_FRAME, vec![])
+        ];
+        let frames = frames(&tags).unwrap();
+        assert_eq!(frames.len(), 3);
+
+        let first = frames[0].get(1).unwrap();
+        assert_eq!(first.character_id, 3);
+        assert_eq!(first.matrix.translate_x, 1);
+        assert_eq!(first.matrix.translate_y, 1);
+        assert_eq!(first.name, None);
+
+        let second = frames[1].get(1).unwrap();
+        assert_eq!(second.character_id, 3);
+        assert_eq!(second.matrix.translate_x, 1);
+        assert_eq!(second.name, Some("a".to_owned()));
+
+        assert!(frames[2].is_empty());
+    }
+
+    #[test]
+    fn test_frame_scripts() {
+        let tags = vec![
+            Tag::new(codes::SHOW_FRAME, vec![]),
+            Tag::new(codes::DO_INIT_ACTION, vec![1, 0, 0]),
+            Tag::new(codes::DO_ACTION, vec![0]),
+            Tag::new(codes::DO_ACTION, vec![0]),
+            Tag::new(codes::SHOW_FRAME, vec![]),
+            Tag::new(codes::DO_ABC, vec![]),
+            Tag::new(codes::SHOW_FRAME, vec![]),
+            // Never shown, so never run.
+            Tag::new(codes::DO_ACTION, vec![0])
+        ];
+        let frames = frame_scripts(&tags);
+        assert_eq!(frames.len(), 3);
+        assert!(!frames[0].has_code());
+        assert_eq!(frames[1], FrameScripts { actions: 2, init_actions: 1, abc: 0 });
+        assert!(frames[2].has_code());
+    }
+}
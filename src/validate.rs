@@ -0,0 +1,505 @@
+//! Strict checks of a SWF file against the spec.
+//!
+//! Flash Player shrugs off a lot of damage, so a file that plays can still
+//! be broken in ways that trip up other players and tools. `validate()`
+//! reports every problem it can find rather than stopping at the first,
+//! each with a short code that stays the same between releases, so scripts
+//! can match on them.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use decoded_swf::DecodedSwf;
+use error::Error;
+use options::ParseOptions;
+use primitives::{read_string, read_u16_at, BitReader, Rect};
+use tag::{self, codes, Tag};
+use tag_codes;
+use timeline::Placement;
+use super::Signature;
+
+/// The FileAttributes flag saying the file has a Metadata tag.
+const FILE_ATTRIBUTES_HAS_METADATA: u8 = 0x10;
+
+/// How serious a finding is.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Severity {
+    /// Breaks the spec, so some player or tool is likely to choke on it.
+    Error,
+    /// Allowed, but suspicious.
+    Warning
+}
+
+impl Severity {
+    /// Returns `"error"` or `"warning"`.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Severity::Error => "error",
+            Severity::Warning => "warning"
+        }
+    }
+}
+
+/// A single problem found in a file.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Finding {
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// What kind of problem it is, such as `"file-length"` or
+    /// `"undefined-character"`.
+    pub code: &'static str,
+    /// A description for people.
+    pub message: String,
+    /// Where the problem is, as a byte offset into the decompressed file,
+    /// header included. `None` for problems with the file as a whole.
+    pub offset: Option<u64>
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}[{}]", self.severity.as_str(), self.code));
+        if let Some(offset) = self.offset {
+            try!(write!(f, " at byte {}", offset));
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Checks a file, returning everything wrong with it. An empty list means
+/// the file passed.
+///
+/// Only failing to read the file is an `Err`. Anything wrong with what's in
+/// it, including not being a SWF at all, is a finding.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::validate;
+/// if let Ok(findings) = validate::validate("example.swf") {
+///     for finding in &findings {
+///         println!("{}", finding);
+///     }
+///     if validate::has_errors(&findings) {
+///         println!("Don't ship it");
+///     }
+/// }
+/// ```
+pub fn validate<P: AsRef<Path>>(path: P) -> Result<Vec<Finding>, Error> {
+    validate_from(try!(File::open(path)))
+}
+
+/// Like `validate()`, but reads the file from any stream.
+pub fn validate_from<R: Read>(mut reader: R) -> Result<Vec<Finding>, Error> {
+    // Everything is read up front, so that any error from here on is the
+    // file's fault rather than the disk's.
+    let mut raw = Vec::new();
+    try!(reader.read_to_end(&mut raw));
+    Ok(validate_bytes(&raw))
+}
+
+/// Like `validate()`, but for a whole file that's already in memory.
+pub fn validate_bytes(raw: &[u8]) -> Vec<Finding> {
+    let mut checker = Checker { findings: Vec::new() };
+    checker.check_file(raw);
+    checker.findings
+}
+
+/// Returns true if any of the findings are errors.
+pub fn has_errors(findings: &[Finding]) -> bool {
+    findings.iter().any(|finding| finding.severity == Severity::Error)
+}
+
+/// A tag along with where it was found.
+struct Entry {
+    offset: u64,
+    payload_offset: u64,
+    tag: Tag
+}
+
+struct Checker {
+    findings: Vec<Finding>
+}
+
+impl Checker {
+    fn error(&mut self, code: &'static str, offset: Option<u64>, message: String) {
+        self.report(Severity::Error, code, offset, message);
+    }
+
+    fn warning(&mut self, code: &'static str, offset: Option<u64>, message: String) {
+        self.report(Severity::Warning, code, offset, message);
+    }
+
+    fn report(&mut self, severity: Severity, code: &'static str, offset: Option<u64>, message: String) {
+        self.findings.push(Finding {
+            severity: severity,
+            code: code,
+            message: message,
+            offset: offset
+        });
+    }
+
+    fn check_file(&mut self, raw: &[u8]) {
+        let signature = match raw.get(..3) {
+            Some(magic) if magic == b"FWS" => Signature::Uncompressed,
+            Some(magic) if magic == b"CWS" => Signature::ZlibCompressed,
+            Some(magic) if magic == b"ZWS" => Signature::LzmaCompressed,
+            _ => return self.error("not-swf", Some(0), "the file doesn't start with FWS, CWS or ZWS".to_string())
+        };
+        if raw.len() < 8 {
+            return self.error("truncated-header", Some(0), "the file ends inside the header".to_string());
+        }
+        let version = raw[3];
+        let file_length = match (&raw[4..8]).read_u32::<LittleEndian>() {
+            Ok(file_length) => file_length,
+            Err(_) => return self.error("truncated-header", Some(4), "the file ends inside the header".to_string())
+        };
+        if !signature.is_supported_by(version) {
+            let method = if signature == Signature::LzmaCompressed { "LZMA" } else { "zlib" };
+            self.error("signature-version", Some(0),
+                       format!("SWF {} files can't be {} compressed", version, method));
+        }
+        if signature == Signature::LzmaCompressed {
+            self.check_lzma_length(raw);
+        }
+
+        // A stream that fails partway still leaves what came before it to
+        // check, but its length means nothing.
+        let mut body = Vec::new();
+        let decompressed = DecodedSwf::decompress(Cursor::new(raw[8..].to_vec()), signature)
+            .and_then(|mut decoded| decoded.read_to_end(&mut body).map_err(Error::from));
+        match decompressed {
+            Ok(_) => {
+                let actual = 8 + body.len() as u64;
+                if file_length as u64 != actual {
+                    self.error("file-length", Some(4),
+                               format!("the header gives the file length as {} bytes, but it's {}",
+                                       file_length, actual));
+                }
+            },
+            Err(err) => self.error("compression", None, format!("the body couldn't be decompressed: {:?}", err))
+        }
+        self.check_body(&body, version);
+    }
+
+    /// Checks the compressed length a ZWS file gives against the stream
+    /// that follows its 5 bytes of LZMA properties. Less than it says means
+    /// the file was cut short, and more is usually a file edited by hand.
+    fn check_lzma_length(&mut self, raw: &[u8]) {
+        let declared = match raw.get(8..12) {
+            Some(mut bytes) => bytes.read_u32::<LittleEndian>().unwrap() as u64,
+            None => return self.error("truncated-header", Some(8), "the file ends inside the header".to_string())
+        };
+        let actual = raw.len().saturating_sub(17) as u64;
+        if actual < declared {
+            self.error("lzma-length", Some(8),
+                       format!("the header gives the LZMA stream as {} bytes, but only {} are there",
+                               declared, actual));
+        } else if actual > declared {
+            self.warning("lzma-length", Some(8),
+                         format!("the header gives the LZMA stream as {} bytes, but {} follow",
+                                 declared, actual));
+        }
+    }
+
+    fn check_body(&mut self, body: &[u8], version: u8) {
+        let mut bits = BitReader::new(body);
+        let stage = match Rect::read(&mut bits) {
+            Ok(stage) => stage,
+            Err(_) => return self.error("truncated-header", Some(8), "the file ends inside the header".to_string())
+        };
+        let pos = bits.byte_pos();
+        let (frame_rate, frame_count) = match (read_u16_at(body, pos), read_u16_at(body, pos + 2)) {
+            (Ok(frame_rate), Ok(frame_count)) => (frame_rate, frame_count),
+            _ => return self.error("truncated-header", Some(8), "the file ends inside the header".to_string())
+        };
+
+        if stage.x_min != 0 || stage.y_min != 0 {
+            self.warning("stage-origin", Some(8),
+                         format!("the stage starts at ({}, {}) twips rather than (0, 0)", stage.x_min, stage.y_min));
+        }
+        if body[0] >> 3 == 0 {
+            self.warning("empty-stage", Some(8), "the stage RECT has 0 bits per field, so it's 0x0".to_string());
+        } else if stage.width() <= 0 || stage.height() <= 0 {
+            self.warning("empty-stage", Some(8),
+                         format!("the stage is {}x{} twips", stage.width(), stage.height()));
+        }
+        if frame_rate == 0 {
+            self.error("zero-frame-rate", Some(8 + pos as u64), "the frame rate is 0".to_string());
+        }
+        if frame_count == 0 {
+            self.error("zero-frame-count", Some(8 + pos as u64 + 2), "the frame count is 0".to_string());
+        }
+
+        let tags_start = pos + 4;
+        let entries = self.read_tags(&body[tags_start..], 8 + tags_start as u64, "the movie");
+        self.check_file_attributes(&entries, version);
+        self.check_timeline(&entries, frame_count, 0, &mut HashSet::new(), "the movie");
+    }
+
+    /// Reads the tags in `data` up to its End tag, where `base` is the
+    /// offset of `data` in the file.
+    fn read_tags(&mut self, data: &[u8], base: u64, what: &str) -> Vec<Entry> {
+        let mut reader = data;
+        let mut entries = Vec::new();
+        loop {
+            let offset = base + (data.len() - reader.len()) as u64;
+            match Tag::read_from(&mut reader) {
+                Ok(Some(tag)) => {
+                    let end = base + (data.len() - reader.len()) as u64;
+                    if tag.code() == codes::END {
+                        if !reader.is_empty() {
+                            self.warning("trailing-data", Some(end),
+                                         format!("{} bytes follow the End tag of {}", reader.len(), what));
+                        }
+                        return entries;
+                    }
+                    entries.push(Entry {
+                        offset: offset,
+                        payload_offset: end - tag.data().len() as u64,
+                        tag: tag
+                    });
+                },
+                Ok(None) => {
+                    self.error("missing-end", Some(offset), format!("{} has no End tag", what));
+                    return entries;
+                },
+                Err(_) => {
+                    self.error("truncated-tag", Some(offset), format!("a tag runs past the end of {}", what));
+                    return entries;
+                }
+            }
+        }
+    }
+
+    fn check_file_attributes(&mut self, entries: &[Entry], version: u8) {
+        for entry in entries.iter().skip(1).filter(|entry| entry.tag.code() == codes::FILE_ATTRIBUTES) {
+            self.error("file-attributes", Some(entry.offset),
+                       "FileAttributes can only be the first tag".to_string());
+        }
+        let first = match entries.first() {
+            Some(first) if first.tag.code() == codes::FILE_ATTRIBUTES => first,
+            first => {
+                if version >= 8 {
+                    self.error("file-attributes", first.map(|first| first.offset),
+                               format!("SWF {} files must start with a FileAttributes tag", version));
+                }
+                return;
+            }
+        };
+        let has_metadata_flag = first.tag.data().first().map_or(false, |flags| {
+            flags & FILE_ATTRIBUTES_HAS_METADATA != 0
+        });
+        let has_metadata = entries.iter().any(|entry| entry.tag.code() == codes::METADATA);
+        if has_metadata_flag != has_metadata {
+            let message = if has_metadata {
+                "there's a Metadata tag, but FileAttributes doesn't say so"
+            } else {
+                "FileAttributes says there's a Metadata tag, but there isn't one"
+            };
+            self.warning("metadata-flag", Some(first.offset), message.to_string());
+        }
+    }
+
+    /// Checks a timeline, with `depth` being 0 for the main one and
+    /// counting up through nested sprites. `defined` collects character IDs
+    /// in file order, so sprites can use whatever came before them.
+    fn check_timeline(&mut self, entries: &[Entry], frame_count: u16, depth: u32, defined: &mut HashSet<u16>,
+                      what: &str) {
+        let mut frames = 0u32;
+        for entry in entries {
+            let tag = &entry.tag;
+            let code = tag.code();
+            if code == codes::SHOW_FRAME {
+                frames += 1;
+            }
+            if code == codes::IMPORT_ASSETS || code == codes::IMPORT_ASSETS_2 {
+                match imported_ids(tag) {
+                    Ok(ids) => defined.extend(ids),
+                    Err(_) => self.error("malformed-tag", Some(entry.offset),
+                                         "an ImportAssets tag is cut short".to_string())
+                }
+            }
+
+            if tag::defines_character(code) {
+                match tag::character_id(tag) {
+                    Some(id) => {
+                        if !defined.insert(id) {
+                            self.error("duplicate-character", Some(entry.offset),
+                                       format!("character {} is defined more than once", id));
+                        }
+                        if depth > 0 && code != codes::DEFINE_SPRITE {
+                            self.error("definition-in-sprite", Some(entry.offset),
+                                       format!("character {} is defined inside {}", id, what));
+                        }
+                    },
+                    None => self.error("malformed-tag", Some(entry.offset),
+                                       format!("a {} tag is too short to hold a character ID",
+                                               tag_codes::name(code).unwrap_or("definition")))
+                }
+            }
+            if tag::refers_to_character(code) {
+                if let Ok(id) = read_u16_at(tag.data(), 0) {
+                    self.check_defined(id, entry, defined);
+                }
+            }
+            match Placement::parse(tag) {
+                Ok(Some(Placement { character_id: Some(id), .. })) => self.check_defined(id, entry, defined),
+                Ok(_) => {},
+                Err(_) => self.error("malformed-tag", Some(entry.offset), "a PlaceObject tag is cut short".to_string())
+            }
+
+            if code == codes::DEFINE_SPRITE {
+                self.check_sprite(entry, depth + 1, defined);
+            }
+        }
+        if frames != frame_count as u32 {
+            self.error("frame-count", None,
+                       format!("{} says it has {} frames, but has {} ShowFrame tags", what, frame_count, frames));
+        }
+    }
+
+    fn check_defined(&mut self, id: u16, entry: &Entry, defined: &HashSet<u16>) {
+        if !defined.contains(&id) {
+            self.error("undefined-character", Some(entry.offset),
+                       format!("character {} is used before it's defined", id));
+        }
+    }
+
+    fn check_sprite(&mut self, entry: &Entry, depth: u32, defined: &mut HashSet<u16>) {
+        let data = entry.tag.data();
+        let (id, frame_count) = match (read_u16_at(data, 0), read_u16_at(data, 2)) {
+            (Ok(id), Ok(frame_count)) => (id, frame_count),
+            _ => return self.error("malformed-tag", Some(entry.offset), "a DefineSprite tag is cut short".to_string())
+        };
+        if depth > 1 {
+            self.warning("nested-sprite", Some(entry.offset),
+                         format!("sprite {} is defined inside another sprite", id));
+        }
+        if depth > ParseOptions::default().sprite_depth_limit() {
+            return self.error("sprite-depth", Some(entry.offset),
+                              format!("sprite {} is nested {} deep", id, depth));
+        }
+        let what = format!("sprite {}", id);
+        let entries = self.read_tags(&data[4..], entry.payload_offset + 4, &what);
+        self.check_timeline(&entries, frame_count, depth, defined, &what);
+    }
+}
+
+/// Returns the character IDs an ImportAssets or ImportAssets2 tag brings in.
+fn imported_ids(tag: &Tag) -> Result<Vec<u16>, Error> {
+    let mut reader = tag.data();
+    try!(read_string(&mut reader));
+    if tag.code() == codes::IMPORT_ASSETS_2 {
+        // Two reserved bytes.
+        try!(reader.read_u16::<LittleEndian>());
+    }
+    let count = try!(reader.read_u16::<LittleEndian>());
+    let mut ids = Vec::new();
+    for _ in 0..count {
+        ids.push(try!(reader.read_u16::<LittleEndian>()));
+        try!(read_string(&mut reader));
+    }
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use byteorder::{LittleEndian, ReadBytesExt};
+
+    use {Signature, SwfHeaders};
+    use tag::{codes, Tag};
+    use writer;
+
+    fn movie(signature: Signature, version: u8, frame_count: u16, tags: &[Tag]) -> Vec<u8> {
+        let headers = SwfHeaders {
+            signature: signature,
+            version: version,
+            file_length: 0,
+            width: 11000,
+            height: 8000,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: frame_count
+        };
+        writer::to_bytes(&headers, tags).unwrap()
+    }
+
+    fn codes_of(bytes: Vec<u8>) -> Vec<&'static str> {
+        validate_from(Cursor::new(bytes)).unwrap().iter().map(|finding| finding.code).collect()
+    }
+
+    #[test]
+    fn test_validate() {
+        let shape = Tag::new(codes::DEFINE_SHAPE, vec![1, 0, 0]);
+        let place = Tag::new(codes::PLACE_OBJECT_2, vec![0x02, 1, 0, 1, 0]);
+        let show = Tag::new(codes::SHOW_FRAME, vec![]);
+        let attributes = Tag::new(codes::FILE_ATTRIBUTES, vec![0, 0, 0, 0]);
+        let zlib = Signature::ZlibCompressed;
+
+        let good = movie(zlib, 10, 1, &[attributes.clone(), shape.clone(), place.clone(), show.clone()]);
+        assert_eq!(codes_of(good), Vec::<&str>::new());
+
+        assert_eq!(codes_of(movie(zlib, 10, 2, &[attributes.clone(), show.clone()])), vec!["frame-count"]);
+        assert_eq!(codes_of(movie(zlib, 10, 1, &[show.clone()])), vec!["file-attributes"]);
+        assert_eq!(codes_of(movie(zlib, 7, 1, &[place.clone(), show.clone()])), vec!["undefined-character"]);
+        assert_eq!(codes_of(movie(zlib, 5, 1, &[show.clone()])), vec!["signature-version"]);
+        assert_eq!(codes_of(movie(zlib, 7, 1, &[shape.clone(), shape.clone(), show.clone()])),
+                   vec!["duplicate-character"]);
+
+        let sprite = Tag::new(codes::DEFINE_SPRITE, vec![2, 0, 1, 0, 0x40, 0x00, 0x00, 0x00]);
+        assert_eq!(codes_of(movie(zlib, 7, 1, &[shape, sprite, show.clone()])), Vec::<&str>::new());
+
+        assert_eq!(codes_of(b"GIF89a".to_vec()), vec!["not-swf"]);
+
+        // Without its End tag, and with the file length left as it was.
+        let mut truncated = movie(Signature::Uncompressed, 7, 1, &[show]);
+        let cut = truncated.len() - 2;
+        truncated.truncate(cut);
+        let findings = validate_from(Cursor::new(truncated)).unwrap();
+        assert!(has_errors(&findings));
+        assert_eq!(findings.iter().map(|finding| finding.code).collect::<Vec<_>>(), vec!["file-length", "missing-end"]);
+        assert_eq!(findings[1].offset, Some(cut as u64));
+
+        // A zero-size stage, written as a RECT with 0 bits per field.
+        let headers = SwfHeaders {
+            signature: Signature::Uncompressed,
+            version: 7,
+            file_length: 0,
+            width: 0,
+            height: 0,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let empty = writer::to_bytes(&headers, &[Tag::new(codes::SHOW_FRAME, vec![])]).unwrap();
+        assert_eq!(empty[8], 0x00);
+        assert_eq!(codes_of(empty), vec!["empty-stage"]);
+    }
+
+    #[test]
+    fn test_lzma_length() {
+        let show = Tag::new(codes::SHOW_FRAME, vec![]);
+        let attributes = Tag::new(codes::FILE_ATTRIBUTES, vec![0, 0, 0, 0]);
+        let zws = movie(Signature::LzmaCompressed, 13, 1, &[attributes, show]);
+        assert!(!codes_of(zws.clone()).contains(&"lzma-length"));
+
+        let declared = (&zws[8..12]).read_u32::<LittleEndian>().unwrap();
+        let with_length = |length: u32| {
+            let mut edited = zws.clone();
+            edited[8..12].copy_from_slice(&[length as u8, (length >> 8) as u8, (length >> 16) as u8,
+                                            (length >> 24) as u8]);
+            validate_from(Cursor::new(edited)).unwrap().into_iter().find(|finding| finding.code == "lzma-length")
+        };
+        assert_eq!(with_length(declared + 1).unwrap().severity, Severity::Error);
+        assert_eq!(with_length(declared - 1).unwrap().severity, Severity::Warning);
+        assert_eq!(with_length(declared), None);
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt;
+
+/// A SWF version number, as stored in the header.
+///
+/// Each Flash Player release brought its own SWF version, one for one up to
+/// Flash Player 10, then counting on separately through the point releases.
+/// The constants name the first version of each major release, so checks
+/// like "needs at least Flash Player 8" can be written without looking up
+/// the numbers.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::SwfVersion;
+/// let version = SwfVersion::new(13);
+/// assert!(version >= SwfVersion::FLASH_PLAYER_11);
+/// assert_eq!(version.to_string(), "SWF 13 (Flash Player 11)");
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct SwfVersion(u8);
+
+impl SwfVersion {
+    /// The first version for Flash Player 1.
+    pub const FLASH_PLAYER_1: SwfVersion = SwfVersion(1);
+    /// The first version for Flash Player 2.
+    pub const FLASH_PLAYER_2: SwfVersion = SwfVersion(2);
+    /// The first version for Flash Player 3.
+    pub const FLASH_PLAYER_3: SwfVersion = SwfVersion(3);
+    /// The first version for Flash Player 4.
+    pub const FLASH_PLAYER_4: SwfVersion = SwfVersion(4);
+    /// The first version for Flash Player 5.
+    pub const FLASH_PLAYER_5: SwfVersion = SwfVersion(5);
+    /// The first version for Flash Player 6.
+    pub const FLASH_PLAYER_6: SwfVersion = SwfVersion(6);
+    /// The first version for Flash Player 7.
+    pub const FLASH_PLAYER_7: SwfVersion = SwfVersion(7);
+    /// The first version for Flash Player 8.
+    pub const FLASH_PLAYER_8: SwfVersion = SwfVersion(8);
+    /// The first version for Flash Player 9.
+    pub const FLASH_PLAYER_9: SwfVersion = SwfVersion(9);
+    /// The first version for Flash Player 10.
+    pub const FLASH_PLAYER_10: SwfVersion = SwfVersion(10);
+    /// The first version for Flash Player 11.
+    pub const FLASH_PLAYER_11: SwfVersion = SwfVersion(13);
+    /// The first version for Flash Player 12.
+    pub const FLASH_PLAYER_12: SwfVersion = SwfVersion(23);
+    /// The first version for Flash Player 13.
+    pub const FLASH_PLAYER_13: SwfVersion = SwfVersion(24);
+    /// The first version for Flash Player 14.
+    pub const FLASH_PLAYER_14: SwfVersion = SwfVersion(25);
+    /// The first version for Flash Player 15.
+    pub const FLASH_PLAYER_15: SwfVersion = SwfVersion(26);
+    /// The first version for Flash Player 16.
+    pub const FLASH_PLAYER_16: SwfVersion = SwfVersion(27);
+    /// The first version for Flash Player 17.
+    pub const FLASH_PLAYER_17: SwfVersion = SwfVersion(28);
+    /// The first version for Flash Player 18.
+    pub const FLASH_PLAYER_18: SwfVersion = SwfVersion(29);
+    /// The first version for Flash Player 19.
+    pub const FLASH_PLAYER_19: SwfVersion = SwfVersion(30);
+    /// The first version for Flash Player 20.
+    pub const FLASH_PLAYER_20: SwfVersion = SwfVersion(31);
+    /// The first version for Flash Player 21.
+    pub const FLASH_PLAYER_21: SwfVersion = SwfVersion(32);
+    /// The first version for Flash Player 22.
+    pub const FLASH_PLAYER_22: SwfVersion = SwfVersion(33);
+    /// The first version for Flash Player 23.
+    pub const FLASH_PLAYER_23: SwfVersion = SwfVersion(34);
+    /// The first version for Flash Player 24.
+    pub const FLASH_PLAYER_24: SwfVersion = SwfVersion(35);
+    /// The first version for Flash Player 25.
+    pub const FLASH_PLAYER_25: SwfVersion = SwfVersion(36);
+    /// The first version for Flash Player 26.
+    pub const FLASH_PLAYER_26: SwfVersion = SwfVersion(37);
+    /// The first version for Flash Player 27.
+    pub const FLASH_PLAYER_27: SwfVersion = SwfVersion(38);
+    /// The first version for Flash Player 28.
+    pub const FLASH_PLAYER_28: SwfVersion = SwfVersion(39);
+    /// The first version for Flash Player 29.
+    pub const FLASH_PLAYER_29: SwfVersion = SwfVersion(40);
+    /// The first version for Flash Player 30.
+    pub const FLASH_PLAYER_30: SwfVersion = SwfVersion(41);
+    /// The first version for Flash Player 31.
+    pub const FLASH_PLAYER_31: SwfVersion = SwfVersion(42);
+    /// The first version for Flash Player 32.
+    pub const FLASH_PLAYER_32: SwfVersion = SwfVersion(43);
+
+    /// Wraps a raw version number.
+    pub fn new(version: u8) -> Self {
+        SwfVersion(version)
+    }
+
+    /// Returns the raw version number.
+    pub fn as_u8(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns the Flash Player release that introduced this version, such
+    /// as "10.2", or `None` for versions newer than the last release.
+    pub fn flash_player(&self) -> Option<&'static str> {
+        Some(match self.0 {
+            1 => "1",
+            2 => "2",
+            3 => "3",
+            4 => "4",
+            5 => "5",
+            6 => "6",
+            7 => "7",
+            8 => "8",
+            9 => "9",
+            10 => "10",
+            11 => "10.2",
+            12 => "10.3",
+            13 => "11",
+            14 => "11.1",
+            15 => "11.2",
+            16 => "11.3",
+            17 => "11.4",
+            18 => "11.5",
+            19 => "11.6",
+            20 => "11.7",
+            21 => "11.8",
+            22 => "11.9",
+            23 => "12",
+            24 => "13",
+            25 => "14",
+            26 => "15",
+            27 => "16",
+            28 => "17",
+            29 => "18",
+            30 => "19",
+            31 => "20",
+            32 => "21",
+            33 => "22",
+            34 => "23",
+            35 => "24",
+            36 => "25",
+            37 => "26",
+            38 => "27",
+            39 => "28",
+            40 => "29",
+            41 => "30",
+            42 => "31",
+            43 => "32",
+            _ => return None
+        })
+    }
+}
+
+impl From<u8> for SwfVersion {
+    fn from(version: u8) -> Self {
+        SwfVersion(version)
+    }
+}
+
+impl fmt::Display for SwfVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.flash_player() {
+            Some(player) => write!(f, "SWF {} (Flash Player {})", self.0, player),
+            None => write!(f, "SWF {}", self.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(SwfVersion::new(9).to_string(), "SWF 9 (Flash Player 9)");
+        assert_eq!(SwfVersion::new(11).to_string(), "SWF 11 (Flash Player 10.2)");
+        assert_eq!(SwfVersion::new(43).to_string(), "SWF 43 (Flash Player 32)");
+        assert_eq!(SwfVersion::new(0).to_string(), "SWF 0");
+        assert!(SwfVersion::FLASH_PLAYER_10 < SwfVersion::new(12));
+        assert!(SwfVersion::new(12) < SwfVersion::FLASH_PLAYER_11);
+    }
+}
@@ -0,0 +1,417 @@
+//! Pulling embedded video out of a tag list.
+//!
+//! A DefineVideoStream only describes a stream; its frames are VideoFrame
+//! tags, spread over whichever timeline plays the video. The codecs are
+//! the ones FLV uses, with the same IDs, so most streams can go straight
+//! into an FLV file that any transcoder reads. Screen Video is simple
+//! enough to decode here instead, into one image per frame.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::FlateReadExt;
+
+use assets::RgbaImage;
+use error::Error;
+use primitives::{read_u16_at, BitReader};
+use tag::{self, codes, Tag};
+
+/// The codecs video can be stored in.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum VideoCodec {
+    /// Sorenson Spark, a variant of H.263.
+    SorensonH263,
+    /// Screen Video: zlib-compressed blocks, only sent when they change.
+    ScreenVideo,
+    /// On2 VP6.
+    Vp6,
+    /// On2 VP6, with a second VP6 stream for the alpha channel.
+    Vp6Alpha,
+    /// Screen Video 2, which adds palettes and partial block updates.
+    ScreenVideo2,
+    /// A codec ID the spec doesn't define.
+    Unknown(u8)
+}
+
+impl VideoCodec {
+    /// Describes what a transcoder has to do with what
+    /// `VideoStream::extract()` gives for this codec.
+    pub fn next_step(&self) -> &'static str {
+        match *self {
+            VideoCodec::SorensonH263 => "Sorenson H.263 in FLV: decode as FLV1, such as with ffmpeg's flv decoder",
+            VideoCodec::ScreenVideo => "decoded to one image per frame: encode the images at the movie's frame rate",
+            VideoCodec::Vp6 => "VP6 in FLV: decode as VP6, such as with ffmpeg's vp6f decoder",
+            VideoCodec::Vp6Alpha => "VP6 with alpha in FLV: decode with ffmpeg's vp6a decoder, and encode to a \
+                                     format with an alpha channel to keep the transparency",
+            VideoCodec::ScreenVideo2 => "Screen Video 2 in FLV: decode with ffmpeg's flashsv2 decoder",
+            VideoCodec::Unknown(_) => "unknown codec: the frames can't be extracted"
+        }
+    }
+
+    fn flv_id(&self) -> Option<u8> {
+        match *self {
+            VideoCodec::SorensonH263 => Some(2),
+            VideoCodec::ScreenVideo => Some(3),
+            VideoCodec::Vp6 => Some(4),
+            VideoCodec::Vp6Alpha => Some(5),
+            VideoCodec::ScreenVideo2 => Some(6),
+            VideoCodec::Unknown(_) => None
+        }
+    }
+}
+
+/// A video stream, with its frames.
+#[derive(Clone, PartialEq, Debug)]
+pub struct VideoStream {
+    /// The character ID.
+    pub id: u16,
+    /// How many frames the DefineVideoStream says there are.
+    pub frame_count: u16,
+    /// The width in pixels.
+    pub width: u16,
+    /// The height in pixels.
+    pub height: u16,
+    /// The deblocking filter to use, where 0 leaves it to the video packets
+    /// and 1 turns it off.
+    pub deblocking: u8,
+    /// Whether the player should smooth the video when scaling it.
+    pub smoothing: bool,
+    /// The codec.
+    pub codec: VideoCodec,
+    /// The frames, as their frame number within the stream and the video
+    /// packet, in the order they appear.
+    pub frames: Vec<(u16, Vec<u8>)>
+}
+
+/// What `VideoStream::extract()` made of a stream.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ExtractedVideo {
+    /// An FLV file holding the frames.
+    Flv(Vec<u8>),
+    /// One decoded image per frame.
+    Frames(Vec<RgbaImage>)
+}
+
+impl VideoStream {
+    /// Parses a DefineVideoStream, returning `None` for other tags. The
+    /// frames are left empty; `video_streams()` fills them in.
+    pub fn parse(tag: &Tag) -> Result<Option<Self>, Error> {
+        if tag.code() != codes::DEFINE_VIDEO_STREAM {
+            return Ok(None);
+        }
+        let mut reader = tag.data();
+        let id = try!(reader.read_u16::<LittleEndian>());
+        let frame_count = try!(reader.read_u16::<LittleEndian>());
+        let width = try!(reader.read_u16::<LittleEndian>());
+        let height = try!(reader.read_u16::<LittleEndian>());
+        let flags = try!(reader.read_u8());
+        let codec = match try!(reader.read_u8()) {
+            2 => VideoCodec::SorensonH263,
+            3 => VideoCodec::ScreenVideo,
+            4 => VideoCodec::Vp6,
+            5 => VideoCodec::Vp6Alpha,
+            6 => VideoCodec::ScreenVideo2,
+            other => VideoCodec::Unknown(other)
+        };
+        Ok(Some(VideoStream {
+            id: id,
+            frame_count: frame_count,
+            width: width,
+            height: height,
+            deblocking: (flags >> 1) & 0x07,
+            smoothing: flags & 0x01 != 0,
+            codec: codec,
+            frames: Vec::new()
+        }))
+    }
+
+    /// Extracts the stream in the most usable form there is for its codec:
+    /// decoded frames for Screen Video, and an FLV file for the rest.
+    /// `VideoCodec::next_step()` says what's left to do. Fails for unknown
+    /// codecs.
+    pub fn extract(&self, frame_rate: u16) -> Result<ExtractedVideo, Error> {
+        match try!(self.decode_screen_video()) {
+            Some(frames) => Ok(ExtractedVideo::Frames(frames)),
+            None => self.to_flv(frame_rate).map(ExtractedVideo::Flv)
+        }
+    }
+
+    /// Writes the frames out as an FLV file, timed by their frame numbers at
+    /// the given frame rate. Fails for unknown codecs.
+    ///
+    /// The packets are copied as they are, except for VP6. FLV expects VP6
+    /// packets to start with how far the encoded picture, a whole number of
+    /// 16 pixel macroblocks, is cropped to get the real size, which SWF
+    /// leaves out as it has the size in the DefineVideoStream.
+    pub fn to_flv(&self, frame_rate: u16) -> Result<Vec<u8>, Error> {
+        let codec_id = match self.codec.flv_id() {
+            Some(id) => id,
+            None => return Err(Error::NotSwf)
+        };
+        // Version 1, with video only, then the header length and the size of
+        // the tag before the first, of which there isn't one.
+        let mut flv = vec![b'F', b'L', b'V', 1, 0x01];
+        try!(flv.write_u32::<BigEndian>(9));
+        try!(flv.write_u32::<BigEndian>(0));
+
+        let is_vp6 = self.codec == VideoCodec::Vp6 || self.codec == VideoCodec::Vp6Alpha;
+        let crop = |size: u16| ((16 - size % 16) % 16) as u8;
+        let adjustment = crop(self.width) << 4 | crop(self.height);
+        for (index, &(frame, ref data)) in self.frames.iter().enumerate() {
+            let frame_type = if try!(self.is_key_frame(index, data)) { 1 } else { 2 };
+            let mut body = vec![frame_type << 4 | codec_id];
+            if is_vp6 {
+                body.push(adjustment);
+            }
+            body.extend_from_slice(data);
+
+            let timestamp = (frame as u64 * 1000 / frame_rate.max(1) as u64) as u32;
+            let body_len = body.len() as u32;
+            flv.push(9);
+            write_u24(&mut flv, body_len);
+            write_u24(&mut flv, timestamp);
+            // The top byte of the timestamp comes after the rest, then the
+            // stream ID, which is always 0.
+            flv.push((timestamp >> 24) as u8);
+            write_u24(&mut flv, 0);
+            flv.extend(body);
+            try!(flv.write_u32::<BigEndian>(11 + body_len));
+        }
+        Ok(flv)
+    }
+
+    /// Decodes a Screen Video stream into one image per frame, returning
+    /// `None` for other codecs.
+    ///
+    /// Blocks that didn't change are left out of a frame, so each image
+    /// starts as a copy of the one before, and the first as opaque black.
+    pub fn decode_screen_video(&self) -> Result<Option<Vec<RgbaImage>>, Error> {
+        if self.codec != VideoCodec::ScreenVideo {
+            return Ok(None);
+        }
+        let (width, height) = (self.width as usize, self.height as usize);
+        let mut pixels = Vec::with_capacity(width * height * 4);
+        for _ in 0..width * height {
+            pixels.extend_from_slice(&[0, 0, 0, 0xff]);
+        }
+
+        let mut images = Vec::with_capacity(self.frames.len());
+        for &(_, ref data) in &self.frames {
+            // SCREENVIDEOPACKET overview:
+            // BlockWidth: UB4, ImageWidth: UB12, BlockHeight: UB4,
+            // ImageHeight: UB12, then a block at a time, left to right and
+            // bottom to top, each a big-endian u16 size and that much zlib
+            // data.
+            let mut bits = BitReader::new(data);
+            let block_width = (try!(bits.read_ub(4)) as usize + 1) * 16;
+            let image_width = try!(bits.read_ub(12)) as usize;
+            let block_height = (try!(bits.read_ub(4)) as usize + 1) * 16;
+            let image_height = try!(bits.read_ub(12)) as usize;
+            if image_width != width || image_height != height {
+                return Err(Error::NotSwf);
+            }
+
+            let mut reader = &data[4..];
+            for block_y in (0..height).step_by(block_height) {
+                for block_x in (0..width).step_by(block_width) {
+                    let size = try!(reader.read_u16::<BigEndian>()) as usize;
+                    if size == 0 {
+                        continue;
+                    }
+                    if reader.len() < size {
+                        return Err(Error::NotSwf);
+                    }
+                    let (compressed, rest) = reader.split_at(size);
+                    reader = rest;
+                    let mut block = Vec::new();
+                    try!(compressed.zlib_decode().read_to_end(&mut block));
+
+                    // Blocks on the right and top edges are cut short, and
+                    // their rows go bottom to top too, in BGR.
+                    let block_w = block_width.min(width - block_x);
+                    let block_h = block_height.min(height - block_y);
+                    if block.len() < block_w * block_h * 3 {
+                        return Err(Error::NotSwf);
+                    }
+                    for (row, bgr) in block.chunks(block_w * 3).take(block_h).enumerate() {
+                        let y = height - 1 - (block_y + row);
+                        for (column, color) in bgr.chunks(3).enumerate() {
+                            let pos = (y * width + block_x + column) * 4;
+                            pixels[pos] = color[2];
+                            pixels[pos + 1] = color[1];
+                            pixels[pos + 2] = color[0];
+                        }
+                    }
+                }
+            }
+            images.push(RgbaImage {
+                width: self.width,
+                height: self.height,
+                pixels: pixels.clone()
+            });
+        }
+        Ok(Some(images))
+    }
+
+    /// Works out whether a frame can be decoded on its own, which FLV
+    /// marks on every frame. The screen codecs only have a cheap way of
+    /// telling for the first frame, which always is one.
+    fn is_key_frame(&self, index: usize, data: &[u8]) -> Result<bool, Error> {
+        match self.codec {
+            VideoCodec::SorensonH263 => {
+                // The start code, version and temporal reference, then the
+                // picture size, which may be followed by a custom size,
+                // then the picture type, where 0 is an intra frame.
+                let mut bits = BitReader::new(data);
+                try!(bits.read_ub(30));
+                match try!(bits.read_ub(3)) {
+                    0 => { try!(bits.read_ub(16)); },
+                    1 => { try!(bits.read_ub(32)); },
+                    _ => {}
+                }
+                Ok(try!(bits.read_ub(2)) == 0)
+            },
+            // The top bit of the first byte is set for inter frames, and
+            // VP6 alpha packets start with the u24 offset of the alpha data.
+            VideoCodec::Vp6 => data.first().map(|&byte| byte & 0x80 == 0).ok_or(Error::NotSwf),
+            VideoCodec::Vp6Alpha => data.get(3).map(|&byte| byte & 0x80 == 0).ok_or(Error::NotSwf),
+            _ => Ok(index == 0)
+        }
+    }
+}
+
+/// Collects every video stream defined in a tag list, with the frames for
+/// each, including those inside sprites.
+pub fn video_streams(tags: &[Tag]) -> Result<Vec<VideoStream>, Error> {
+    let mut streams = Vec::new();
+    for tag in tags {
+        if let Some(stream) = try!(VideoStream::parse(tag)) {
+            streams.push(stream);
+        }
+    }
+    let mut frames = HashMap::new();
+    try!(collect_frames(tags, &mut frames));
+    for stream in &mut streams {
+        if let Some(stream_frames) = frames.remove(&stream.id) {
+            stream.frames = stream_frames;
+        }
+    }
+    Ok(streams)
+}
+
+fn collect_frames(tags: &[Tag], frames: &mut HashMap<u16, Vec<(u16, Vec<u8>)>>) -> Result<(), Error> {
+    for tag in tags {
+        match tag.code() {
+            codes::VIDEO_FRAME => {
+                // VIDEOFRAME overview:
+                // StreamID: u16, FrameNum: u16, then the video packet.
+                let data = tag.data();
+                let stream_id = try!(read_u16_at(data, 0));
+                let frame = try!(read_u16_at(data, 2));
+                frames.entry(stream_id).or_insert_with(Vec::new).push((frame, data[4..].to_vec()));
+            },
+            codes::DEFINE_SPRITE if tag.data().len() >= 4 => {
+                try!(collect_frames(&try!(tag::read_all(&tag.data()[4..])), frames));
+            },
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn write_u24(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&[(value >> 16) as u8, (value >> 8) as u8, value as u8]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    use flate2::Compression;
+    use flate2::write::ZlibEncoder;
+
+    use tag::{codes, Tag};
+
+    fn stream(codec: u8, width: u16, height: u16) -> Tag {
+        let mut data = vec![1, 0, 2, 0];
+        data.write_u16::<LittleEndian>(width).unwrap();
+        data.write_u16::<LittleEndian>(height).unwrap();
+        data.extend_from_slice(&[0x03, codec]);
+        Tag::new(codes::DEFINE_VIDEO_STREAM, data)
+    }
+
+    fn frame(number: u16, packet: &[u8]) -> Tag {
+        let mut data = vec![1, 0];
+        data.write_u16::<LittleEndian>(number).unwrap();
+        data.extend_from_slice(packet);
+        Tag::new(codes::VIDEO_FRAME, data)
+    }
+
+    #[test]
+    fn test_screen_video() {
+        // A 2x2 stream in one 16x16 block: red and green along the
+        // bottom, blue and white along the top.
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::Default);
+        encoder.write_all(&[0, 0, 0xff, 0, 0xff, 0, 0xff, 0, 0, 0xff, 0xff, 0xff]).unwrap();
+        let block = encoder.finish().unwrap();
+        let mut first = vec![0x00, 0x02, 0x00, 0x02];
+        first.write_u16::<BigEndian>(block.len() as u16).unwrap();
+        first.extend(block);
+
+        let mut sprite = vec![2, 0, 1, 0];
+        frame(1, &[0x00, 0x02, 0x00, 0x02, 0, 0]).write_to(&mut sprite).unwrap();
+        Tag::new(codes::END, vec![]).write_to(&mut sprite).unwrap();
+        let tags = vec![
+            stream(3, 2, 2),
+            frame(0, &first),
+            Tag::new(codes::DEFINE_SPRITE, sprite)
+        ];
+
+        let streams = video_streams(&tags).unwrap();
+        assert_eq!(streams.len(), 1);
+        assert_eq!(streams[0].codec, VideoCodec::ScreenVideo);
+        assert_eq!((streams[0].deblocking, streams[0].smoothing), (1, true));
+        assert_eq!(streams[0].frames.len(), 2);
+        let images = match streams[0].extract(12).unwrap() {
+            ExtractedVideo::Frames(images) => images,
+            other => panic!("expected frames, got {:?}", other)
+        };
+        let expected = vec![
+            0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0xff, 0, 0, 0xff, 0, 0xff, 0, 0xff
+        ];
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].pixels, expected);
+        // The second frame has no blocks, so nothing changes.
+        assert_eq!(images[1].pixels, expected);
+    }
+
+    #[test]
+    fn test_vp6_to_flv() {
+        let tags = vec![
+            stream(4, 20, 16),
+            frame(0, &[0x00, 0xaa]),
+            frame(1, &[0x80, 0xbb])
+        ];
+        let streams = video_streams(&tags).unwrap();
+        let flv = streams[0].to_flv(25).unwrap();
+        assert_eq!(&flv[..13], &[b'F', b'L', b'V', 1, 1, 0, 0, 0, 9, 0, 0, 0, 0]);
+        // A key frame at 0ms, cropped by 12 pixels horizontally.
+        assert_eq!(&flv[13..32], &[
+            9, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0,
+            0x14, 0xc0, 0x00, 0xaa,
+            0, 0, 0, 15
+        ]);
+        // An inter frame at 40ms.
+        assert_eq!(&flv[32..], &[
+            9, 0, 0, 4, 0, 0, 40, 0, 0, 0, 0,
+            0x24, 0xc0, 0x80, 0xbb,
+            0, 0, 0, 15
+        ]);
+
+        let unknown = VideoStream { codec: VideoCodec::Unknown(9), ..streams[0].clone() };
+        assert!(unknown.extract(25).is_err());
+    }
+}
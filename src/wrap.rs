@@ -0,0 +1,342 @@
+//! Building a tiny SWF around a single image or sound.
+//!
+//! Handy for checking how a player copes with a particular asset, and for
+//! rebuilding the loader shells that used to sit in front of a movie when
+//! all that's left is the asset itself.
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, WriteBytesExt};
+
+use super::{Signature, SwfHeaders};
+use error::Error;
+use primitives::{read_u16_at, signed_bits_needed, BitWriter, Matrix};
+use sound;
+use tag::{codes, Tag};
+use writer;
+
+/// What sort of asset is being wrapped.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AssetKind {
+    /// A JPEG, PNG or GIF, shown in the middle of the stage.
+    Image,
+    /// An MP3, or a WAV holding 8 or 16-bit PCM, played once as an event
+    /// sound. The sample rate has to be one SWF supports: 5512 (WAV only),
+    /// 11025, 22050 or 44100Hz.
+    Sound
+}
+
+/// Wraps an asset in a one-frame SWF, with a stage of the given size in
+/// pixels.
+///
+/// The format is worked out from the data itself. The movie is SWF 8, the
+/// first version that can hold a PNG or GIF, and is zlib compressed.
+/// Returns `Error::NotSwf` for data that isn't in a format `kind` allows.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::fs::File;
+/// use std::io::Read;
+/// use swf_headers::wrap::{self, AssetKind};
+/// let mut png = Vec::new();
+/// if let Ok(mut file) = File::open("logo.png") {
+///     file.read_to_end(&mut png).unwrap();
+///     let swf = wrap::wrap_asset(&png, AssetKind::Image, (550, 400)).unwrap();
+///     // ...
+/// }
+/// ```
+pub fn wrap_asset(data: &[u8], kind: AssetKind, stage_size: (u32, u32)) -> Result<Vec<u8>, Error> {
+    let (stage_width, stage_height) = stage_size;
+    let mut tags = vec![
+        // No flags, so an AVM1 movie with no network access.
+        Tag::new(codes::FILE_ATTRIBUTES, vec![0, 0, 0, 0]),
+        Tag::new(codes::SET_BACKGROUND_COLOR, vec![0xff, 0xff, 0xff])
+    ];
+    match kind {
+        AssetKind::Image => {
+            let (width, height) = match image_size(data) {
+                Some(size) => size,
+                None => return Err(Error::NotSwf)
+            };
+            let mut bitmap = vec![1, 0];
+            bitmap.extend_from_slice(data);
+            tags.push(Tag::new(codes::DEFINE_BITS_JPEG_2, bitmap));
+            tags.push(Tag::new(codes::DEFINE_SHAPE, bitmap_shape(2, 1, width * 20, height * 20)));
+
+            // HasMatrix and HasCharacter, at depth 1.
+            let mut place = vec![0x06, 1, 0, 2, 0];
+            let mut matrix = BitWriter::new();
+            Matrix {
+                translate_x: (stage_width as i32 - width as i32) * 10,
+                translate_y: (stage_height as i32 - height as i32) * 10,
+                ..Matrix::identity()
+            }.write(&mut matrix);
+            place.extend(matrix.into_bytes());
+            tags.push(Tag::new(codes::PLACE_OBJECT_2, place));
+        },
+        AssetKind::Sound => {
+            tags.push(try!(define_sound(1, data)));
+            // SyncNoMultiple, so it can't be started twice.
+            tags.push(Tag::new(codes::START_SOUND, vec![1, 0, 0x10]));
+        }
+    }
+    tags.push(Tag::new(codes::SHOW_FRAME, vec![]));
+
+    let headers = SwfHeaders {
+        signature: Signature::ZlibCompressed,
+        version: 8,
+        file_length: 0,
+        width: stage_width * 20,
+        height: stage_height * 20,
+        frame_rate: 24,
+        frame_rate_fraction: 0,
+        frame_count: 1
+    };
+    writer::to_bytes(&headers, &tags)
+}
+
+/// Returns the size in pixels of a JPEG, PNG or GIF.
+fn image_size(data: &[u8]) -> Option<(u32, u32)> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") && data.len() >= 24 && &data[12..16] == b"IHDR" {
+        return Some((BigEndian::read_u32(&data[16..20]), BigEndian::read_u32(&data[20..24])));
+    }
+    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        return match (read_u16_at(data, 6), read_u16_at(data, 8)) {
+            (Ok(width), Ok(height)) => Some((width as u32, height as u32)),
+            _ => None
+        };
+    }
+    if !data.starts_with(&[0xff, 0xd8]) {
+        return None;
+    }
+    // Walk the segments up to the start of frame, which has the size.
+    let mut pos = 2;
+    while pos + 4 <= data.len() && data[pos] == 0xff {
+        let marker = data[pos + 1];
+        let length = BigEndian::read_u16(&data[pos + 2..pos + 4]) as usize;
+        let is_start_of_frame = marker >= 0xc0 && marker <= 0xcf &&
+                                marker != 0xc4 && marker != 0xc8 && marker != 0xcc;
+        if is_start_of_frame && pos + 9 <= data.len() {
+            let height = BigEndian::read_u16(&data[pos + 5..pos + 7]);
+            let width = BigEndian::read_u16(&data[pos + 7..pos + 9]);
+            return Some((width as u32, height as u32));
+        }
+        pos += 2 + length;
+    }
+    None
+}
+
+/// Encodes a DefineShape of a rectangle from the origin, filled with a
+/// bitmap at one pixel per 20 twips.
+fn bitmap_shape(id: u16, bitmap_id: u16, width: u32, height: u32) -> Vec<u8> {
+    let (width, height) = (width as i32, height as i32);
+    let mut writer = BitWriter::new();
+    let nbits = signed_bits_needed(&[width, height]);
+    writer.write_ub(5, nbits);
+    for &value in &[0, width, 0, height] {
+        writer.write_sb(nbits, value);
+    }
+    let mut data = vec![id as u8, (id >> 8) as u8];
+    data.extend(writer.into_bytes());
+
+    // One clipped, smoothed bitmap fill and no line styles.
+    data.extend_from_slice(&[1, 0x41, bitmap_id as u8, (bitmap_id >> 8) as u8]);
+    let mut matrix = BitWriter::new();
+    Matrix {
+        scale_x: 20.0,
+        scale_y: 20.0,
+        ..Matrix::identity()
+    }.write(&mut matrix);
+    data.extend(matrix.into_bytes());
+    data.push(0);
+
+    let mut records = BitWriter::new();
+    // One fill bit, no line bits.
+    records.write_ub(4, 1);
+    records.write_ub(4, 0);
+    // A style change selecting fill 1 on the left.
+    records.write_ub(6, 0b000010);
+    records.write_ub(1, 1);
+    // Straight edges around the rectangle, each either horizontal or
+    // vertical.
+    let nbits = signed_bits_needed(&[width, height]).max(2);
+    for &(vertical, delta) in &[(false, width), (true, height), (false, -width), (true, -height)] {
+        records.write_flag(true);
+        records.write_flag(true);
+        records.write_ub(4, nbits - 2);
+        records.write_flag(false);
+        records.write_flag(vertical);
+        records.write_sb(nbits, delta);
+    }
+    // The end record.
+    records.write_ub(6, 0);
+    data.extend(records.into_bytes());
+    data
+}
+
+/// Encodes a DefineSound for an MP3 or WAV file.
+fn define_sound(id: u16, data: &[u8]) -> Result<Tag, Error> {
+    let (format, sample_rate, is_16_bit, stereo, sample_count, samples) = try!(if is_wav(data) {
+        parse_wav(data)
+    } else {
+        parse_mp3(data)
+    });
+    let rate_index = match sample_rate {
+        5512 => 0,
+        11025 => 1,
+        22050 => 2,
+        44100 => 3,
+        _ => return Err(Error::NotSwf)
+    };
+
+    let mut out = Vec::with_capacity(7 + samples.len());
+    try!(out.write_u16::<LittleEndian>(id));
+    out.push(format << 4 | rate_index << 2 | (is_16_bit as u8) << 1 | stereo as u8);
+    try!(out.write_u32::<LittleEndian>(sample_count));
+    out.extend(samples);
+    Ok(Tag::new(codes::DEFINE_SOUND, out))
+}
+
+fn is_wav(data: &[u8]) -> bool {
+    data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WAVE"
+}
+
+/// The sound format code, sample rate, 16-bitness, stereoness, sample count
+/// and sound data for a DefineSound.
+type SoundParts = (u8, u32, bool, bool, u32, Vec<u8>);
+
+fn parse_mp3(data: &[u8]) -> Result<SoundParts, Error> {
+    // Skip over an ID3v2 tag, whose size is stored 7 bits to a byte.
+    let mut frames = data;
+    if frames.starts_with(b"ID3") && frames.len() >= 10 {
+        let size = frames[6..10].iter().fold(0, |size, &byte| (size << 7) | (byte & 0x7f) as usize);
+        frames = frames.get(10 + size..).unwrap_or(&[]);
+    }
+
+    let sample_count = sound::mp3_sample_count(frames);
+    if sample_count == 0 {
+        return Err(Error::NotSwf);
+    }
+    let version = (frames[1] >> 3) & 0x03;
+    let rate_index = ((frames[2] >> 2) & 0x03) as usize;
+    let sample_rate = match version {
+        3 => [44100, 48000, 32000][rate_index],
+        2 => [22050, 24000, 16000][rate_index],
+        _ => [11025, 12000, 8000][rate_index]
+    };
+    // Every channel mode but the last is some kind of stereo.
+    let stereo = frames[3] >> 6 != 0x03;
+
+    // SeekSamples, then the frames.
+    let mut samples = vec![0, 0];
+    samples.extend_from_slice(frames);
+    Ok((2, sample_rate, true, stereo, sample_count, samples))
+}
+
+fn parse_wav(data: &[u8]) -> Result<SoundParts, Error> {
+    let mut format = None;
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let kind = &data[pos..pos + 4];
+        let length = LittleEndian::read_u32(&data[pos + 4..pos + 8]) as usize;
+        let chunk = match data.get(pos + 8..pos + 8 + length) {
+            Some(chunk) => chunk,
+            None => return Err(Error::NotSwf)
+        };
+        if kind == b"fmt " && chunk.len() >= 16 {
+            format = Some((
+                LittleEndian::read_u16(&chunk[0..2]),
+                LittleEndian::read_u16(&chunk[2..4]),
+                LittleEndian::read_u32(&chunk[4..8]),
+                LittleEndian::read_u16(&chunk[14..16])
+            ));
+        } else if kind == b"data" {
+            return match format {
+                // PCM, in mono or stereo, at 8 or 16 bits.
+                Some((1, channels @ 1..=2, sample_rate, bits @ 8)) |
+                Some((1, channels @ 1..=2, sample_rate, bits @ 16)) => {
+                    let frame_len = channels as usize * bits as usize / 8;
+                    let sample_count = (chunk.len() / frame_len) as u32;
+                    Ok((3, sample_rate, bits == 16, channels == 2, sample_count, chunk.to_vec()))
+                },
+                _ => Err(Error::NotSwf)
+            };
+        }
+        // Chunks are padded to an even length.
+        pos += 8 + length + length % 2;
+    }
+    Err(Error::NotSwf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use SwfHeaders;
+    use assets::{Sound, SoundFormat};
+    use shape::{Shape, ShapeRecord};
+    use tag::{self, codes};
+
+    #[test]
+    fn test_wrap_image() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&[0, 0, 0, 100, 0, 0, 0, 50, 8, 6, 0, 0, 0]);
+        let swf = wrap_asset(&png, AssetKind::Image, (300, 200)).unwrap();
+
+        let (headers, body) = SwfHeaders::read_from(Cursor::new(swf)).unwrap();
+        assert_eq!(headers.dimensions(), (300, 200));
+        let tags = tag::read_all(body).unwrap();
+        let codes: Vec<u16> = tags.iter().map(|tag| tag.code()).collect();
+        assert_eq!(codes, vec![
+            codes::FILE_ATTRIBUTES,
+            codes::SET_BACKGROUND_COLOR,
+            codes::DEFINE_BITS_JPEG_2,
+            codes::DEFINE_SHAPE,
+            codes::PLACE_OBJECT_2,
+            codes::SHOW_FRAME
+        ]);
+        assert_eq!(&tags[2].data()[2..], &png[..]);
+
+        let shape = Shape::parse_with_records(&tags[3]).unwrap().unwrap();
+        assert_eq!((shape.bounds.width(), shape.bounds.height()), (2000, 1000));
+        let edges = shape.records.unwrap().into_iter().filter(|record| match *record {
+            ShapeRecord::StraightEdge { .. } => true,
+            _ => false
+        }).count();
+        assert_eq!(edges, 4);
+
+        assert!(wrap_asset(b"not an image", AssetKind::Image, (300, 200)).is_err());
+    }
+
+    #[test]
+    fn test_wrap_sound() {
+        // Two silent 128kbps 44.1kHz stereo MP3 frames.
+        let mut mp3 = Vec::new();
+        for _ in 0..2 {
+            mp3.extend_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+            mp3.extend_from_slice(&[0; 413]);
+        }
+        let swf = wrap_asset(&mp3, AssetKind::Sound, (100, 100)).unwrap();
+        let (_, body) = SwfHeaders::read_from(Cursor::new(swf)).unwrap();
+        let tags = tag::read_all(body).unwrap();
+        let sound = Sound::from_tag(&tags[2]).unwrap().unwrap();
+        assert_eq!(sound.format, SoundFormat::Mp3);
+        assert_eq!(sound.sample_rate, 44100);
+        assert!(sound.stereo);
+        assert_eq!(sound.sample_count, 2304);
+        assert_eq!(tags[3].code(), codes::START_SOUND);
+
+        // A second of 8-bit mono silence at 11kHz.
+        let mut wav = b"RIFF\0\0\0\0WAVEfmt \x10\0\0\0\x01\0\x01\0\x11\x2b\0\0\x11\x2b\0\0\x01\0\x08\0data".to_vec();
+        wav.extend_from_slice(&[0x11, 0x2b, 0, 0]);
+        wav.extend_from_slice(&[0x80; 11025]);
+        let swf = wrap_asset(&wav, AssetKind::Sound, (100, 100)).unwrap();
+        let (_, body) = SwfHeaders::read_from(Cursor::new(swf)).unwrap();
+        let sound = Sound::from_tag(&tag::read_all(body).unwrap()[2]).unwrap().unwrap();
+        assert_eq!(sound.format, SoundFormat::UncompressedLittleEndian);
+        assert_eq!(sound.sample_rate, 11025);
+        assert!(!sound.is_16_bit && !sound.stereo);
+        assert_eq!(sound.sample_count, 11025);
+        assert_eq!(sound.data, vec![0x80; 11025]);
+    }
+}
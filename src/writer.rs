@@ -0,0 +1,1398 @@
+//! Writing SWF files back out, compressed however the headers ask for.
+
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+pub use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use xz2::stream::{LzmaOptions, Stream};
+use xz2::write::XzEncoder;
+
+use super::{ParseOptions, Signature, SwfHeaders};
+use decoded_swf;
+use error::Error;
+use primitives::{encode_rect, read_string, read_u16_at, BitReader, BitWriter, Matrix, Rect};
+use tag::{codes, Tag, Tags};
+
+/// liblzma's flag for the extreme variant of a preset.
+const LZMA_PRESET_EXTREME: u32 = 0x8000_0000;
+
+/// The HasMetadata bit in the first byte of a FileAttributes tag.
+const FILE_ATTRIBUTES_HAS_METADATA: u8 = 0x10;
+
+/// How the writer decides which compression to use.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SignatureChoice {
+    /// Use the signature in the headers, as is.
+    FromHeaders,
+    /// Use `Signature::best_for()` with the version in the headers.
+    Best,
+    /// Compress with every signature the version allows, and keep whichever
+    /// comes out smallest. Slow, but never worse than `Best`.
+    Smallest,
+    /// Use this signature, whatever the headers say. Nothing checks that
+    /// the version supports it.
+    Fixed(Signature)
+}
+
+/// What to do with the build time compilers record in a file.
+///
+/// Nothing here reads the clock, and compressing the same body with the
+/// same options always gives the same bytes, so the output only changes
+/// when the input does. ProductInfo tags are the catch: Flex and the AIR
+/// SDK stamp them with the time of the build, so two builds of the same
+/// sources never match unless the stamp is replaced.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Determinism {
+    /// Copy ProductInfo tags as they are.
+    Preserve,
+    /// Set the compile date in every ProductInfo tag to this many
+    /// milliseconds since the Unix epoch, such as `SOURCE_DATE_EPOCH`
+    /// times 1000.
+    Timestamp(u64)
+}
+
+/// How `transform()` and the other streaming writers fill in the lengths
+/// near the start of a file, when the sink can't seek back to them.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LengthMode {
+    /// Build the whole file in memory, then write it out with the lengths
+    /// already in place. Needs as much memory as the output is big.
+    Buffer,
+    /// Write the file as it's built, with placeholders for the lengths, and
+    /// hand the sink a `FixUp` holding the real ones at the end. Needs next
+    /// to no memory, but leaves whoever reads the output to apply the fix-up.
+    FixUp
+}
+
+/// Bytes that have to be written over a file's placeholders for it to be
+/// valid, from `LengthMode::FixUp`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FixUp {
+    /// Where the bytes go, from the start of the file.
+    pub offset: u64,
+    /// The bytes to write there.
+    pub bytes: Vec<u8>
+}
+
+impl FixUp {
+    /// Writes the bytes over their placeholders in a complete file held in
+    /// memory.
+    pub fn apply(&self, file: &mut [u8]) -> Result<(), Error> {
+        let start = self.offset as usize;
+        match file.get_mut(start..start + self.bytes.len()) {
+            Some(placeholders) => {
+                placeholders.copy_from_slice(&self.bytes);
+                Ok(())
+            },
+            None => Err(Error::NotSwf)
+        }
+    }
+}
+
+/// How to compress, for CWS and ZWS output, and whether to replace build
+/// times.
+///
+/// The defaults match what the Flash IDE produces: zlib's default level,
+/// and LZMA preset 6. Build times are kept.
+///
+/// # Examples
+///
+/// ```rust
+/// use swf_headers::writer::{Compression, WriteOptions};
+/// // Smallest output, for release builds.
+/// let small = WriteOptions::new().zlib_level(Compression::Best).lzma_preset(9).lzma_extreme(true);
+/// // Fastest output, for iterating on assets.
+/// let fast = WriteOptions::new().zlib_level(Compression::Fast).lzma_preset(0);
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct WriteOptions {
+    signature: SignatureChoice,
+    zlib_level: Compression,
+    lzma_preset: u32,
+    lzma_extreme: bool,
+    lzma_dict_size: Option<u32>,
+    determinism: Determinism,
+    length_mode: LengthMode
+}
+
+impl WriteOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        WriteOptions {
+            signature: SignatureChoice::FromHeaders,
+            zlib_level: Compression::Default,
+            lzma_preset: 6,
+            lzma_extreme: false,
+            lzma_dict_size: None,
+            determinism: Determinism::Preserve,
+            length_mode: LengthMode::Buffer
+        }
+    }
+
+    /// Sets how the signature, and so the compression, is picked.
+    pub fn signature(mut self, choice: SignatureChoice) -> Self {
+        self.signature = choice;
+        self
+    }
+
+    /// Sets the zlib compression level, for CWS output.
+    pub fn zlib_level(mut self, level: Compression) -> Self {
+        self.zlib_level = level;
+        self
+    }
+
+    /// Sets the LZMA preset from 0 (fastest) to 9 (smallest), for ZWS
+    /// output. Higher presets are clamped to 9.
+    pub fn lzma_preset(mut self, preset: u32) -> Self {
+        self.lzma_preset = preset.min(9);
+        self
+    }
+
+    /// Sets whether to use the slower "extreme" variant of the LZMA preset,
+    /// which usually squeezes out a little more.
+    pub fn lzma_extreme(mut self, extreme: bool) -> Self {
+        self.lzma_extreme = extreme;
+        self
+    }
+
+    /// Overrides the preset's LZMA dictionary size, in bytes. Bigger
+    /// dictionaries help big files, at the cost of memory when both
+    /// compressing and decompressing. liblzma accepts 4KiB up to 1.5GiB.
+    pub fn lzma_dict_size(mut self, size: u32) -> Self {
+        self.lzma_dict_size = Some(size);
+        self
+    }
+
+    /// Sets what happens to build times, for reproducible builds.
+    pub fn determinism(mut self, determinism: Determinism) -> Self {
+        self.determinism = determinism;
+        self
+    }
+
+    /// Sets how lengths are filled in for sinks that can't seek. The
+    /// default is `LengthMode::Buffer`.
+    pub fn length_mode(mut self, mode: LengthMode) -> Self {
+        self.length_mode = mode;
+        self
+    }
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions::new()
+    }
+}
+
+/// Writes a complete SWF file built from the given headers and tags.
+///
+/// The signature, version, dimensions, frame rate and frame count are taken
+/// from the headers, while the file length is recomputed from what actually
+/// gets written. An End tag is appended for you, so the tag list shouldn't
+/// contain one.
+pub fn write_swf<W: Write>(writer: W, headers: &SwfHeaders, tags: &[Tag]) -> Result<(), Error> {
+    write_swf_with_options(writer, headers, tags, &WriteOptions::default())
+}
+
+/// Like `write_swf()`, compressing as set in `options`.
+///
+/// With a `SignatureChoice` other than `FromHeaders`, the signature written
+/// can differ from the one in the headers.
+pub fn write_swf_with_options<W: Write>(mut writer: W, headers: &SwfHeaders, tags: &[Tag], options: &WriteOptions)
+    -> Result<(), Error>
+{
+    let body = try!(encode_body(headers, tags, options));
+    let file_length = try!(file_length_for(body.len() as u64));
+    let version = headers.version();
+
+    let (signature, compressed) = match options.signature {
+        SignatureChoice::FromHeaders => (headers.signature(), None),
+        SignatureChoice::Best => (Signature::best_for(version, body.len()), None),
+        SignatureChoice::Fixed(signature) => (signature, None),
+        SignatureChoice::Smallest => {
+            let mut smallest = (Signature::Uncompressed, None);
+            let mut smallest_len = body.len();
+            for &candidate in &[Signature::ZlibCompressed, Signature::LzmaCompressed] {
+                if !candidate.is_supported_by(version) {
+                    continue;
+                }
+                let mut out = Vec::new();
+                try!(compress(&mut out, candidate, &body, options));
+                if out.len() < smallest_len {
+                    smallest_len = out.len();
+                    smallest = (candidate, Some(out));
+                }
+            }
+            smallest
+        }
+    };
+
+    try!(writer.write_all(&[signature_byte(signature), b'W', b'S', version]));
+    try!(writer.write_u32::<LittleEndian>(file_length));
+    match compressed {
+        Some(compressed) => try!(writer.write_all(&compressed)),
+        None => try!(compress(&mut writer, signature, &body, options))
+    }
+    Ok(())
+}
+
+/// Works out the file length for a body of `body_len` bytes, which has to
+/// fit in the header's 32 bits along with the first 8 bytes.
+fn file_length_for(body_len: u64) -> Result<u32, Error> {
+    match body_len.checked_add(8) {
+        Some(file_length) if file_length <= u32::max_value() as u64 => Ok(file_length as u32),
+        _ => Err(Error::IoError(io::Error::new(io::ErrorKind::InvalidInput, "body too long for a SWF")))
+    }
+}
+
+fn compress<W: Write>(writer: &mut W, signature: Signature, body: &[u8], options: &WriteOptions) -> Result<(), Error> {
+    match signature {
+        Signature::Uncompressed => {
+            try!(writer.write_all(body));
+        },
+        Signature::ZlibCompressed => {
+            let mut encoder = ZlibEncoder::new(writer, options.zlib_level);
+            try!(encoder.write_all(body));
+            try!(encoder.finish());
+        },
+        Signature::LzmaCompressed => {
+            try!(write_lzma(writer, body, options));
+        }
+    }
+    Ok(())
+}
+
+/// Writes a SWF into a freshly allocated vector.
+pub fn to_bytes(headers: &SwfHeaders, tags: &[Tag]) -> Result<Vec<u8>, Error> {
+    to_bytes_with_options(headers, tags, &WriteOptions::default())
+}
+
+/// Like `to_bytes()`, compressing as set in `options`.
+pub fn to_bytes_with_options(headers: &SwfHeaders, tags: &[Tag], options: &WriteOptions) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    try!(write_swf_with_options(&mut out, headers, tags, options));
+    Ok(out)
+}
+
+/// Encodes everything after the first 8 bytes, before any compression.
+fn encode_body(headers: &SwfHeaders, tags: &[Tag], options: &WriteOptions) -> Result<Vec<u8>, Error> {
+    let (width, height) = headers.dimensions_twips();
+    let mut body = try!(encode_rect(width, height));
+    // The frame rate is 8.8 fixed point.
+    try!(body.write_u16::<LittleEndian>(headers.frame_rate() << 8 | headers.frame_rate_fraction() as u16));
+    try!(body.write_u16::<LittleEndian>(headers.frame_count()));
+    for tag in tags {
+        try!(write_tag(tag, &mut body, options));
+    }
+    try!(Tag::new(codes::END, vec![]).write_to(&mut body));
+    Ok(body)
+}
+
+/// Writes a tag, with its build time replaced if `options` asks for it.
+fn write_tag<W: Write>(tag: &Tag, writer: &mut W, options: &WriteOptions) -> Result<(), Error> {
+    match options.determinism {
+        // ProductID, Edition, the major and minor version, and a 64-bit
+        // build number come before the date.
+        Determinism::Timestamp(millis) if tag.code() == codes::PRODUCT_INFO && tag.data().len() >= 26 => {
+            let mut data = tag.data().to_vec();
+            try!((&mut data[18..26]).write_u64::<LittleEndian>(millis));
+            let stamped = Tag::new(codes::PRODUCT_INFO, data);
+            if tag.was_long_header() {
+                stamped.with_long_header().write_to(writer)
+            } else {
+                stamped.write_to(writer)
+            }
+        },
+        _ => tag.write_to(writer)
+    }
+}
+
+fn write_lzma<W: Write>(writer: &mut W, body: &[u8], options: &WriteOptions) -> Result<(), Error> {
+    // liblzma gives us the .lzma container: 5 bytes of properties, 8 bytes of
+    // uncompressed size, then the stream. SWF wants the compressed length as
+    // a u32, then the properties, then the stream, with no size field.
+    let mut encoder = XzEncoder::new_stream(Vec::new(), try!(lzma_stream(options)));
+    try!(encoder.write_all(body));
+    let encoded = try!(encoder.finish());
+    if encoded.len() < 13 {
+        return Err(Error::IoError(io::Error::new(io::ErrorKind::Other, "LZMA encoder output too short")));
+    }
+
+    let (props, data) = (&encoded[..5], &encoded[13..]);
+    try!(writer.write_u32::<LittleEndian>(data.len() as u32));
+    try!(writer.write_all(props));
+    try!(writer.write_all(data));
+    Ok(())
+}
+
+fn lzma_stream(options: &WriteOptions) -> Result<Stream, Error> {
+    let preset = options.lzma_preset | if options.lzma_extreme { LZMA_PRESET_EXTREME } else { 0 };
+    let mut lzma_options = try!(LzmaOptions::new_preset(preset).map_err(io::Error::from));
+    if let Some(size) = options.lzma_dict_size {
+        lzma_options.dict_size(size);
+    }
+    Ok(try!(Stream::new_lzma_encoder(&lzma_options).map_err(io::Error::from)))
+}
+
+/// Somewhere `transform()` and the other streaming writers can write a
+/// file to.
+///
+/// The file length, and for LZMA the compressed length, are only known
+/// once the whole body has been written, and they go near the start. Sinks
+/// that can seek get placeholders, patched at the end. Sinks that can't,
+/// such as sockets and pipes wrapped in `Streaming`, get what
+/// `WriteOptions::length_mode()` asks for: either the file built in memory
+/// first and written all at once, or placeholders and a `FixUp`.
+///
+/// Everything that's `Write + Seek` is a `SwfSink` already.
+pub trait SwfSink: Write {
+    /// Returns the current position, or `None` if the sink can't seek back
+    /// to it later.
+    fn position(&mut self) -> io::Result<Option<u64>>;
+
+    /// Overwrites the bytes at `pos` with `bytes`, leaving the position
+    /// where it was. Only called with positions `position()` returned.
+    fn patch(&mut self, pos: u64, bytes: &[u8]) -> io::Result<()>;
+
+    /// Takes the bytes that belong over the placeholders, once a file has
+    /// been written with `LengthMode::FixUp`. Sinks that don't override
+    /// this refuse them.
+    fn record_fix_up(&mut self, fix_up: FixUp) -> io::Result<()> {
+        let _ = fix_up;
+        Err(io::Error::new(io::ErrorKind::Other, "sink can't take a fix-up"))
+    }
+}
+
+impl<W: Write + Seek> SwfSink for W {
+    fn position(&mut self) -> io::Result<Option<u64>> {
+        self.seek(SeekFrom::Current(0)).map(Some)
+    }
+
+    fn patch(&mut self, pos: u64, bytes: &[u8]) -> io::Result<()> {
+        let end = try!(self.seek(SeekFrom::Current(0)));
+        try!(self.seek(SeekFrom::Start(pos)));
+        try!(self.write_all(bytes));
+        try!(self.seek(SeekFrom::Start(end)));
+        Ok(())
+    }
+}
+
+/// A `SwfSink` for writers that can't seek, such as sockets and stdout.
+///
+/// With `LengthMode::FixUp`, it keeps the fix-up for the caller to send on
+/// or apply.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io;
+/// use swf_headers::writer::{self, Streaming};
+/// let result = writer::normalize("example.swf", Streaming::new(io::stdout()));
+/// ```
+#[derive(Debug)]
+pub struct Streaming<W> {
+    inner: W,
+    fix_up: Option<FixUp>
+}
+
+impl<W: Write> Streaming<W> {
+    /// Wraps a writer.
+    pub fn new(inner: W) -> Self {
+        Streaming {
+            inner: inner,
+            fix_up: None
+        }
+    }
+
+    /// Returns the fix-up for the last file written, if it was written with
+    /// `LengthMode::FixUp`.
+    pub fn fix_up(&self) -> Option<&FixUp> {
+        self.fix_up.as_ref()
+    }
+
+    /// Unwraps the writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for Streaming<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> SwfSink for Streaming<W> {
+    fn position(&mut self) -> io::Result<Option<u64>> {
+        Ok(None)
+    }
+
+    fn patch(&mut self, _pos: u64, _bytes: &[u8]) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Other, "can't patch a streaming sink"))
+    }
+
+    fn record_fix_up(&mut self, fix_up: FixUp) -> io::Result<()> {
+        self.fix_up = Some(fix_up);
+        Ok(())
+    }
+}
+
+/// Lends a `Streaming` to a writer, so the fix-up can be read afterwards.
+impl<'a, W: Write> SwfSink for &'a mut Streaming<W> {
+    fn position(&mut self) -> io::Result<Option<u64>> {
+        (**self).position()
+    }
+
+    fn patch(&mut self, pos: u64, bytes: &[u8]) -> io::Result<()> {
+        (**self).patch(pos, bytes)
+    }
+
+    fn record_fix_up(&mut self, fix_up: FixUp) -> io::Result<()> {
+        (**self).record_fix_up(fix_up)
+    }
+}
+
+/// Stands in for a sink that can't seek during `LengthMode::FixUp`,
+/// counting what's written so placeholders have positions, and keeping the
+/// patch instead of making it.
+struct Placeholders<'a, W: 'a> {
+    inner: &'a mut W,
+    written: u64,
+    fix_up: Option<FixUp>
+}
+
+impl<'a, W: Write> Write for Placeholders<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = try!(self.inner.write(buf));
+        self.written += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<'a, W: Write> SwfSink for Placeholders<'a, W> {
+    fn position(&mut self) -> io::Result<Option<u64>> {
+        Ok(Some(self.written))
+    }
+
+    fn patch(&mut self, pos: u64, bytes: &[u8]) -> io::Result<()> {
+        self.fix_up = Some(FixUp { offset: pos, bytes: bytes.to_vec() });
+        Ok(())
+    }
+}
+
+/// What a `transform()` callback wants done with a tag.
+#[derive(Clone, PartialEq, Debug)]
+pub enum TagAction {
+    /// Write the tag out unchanged.
+    Keep,
+    /// Leave the tag out.
+    Drop,
+    /// Write these tags in its place. The list can be empty, or include the
+    /// original tag alongside new ones.
+    Replace(Vec<Tag>)
+}
+
+/// Rewrites a SWF file tag by tag, writing the result to `output` and
+/// returning its length.
+///
+/// `action` is called with every tag on the main timeline, in order, and
+/// last with an End tag, whether or not the file has one, so it can add
+/// tags at the very end. Exactly one End tag is always written, after
+/// anything else the callback gave for it. Tags inside sprites are part
+/// of their DefineSprite tag's payload, and aren't visited separately.
+/// Everything in the header besides the file length is copied as is,
+/// including fractional frame rates.
+///
+/// Only one tag is held in memory at a time, and the output is compressed
+/// as it's written, so this works on files of any size. The file length,
+/// and for LZMA the compressed length, are only known at the end, so they
+/// have to be patched in; see `SwfSink` for what happens when `output`
+/// can't seek. If anything goes wrong, whatever was written so far is left
+/// in `output`.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io::Cursor;
+/// use swf_headers::tag::codes;
+/// use swf_headers::writer::{self, TagAction};
+/// let mut quiet = Cursor::new(Vec::new());
+/// let result = writer::transform("loud.swf", &mut quiet, |tag| match tag.code() {
+///     codes::DEFINE_SOUND | codes::START_SOUND => TagAction::Drop,
+///     _ => TagAction::Keep
+/// });
+/// ```
+pub fn transform<P, W, F>(input: P, output: W, action: F) -> Result<u64, Error>
+    where P: AsRef<Path>, W: SwfSink, F: FnMut(&Tag) -> TagAction
+{
+    transform_with_options(input, output, action, &WriteOptions::default())
+}
+
+/// Like `transform()`, compressing as set in `options`.
+///
+/// `SignatureChoice::FromHeaders` keeps the input file's compression.
+/// Since the output is streamed, `SignatureChoice::Smallest` can't try
+/// every compression, and is treated like `SignatureChoice::Best`, going by
+/// the input file's length.
+pub fn transform_with_options<P, W, F>(input: P, output: W, action: F, options: &WriteOptions)
+    -> Result<u64, Error>
+    where P: AsRef<Path>, W: SwfSink, F: FnMut(&Tag) -> TagAction
+{
+    rewrite(input, output, |file_header| choose_signature(file_header, options), |rest| Ok(rest.to_vec()),
+            action, options)
+}
+
+/// Scales a whole movie up or down by `scale`, writing the result to
+/// `output` and returning its length.
+///
+/// The stage size in the header is scaled, and so is the matrix of every
+/// placement on the main timeline, which scales everything inside them
+/// along with it. Doubling a 550x400 movie gives an 1100x800 one that looks
+/// the same, only sharper, for republishing old movies for HiDPI screens.
+///
+/// Anything ActionScript positions itself, such as with `_x` or
+/// `Stage.stageWidth`, isn't scaled. Placements too broken to parse are
+/// copied as they are.
+///
+/// A `scale` that isn't a finite number above zero is an
+/// `io::ErrorKind::InvalidInput` error, and nothing is read or written.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io::Cursor;
+/// use swf_headers::writer;
+/// let mut hidpi = Cursor::new(Vec::new());
+/// let result = writer::resize_stage("game.swf", &mut hidpi, 2.0);
+/// ```
+pub fn resize_stage<P, W>(input: P, output: W, scale: f64) -> Result<u64, Error>
+    where P: AsRef<Path>, W: SwfSink
+{
+    if !scale.is_finite() || scale <= 0.0 {
+        return Err(Error::IoError(io::Error::new(io::ErrorKind::InvalidInput, "scale must be finite and positive")));
+    }
+    let options = WriteOptions::default();
+    rewrite(input, output, |file_header| choose_signature(file_header, &options), |rest| {
+        let mut bits = BitReader::new(rest);
+        let rect = try!(Rect::read(&mut bits));
+        let scaled = |value: i32| (value as f64 * scale).round() as i32;
+        let mut writer = BitWriter::new();
+        Rect {
+            x_min: scaled(rect.x_min),
+            x_max: scaled(rect.x_max),
+            y_min: scaled(rect.y_min),
+            y_max: scaled(rect.y_max)
+        }.write(&mut writer);
+        let mut header = writer.into_bytes();
+        header.extend_from_slice(&rest[bits.byte_pos()..]);
+        Ok(header)
+    }, |tag| match scale_placement(tag, scale) {
+        Ok(Some(tag)) => TagAction::Replace(vec![tag]),
+        _ => TagAction::Keep
+    }, &options)
+}
+
+/// Scales the matrix of a placement tag, returning `None` for other tags,
+/// and for placements that keep the matrix already at their depth.
+fn scale_placement(tag: &Tag, scale: f64) -> Result<Option<Tag>, Error> {
+    let data = tag.data();
+    let (matrix_pos, has_matrix) = match tag.code() {
+        codes::PLACE_OBJECT => {
+            // The character ID and depth come before the matrix.
+            try!(read_u16_at(data, 2));
+            (4, true)
+        },
+        codes::PLACE_OBJECT_2 | codes::PLACE_OBJECT_3 => {
+            let mut reader = data;
+            let flags = try!(reader.read_u8());
+            let flags2 = if tag.code() == codes::PLACE_OBJECT_3 { try!(reader.read_u8()) } else { 0 };
+            try!(reader.read_u16::<LittleEndian>());
+            if flags2 & 0x08 != 0 || (flags2 & 0x10 != 0 && flags & 0x02 != 0) {
+                try!(read_string(&mut reader));
+            }
+            if flags & 0x02 != 0 {
+                try!(reader.read_u16::<LittleEndian>());
+            }
+            // A move without a matrix keeps the one at its depth, which
+            // was scaled when it was placed. A new placement without one
+            // gets a matrix holding just the scale.
+            if flags & 0x05 == 0x01 {
+                return Ok(None);
+            }
+            (data.len() - reader.len(), flags & 0x04 != 0)
+        },
+        _ => return Ok(None)
+    };
+
+    let (matrix, matrix_len) = if has_matrix {
+        let mut bits = BitReader::new(&data[matrix_pos..]);
+        (try!(Matrix::read(&mut bits)), bits.byte_pos())
+    } else {
+        (Matrix::identity(), 0)
+    };
+    // Scaling the stage multiplies every term of the matrix.
+    let mut writer = BitWriter::new();
+    Matrix {
+        scale_x: matrix.scale_x * scale,
+        scale_y: matrix.scale_y * scale,
+        rotate_skew_0: matrix.rotate_skew_0 * scale,
+        rotate_skew_1: matrix.rotate_skew_1 * scale,
+        translate_x: (matrix.translate_x as f64 * scale).round() as i32,
+        translate_y: (matrix.translate_y as f64 * scale).round() as i32
+    }.write(&mut writer);
+
+    let mut scaled = data[..matrix_pos].to_vec();
+    if !has_matrix {
+        scaled[0] |= 0x04;
+    }
+    scaled.extend(writer.into_bytes());
+    scaled.extend_from_slice(&data[matrix_pos + matrix_len..]);
+    Ok(Some(Tag::new(tag.code(), scaled)))
+}
+
+/// Rewrites a SWF file in a canonical form, so that files differing only in
+/// how they were encoded come out byte-identical, for deduplicating
+/// archives and checking builds are reproducible. Returns the length
+/// written.
+///
+/// The output is always zlib compressed at the default level, or
+/// uncompressed for versions before 6. The stage RECT uses the fewest bits
+/// it can, every tag, including those inside sprites, uses the short
+/// header whenever it fits, and anything after the End tag is dropped.
+/// Tag payloads are otherwise left exactly as they are.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io::Cursor;
+/// use swf_headers::writer;
+/// let mut canonical = Cursor::new(Vec::new());
+/// let result = writer::normalize("example.swf", &mut canonical);
+/// ```
+pub fn normalize<P, W>(input: P, output: W) -> Result<u64, Error>
+    where P: AsRef<Path>, W: SwfSink
+{
+    let options = WriteOptions::default();
+    let depth_limit = ParseOptions::default().sprite_depth_limit();
+    rewrite(input, output, |file_header| {
+        if Signature::ZlibCompressed.is_supported_by(file_header[3]) {
+            Signature::ZlibCompressed
+        } else {
+            Signature::Uncompressed
+        }
+    }, |rest| {
+        let mut bits = BitReader::new(rest);
+        let rect = try!(Rect::read(&mut bits));
+        let mut writer = BitWriter::new();
+        rect.write(&mut writer);
+        let mut header = writer.into_bytes();
+        header.extend_from_slice(&rest[bits.byte_pos()..]);
+        Ok(header)
+    }, |tag| match normalize_sprite(tag, depth_limit) {
+        Ok(Some(tag)) => TagAction::Replace(vec![tag]),
+        _ if tag.was_long_header() => TagAction::Replace(vec![Tag::new(tag.code(), tag.data().to_vec())]),
+        _ => TagAction::Keep
+    }, &options)
+}
+
+/// Re-encodes the tags inside a DefineSprite, returning `None` for other
+/// tags.
+fn normalize_sprite(tag: &Tag, depth_limit: u32) -> Result<Option<Tag>, Error> {
+    if tag.code() != codes::DEFINE_SPRITE || depth_limit == 0 {
+        return Ok(None);
+    }
+    let data = tag.data();
+    // The sprite ID and frame count come before the tags.
+    try!(read_u16_at(data, 2));
+    let mut normalized = data[..4].to_vec();
+    for inner in Tags::new(&data[4..]) {
+        let inner = try!(inner);
+        match try!(normalize_sprite(&inner, depth_limit - 1)) {
+            Some(inner) => try!(inner.write_to(&mut normalized)),
+            None => try!(Tag::new(inner.code(), inner.into_data()).write_to(&mut normalized))
+        }
+    }
+    try!(Tag::new(codes::END, vec![]).write_to(&mut normalized));
+    Ok(Some(Tag::new(codes::DEFINE_SPRITE, normalized)))
+}
+
+/// Picks the signature for `transform_with_options()` from the input
+/// file's first 8 bytes.
+fn choose_signature(file_header: &[u8; 8], options: &WriteOptions) -> Signature {
+    match options.signature {
+        SignatureChoice::FromHeaders => match file_header[0] {
+            b'F' => Signature::Uncompressed,
+            b'C' => Signature::ZlibCompressed,
+            _ => Signature::LzmaCompressed
+        },
+        SignatureChoice::Fixed(signature) => signature,
+        SignatureChoice::Best | SignatureChoice::Smallest => {
+            let declared = (&file_header[4..]).read_u32::<LittleEndian>().unwrap_or(0);
+            Signature::best_for(file_header[3], declared.saturating_sub(8) as usize)
+        }
+    }
+}
+
+/// Does the work for `transform_with_options()`, with the signature picked
+/// by `signature`, and also passing the RECT, frame rate and frame count
+/// through `header`, as raw bytes.
+fn rewrite<P, W, S, H, F>(input: P, mut output: W, signature: S, header: H, action: F, options: &WriteOptions)
+    -> Result<u64, Error>
+    where P: AsRef<Path>,
+          W: SwfSink,
+          S: FnOnce(&[u8; 8]) -> Signature,
+          H: FnOnce(&[u8]) -> Result<Vec<u8>, Error>,
+          F: FnMut(&Tag) -> TagAction
+{
+    match (try!(output.position()), options.length_mode) {
+        (Some(start), _) => rewrite_at(input, &mut output, start, signature, header, action, options),
+        (None, LengthMode::Buffer) => {
+            let mut buffered = io::Cursor::new(Vec::new());
+            let file_length = try!(rewrite_at(input, &mut buffered, 0, signature, header, action, options));
+            try!(output.write_all(&buffered.into_inner()));
+            try!(output.flush());
+            Ok(file_length)
+        },
+        (None, LengthMode::FixUp) => {
+            let (file_length, fix_up) = {
+                let mut placeholders = Placeholders {
+                    inner: &mut output,
+                    written: 0,
+                    fix_up: None
+                };
+                let file_length = try!(rewrite_at(input, &mut placeholders, 0, signature, header, action, options));
+                (file_length, placeholders.fix_up)
+            };
+            if let Some(fix_up) = fix_up {
+                try!(output.record_fix_up(fix_up));
+            }
+            Ok(file_length)
+        }
+    }
+}
+
+/// Does the work for `rewrite()`, with a sink that can be patched, where
+/// the file starts at `start`.
+fn rewrite_at<P, W, S, H, F>(input: P, output: &mut W, start: u64, signature: S, header: H, mut action: F,
+                             options: &WriteOptions)
+    -> Result<u64, Error>
+    where P: AsRef<Path>,
+          W: SwfSink,
+          S: FnOnce(&[u8; 8]) -> Signature,
+          H: FnOnce(&[u8]) -> Result<Vec<u8>, Error>,
+          F: FnMut(&Tag) -> TagAction
+{
+    let (file_header, mut body) = try!(decoded_swf::open_body(input));
+    let version = file_header[3];
+    let signature = signature(&file_header);
+
+    try!(output.write_all(&[signature_byte(signature), b'W', b'S', version]));
+    // Placeholders for the file length, and for LZMA, the compressed length
+    // and properties.
+    try!(output.write_u32::<LittleEndian>(0));
+    if signature == Signature::LzmaCompressed {
+        try!(output.write_all(&[0; 9]));
+    }
+
+    let (body_len, lzma) = {
+        let mut sink = try!(BodyWriter::new(output, signature, options));
+
+        // The RECT is 5 bits of field size, then four fields of that size.
+        let mut fixed = [0; 21];
+        try!(body.read_exact(&mut fixed[..1]));
+        let rect_len = (5 + 4 * (fixed[0] >> 3) as usize + 7) / 8;
+        try!(body.read_exact(&mut fixed[1..rect_len + 4]));
+        try!(sink.write_all(&try!(header(&fixed[..rect_len + 4]))));
+
+        for tag in Tags::new(&mut body) {
+            let tag = try!(tag);
+            match action(&tag) {
+                TagAction::Keep => try!(write_tag(&tag, &mut sink, options)),
+                TagAction::Drop => {},
+                TagAction::Replace(tags) => for tag in &tags {
+                    try!(write_tag(tag, &mut sink, options));
+                }
+            }
+        }
+        let end = Tag::new(codes::END, vec![]);
+        if let TagAction::Replace(tags) = action(&end) {
+            for tag in tags.iter().filter(|tag| tag.code() != codes::END) {
+                try!(write_tag(tag, &mut sink, options));
+            }
+        }
+        try!(end.write_to(&mut sink));
+        try!(sink.finish())
+    };
+
+    let file_length = try!(file_length_for(body_len));
+    let mut lengths = Vec::with_capacity(13);
+    try!(lengths.write_u32::<LittleEndian>(file_length));
+    if let Some((compressed_len, props)) = lzma {
+        try!(lengths.write_u32::<LittleEndian>(compressed_len as u32));
+        lengths.extend_from_slice(&props[..5]);
+    }
+    try!(output.patch(start + 4, &lengths));
+    try!(output.flush());
+    Ok(file_length as u64)
+}
+
+/// A `transform()` callback that removes every Metadata tag, for scrubbing
+/// authoring details out of files before they're published.
+///
+/// The HasMetadata flag in the FileAttributes tag is cleared to match.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io::Cursor;
+/// use swf_headers::writer;
+/// let mut scrubbed = Cursor::new(Vec::new());
+/// let result = writer::transform("example.swf", &mut scrubbed, writer::strip_metadata);
+/// ```
+pub fn strip_metadata(tag: &Tag) -> TagAction {
+    match tag.code() {
+        codes::METADATA => TagAction::Drop,
+        codes::FILE_ATTRIBUTES => match tag.data().first() {
+            Some(&flags) if flags & FILE_ATTRIBUTES_HAS_METADATA != 0 => {
+                let mut data = tag.data().to_vec();
+                data[0] = flags & !FILE_ATTRIBUTES_HAS_METADATA;
+                TagAction::Replace(vec![Tag::new(codes::FILE_ATTRIBUTES, data)])
+            },
+            _ => TagAction::Keep
+        },
+        _ => TagAction::Keep
+    }
+}
+
+/// Returns a `transform()` callback that swaps the XMP document in every
+/// Metadata tag for `xmp`, such as a standard one saying only who owns the
+/// file. Files without a Metadata tag are left without one.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io::Cursor;
+/// use swf_headers::writer;
+/// let xmp = r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"/>"#;
+/// let mut scrubbed = Cursor::new(Vec::new());
+/// let result = writer::transform("example.swf", &mut scrubbed, writer::replace_metadata(xmp));
+/// ```
+pub fn replace_metadata(xmp: &str) -> impl FnMut(&Tag) -> TagAction {
+    // The payload is a null-terminated string.
+    let mut data = xmp.as_bytes().to_vec();
+    data.push(0);
+    move |tag| match tag.code() {
+        codes::METADATA => TagAction::Replace(vec![Tag::new(codes::METADATA, data.clone())]),
+        _ => TagAction::Keep
+    }
+}
+
+/// Returns a `transform()` callback that lets the debugger connect, with
+/// the password whose MD5-crypt hash (as in `$1$...`) is `password_hash`.
+/// An empty hash allows connecting without a password.
+///
+/// An EnableDebugger2 tag is added just after the FileAttributes tag, or at
+/// the start for files without one, and any existing EnableDebugger or
+/// EnableDebugger2 tags are removed.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io::Cursor;
+/// use swf_headers::writer;
+/// let mut debuggable = Cursor::new(Vec::new());
+/// let result = writer::transform("app.swf", &mut debuggable, writer::enable_debugger(""));
+/// ```
+pub fn enable_debugger(password_hash: &str) -> impl FnMut(&Tag) -> TagAction {
+    // A reserved u16, then the hash as a null-terminated string.
+    let mut data = vec![0, 0];
+    data.extend_from_slice(password_hash.as_bytes());
+    data.push(0);
+    insert_tag(Tag::new(codes::ENABLE_DEBUGGER_2, data), &[codes::ENABLE_DEBUGGER, codes::ENABLE_DEBUGGER_2])
+}
+
+/// A `transform()` callback that removes any EnableDebugger and
+/// EnableDebugger2 tags, so the debugger can't connect.
+pub fn disable_debugger(tag: &Tag) -> TagAction {
+    match tag.code() {
+        codes::ENABLE_DEBUGGER | codes::ENABLE_DEBUGGER_2 => TagAction::Drop,
+        _ => TagAction::Keep
+    }
+}
+
+/// Returns a `transform()` callback that lets profilers such as Adobe
+/// Scout connect, optionally only with the password whose SHA-256 digest
+/// is `password_hash`.
+///
+/// An EnableTelemetry tag is added just after the FileAttributes tag, or at
+/// the start for files without one, and any existing one is removed.
+pub fn enable_telemetry(password_hash: Option<[u8; 32]>) -> impl FnMut(&Tag) -> TagAction {
+    // A reserved u16, then the digest if there is one.
+    let mut data = vec![0, 0];
+    if let Some(hash) = password_hash {
+        data.extend_from_slice(&hash);
+    }
+    insert_tag(Tag::new(codes::ENABLE_TELEMETRY, data), &[codes::ENABLE_TELEMETRY])
+}
+
+/// A `transform()` callback that removes any EnableTelemetry tag, so
+/// profilers can't connect.
+pub fn disable_telemetry(tag: &Tag) -> TagAction {
+    match tag.code() {
+        codes::ENABLE_TELEMETRY => TagAction::Drop,
+        _ => TagAction::Keep
+    }
+}
+
+/// Builds a callback that drops every tag with one of the `replaced` codes,
+/// and adds `new` once, after FileAttributes if that comes first. A
+/// timeline with nothing else gets it before the End tag.
+fn insert_tag(new: Tag, replaced: &'static [u16]) -> impl FnMut(&Tag) -> TagAction {
+    let mut new = Some(new);
+    move |tag| {
+        if replaced.contains(&tag.code()) {
+            return TagAction::Drop;
+        }
+        match new.take() {
+            Some(new) => if tag.code() == codes::FILE_ATTRIBUTES {
+                TagAction::Replace(vec![tag.clone(), new])
+            } else {
+                TagAction::Replace(vec![new, tag.clone()])
+            },
+            None => TagAction::Keep
+        }
+    }
+}
+
+fn signature_byte(signature: Signature) -> u8 {
+    match signature {
+        Signature::Uncompressed => b'F',
+        Signature::ZlibCompressed => b'C',
+        Signature::LzmaCompressed => b'Z'
+    }
+}
+
+/// Compresses a body as it's written, counting the bytes before
+/// compression.
+struct BodyWriter<'a, W: Write + 'a> {
+    inner: BodyInner<'a, W>,
+    len: u64
+}
+
+enum BodyInner<'a, W: Write + 'a> {
+    Raw(&'a mut W),
+    Zlib(ZlibEncoder<&'a mut W>),
+    Lzma(XzEncoder<LzmaBody<&'a mut W>>)
+}
+
+impl<'a, W: Write> BodyWriter<'a, W> {
+    fn new(writer: &'a mut W, signature: Signature, options: &WriteOptions) -> Result<Self, Error> {
+        let inner = match signature {
+            Signature::Uncompressed => BodyInner::Raw(writer),
+            Signature::ZlibCompressed => BodyInner::Zlib(ZlibEncoder::new(writer, options.zlib_level)),
+            Signature::LzmaCompressed => {
+                let body = LzmaBody {
+                    inner: writer,
+                    props: Vec::with_capacity(13),
+                    len: 0
+                };
+                BodyInner::Lzma(XzEncoder::new_stream(body, try!(lzma_stream(options))))
+            }
+        };
+        Ok(BodyWriter {
+            inner: inner,
+            len: 0
+        })
+    }
+
+    /// Flushes the compressor, returning the uncompressed length, and for
+    /// LZMA, what goes in the space left for it after the file length.
+    fn finish(self) -> Result<(u64, Option<(u64, Vec<u8>)>), Error> {
+        let lzma = match self.inner {
+            BodyInner::Raw(_) => None,
+            BodyInner::Zlib(encoder) => {
+                try!(encoder.finish());
+                None
+            },
+            BodyInner::Lzma(encoder) => {
+                let body = try!(encoder.finish());
+                if body.props.len() < 13 {
+                    return Err(Error::IoError(io::Error::new(io::ErrorKind::Other, "LZMA encoder output too short")));
+                }
+                Some((body.len, body.props))
+            }
+        };
+        Ok((self.len, lzma))
+    }
+}
+
+impl<'a, W: Write> Write for BodyWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = try!(match self.inner {
+            BodyInner::Raw(ref mut writer) => writer.write(buf),
+            BodyInner::Zlib(ref mut encoder) => encoder.write(buf),
+            BodyInner::Lzma(ref mut encoder) => encoder.write(buf)
+        });
+        self.len += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.inner {
+            BodyInner::Raw(ref mut writer) => writer.flush(),
+            BodyInner::Zlib(ref mut encoder) => encoder.flush(),
+            BodyInner::Lzma(ref mut encoder) => encoder.flush()
+        }
+    }
+}
+
+/// Turns liblzma's .lzma container into what SWF wants as it's written, as
+/// in `write_lzma()`: the first 13 bytes are held back, and the rest passed
+/// through and counted.
+struct LzmaBody<W> {
+    inner: W,
+    props: Vec<u8>,
+    len: u64
+}
+
+impl<W: Write> Write for LzmaBody<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.props.len() < 13 {
+            let taken = buf.len().min(13 - self.props.len());
+            self.props.extend_from_slice(&buf[..taken]);
+            return Ok(taken);
+        }
+        let written = try!(self.inner.write(buf));
+        self.len += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    use {Signature, SwfHeaders};
+    use tag::{codes, read_all, Tag};
+    use timeline::Placement;
+
+    #[test]
+    fn test_smallest_signature() {
+        let headers = |version| SwfHeaders {
+            signature: Signature::Uncompressed,
+            version: version,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = vec![Tag::new(codes::DEFINE_BINARY_DATA, vec![0; 4096])];
+        let options = WriteOptions::new().signature(SignatureChoice::Smallest);
+
+        // Too old for any compression.
+        let old = to_bytes_with_options(&headers(5), &tags, &options).unwrap();
+        assert_eq!(&old[..3], b"FWS");
+        // Compressible, and only zlib allowed.
+        let zlib = to_bytes_with_options(&headers(10), &tags, &options).unwrap();
+        assert_eq!(&zlib[..3], b"CWS");
+        assert!(zlib.len() < old.len());
+    }
+
+    #[test]
+    fn test_length_limits() {
+        let largest = u32::max_value() as u64 - 8;
+        assert_eq!(file_length_for(largest).unwrap(), u32::max_value());
+        assert!(file_length_for(largest + 1).is_err());
+        assert!(file_length_for(u64::max_value()).is_err());
+
+        // The stage won't fit a RECT.
+        let headers = SwfHeaders {
+            signature: Signature::Uncompressed,
+            version: 10,
+            file_length: 0,
+            width: 1 << 30,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        assert!(to_bytes(&headers, &[]).is_err());
+    }
+
+    #[test]
+    fn test_transform() {
+        let headers = |signature| SwfHeaders {
+            signature: signature,
+            version: 13,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let background = Tag::new(codes::SET_BACKGROUND_COLOR, vec![0xff, 0, 0]);
+        let metadata = Tag::new(codes::METADATA, vec![b'x'; 100]);
+        let show = Tag::new(codes::SHOW_FRAME, vec![]);
+        let before = vec![metadata.clone(), background.clone(), show.clone()];
+        let white = Tag::new(codes::SET_BACKGROUND_COLOR, vec![0xff, 0xff, 0xff]);
+        let after = vec![white.clone(), background.clone(), show.clone()];
+
+        let input = env::temp_dir().join("swf_headers_test_transform.swf");
+        let cases = [
+            (Signature::Uncompressed, SignatureChoice::FromHeaders, Signature::Uncompressed),
+            (Signature::ZlibCompressed, SignatureChoice::FromHeaders, Signature::ZlibCompressed),
+            // The input is long enough for LZMA to be the best choice.
+            (Signature::Uncompressed, SignatureChoice::Best, Signature::LzmaCompressed)
+        ];
+        for &(signature, choice, expected) in &cases {
+            File::create(&input).unwrap().write_all(&to_bytes(&headers(signature), &before).unwrap()).unwrap();
+            let action = |tag: &Tag| match tag.code() {
+                codes::METADATA => TagAction::Drop,
+                codes::SET_BACKGROUND_COLOR => TagAction::Replace(vec![white.clone(), tag.clone()]),
+                _ => TagAction::Keep
+            };
+            let options = WriteOptions::new().signature(choice);
+            let mut output = io::Cursor::new(Vec::new());
+            let length = transform_with_options(&input, &mut output, action, &options).unwrap();
+
+            // Streaming compresses to exactly what compressing all at once
+            // does, so the whole file can be compared.
+            let output = output.into_inner();
+            assert_eq!(output, to_bytes(&headers(expected), &after).unwrap());
+            assert_eq!(length, to_bytes(&headers(Signature::Uncompressed), &after).unwrap().len() as u64);
+
+            // A sink that can't seek gets the same file.
+            let mut streamed = Vec::new();
+            assert_eq!(transform_with_options(&input, Streaming::new(&mut streamed), action, &options).unwrap(),
+                       length);
+            assert_eq!(streamed, output);
+
+            // Or, with a fix-up, the same file with placeholders for the
+            // lengths.
+            let mut sink = Streaming::new(Vec::new());
+            let options = options.length_mode(LengthMode::FixUp);
+            assert_eq!(transform_with_options(&input, &mut sink, action, &options).unwrap(), length);
+            let fix_up = sink.fix_up().unwrap().clone();
+            assert_eq!(fix_up.offset, 4);
+            let mut fixed = sink.into_inner();
+            assert!(fixed != output);
+            fix_up.apply(&mut fixed).unwrap();
+            assert_eq!(fixed, output);
+        }
+        fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn test_determinism() {
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let product_info = |date: u8| {
+            let mut data = vec![3, 0, 0, 0, 6, 0, 0, 0, 4, 6, 0x2a, 0, 0, 0, 0, 0, 0, 0];
+            data.extend_from_slice(&[date, 1, 0, 0, 0, 0, 0, 0]);
+            Tag::new(codes::PRODUCT_INFO, data)
+        };
+        let show = Tag::new(codes::SHOW_FRAME, vec![]);
+        let options = WriteOptions::new().determinism(Determinism::Timestamp(0x1ff));
+
+        let first = to_bytes_with_options(&headers, &[product_info(1), show.clone()], &options).unwrap();
+        let second = to_bytes_with_options(&headers, &[product_info(2), show.clone()], &options).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first, to_bytes(&headers, &[product_info(0xff), show.clone()]).unwrap());
+        assert!(to_bytes(&headers, &[product_info(1), show.clone()]).unwrap() != first);
+
+        // Transforming stamps the tags the callback keeps or adds.
+        let input = env::temp_dir().join("swf_headers_test_determinism.swf");
+        File::create(&input).unwrap().write_all(&to_bytes(&headers, &[product_info(3), show]).unwrap()).unwrap();
+        let mut output = io::Cursor::new(Vec::new());
+        transform_with_options(&input, &mut output, |_| TagAction::Keep, &options).unwrap();
+        assert_eq!(output.into_inner(), first);
+        fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn test_scrub_metadata() {
+        let attributes = Tag::new(codes::FILE_ATTRIBUTES, vec![0x19, 0, 0, 0]);
+        let metadata = Tag::new(codes::METADATA, b"<rdf:RDF>secret</rdf:RDF>\0".to_vec());
+        let show = Tag::new(codes::SHOW_FRAME, vec![]);
+
+        assert_eq!(strip_metadata(&attributes),
+                   TagAction::Replace(vec![Tag::new(codes::FILE_ATTRIBUTES, vec![0x09, 0, 0, 0])]));
+        assert_eq!(strip_metadata(&metadata), TagAction::Drop);
+        assert_eq!(strip_metadata(&show), TagAction::Keep);
+
+        let mut replace = replace_metadata("<rdf:RDF/>");
+        assert_eq!(replace(&metadata), TagAction::Replace(vec![Tag::new(codes::METADATA, b"<rdf:RDF/>\0".to_vec())]));
+        assert_eq!(replace(&attributes), TagAction::Keep);
+    }
+
+    #[test]
+    fn test_enable_debugger() {
+        let attributes = Tag::new(codes::FILE_ATTRIBUTES, vec![0x08, 0, 0, 0]);
+        let old = Tag::new(codes::ENABLE_DEBUGGER_2, b"\0\0old\0".to_vec());
+        let show = Tag::new(codes::SHOW_FRAME, vec![]);
+        let debugger = Tag::new(codes::ENABLE_DEBUGGER_2, b"\0\0$1$x\0".to_vec());
+
+        let mut enable = enable_debugger("$1$x");
+        assert_eq!(enable(&attributes), TagAction::Replace(vec![attributes.clone(), debugger.clone()]));
+        assert_eq!(enable(&old), TagAction::Drop);
+        assert_eq!(enable(&show), TagAction::Keep);
+
+        // Without FileAttributes, it goes first.
+        let mut enable = enable_telemetry(None);
+        let telemetry = Tag::new(codes::ENABLE_TELEMETRY, vec![0, 0]);
+        assert_eq!(enable(&show), TagAction::Replace(vec![telemetry.clone(), show.clone()]));
+        assert_eq!(disable_telemetry(&telemetry), TagAction::Drop);
+        assert_eq!(disable_debugger(&old), TagAction::Drop);
+        assert_eq!(disable_debugger(&show), TagAction::Keep);
+
+        // A movie with nothing but its End tag still gets one.
+        let end = Tag::new(codes::END, vec![]);
+        assert_eq!(enable_debugger("$1$x")(&end), TagAction::Replace(vec![debugger.clone(), end]));
+        let headers = SwfHeaders {
+            signature: Signature::Uncompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let input = env::temp_dir().join("swf_headers_test_enable_debugger.swf");
+        File::create(&input).unwrap().write_all(&to_bytes(&headers, &[]).unwrap()).unwrap();
+        let mut output = io::Cursor::new(Vec::new());
+        transform(&input, &mut output, enable_debugger("$1$x")).unwrap();
+        fs::remove_file(&input).unwrap();
+        assert_eq!(output.into_inner(), to_bytes(&headers, &[debugger]).unwrap());
+    }
+
+    #[test]
+    fn test_resize_stage() {
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 11000,
+            height: 8000,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let mut moved = Matrix::identity();
+        moved.translate_x = 100;
+        moved.rotate_skew_0 = 0.5;
+        let mut bits = BitWriter::new();
+        moved.write(&mut bits);
+        let mut move_data = vec![0x05, 2, 0];
+        move_data.extend(bits.into_bytes());
+        let tags = vec![
+            // A new placement at depth 1 without a matrix.
+            Tag::new(codes::PLACE_OBJECT_2, vec![0x02, 1, 0, 7, 0]),
+            // Moving depth 2 somewhere else.
+            Tag::new(codes::PLACE_OBJECT_2, move_data),
+            // Changing depth 2's ratio only.
+            Tag::new(codes::PLACE_OBJECT_2, vec![0x11, 2, 0, 9, 0]),
+            Tag::new(codes::SHOW_FRAME, vec![])
+        ];
+        let input = env::temp_dir().join("swf_headers_test_resize_stage.swf");
+        File::create(&input).unwrap().write_all(&to_bytes(&headers, &tags).unwrap()).unwrap();
+        let output = env::temp_dir().join("swf_headers_test_resize_stage_out.swf");
+        resize_stage(&input, File::create(&output).unwrap(), 2.0).unwrap();
+
+        let (resized, body) = SwfHeaders::open(&output).unwrap();
+        assert_eq!(resized.dimensions(), (1100, 800));
+        let written = read_all(body).unwrap();
+        let placements: Vec<_> = written.iter().map(|tag| Placement::parse(tag).unwrap()).collect();
+        let mut doubled = Matrix::identity();
+        doubled.scale_x = 2.0;
+        doubled.scale_y = 2.0;
+        assert_eq!(placements[0].as_ref().unwrap().matrix, Some(doubled));
+        assert_eq!(placements[0].as_ref().unwrap().character_id, Some(7));
+        doubled.translate_x = 200;
+        doubled.rotate_skew_0 = 1.0;
+        assert_eq!(placements[1].as_ref().unwrap().matrix, Some(doubled));
+        assert_eq!(written[2], tags[2]);
+        assert_eq!(written[3], tags[3]);
+
+        // Scales that would collapse or flip the stage are refused.
+        for &scale in &[0.0, -2.0, f64::NAN, f64::INFINITY] {
+            let mut sink = io::Cursor::new(Vec::new());
+            let err = resize_stage(&input, &mut sink, scale).unwrap_err();
+            assert_eq!(err.kind(), "io");
+            assert!(sink.get_ref().is_empty());
+        }
+        fs::remove_file(&input).unwrap();
+        fs::remove_file(&output).unwrap();
+    }
+
+    #[test]
+    fn test_normalize() {
+        let headers = SwfHeaders {
+            signature: Signature::ZlibCompressed,
+            version: 10,
+            file_length: 0,
+            width: 200,
+            height: 200,
+            frame_rate: 24,
+            frame_rate_fraction: 0,
+            frame_count: 1
+        };
+        let tags = vec![
+            Tag::new(codes::DEFINE_SPRITE, vec![1, 0, 1, 0, 0x40, 0, 0, 0]),
+            Tag::new(codes::SHOW_FRAME, vec![])
+        ];
+        // Sprite 1 with one frame, with long ShowFrame and End headers.
+        let sprite = [1, 0, 1, 0, 0x7f, 0, 0, 0, 0, 0, 0x3f, 0, 0, 0, 0, 0];
+
+        // Uncompressed, a RECT with 31-bit fields, long tag headers and
+        // junk after the End tag.
+        let mut rect = BitWriter::new();
+        rect.write_ub(5, 31);
+        for &value in &[0, 200, 0, 200] {
+            rect.write_sb(31, value);
+        }
+        let mut bytes = vec![b'F', b'W', b'S', 10, 0, 0, 0, 0];
+        bytes.extend(rect.into_bytes());
+        bytes.extend_from_slice(&[0, 24, 1, 0]);
+        bytes.extend_from_slice(&[0xff, 0x09, sprite.len() as u8, 0, 0, 0]);
+        bytes.extend_from_slice(&sprite);
+        bytes.extend_from_slice(&[0x7f, 0, 0, 0, 0, 0, 0, 0, 0xde, 0xad]);
+
+        let input = env::temp_dir().join("swf_headers_test_normalize.swf");
+        File::create(&input).unwrap().write_all(&bytes).unwrap();
+        let mut output = io::Cursor::new(Vec::new());
+        let length = normalize(&input, &mut output).unwrap();
+        assert_eq!(output.into_inner(), to_bytes(&headers, &tags).unwrap());
+        let uncompressed = SwfHeaders { signature: Signature::Uncompressed, ..headers };
+        assert_eq!(length, to_bytes(&uncompressed, &tags).unwrap().len() as u64);
+        fs::remove_file(&input).unwrap();
+    }
+}